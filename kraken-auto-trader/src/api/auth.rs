@@ -0,0 +1,36 @@
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+
+use crate::{errors::Error, streaming::StreamAuth};
+
+/// Token sent as `Authorization: Bearer <token>`
+pub(crate) fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Admit a request that changes state only with one of the stream tokens as its bearer token.
+/// Without configured tokens no request may change state
+pub(crate) fn authorize(
+    req: &HttpRequest,
+    auth: Option<&web::Data<StreamAuth>>,
+) -> Result<(), Error> {
+    let authorized = match auth {
+        Some(auth) => auth.authorize(bearer_token(req))?,
+        None => false,
+    };
+    if authorized {
+        Ok(())
+    } else {
+        Err(Error::Auth(
+            "No API tokens are configured, set KRAKEN_STREAM_TOKENS".to_string(),
+        ))
+    }
+}
+
+pub(crate) fn unauthorized(e: Error) -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+        .body(e.to_string())
+}
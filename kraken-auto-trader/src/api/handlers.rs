@@ -1,26 +1,90 @@
-use actix_web::{get, HttpResponse, Responder};
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
+use serde::Deserialize;
+use serde_json::json;
 
-use crate::services::{account_details::Account, market_data::MarketData};
+use super::auth::{authorize, unauthorized};
+use crate::{
+    errors::Error,
+    execution::{engine::ExecutionEngine, ParentOrderRequest},
+    middleware::KrakenClientState,
+    models::symbols::Pair,
+    orders::{
+        dead_man_switch::DeadManSwitch,
+        synthetic::{SyntheticOrderManager, SyntheticOrderRequest},
+    },
+    services::{account_details::Account, market_data::MarketData},
+    streaming::StreamAuth,
+};
 
 #[get("/hello")]
 pub async fn hello() -> impl Responder {
     HttpResponse::Ok().body("Hello world!")
 }
 
+#[get("/health")]
+pub async fn health(dead_man_switch: Option<web::Data<DeadManSwitch>>) -> impl Responder {
+    let dead_man_switch = match dead_man_switch {
+        Some(switch) => Some(switch.status().await),
+        None => None,
+    };
+    HttpResponse::Ok().json(json!({
+        "status": "ok",
+        "dead_man_switch": dead_man_switch,
+    }))
+}
+
 #[get("/balance")]
-pub async fn get_balance(req: actix_web::HttpRequest) -> impl Responder {
-    let account = Account::new().unwrap();
+pub async fn get_balance(
+    req: actix_web::HttpRequest,
+    account: Option<web::Data<Account>>,
+) -> impl Responder {
+    let Some(account) = account else {
+        return account_unavailable();
+    };
     match account.get_balance(req).await {
         Ok(balance) => HttpResponse::Ok().json(balance),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
 
+#[get("/open-orders")]
+pub async fn get_open_orders(
+    req: actix_web::HttpRequest,
+    account: Option<web::Data<Account>>,
+    synthetic: Option<web::Data<SyntheticOrderManager>>,
+) -> impl Responder {
+    let Some(account) = account else {
+        return account_unavailable();
+    };
+    let native = match account.get_open_orders(req, None, None, None).await {
+        Ok(orders) => orders,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let synthetic = match synthetic {
+        Some(synthetic) => synthetic.open_orders().await,
+        None => Vec::new(),
+    };
+    HttpResponse::Ok().json(json!({
+        "open": native.open,
+        "count": native.count,
+        "synthetic": synthetic,
+    }))
+}
+
+fn account_unavailable() -> HttpResponse {
+    HttpResponse::ServiceUnavailable().body("No API key is configured for account data")
+}
+
 #[get("/trade-volume")]
-pub async fn get_trade_volume(req: actix_web::HttpRequest) -> impl Responder {
-    let account = Account::new().unwrap();
+pub async fn get_trade_volume(
+    req: actix_web::HttpRequest,
+    account: Option<web::Data<Account>>,
+) -> impl Responder {
+    let Some(account) = account else {
+        return account_unavailable();
+    };
     match account
-        .get_trade_volume(req, Some("ETHUSD".to_string()))
+        .get_trade_volume(req, Some(&[Pair::from("ETHUSD")]))
         .await
     {
         Ok(volume) => HttpResponse::Ok().json(volume),
@@ -49,7 +113,7 @@ pub async fn get_server_time(req: actix_web::HttpRequest) -> impl Responder {
 #[get("/ticker")]
 pub async fn get_ticker(req: actix_web::HttpRequest) -> impl Responder {
     let market = MarketData::new();
-    match market.get_ticker(req, "XBTUSD".to_string()).await {
+    match market.get_ticker(req, &[Pair::from("XBTUSD")]).await {
         Ok(status) => HttpResponse::Ok().json(status),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -59,7 +123,7 @@ pub async fn get_ticker(req: actix_web::HttpRequest) -> impl Responder {
 pub async fn get_recent_trades(req: actix_web::HttpRequest) -> impl Responder {
     let market = MarketData::new();
     match market
-        .get_recent_trades(req, "ETHGBP".to_string(), Some(1616663618), Some(10))
+        .get_recent_trades(req, Pair::from("ETHGBP"), Some(1616663618), Some(10))
         .await
     {
         Ok(trades) => HttpResponse::Ok().json(trades),
@@ -71,10 +135,225 @@ pub async fn get_recent_trades(req: actix_web::HttpRequest) -> impl Responder {
 pub async fn get_recent_spreads(req: actix_web::HttpRequest) -> impl Responder {
     let market = MarketData::new();
     match market
-        .get_recent_spreads(req, "ETHGBP".to_string(), None)
+        .get_recent_spreads(req, Pair::from("ETHGBP"), None)
         .await
     {
         Ok(trades) => HttpResponse::Ok().json(trades),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
+
+fn request_error(e: Error) -> HttpResponse {
+    match e {
+        Error::InvalidParameter(_) | Error::ValidationError(_) => {
+            HttpResponse::BadRequest().body(e.to_string())
+        }
+        _ => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+fn algos_unavailable() -> HttpResponse {
+    HttpResponse::ServiceUnavailable().body("Execution engine is not configured")
+}
+
+#[post("/algos")]
+pub async fn start_algo(
+    req: HttpRequest,
+    auth: Option<web::Data<StreamAuth>>,
+    engine: Option<web::Data<ExecutionEngine>>,
+    request: web::Json<ParentOrderRequest>,
+) -> impl Responder {
+    if let Err(e) = authorize(&req, auth.as_ref()) {
+        return unauthorized(e);
+    }
+    let Some(engine) = engine else {
+        return algos_unavailable();
+    };
+    match engine.start(request.into_inner()).await {
+        Ok(progress) => HttpResponse::Created().json(progress),
+        Err(e) => request_error(e),
+    }
+}
+
+#[get("/algos")]
+pub async fn list_algos(engine: Option<web::Data<ExecutionEngine>>) -> impl Responder {
+    match engine {
+        Some(engine) => HttpResponse::Ok().json(engine.list().await),
+        None => algos_unavailable(),
+    }
+}
+
+#[get("/algos/{id}")]
+pub async fn get_algo(
+    engine: Option<web::Data<ExecutionEngine>>,
+    id: web::Path<String>,
+) -> impl Responder {
+    let Some(engine) = engine else {
+        return algos_unavailable();
+    };
+    match engine.get(&id).await {
+        Some(progress) => HttpResponse::Ok().json(progress),
+        None => HttpResponse::NotFound().body(format!("Unknown parent order {}", id)),
+    }
+}
+
+#[post("/algos/{id}/pause")]
+pub async fn pause_algo(
+    req: HttpRequest,
+    auth: Option<web::Data<StreamAuth>>,
+    engine: Option<web::Data<ExecutionEngine>>,
+    id: web::Path<String>,
+) -> impl Responder {
+    if let Err(e) = authorize(&req, auth.as_ref()) {
+        return unauthorized(e);
+    }
+    let Some(engine) = engine else {
+        return algos_unavailable();
+    };
+    match engine.pause(&id).await {
+        Ok(progress) => HttpResponse::Ok().json(progress),
+        Err(e) => request_error(e),
+    }
+}
+
+#[post("/algos/{id}/resume")]
+pub async fn resume_algo(
+    req: HttpRequest,
+    auth: Option<web::Data<StreamAuth>>,
+    engine: Option<web::Data<ExecutionEngine>>,
+    id: web::Path<String>,
+) -> impl Responder {
+    if let Err(e) = authorize(&req, auth.as_ref()) {
+        return unauthorized(e);
+    }
+    let Some(engine) = engine else {
+        return algos_unavailable();
+    };
+    match engine.resume(&id).await {
+        Ok(progress) => HttpResponse::Ok().json(progress),
+        Err(e) => request_error(e),
+    }
+}
+
+#[delete("/algos/{id}")]
+pub async fn cancel_algo(
+    req: HttpRequest,
+    auth: Option<web::Data<StreamAuth>>,
+    engine: Option<web::Data<ExecutionEngine>>,
+    id: web::Path<String>,
+) -> impl Responder {
+    if let Err(e) = authorize(&req, auth.as_ref()) {
+        return unauthorized(e);
+    }
+    let Some(engine) = engine else {
+        return algos_unavailable();
+    };
+    match engine.cancel(&id).await {
+        Ok(progress) => HttpResponse::Accepted().json(progress),
+        Err(e) => request_error(e),
+    }
+}
+
+fn synthetic_unavailable() -> HttpResponse {
+    HttpResponse::ServiceUnavailable().body("Synthetic orders are not configured")
+}
+
+#[post("/synthetic-orders")]
+pub async fn create_synthetic_order(
+    req: HttpRequest,
+    auth: Option<web::Data<StreamAuth>>,
+    synthetic: Option<web::Data<SyntheticOrderManager>>,
+    request: web::Json<SyntheticOrderRequest>,
+) -> impl Responder {
+    if let Err(e) = authorize(&req, auth.as_ref()) {
+        return unauthorized(e);
+    }
+    let Some(synthetic) = synthetic else {
+        return synthetic_unavailable();
+    };
+    match synthetic.submit(request.into_inner()).await {
+        Ok(order) => HttpResponse::Created().json(order),
+        Err(e) => request_error(e),
+    }
+}
+
+#[get("/synthetic-orders")]
+pub async fn list_synthetic_orders(
+    synthetic: Option<web::Data<SyntheticOrderManager>>,
+) -> impl Responder {
+    match synthetic {
+        Some(synthetic) => HttpResponse::Ok().json(synthetic.list().await),
+        None => synthetic_unavailable(),
+    }
+}
+
+#[get("/synthetic-orders/{id}")]
+pub async fn get_synthetic_order(
+    synthetic: Option<web::Data<SyntheticOrderManager>>,
+    id: web::Path<String>,
+) -> impl Responder {
+    let Some(synthetic) = synthetic else {
+        return synthetic_unavailable();
+    };
+    match synthetic.get(&id).await {
+        Some(order) => HttpResponse::Ok().json(order),
+        None => HttpResponse::NotFound().body(format!("Unknown synthetic order {}", id)),
+    }
+}
+
+#[delete("/synthetic-orders/{id}")]
+pub async fn cancel_synthetic_order(
+    req: HttpRequest,
+    auth: Option<web::Data<StreamAuth>>,
+    synthetic: Option<web::Data<SyntheticOrderManager>>,
+    id: web::Path<String>,
+) -> impl Responder {
+    if let Err(e) = authorize(&req, auth.as_ref()) {
+        return unauthorized(e);
+    }
+    let Some(synthetic) = synthetic else {
+        return synthetic_unavailable();
+    };
+    match synthetic.cancel(&id).await {
+        Ok(order) => HttpResponse::Ok().json(order),
+        Err(e) => request_error(e),
+    }
+}
+
+fn cache_unavailable() -> HttpResponse {
+    HttpResponse::ServiceUnavailable().body("Public data cache is not configured")
+}
+
+#[get("/cache")]
+pub async fn get_cache_stats(state: Option<web::Data<KrakenClientState>>) -> impl Responder {
+    match state.and_then(|state| state.public_cache.clone()) {
+        Some(cache) => HttpResponse::Ok().json(cache.stats()),
+        None => cache_unavailable(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct InvalidateCache {
+    /// Endpoint whose responses are dropped, e.g. `/0/public/AssetPairs`; all when missing
+    endpoint: Option<String>,
+}
+
+#[delete("/cache")]
+pub async fn invalidate_cache(
+    req: HttpRequest,
+    auth: Option<web::Data<StreamAuth>>,
+    state: Option<web::Data<KrakenClientState>>,
+    query: web::Query<InvalidateCache>,
+) -> impl Responder {
+    if let Err(e) = authorize(&req, auth.as_ref()) {
+        return unauthorized(e);
+    }
+    let Some(cache) = state.and_then(|state| state.public_cache.clone()) else {
+        return cache_unavailable();
+    };
+    match &query.endpoint {
+        Some(endpoint) => cache.invalidate(endpoint),
+        None => cache.clear(),
+    }
+    HttpResponse::Ok().json(cache.stats())
+}
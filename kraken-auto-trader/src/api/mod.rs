@@ -1,15 +1,32 @@
 use actix_web::web;
 
+mod auth;
 pub mod handlers;
+pub mod streaming;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(handlers::hello)
+        .service(handlers::health)
         .service(handlers::get_balance)
+        .service(handlers::get_open_orders)
         .service(handlers::get_trade_volume)
         .service(handlers::get_system_status)
         .service(handlers::get_server_time)
         .service(handlers::get_ticker)
         .service(handlers::get_recent_trades)
-        .service(handlers::get_recent_spreads);
-    // .service(handlers::get_open_orders);
+        .service(handlers::get_recent_spreads)
+        .service(handlers::start_algo)
+        .service(handlers::list_algos)
+        .service(handlers::get_algo)
+        .service(handlers::pause_algo)
+        .service(handlers::resume_algo)
+        .service(handlers::cancel_algo)
+        .service(handlers::create_synthetic_order)
+        .service(handlers::list_synthetic_orders)
+        .service(handlers::get_synthetic_order)
+        .service(handlers::cancel_synthetic_order)
+        .service(handlers::get_cache_stats)
+        .service(handlers::invalidate_cache)
+        .service(streaming::stream_events)
+        .service(streaming::stream_ws);
 }
@@ -0,0 +1,203 @@
+use actix_web::{get, http::header, rt, web, HttpRequest, HttpResponse};
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
+use futures::{stream, StreamExt};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time::{interval_at, Instant, Interval};
+
+use super::auth::{bearer_token, unauthorized};
+use crate::{
+    errors::Error,
+    streaming::{
+        hub::{EventHub, ServerMessage, Subscriber},
+        StreamAuth, Topic,
+    },
+};
+
+#[derive(Debug, Deserialize)]
+pub struct StreamParams {
+    /// Comma separated topics to subscribe to on connect, e.g. `ticker:BTC/USD,fills`
+    subscribe: Option<String>,
+    /// Stream token, for clients that cannot send an `Authorization` header
+    token: Option<String>,
+}
+
+/// Server-Sent Events stream of the topics in `subscribe`
+#[get("/stream/events")]
+pub async fn stream_events(
+    req: HttpRequest,
+    hub: Option<web::Data<EventHub>>,
+    auth: Option<web::Data<StreamAuth>>,
+    params: web::Query<StreamParams>,
+) -> HttpResponse {
+    let Some(hub) = hub else {
+        return streaming_unavailable();
+    };
+    let subscriber = match open(&req, &hub, auth.as_ref(), &params, "sse") {
+        Ok(subscriber) => subscriber,
+        Err(e) => return stream_error(e),
+    };
+
+    let subscribed = sse_frame("subscribed", &subscribed(&subscriber));
+    let events = stream::unfold(
+        (Some(subscriber), keepalive(hub.keepalive())),
+        |(subscriber, mut keepalive)| async move {
+            let mut subscriber = subscriber?;
+            tokio::select! {
+                delivery = subscriber.recv() => Some(match delivery {
+                    Some(delivery) => (
+                        sse_frame(delivery.name(), &delivery.payload()),
+                        (Some(subscriber), keepalive),
+                    ),
+                    // End the response after telling the client why
+                    None => (sse_frame("error", &too_slow()), (None, keepalive)),
+                }),
+                _ = keepalive.tick() => Some((
+                    web::Bytes::from_static(b": keep-alive\n\n"),
+                    (Some(subscriber), keepalive),
+                )),
+            }
+        },
+    );
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // Keep reverse proxies from buffering the stream
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(
+            stream::once(async { subscribed })
+                .chain(events)
+                .map(Ok::<_, actix_web::Error>),
+        )
+}
+
+/// WebSocket stream; clients change their topics with `subscribe` and `unsubscribe` messages
+#[get("/stream/ws")]
+pub async fn stream_ws(
+    req: HttpRequest,
+    body: web::Payload,
+    hub: Option<web::Data<EventHub>>,
+    auth: Option<web::Data<StreamAuth>>,
+    params: web::Query<StreamParams>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(hub) = hub else {
+        return Ok(streaming_unavailable());
+    };
+    let subscriber = match open(&req, &hub, auth.as_ref(), &params, "ws") {
+        Ok(subscriber) => subscriber,
+        Err(e) => return Ok(stream_error(e)),
+    };
+    let (response, session, messages) = actix_ws::handle(&req, body)?;
+    rt::spawn(serve_ws(
+        session,
+        messages.max_frame_size(64 * 1024),
+        subscriber,
+        hub.keepalive(),
+    ));
+    Ok(response)
+}
+
+async fn serve_ws(
+    mut session: Session,
+    mut messages: MessageStream,
+    mut subscriber: Subscriber,
+    keepalive_interval: Duration,
+) {
+    if session.text(subscribed(&subscriber)).await.is_err() {
+        return;
+    }
+    let mut keepalive = keepalive(keepalive_interval);
+    let reason = loop {
+        tokio::select! {
+            message = messages.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    if session.text(subscriber.handle(&text)).await.is_err() {
+                        break None;
+                    }
+                }
+                Some(Ok(Message::Ping(payload))) => {
+                    if session.pong(&payload).await.is_err() {
+                        break None;
+                    }
+                }
+                Some(Ok(Message::Close(reason))) => break reason,
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => break None,
+            },
+            delivery = subscriber.recv() => match delivery {
+                Some(delivery) => {
+                    if session.text(delivery.payload()).await.is_err() {
+                        break None;
+                    }
+                }
+                None => {
+                    let _ = session.text(too_slow()).await;
+                    break Some(CloseReason {
+                        code: CloseCode::Policy,
+                        description: Some("Too slow".to_string()),
+                    });
+                }
+            },
+            _ = keepalive.tick() => {
+                if session.ping(b"").await.is_err() {
+                    break None;
+                }
+            }
+        }
+    };
+    let _ = session.close(reason).await;
+}
+
+/// Authorize the client and subscribe it to the topics of the query
+fn open(
+    req: &HttpRequest,
+    hub: &web::Data<EventHub>,
+    auth: Option<&web::Data<StreamAuth>>,
+    params: &StreamParams,
+    transport: &'static str,
+) -> Result<Subscriber, Error> {
+    let token = bearer_token(req).or(params.token.as_deref());
+    let authorized = match auth {
+        Some(auth) => auth.authorize(token)?,
+        None => false,
+    };
+    let topics = Topic::parse_list(params.subscribe.as_deref().unwrap_or_default())?;
+    let subscriber = hub.clone().into_inner().connect(authorized, transport)?;
+    subscriber.subscribe(&topics)?;
+    Ok(subscriber)
+}
+
+fn keepalive(period: Duration) -> Interval {
+    interval_at(Instant::now() + period, period)
+}
+
+fn subscribed(subscriber: &Subscriber) -> String {
+    ServerMessage::Subscribed {
+        topics: subscriber.topics().iter().map(Topic::to_string).collect(),
+    }
+    .to_json()
+}
+
+fn too_slow() -> String {
+    ServerMessage::Error {
+        message: "Disconnected for falling behind the stream".to_string(),
+    }
+    .to_json()
+}
+
+fn sse_frame(event: &str, data: &str) -> web::Bytes {
+    web::Bytes::from(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+fn stream_error(e: Error) -> HttpResponse {
+    match e {
+        Error::Auth(_) => unauthorized(e),
+        Error::InvalidParameter(_) => HttpResponse::BadRequest().body(e.to_string()),
+        Error::RateLimitExceeded(_) => HttpResponse::ServiceUnavailable().body(e.to_string()),
+        _ => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+fn streaming_unavailable() -> HttpResponse {
+    HttpResponse::ServiceUnavailable().body("Streaming is not enabled")
+}
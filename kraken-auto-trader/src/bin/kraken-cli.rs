@@ -0,0 +1,45 @@
+use clap::Parser;
+use dotenv::dotenv;
+use kraken_auto_trader::{
+    cli::{Cli, CliContext, Command},
+    utils::app_config::AppConfig,
+};
+use tracing_subscriber::EnvFilter;
+
+#[tokio::main]
+async fn main() {
+    dotenv().ok();
+    let cli = Cli::parse();
+    let config = match AppConfig::load(&cli.config) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    // Logs go to stderr so they never mix with the output of the command, and are off by
+    // default while the dashboard owns the terminal
+    let default_filter = match cli.command {
+        Command::Tui(_) => "off",
+        _ => "warn",
+    };
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_filter)),
+        )
+        .init();
+
+    let result = match CliContext::from_config(&config) {
+        Ok(ctx) => cli.run(&ctx).await,
+        Err(e) => Err(e),
+    };
+    match result.and_then(|output| output.render(cli.output)) {
+        Ok(text) => print!("{}", text),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
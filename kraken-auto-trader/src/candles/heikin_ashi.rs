@@ -0,0 +1,71 @@
+use crate::models::market::Candle;
+
+/// Turns regular candles into Heikin-Ashi candles, one at a time
+#[derive(Debug, Clone, Default)]
+pub struct HeikinAshi {
+    previous: Option<Candle>,
+}
+
+impl HeikinAshi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Transform the next candle
+    pub fn next(&mut self, candle: &Candle) -> Candle {
+        let close = (candle.open + candle.high + candle.low + candle.close) / 4.0;
+        let open = match self.previous {
+            Some(previous) => (previous.open + previous.close) / 2.0,
+            None => (candle.open + candle.close) / 2.0,
+        };
+        let ha = Candle {
+            open,
+            high: candle.high.max(open).max(close),
+            low: candle.low.min(open).min(close),
+            close,
+            ..*candle
+        };
+        self.previous = Some(ha);
+        ha
+    }
+
+    /// Transform a series of candles, oldest first
+    pub fn series(candles: &[Candle]) -> Vec<Candle> {
+        let mut heikin_ashi = Self::new();
+        candles
+            .iter()
+            .map(|candle| heikin_ashi.next(candle))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heikin_ashi() {
+        let candles = [
+            Candle {
+                time: 0,
+                open: 10.0,
+                high: 14.0,
+                low: 8.0,
+                close: 12.0,
+                ..Candle::default()
+            },
+            Candle {
+                time: 60,
+                open: 12.0,
+                high: 16.0,
+                low: 11.0,
+                close: 15.0,
+                ..Candle::default()
+            },
+        ];
+        let ha = HeikinAshi::series(&candles);
+        assert_eq!((ha[0].open, ha[0].close), (11.0, 11.0));
+        assert_eq!((ha[1].open, ha[1].close), (11.0, 13.5));
+        assert_eq!((ha[1].high, ha[1].low), (16.0, 11.0));
+    }
+}
@@ -0,0 +1,262 @@
+//! Candles built locally from trades, at any timeframe or as alternative bars.
+
+pub mod heikin_ashi;
+pub mod renko;
+pub mod threshold;
+pub mod time_bars;
+
+pub use heikin_ashi::HeikinAshi;
+pub use renko::RenkoBuilder;
+pub use threshold::{BarThreshold, ThresholdBarBuilder};
+pub use time_bars::{resample, TimeBarBuilder};
+
+use crate::{
+    errors::Error,
+    models::market::{Candle, Trade},
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt, str::FromStr};
+
+/// Intervals served by the OHLC endpoint, in minutes
+const KRAKEN_INTERVALS: [u64; 9] = [1, 5, 15, 30, 60, 240, 1440, 10080, 21600];
+
+/// A single trade with numeric values
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Tick {
+    /// Unix time in seconds
+    pub time: f64,
+    pub price: f64,
+    pub volume: f64,
+}
+
+impl From<&Trade> for Tick {
+    fn from(trade: &Trade) -> Self {
+        Self {
+            time: trade.time,
+            price: trade.price.parse().unwrap_or_default(),
+            volume: trade.volume.parse().unwrap_or_default(),
+        }
+    }
+}
+
+/// Duration of a time bar, written like `3m`, `2h`, `12h` or `1d`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Timeframe(u64);
+
+impl Timeframe {
+    pub fn from_secs(secs: u64) -> Self {
+        Self(secs.max(1))
+    }
+
+    pub fn minutes(minutes: u64) -> Self {
+        Self::from_secs(minutes * 60)
+    }
+
+    pub fn hours(hours: u64) -> Self {
+        Self::from_secs(hours * 3600)
+    }
+
+    pub fn secs(&self) -> u64 {
+        self.0
+    }
+
+    /// Start time of the bar containing `time`
+    pub fn bar_start(&self, time: f64) -> i64 {
+        (time / self.0 as f64).floor() as i64 * self.0 as i64
+    }
+
+    /// Matching interval of the OHLC endpoint, in minutes, if there is one
+    pub fn kraken_interval(&self) -> Option<u32> {
+        self.0
+            .is_multiple_of(60)
+            .then_some(self.0 / 60)
+            .filter(|minutes| KRAKEN_INTERVALS.contains(minutes))
+            .map(|minutes| minutes as u32)
+    }
+}
+
+impl FromStr for Timeframe {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (count, unit) = s.split_at(split);
+        let count: u64 = count
+            .parse()
+            .map_err(|_| Error::InvalidParameter(format!("Invalid timeframe {}", s)))?;
+        let unit_secs = match unit {
+            "s" => 1,
+            "m" | "" => 60,
+            "h" => 3600,
+            "d" => 86_400,
+            "w" => 604_800,
+            _ => return Err(Error::InvalidParameter(format!("Invalid timeframe {}", s))),
+        };
+        if count == 0 {
+            return Err(Error::InvalidParameter(format!("Invalid timeframe {}", s)));
+        }
+        Ok(Self(count * unit_secs))
+    }
+}
+
+impl fmt::Display for Timeframe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (unit, secs) in [("w", 604_800), ("d", 86_400), ("h", 3600), ("m", 60)] {
+            if self.0.is_multiple_of(secs) {
+                return write!(f, "{}{}", self.0 / secs, unit);
+            }
+        }
+        write!(f, "{}s", self.0)
+    }
+}
+
+/// The most recent candles up to a fixed capacity, oldest first
+#[derive(Debug, Clone)]
+pub struct CandleHistory {
+    capacity: usize,
+    candles: VecDeque<Candle>,
+}
+
+impl CandleHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            candles: VecDeque::new(),
+        }
+    }
+
+    /// Append a candle, dropping the oldest one when full
+    pub fn push(&mut self, candle: Candle) {
+        if self.candles.len() == self.capacity {
+            self.candles.pop_front();
+        }
+        self.candles.push_back(candle);
+    }
+
+    pub fn len(&self) -> usize {
+        self.candles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candles.is_empty()
+    }
+
+    pub fn last(&self) -> Option<&Candle> {
+        self.candles.back()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Candle> {
+        self.candles.iter()
+    }
+
+    /// Candles starting at or after `time`
+    pub fn since(&self, time: i64) -> Vec<Candle> {
+        let start = self.candles.partition_point(|candle| candle.time < time);
+        self.candles.range(start..).copied().collect()
+    }
+
+    /// The `count` most recent candles
+    pub fn latest(&self, count: usize) -> Vec<Candle> {
+        let start = self.candles.len().saturating_sub(count);
+        self.candles.range(start..).copied().collect()
+    }
+}
+
+impl Default for CandleHistory {
+    /// A year of minute candles
+    fn default() -> Self {
+        Self::new(525_600)
+    }
+}
+
+/// A bar being built from ticks, tolerant to ticks arriving out of order
+#[derive(Debug, Clone)]
+pub(crate) struct OpenBar {
+    candle: Candle,
+    first_time: f64,
+    last_time: f64,
+    price_volume: f64,
+}
+
+impl OpenBar {
+    pub fn new(time: i64, tick: &Tick) -> Self {
+        Self {
+            candle: Candle {
+                time,
+                open: tick.price,
+                high: tick.price,
+                low: tick.price,
+                close: tick.price,
+                vwap: tick.price,
+                volume: 0.0,
+                count: 0,
+            },
+            first_time: tick.time,
+            last_time: tick.time,
+            price_volume: 0.0,
+        }
+    }
+
+    /// Add a tick; a late tick can still become the open and a later one the close
+    pub fn add(&mut self, tick: &Tick) {
+        let candle = &mut self.candle;
+        if tick.time < self.first_time {
+            self.first_time = tick.time;
+            candle.open = tick.price;
+        }
+        if tick.time >= self.last_time {
+            self.last_time = tick.time;
+            candle.close = tick.price;
+        }
+        candle.high = candle.high.max(tick.price);
+        candle.low = candle.low.min(tick.price);
+        candle.volume += tick.volume;
+        candle.count += 1;
+        self.price_volume += tick.price * tick.volume;
+        if candle.volume > 0.0 {
+            candle.vwap = self.price_volume / candle.volume;
+        }
+    }
+
+    pub fn candle(&self) -> Candle {
+        self.candle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeframe_parsing() {
+        assert_eq!("3m".parse::<Timeframe>().unwrap(), Timeframe::minutes(3));
+        assert_eq!("12h".parse::<Timeframe>().unwrap(), Timeframe::hours(12));
+        assert_eq!("1d".parse::<Timeframe>().unwrap().secs(), 86_400);
+        assert!("0m".parse::<Timeframe>().is_err());
+        assert!("5x".parse::<Timeframe>().is_err());
+        assert_eq!(Timeframe::hours(2).to_string(), "2h");
+        assert_eq!(Timeframe::from_secs(90).to_string(), "90s");
+    }
+
+    #[test]
+    fn test_kraken_interval() {
+        assert_eq!(Timeframe::minutes(15).kraken_interval(), Some(15));
+        assert_eq!(Timeframe::hours(4).kraken_interval(), Some(240));
+        assert_eq!(Timeframe::minutes(3).kraken_interval(), None);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = CandleHistory::new(3);
+        for time in 0..5 {
+            history.push(Candle {
+                time,
+                ..Candle::default()
+            });
+        }
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.since(3).len(), 2);
+        assert_eq!(history.latest(1)[0].time, 4);
+    }
+}
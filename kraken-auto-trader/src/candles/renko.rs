@@ -0,0 +1,121 @@
+use super::{CandleHistory, Tick};
+use crate::models::market::{Candle, Trade};
+
+/// Builds Renko bricks of a fixed size from trade prices.
+///
+/// A brick continues the trend when the price moves one brick beyond the last close, and
+/// reverses it when the price moves one brick beyond the last open.
+#[derive(Debug, Clone)]
+pub struct RenkoBuilder {
+    brick: f64,
+    anchor: Option<f64>,
+    last: Option<Candle>,
+    volume: f64,
+    count: i32,
+    history: CandleHistory,
+}
+
+impl RenkoBuilder {
+    pub fn new(brick: f64) -> Self {
+        Self {
+            brick,
+            anchor: None,
+            last: None,
+            volume: 0.0,
+            count: 0,
+            history: CandleHistory::default(),
+        }
+    }
+
+    /// Keep at most `capacity` bricks
+    pub fn with_history(mut self, capacity: usize) -> Self {
+        self.history = CandleHistory::new(capacity);
+        self
+    }
+
+    /// Add a trade, returning the bricks it completed
+    pub fn push(&mut self, tick: Tick) -> Vec<Candle> {
+        self.volume += tick.volume;
+        self.count += 1;
+        let Some(anchor) = self.anchor else {
+            self.anchor = Some(tick.price);
+            return Vec::new();
+        };
+        if self.brick <= 0.0 {
+            return Vec::new();
+        }
+
+        let mut bricks = Vec::new();
+        loop {
+            let (top, bottom) = match self.last {
+                Some(last) => (last.open.max(last.close), last.open.min(last.close)),
+                None => (anchor, anchor),
+            };
+            let (open, close) = if tick.price >= top + self.brick {
+                (top, top + self.brick)
+            } else if tick.price <= bottom - self.brick {
+                (bottom, bottom - self.brick)
+            } else {
+                break;
+            };
+            let brick = Candle {
+                time: tick.time as i64,
+                open,
+                high: open.max(close),
+                low: open.min(close),
+                close,
+                vwap: close,
+                volume: std::mem::take(&mut self.volume),
+                count: std::mem::take(&mut self.count),
+            };
+            self.history.push(brick);
+            self.last = Some(brick);
+            bricks.push(brick);
+        }
+        bricks
+    }
+
+    /// Add a batch of trades
+    pub fn push_trades(&mut self, trades: &[Trade]) -> Vec<Candle> {
+        trades
+            .iter()
+            .flat_map(|trade| self.push(Tick::from(trade)))
+            .collect()
+    }
+
+    /// Completed bricks, oldest first
+    pub fn history(&self) -> &CandleHistory {
+        &self.history
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(builder: &mut RenkoBuilder, price: f64) -> Vec<(f64, f64)> {
+        builder
+            .push(Tick {
+                time: 0.0,
+                price,
+                volume: 1.0,
+            })
+            .iter()
+            .map(|brick| (brick.open, brick.close))
+            .collect()
+    }
+
+    #[test]
+    fn test_renko_bricks() {
+        let mut renko = RenkoBuilder::new(10.0);
+        assert!(price(&mut renko, 100.0).is_empty());
+        assert_eq!(
+            price(&mut renko, 125.0),
+            vec![(100.0, 110.0), (110.0, 120.0)]
+        );
+        // Less than a brick below the last open does not reverse
+        assert!(price(&mut renko, 105.0).is_empty());
+        assert_eq!(price(&mut renko, 100.0), vec![(110.0, 100.0)]);
+        assert_eq!(renko.history().len(), 3);
+    }
+}
@@ -0,0 +1,128 @@
+use super::{CandleHistory, OpenBar, Tick};
+use crate::models::market::{Candle, Trade};
+
+/// What closes a threshold bar
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BarThreshold {
+    /// A fixed number of trades
+    Ticks(u64),
+    /// A fixed traded volume, in base currency
+    Volume(f64),
+    /// A fixed traded value, in quote currency
+    Dollar(f64),
+}
+
+impl BarThreshold {
+    fn amount(&self, tick: &Tick) -> f64 {
+        match self {
+            BarThreshold::Ticks(_) => 1.0,
+            BarThreshold::Volume(_) => tick.volume,
+            BarThreshold::Dollar(_) => tick.price * tick.volume,
+        }
+    }
+
+    fn limit(&self) -> f64 {
+        match *self {
+            BarThreshold::Ticks(ticks) => ticks as f64,
+            BarThreshold::Volume(volume) => volume,
+            BarThreshold::Dollar(value) => value,
+        }
+    }
+}
+
+/// Builds tick, volume or dollar bars: a bar closes once the trades it holds reach the
+/// threshold. A single trade is never split, so bars can overshoot the threshold.
+#[derive(Debug, Clone)]
+pub struct ThresholdBarBuilder {
+    threshold: BarThreshold,
+    current: Option<OpenBar>,
+    progress: f64,
+    history: CandleHistory,
+}
+
+impl ThresholdBarBuilder {
+    pub fn new(threshold: BarThreshold) -> Self {
+        Self {
+            threshold,
+            current: None,
+            progress: 0.0,
+            history: CandleHistory::default(),
+        }
+    }
+
+    /// Keep at most `capacity` closed bars
+    pub fn with_history(mut self, capacity: usize) -> Self {
+        self.history = CandleHistory::new(capacity);
+        self
+    }
+
+    /// Add a trade, returning the bar it closed. Bars are timed by their first trade.
+    pub fn push(&mut self, tick: Tick) -> Option<Candle> {
+        self.current
+            .get_or_insert_with(|| OpenBar::new(tick.time as i64, &tick))
+            .add(&tick);
+        self.progress += self.threshold.amount(&tick);
+        if self.progress < self.threshold.limit() {
+            return None;
+        }
+        self.progress = 0.0;
+        let candle = self.current.take()?.candle();
+        self.history.push(candle);
+        Some(candle)
+    }
+
+    /// Add a batch of trades
+    pub fn push_trades(&mut self, trades: &[Trade]) -> Vec<Candle> {
+        trades
+            .iter()
+            .filter_map(|trade| self.push(Tick::from(trade)))
+            .collect()
+    }
+
+    /// The bar still being built
+    pub fn current(&self) -> Option<Candle> {
+        self.current.as_ref().map(OpenBar::candle)
+    }
+
+    /// Closed bars, oldest first
+    pub fn history(&self) -> &CandleHistory {
+        &self.history
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(time: f64, price: f64, volume: f64) -> Tick {
+        Tick {
+            time,
+            price,
+            volume,
+        }
+    }
+
+    #[test]
+    fn test_tick_bars() {
+        let mut builder = ThresholdBarBuilder::new(BarThreshold::Ticks(2));
+        assert!(builder.push(tick(1.0, 10.0, 1.0)).is_none());
+        let bar = builder.push(tick(2.0, 11.0, 1.0)).unwrap();
+        assert_eq!(
+            (bar.time, bar.open, bar.close, bar.count),
+            (1, 10.0, 11.0, 2)
+        );
+        assert!(builder.current().is_none());
+    }
+
+    #[test]
+    fn test_volume_and_dollar_bars() {
+        let mut volume = ThresholdBarBuilder::new(BarThreshold::Volume(3.0));
+        assert!(volume.push(tick(1.0, 10.0, 2.0)).is_none());
+        assert_eq!(volume.push(tick(2.0, 10.0, 5.0)).unwrap().volume, 7.0);
+
+        let mut dollar = ThresholdBarBuilder::new(BarThreshold::Dollar(100.0));
+        assert!(dollar.push(tick(1.0, 10.0, 5.0)).is_none());
+        assert!(dollar.push(tick(2.0, 20.0, 2.5)).is_some());
+        assert_eq!(dollar.history().len(), 1);
+    }
+}
@@ -0,0 +1,284 @@
+use super::{CandleHistory, OpenBar, Tick, Timeframe};
+use crate::{
+    clock,
+    models::market::{Candle, Trade},
+};
+use std::collections::BTreeMap;
+
+/// Builds time bars of any timeframe from trades.
+///
+/// Bars stay open for `grace` seconds after their end so that late trades revise them
+/// instead of being lost. A bar closes once a trade at least `grace` seconds past its end
+/// has been seen, or when [`TimeBarBuilder::flush`] is called with such a time. Trades
+/// for bars that already closed are dropped and counted. Periods without trades produce
+/// flat bars at the previous close, as the OHLC endpoint does.
+#[derive(Debug, Clone)]
+pub struct TimeBarBuilder {
+    timeframe: Timeframe,
+    grace: f64,
+    open: BTreeMap<i64, OpenBar>,
+    last_closed: Option<Candle>,
+    watermark: f64,
+    late_trades: u64,
+    history: CandleHistory,
+}
+
+impl TimeBarBuilder {
+    pub fn new(timeframe: Timeframe) -> Self {
+        Self {
+            timeframe,
+            grace: 0.0,
+            open: BTreeMap::new(),
+            last_closed: None,
+            watermark: f64::MIN,
+            late_trades: 0,
+            history: CandleHistory::default(),
+        }
+    }
+
+    /// Keep bars open for `secs` after their end to absorb late trades
+    pub fn with_grace(mut self, secs: f64) -> Self {
+        self.grace = secs.max(0.0);
+        self
+    }
+
+    /// Keep at most `capacity` closed bars
+    pub fn with_history(mut self, capacity: usize) -> Self {
+        self.history = CandleHistory::new(capacity);
+        self
+    }
+
+    /// Start from existing candles, e.g. from the OHLC endpoint, oldest first
+    pub fn seed(&mut self, candles: &[Candle]) {
+        for candle in candles {
+            if self.last_closed.is_none_or(|last| candle.time > last.time) {
+                self.history.push(*candle);
+                self.last_closed = Some(*candle);
+            }
+        }
+    }
+
+    pub fn timeframe(&self) -> Timeframe {
+        self.timeframe
+    }
+
+    /// Add a trade, returning the bars it closed
+    pub fn push(&mut self, tick: Tick) -> Vec<Candle> {
+        let start = self.timeframe.bar_start(tick.time);
+        if self.last_closed.is_some_and(|last| start <= last.time) {
+            self.late_trades += 1;
+            return Vec::new();
+        }
+        self.open
+            .entry(start)
+            .or_insert_with(|| OpenBar::new(start, &tick))
+            .add(&tick);
+        self.watermark = self.watermark.max(tick.time);
+        self.close_until(self.watermark - self.grace)
+    }
+
+    /// Add a batch of trades, e.g. a page of the Trades endpoint
+    pub fn push_trades(&mut self, trades: &[Trade]) -> Vec<Candle> {
+        trades
+            .iter()
+            .flat_map(|trade| self.push(Tick::from(trade)))
+            .collect()
+    }
+
+    /// Close the bars that ended at least `grace` seconds before `now`, even without new trades
+    pub fn flush(&mut self, now: f64) -> Vec<Candle> {
+        self.close_until(now - self.grace)
+    }
+
+    /// Close the bars that ended by now on Kraken's clock
+    pub fn flush_now(&mut self) -> Vec<Candle> {
+        self.flush(clock::unix_time())
+    }
+
+    /// The bar still being built for the latest trades
+    pub fn current(&self) -> Option<Candle> {
+        self.open.values().next_back().map(OpenBar::candle)
+    }
+
+    /// Closed bars, oldest first
+    pub fn history(&self) -> &CandleHistory {
+        &self.history
+    }
+
+    /// Number of trades dropped because their bar had already closed
+    pub fn late_trades(&self) -> u64 {
+        self.late_trades
+    }
+
+    fn close_until(&mut self, time: f64) -> Vec<Candle> {
+        let secs = self.timeframe.secs() as i64;
+        let mut closed = Vec::new();
+        while let Some((&start, _)) = self.open.first_key_value() {
+            if (start + secs) as f64 > time {
+                break;
+            }
+            self.fill_gaps(start, &mut closed);
+            if let Some(bar) = self.open.remove(&start) {
+                self.close(bar.candle(), &mut closed);
+            }
+        }
+
+        let complete = self.timeframe.bar_start(time);
+        let until = self
+            .open
+            .keys()
+            .next()
+            .map_or(complete, |start| (*start).min(complete));
+        self.fill_gaps(until, &mut closed);
+        closed
+    }
+
+    /// Close flat bars for the periods without trades before `until`
+    fn fill_gaps(&mut self, until: i64, closed: &mut Vec<Candle>) {
+        let secs = self.timeframe.secs() as i64;
+        while let Some(last) = self.last_closed {
+            let start = last.time + secs;
+            if start >= until {
+                break;
+            }
+            let flat = Candle {
+                time: start,
+                open: last.close,
+                high: last.close,
+                low: last.close,
+                close: last.close,
+                vwap: last.close,
+                volume: 0.0,
+                count: 0,
+            };
+            self.close(flat, closed);
+        }
+    }
+
+    fn close(&mut self, candle: Candle, closed: &mut Vec<Candle>) {
+        self.history.push(candle);
+        self.last_closed = Some(candle);
+        closed.push(candle);
+    }
+}
+
+/// Aggregate candles into a longer timeframe, e.g. 1 minute candles into 3 minute ones.
+///
+/// The last bar is included even if the input ends before it is complete.
+pub fn resample(candles: &[Candle], timeframe: Timeframe) -> Vec<Candle> {
+    let mut resampled: Vec<Candle> = Vec::new();
+    for candle in candles {
+        let start = timeframe.bar_start(candle.time as f64);
+        match resampled.last_mut() {
+            Some(bar) if bar.time == start => {
+                let volume = bar.volume + candle.volume;
+                if volume > 0.0 {
+                    bar.vwap = (bar.vwap * bar.volume + candle.vwap * candle.volume) / volume;
+                }
+                bar.high = bar.high.max(candle.high);
+                bar.low = bar.low.min(candle.low);
+                bar.close = candle.close;
+                bar.volume = volume;
+                bar.count += candle.count;
+            }
+            _ => resampled.push(Candle {
+                time: start,
+                ..*candle
+            }),
+        }
+    }
+    resampled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(time: f64, price: f64, volume: f64) -> Tick {
+        Tick {
+            time,
+            price,
+            volume,
+        }
+    }
+
+    #[test]
+    fn test_time_bars() {
+        let mut builder = TimeBarBuilder::new(Timeframe::minutes(3));
+        assert!(builder.push(tick(0.0, 10.0, 1.0)).is_empty());
+        assert!(builder.push(tick(100.0, 12.0, 1.0)).is_empty());
+        assert!(builder.push(tick(170.0, 9.0, 2.0)).is_empty());
+        assert_eq!(builder.current().unwrap().close, 9.0);
+
+        // A trade two bars later closes the first bar and a flat one for the gap
+        let closed = builder.push(tick(400.0, 11.0, 1.0));
+        assert_eq!(closed.len(), 2);
+        let bar = closed[0];
+        assert_eq!(
+            (bar.time, bar.open, bar.high, bar.low, bar.close),
+            (0, 10.0, 12.0, 9.0, 9.0)
+        );
+        assert_eq!(bar.volume, 4.0);
+        assert_eq!(bar.vwap, 10.0);
+        assert_eq!(bar.count, 3);
+        assert_eq!(
+            (closed[1].time, closed[1].close, closed[1].volume),
+            (180, 9.0, 0.0)
+        );
+        assert_eq!(builder.history().len(), 2);
+    }
+
+    #[test]
+    fn test_late_trades_revise_open_bar() {
+        let mut builder = TimeBarBuilder::new(Timeframe::minutes(1)).with_grace(10.0);
+        builder.push(tick(30.0, 10.0, 1.0));
+        builder.push(tick(65.0, 11.0, 1.0));
+        // Earlier than the first trade of the bar, but within the grace period
+        builder.push(tick(5.0, 8.0, 1.0));
+        let closed = builder.push(tick(75.0, 11.0, 1.0));
+        assert_eq!(closed.len(), 1);
+        assert_eq!(
+            (closed[0].open, closed[0].low, closed[0].close),
+            (8.0, 8.0, 10.0)
+        );
+
+        assert!(builder.push(tick(20.0, 1.0, 1.0)).is_empty());
+        assert_eq!(builder.late_trades(), 1);
+    }
+
+    #[test]
+    fn test_flush_without_trades() {
+        let mut builder = TimeBarBuilder::new(Timeframe::minutes(1));
+        builder.push(tick(10.0, 10.0, 1.0));
+        assert!(builder.flush(59.0).is_empty());
+        let closed = builder.flush(185.0);
+        assert_eq!(
+            closed.iter().map(|c| c.time).collect::<Vec<_>>(),
+            vec![0, 60, 120]
+        );
+    }
+
+    #[test]
+    fn test_resample() {
+        let candles: Vec<Candle> = (0..6)
+            .map(|i| Candle {
+                time: i * 60,
+                open: i as f64,
+                high: i as f64 + 1.0,
+                low: i as f64 - 1.0,
+                close: i as f64 + 0.5,
+                vwap: i as f64,
+                volume: 1.0,
+                count: 1,
+            })
+            .collect();
+        let resampled = resample(&candles, Timeframe::minutes(3));
+        assert_eq!(resampled.len(), 2);
+        let bar = resampled[1];
+        assert_eq!(
+            (bar.time, bar.open, bar.high, bar.low, bar.close),
+            (180, 3.0, 6.0, 2.0, 5.5)
+        );
+        assert_eq!((bar.volume, bar.vwap, bar.count), (3.0, 4.0, 3));
+    }
+}
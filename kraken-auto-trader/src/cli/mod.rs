@@ -0,0 +1,758 @@
+//! Commands of the `kraken-cli` binary, for scripts and quick checks.
+//!
+//! Every command is a call to one of the [`services`](crate::services), printed as a table, as
+//! CSV, or as the JSON Kraken returned. The configuration is loaded like the server's, so
+//! `--profile` selects a `[profiles.<name>]` table of the file, and with it the keystore and
+//! nonce settings the private commands sign with. When a keystore is configured, it also picks
+//! the key profile of that name, which then signs every private command.
+
+pub mod output;
+pub mod tui;
+
+use crate::{
+    client::{
+        kraken_apis::{PrivateApi, PrivateApiBuilder},
+        kraken_client::KrakenClient,
+    },
+    errors::Error,
+    instruments::registry::InstrumentRegistry,
+    middleware::KrakenClientState,
+    models::{
+        account::{ExportReport, Order},
+        symbols::{AssetId, Pair},
+        trading::{NewOrder, OrderSide, OrderType},
+    },
+    services::{
+        account_details::Account, funding::Funding, market_data::MarketData, trading::Trading,
+    },
+    utils::{
+        app_config::{AppConfig, ConfigArgs},
+        endpoints::{
+            account::REQUEST_EXPORT_REPORT,
+            funding::DEPOSIT_ADDRESSES,
+            trading::{CANCEL_ALL_ORDERS, CANCEL_ORDER},
+        },
+        time::format_rfc3339,
+    },
+};
+use clap::{Parser, Subcommand};
+use output::{Output, OutputFormat, Table};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::{collections::HashMap, time::Instant};
+
+/// Command line client of the Kraken API
+#[derive(Debug, Parser)]
+#[command(name = "kraken-cli", version)]
+pub struct Cli {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+
+    /// How results are printed
+    #[arg(long, short = 'o', value_enum, default_value_t = OutputFormat::Table)]
+    pub output: OutputFormat,
+
+    /// Have Kraken validate orders without placing them, and send no other change to the account
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+impl Cli {
+    pub async fn run(&self, ctx: &CliContext) -> Result<Output, Error> {
+        self.command.run(ctx, self.dry_run).await
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Best bid and ask, last trade and daily range of pairs
+    Ticker {
+        #[arg(required = true)]
+        pairs: Vec<String>,
+    },
+    /// Order book of a pair, asks above bids
+    Book {
+        pair: String,
+        /// Price levels on each side
+        #[arg(long, default_value_t = 10)]
+        depth: u32,
+    },
+    /// Candles of a pair
+    Ohlc {
+        pair: String,
+        /// Candle length in minutes
+        #[arg(long, default_value_t = 1)]
+        interval: u32,
+        /// Only candles after this Unix time
+        #[arg(long)]
+        since: Option<u64>,
+    },
+    /// Balance of every asset held
+    Balance {
+        /// Include assets with a zero balance
+        #[arg(long)]
+        all: bool,
+    },
+    /// Open orders, or closed ones
+    Orders {
+        #[arg(long)]
+        closed: bool,
+        /// Only orders with this user reference
+        #[arg(long)]
+        userref: Option<String>,
+    },
+    /// Trade history
+    Trades {
+        /// Unix time or trade id to start from
+        #[arg(long)]
+        start: Option<i64>,
+        /// Unix time or trade id to end at
+        #[arg(long)]
+        end: Option<i64>,
+        /// Offset of the first trade, for paging
+        #[arg(long)]
+        ofs: Option<i64>,
+    },
+    /// Ledger entries
+    Ledger {
+        /// Only entries of this asset, may be repeated
+        #[arg(long = "asset")]
+        assets: Vec<String>,
+        /// Only entries of this type, e.g. trade, deposit or withdrawal
+        #[arg(long = "type")]
+        kind: Option<String>,
+        #[arg(long)]
+        start: Option<i64>,
+        #[arg(long)]
+        end: Option<i64>,
+        #[arg(long)]
+        ofs: Option<i64>,
+    },
+    /// Place an order
+    Place(PlaceArgs),
+    /// Cancel an order by txid, user reference or client order id
+    Cancel {
+        #[arg(required_unless_present = "cl_ord_id")]
+        txid: Option<String>,
+        #[arg(long, conflicts_with = "txid")]
+        cl_ord_id: Option<String>,
+    },
+    /// Cancel every open order
+    CancelAll,
+    /// Deposit addresses of an asset
+    DepositAddress {
+        asset: String,
+        /// Deposit method, the first one Kraken offers for the asset by default
+        #[arg(long)]
+        method: Option<String>,
+        /// Generate a new address
+        #[arg(long)]
+        new: bool,
+    },
+    /// Request an export report, or check on one
+    #[command(subcommand)]
+    Export(ExportCommand),
+    /// Exchange status and server time
+    Status,
+    /// Live dashboard of a market, the account and the server's strategies
+    Tui(tui::TuiArgs),
+}
+
+#[derive(Debug, clap::Args)]
+pub struct PlaceArgs {
+    /// buy or sell
+    #[arg(value_parser = from_name::<OrderSide>)]
+    pub side: OrderSide,
+    pub pair: String,
+    pub volume: String,
+    /// Order type, e.g. market, limit or stop-loss
+    #[arg(long = "type", default_value = "market", value_parser = from_name::<OrderType>)]
+    pub ordertype: OrderType,
+    #[arg(long)]
+    pub price: Option<String>,
+    #[arg(long)]
+    pub price2: Option<String>,
+    /// Comma separated order flags, e.g. post,fciq
+    #[arg(long)]
+    pub oflags: Option<String>,
+    /// GTC, IOC or GTD
+    #[arg(long)]
+    pub timeinforce: Option<String>,
+    #[arg(long, conflicts_with = "cl_ord_id")]
+    pub userref: Option<i32>,
+    #[arg(long)]
+    pub cl_ord_id: Option<String>,
+    #[arg(long)]
+    pub reduce_only: bool,
+}
+
+impl PlaceArgs {
+    fn to_order(&self, validate: bool) -> NewOrder {
+        NewOrder {
+            price: self.price.clone(),
+            price2: self.price2.clone(),
+            oflags: self.oflags.clone(),
+            timeinforce: self.timeinforce.clone(),
+            userref: self.userref,
+            cl_ord_id: self.cl_ord_id.clone(),
+            reduce_only: self.reduce_only.then_some(true),
+            validate,
+            ..NewOrder::new(
+                Pair::from(self.pair.as_str()),
+                self.side,
+                self.ordertype,
+                self.volume.clone(),
+            )
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ExportCommand {
+    /// Request a report of trades or ledger entries
+    Request {
+        #[arg(value_parser = ["trades", "ledgers"])]
+        report: String,
+        #[arg(long, default_value = "kraken-cli export")]
+        description: String,
+        #[arg(long, default_value = "CSV", value_parser = ["CSV", "TSV"])]
+        format: String,
+        /// Unix time of the first entry
+        #[arg(long)]
+        start: Option<i64>,
+        /// Unix time of the last entry
+        #[arg(long)]
+        end: Option<i64>,
+    },
+    /// Status of a report
+    Status { id: String },
+}
+
+/// What commands run against: a client, the private API when credentials are configured, and
+/// the trading server the dashboard supervises
+pub struct CliContext {
+    req: KrakenClientState,
+    private_api: Result<PrivateApi, Error>,
+    server_url: String,
+}
+
+impl CliContext {
+    pub fn new(req: KrakenClientState, private_api: Result<PrivateApi, Error>) -> Self {
+        Self {
+            req,
+            private_api,
+            server_url: "http://127.0.0.1:8080".to_string(),
+        }
+    }
+
+    /// Base URL of the trading server, e.g. `http://127.0.0.1:8080`
+    pub fn with_server_url(mut self, url: impl Into<String>) -> Self {
+        self.server_url = url.into();
+        self
+    }
+
+    /// Client and credentials of `config`. Missing credentials only fail private commands.
+    pub fn from_config(config: &AppConfig) -> Result<Self, Error> {
+        let client = KrakenClient::new(config.client.clone())?
+            .with_rate_limit(&config.rate_limit)
+            .with_circuit_breaker(&config.circuit_breaker);
+        let private_api = PrivateApiBuilder::from_config(config).and_then(PrivateApiBuilder::build);
+        // A server bound to every interface is reached on the loopback one
+        let host = match config.server.host.as_str() {
+            "0.0.0.0" | "::" => "127.0.0.1",
+            host => host,
+        };
+        Ok(Self::new(KrakenClientState::new(client), private_api)
+            .with_server_url(format!("http://{}:{}", host, config.server.port)))
+    }
+
+    fn private_api(&self) -> Result<PrivateApi, Error> {
+        match &self.private_api {
+            Ok(api) => Ok(api.clone()),
+            Err(e) => Err(e.duplicate()),
+        }
+    }
+
+    /// Market data resolving the pairs typed by the user to Kraken's names
+    async fn market(&self) -> Result<MarketData, Error> {
+        let registry = InstrumentRegistry::default();
+        registry.refresh(self.req.clone()).await?;
+        Ok(MarketData::new().with_resolver(registry.resolver()))
+    }
+
+    fn account(&self) -> Result<Account, Error> {
+        self.private_api().map(Account::with_api)
+    }
+
+    fn trading(&self) -> Result<Trading, Error> {
+        self.private_api().map(Trading::with_api)
+    }
+
+    fn funding(&self) -> Result<Funding, Error> {
+        self.private_api().map(Funding::with_api)
+    }
+}
+
+impl Command {
+    pub async fn run(&self, ctx: &CliContext, dry_run: bool) -> Result<Output, Error> {
+        let req = ctx.req.clone();
+        let market = MarketData::new();
+        match self {
+            Command::Ticker { pairs } => {
+                let pairs: Vec<Pair> = pairs.iter().map(|pair| Pair::from(pair.as_str())).collect();
+                let tickers = ctx.market().await?.get_ticker(req, &pairs).await?;
+                let mut table = Table::new(&[
+                    "pair", "bid", "ask", "last", "open", "low", "high", "volume", "vwap",
+                ]);
+                for (pair, ticker) in sorted(&tickers) {
+                    table.push(vec![
+                        pair.to_string(),
+                        nth(&ticker.b, 0),
+                        nth(&ticker.a, 0),
+                        nth(&ticker.c, 0),
+                        ticker.o.clone(),
+                        nth(&ticker.l, 1),
+                        nth(&ticker.h, 1),
+                        nth(&ticker.v, 1),
+                        nth(&ticker.p, 1),
+                    ]);
+                }
+                Output::new(&tickers, table)
+            }
+            Command::Book { pair, depth } => {
+                let books = ctx
+                    .market()
+                    .await?
+                    .get_order_book(req, Pair::from(pair.as_str()), Some(*depth))
+                    .await?;
+                let mut table = Table::new(&["side", "price", "volume", "time"]);
+                for (_, book) in sorted(&books) {
+                    for (side, levels) in [("ask", book.asks.iter().rev().collect::<Vec<_>>())]
+                        .into_iter()
+                        .chain([("bid", book.bids.iter().collect())])
+                    {
+                        for level in levels {
+                            table.push(vec![
+                                side.to_string(),
+                                level.price.clone(),
+                                level.volume.clone(),
+                                time(level.timestamp as f64),
+                            ]);
+                        }
+                    }
+                }
+                Output::new(&books, table)
+            }
+            Command::Ohlc {
+                pair,
+                interval,
+                since,
+            } => {
+                let ohlc = ctx
+                    .market()
+                    .await?
+                    .get_ohlc(req, Pair::from(pair.as_str()), Some(*interval), *since)
+                    .await?;
+                let mut table = Table::new(&[
+                    "time", "open", "high", "low", "close", "vwap", "volume", "count",
+                ]);
+                for (_, candles) in sorted(&ohlc.data) {
+                    for candle in candles {
+                        table.push(vec![
+                            time(candle.time as f64),
+                            candle.open.clone(),
+                            candle.high.clone(),
+                            candle.low.clone(),
+                            candle.close.clone(),
+                            candle.vwap.clone(),
+                            candle.volume.clone(),
+                            candle.count.to_string(),
+                        ]);
+                    }
+                }
+                Output::new(&ohlc, table)
+            }
+            Command::Balance { all } => {
+                let balance = ctx.account()?.get_balance(req).await?.unwrap_or_default();
+                let mut table = Table::new(&["asset", "balance"]);
+                for (asset, amount) in sorted(&balance) {
+                    if *all || amount.parse::<f64>() != Ok(0.0) {
+                        table.push(vec![asset.to_string(), amount.clone()]);
+                    }
+                }
+                Output::new(&balance, table)
+            }
+            Command::Orders { closed, userref } => {
+                let account = ctx.account()?;
+                if *closed {
+                    let orders = account
+                        .get_closed_orders(
+                            req,
+                            None,
+                            userref.clone(),
+                            None,
+                            None,
+                            None,
+                            None,
+                            None,
+                            None,
+                            None,
+                        )
+                        .await?;
+                    Output::new(&orders, orders_table(&orders.closed))
+                } else {
+                    let orders = account
+                        .get_open_orders(req, None, userref.clone(), None)
+                        .await?;
+                    Output::new(&orders, orders_table(&orders.open))
+                }
+            }
+            Command::Trades { start, end, ofs } => {
+                let history = ctx
+                    .account()?
+                    .get_trades_history(req, None, None, *start, *end, *ofs, None)
+                    .await?;
+                let mut trades: Vec<_> = history.trades.iter().collect();
+                trades.sort_by(|a, b| a.1.time.total_cmp(&b.1.time));
+                let mut table = Table::new(&[
+                    "txid", "time", "pair", "side", "type", "price", "volume", "cost", "fee",
+                    "order",
+                ]);
+                for (txid, trade) in trades {
+                    table.push(vec![
+                        txid.clone(),
+                        time(trade.time),
+                        trade.pair.to_string(),
+                        trade.r#type.clone(),
+                        trade.ordertype.clone(),
+                        trade.price.clone(),
+                        trade.vol.clone(),
+                        trade.cost.clone(),
+                        trade.fee.clone(),
+                        trade.ordertxid.clone(),
+                    ]);
+                }
+                Output::new(&history, table)
+            }
+            Command::Ledger {
+                assets,
+                kind,
+                start,
+                end,
+                ofs,
+            } => {
+                let assets: Vec<AssetId> = assets
+                    .iter()
+                    .map(|asset| AssetId::from(asset.as_str()))
+                    .collect();
+                let assets = (!assets.is_empty()).then_some(&assets[..]);
+                let ledgers = ctx
+                    .account()?
+                    .get_ledgers(req, assets, None, kind.clone(), *start, *end, *ofs, None)
+                    .await?;
+                let mut entries: Vec<_> = ledgers.ledger.iter().collect();
+                entries.sort_by(|a, b| a.1.time.total_cmp(&b.1.time));
+                let mut table = Table::new(&[
+                    "id", "time", "type", "asset", "amount", "fee", "balance", "refid",
+                ]);
+                for (id, entry) in entries {
+                    table.push(vec![
+                        id.clone(),
+                        time(entry.time),
+                        entry.r#type.clone(),
+                        entry.asset.to_string(),
+                        entry.amount.clone(),
+                        entry.fee.clone(),
+                        entry.balance.clone(),
+                        entry.refid.clone(),
+                    ]);
+                }
+                Output::new(&ledgers, table)
+            }
+            Command::Place(args) => {
+                let order = args.to_order(dry_run);
+                let response = ctx.trading()?.add_order(req, &order).await?;
+                let mut table = Table::new(&["txid", "order", "close"]);
+                table.push(vec![
+                    if dry_run {
+                        "(validated)".to_string()
+                    } else {
+                        response.txid.join(",")
+                    },
+                    response.descr.order.clone(),
+                    response.descr.close.clone().unwrap_or_default(),
+                ]);
+                Output::new(&response, table)
+            }
+            Command::Cancel { txid, cl_ord_id } => {
+                if dry_run {
+                    let mut params = HashMap::new();
+                    if let Some(txid) = txid {
+                        params.insert("txid", txid.clone());
+                    }
+                    if let Some(cl_ord_id) = cl_ord_id {
+                        params.insert("cl_ord_id", cl_ord_id.clone());
+                    }
+                    return not_sent(CANCEL_ORDER, params);
+                }
+                let response = ctx
+                    .trading()?
+                    .cancel_order(req, txid.clone(), cl_ord_id.clone())
+                    .await?;
+                let mut table = Table::new(&["count", "pending"]);
+                table.push(vec![
+                    response.count.to_string(),
+                    response.pending.unwrap_or_default().to_string(),
+                ]);
+                Output::new(&response, table)
+            }
+            Command::CancelAll => {
+                if dry_run {
+                    return not_sent(CANCEL_ALL_ORDERS, HashMap::new());
+                }
+                let response = ctx.trading()?.cancel_all_orders(req).await?;
+                let mut table = Table::new(&["count"]);
+                table.push(vec![response.count.to_string()]);
+                Output::new(&response, table)
+            }
+            Command::DepositAddress { asset, method, new } => {
+                let funding = ctx.funding()?;
+                let asset = AssetId::from(asset.as_str());
+                let method = match method {
+                    Some(method) => method.clone(),
+                    None => funding
+                        .get_deposit_methods(req.clone(), asset.clone())
+                        .await?
+                        .into_iter()
+                        .next()
+                        .map(|method| method.method)
+                        .ok_or_else(|| {
+                            Error::InvalidParameter(format!("No deposit method for {}", asset))
+                        })?,
+                };
+                if *new && dry_run {
+                    return not_sent(
+                        DEPOSIT_ADDRESSES,
+                        HashMap::from([
+                            ("asset", asset.to_string()),
+                            ("method", method),
+                            ("new", "true".to_string()),
+                        ]),
+                    );
+                }
+                let addresses = funding
+                    .get_deposit_addresses(req, asset, method, *new)
+                    .await?;
+                let mut table = Table::new(&["address", "expires", "new"]);
+                for address in &addresses {
+                    let expires = address
+                        .expiretm
+                        .as_deref()
+                        .and_then(|secs| secs.parse::<f64>().ok())
+                        .filter(|secs| *secs > 0.0)
+                        .map(time)
+                        .unwrap_or_default();
+                    table.push(vec![
+                        address.address.clone(),
+                        expires,
+                        address.new.unwrap_or_default().to_string(),
+                    ]);
+                }
+                Output::new(&addresses, table)
+            }
+            Command::Export(ExportCommand::Request {
+                report,
+                description,
+                format,
+                start,
+                end,
+            }) => {
+                if dry_run {
+                    let mut params = HashMap::from([
+                        ("report", report.clone()),
+                        ("description", description.clone()),
+                        ("format", format.clone()),
+                    ]);
+                    if let Some(start) = start {
+                        params.insert("starttm", start.to_string());
+                    }
+                    if let Some(end) = end {
+                        params.insert("endtm", end.to_string());
+                    }
+                    return not_sent(REQUEST_EXPORT_REPORT, params);
+                }
+                let export = ctx
+                    .account()?
+                    .request_export_report(
+                        req,
+                        report.clone(),
+                        description.clone(),
+                        Some(format.clone()),
+                        *start,
+                        *end,
+                    )
+                    .await?;
+                let table = export_table(&export);
+                Output::new(&export, table)
+            }
+            Command::Export(ExportCommand::Status { id }) => {
+                let export = ctx
+                    .account()?
+                    .get_export_report_status(req, id.clone())
+                    .await?;
+                let table = export_table(&export);
+                Output::new(&export, table)
+            }
+            Command::Tui(args) => {
+                tui::run(ctx, args, dry_run).await?;
+                Ok(Output::none())
+            }
+            Command::Status => {
+                let sent = Instant::now();
+                let server_time = market.get_server_time(req.clone()).await?;
+                let latency_ms = sent.elapsed().as_secs_f64() * 1000.0;
+                let status = market.get_system_status(req).await?;
+                let mut table = Table::new(&["status", "since", "server_time", "latency_ms"]);
+                table.push(vec![
+                    status.status.clone(),
+                    status.timestamp.clone(),
+                    time(server_time.unixtime as f64),
+                    format!("{:.0}", latency_ms),
+                ]);
+                let value = json!({
+                    "status": status,
+                    "time": server_time,
+                    "latency_ms": latency_ms,
+                });
+                Output::new(&value, table)
+            }
+        }
+    }
+}
+
+/// The request a dry run of a command without Kraken side validation would have sent
+fn not_sent(endpoint: &str, params: HashMap<&str, String>) -> Result<Output, Error> {
+    let mut table = Table::new(&["endpoint", "params", "sent"]);
+    let mut names: Vec<_> = params.iter().collect();
+    names.sort();
+    table.push(vec![
+        endpoint.to_string(),
+        names
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join(" "),
+        "false".to_string(),
+    ]);
+    Output::new(
+        &json!({ "endpoint": endpoint, "params": params, "sent": false }),
+        table,
+    )
+}
+
+fn orders_table(orders: &HashMap<String, Order>) -> Table {
+    let mut orders: Vec<_> = orders.iter().collect();
+    orders.sort_by(|a, b| a.1.opentm.total_cmp(&b.1.opentm));
+    let mut table = Table::new(&[
+        "txid",
+        "status",
+        "pair",
+        "side",
+        "type",
+        "price",
+        "volume",
+        "filled",
+        "cl_ord_id",
+        "opened",
+    ]);
+    for (txid, order) in orders {
+        table.push(vec![
+            txid.clone(),
+            order.status.clone(),
+            order.descr.pair.to_string(),
+            order.descr.r#type.clone(),
+            order.descr.ordertype.clone(),
+            order.descr.price.clone(),
+            order.vol.clone(),
+            order.vol_exec.clone(),
+            order.cl_ord_id.clone().unwrap_or_default(),
+            time(order.opentm),
+        ]);
+    }
+    table
+}
+
+fn export_table(export: &ExportReport) -> Table {
+    let mut table = Table::new(&["id", "status", "report", "format", "created", "description"]);
+    table.push(vec![
+        export.id.clone(),
+        export.status.clone(),
+        export.report_type.clone(),
+        export.format.clone(),
+        time(export.created_at),
+        export.description.clone(),
+    ]);
+    table
+}
+
+/// Entries of a map in key order
+fn sorted<K: Ord, V>(map: &HashMap<K, V>) -> Vec<(&K, &V)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+fn nth(values: &[String], index: usize) -> String {
+    values.get(index).cloned().unwrap_or_default()
+}
+
+fn time(secs: f64) -> String {
+    format_rfc3339(secs.max(0.0) as u64)
+}
+
+/// Parse a value by its name in Kraken requests, e.g. `buy` or `stop-loss`
+fn from_name<T: DeserializeOwned>(name: &str) -> Result<T, String> {
+    serde_json::from_value(Value::String(name.to_lowercase()))
+        .map_err(|_| format!("unknown value `{}`", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let cli = Cli::try_parse_from([
+            "kraken-cli",
+            "--profile",
+            "paper",
+            "-o",
+            "csv",
+            "--dry-run",
+            "place",
+            "buy",
+            "XBTUSD",
+            "0.01",
+            "--type",
+            "limit",
+            "--price",
+            "60000",
+        ])
+        .unwrap();
+        assert_eq!(cli.config.profile.as_deref(), Some("paper"));
+        assert_eq!(cli.output, OutputFormat::Csv);
+        let Command::Place(args) = &cli.command else {
+            panic!("expected place, got {:?}", cli.command);
+        };
+        let order = args.to_order(cli.dry_run);
+        assert_eq!(order.ordertype, OrderType::Limit);
+        assert_eq!(order.to_params()["validate"], "true");
+
+        assert!(Cli::try_parse_from(["kraken-cli", "place", "hold", "XBTUSD", "1"]).is_err());
+        assert!(Cli::try_parse_from(["kraken-cli", "cancel"]).is_err());
+        assert!(Cli::try_parse_from(["kraken-cli", "cancel", "--cl-ord-id", "abc"]).is_ok());
+    }
+}
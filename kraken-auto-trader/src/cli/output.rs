@@ -0,0 +1,209 @@
+use crate::errors::Error;
+use serde::Serialize;
+use serde_json::Value;
+
+/// How the result of a command is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns, for reading
+    #[default]
+    Table,
+    /// The response as returned by Kraken
+    Json,
+    /// Comma separated values with a header line
+    Csv,
+}
+
+/// Rows of a result, one value per column
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Self {
+            headers: headers.iter().map(|header| header.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    /// Values of the column named `header`
+    pub fn column(&self, header: &str) -> Vec<&str> {
+        match self.headers.iter().position(|name| name == header) {
+            Some(index) => self
+                .rows
+                .iter()
+                .map(|row| row.get(index).map(String::as_str).unwrap_or(""))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Columns padded to their widest value, numbers aligned to the right
+    pub fn to_text(&self) -> String {
+        let widths: Vec<usize> = (0..self.headers.len())
+            .map(|i| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row.get(i))
+                    .chain([&self.headers[i]])
+                    .map(|value| value.chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let numeric: Vec<bool> = (0..self.headers.len())
+            .map(|i| {
+                self.rows
+                    .iter()
+                    .any(|row| row.get(i).is_some_and(|v| !v.is_empty()))
+                    && self.rows.iter().all(|row| {
+                        row.get(i)
+                            .is_none_or(|value| value.is_empty() || value.parse::<f64>().is_ok())
+                    })
+            })
+            .collect();
+
+        let line = |row: &[String]| {
+            let cells: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(i, width)| {
+                    let value = row.get(i).map(String::as_str).unwrap_or("");
+                    if numeric[i] {
+                        format!("{:>width$}", value, width = width)
+                    } else {
+                        format!("{:<width$}", value, width = width)
+                    }
+                })
+                .collect();
+            cells.join("  ").trim_end().to_string()
+        };
+
+        let mut text = line(
+            &self
+                .headers
+                .iter()
+                .map(|h| h.to_uppercase())
+                .collect::<Vec<_>>(),
+        );
+        text.push('\n');
+        for row in &self.rows {
+            text.push_str(&line(row));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// RFC 4180 CSV, quoting values holding separators, quotes or line breaks
+    pub fn to_csv(&self) -> String {
+        let line = |row: &[String]| {
+            row.iter()
+                .map(|value| {
+                    if value.contains([',', '"', '\n', '\r']) {
+                        format!("\"{}\"", value.replace('"', "\"\""))
+                    } else {
+                        value.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        let mut csv = line(&self.headers);
+        csv.push('\n');
+        for row in &self.rows {
+            csv.push_str(&line(row));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// Result of a command: the response as Kraken returned it, and the rows it is shown as
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub value: Value,
+    pub table: Table,
+}
+
+impl Output {
+    pub fn new(value: &impl Serialize, table: Table) -> Result<Self, Error> {
+        Ok(Self {
+            value: serde_json::to_value(value)?,
+            table,
+        })
+    }
+
+    /// Nothing to print, for commands that ran interactively
+    pub fn none() -> Self {
+        Self {
+            value: Value::Null,
+            table: Table::default(),
+        }
+    }
+
+    pub fn render(&self, format: OutputFormat) -> Result<String, Error> {
+        if self.value.is_null() && self.table.headers.is_empty() {
+            return Ok(String::new());
+        }
+        Ok(match format {
+            OutputFormat::Table => self.table.to_text(),
+            OutputFormat::Json => serde_json::to_string_pretty(&self.value)? + "\n",
+            OutputFormat::Csv => self.table.to_csv(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn table() -> Table {
+        let mut table = Table::new(&["pair", "price", "note"]);
+        table.push(vec!["XBTUSD".into(), "65000.1".into(), "".into()]);
+        table.push(vec![
+            "ETHUSD".into(),
+            "3000".into(),
+            "a \"quoted\", note".into(),
+        ]);
+        table
+    }
+
+    #[test]
+    fn test_text() {
+        assert_eq!(
+            table().to_text(),
+            "PAIR      PRICE  NOTE\n\
+             XBTUSD  65000.1\n\
+             ETHUSD     3000  a \"quoted\", note\n"
+        );
+        assert_eq!(table().column("price"), vec!["65000.1", "3000"]);
+    }
+
+    #[test]
+    fn test_csv() {
+        assert_eq!(
+            table().to_csv(),
+            "pair,price,note\n\
+             XBTUSD,65000.1,\n\
+             ETHUSD,3000,\"a \"\"quoted\"\", note\"\n"
+        );
+    }
+
+    #[test]
+    fn test_json_is_the_response() {
+        let output = Output::new(&json!({ "count": 1 }), table()).unwrap();
+        assert_eq!(
+            output.render(OutputFormat::Json).unwrap(),
+            "{\n  \"count\": 1\n}\n"
+        );
+    }
+}
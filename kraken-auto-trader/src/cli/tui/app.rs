@@ -0,0 +1,347 @@
+use super::state::Dashboard;
+use crate::models::trading::OrderSide;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Panel the selection keys move in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+    Orders,
+    Strategies,
+}
+
+/// What keys currently do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mode {
+    Normal,
+    /// Typing the volume, and a price for a limit order, of a new order
+    Entry {
+        side: OrderSide,
+        input: String,
+    },
+    /// Waiting for `y` before carrying out an action
+    Confirm {
+        action: Action,
+        prompt: String,
+    },
+}
+
+/// Something the operator asked for
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Quit,
+    Refresh,
+    /// A market order without a price, a limit order with one
+    Place {
+        side: OrderSide,
+        volume: f64,
+        price: Option<f64>,
+    },
+    Cancel {
+        txid: String,
+    },
+    Pause {
+        id: String,
+    },
+    Resume {
+        id: String,
+    },
+}
+
+/// Keyboard state of the dashboard
+#[derive(Debug, Clone)]
+pub struct App {
+    pub focus: Focus,
+    pub mode: Mode,
+    pub selected_order: usize,
+    pub selected_strategy: usize,
+    /// Outcome of the last action, shown on the status line
+    pub notice: Option<String>,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            focus: Focus::Orders,
+            mode: Mode::Normal,
+            selected_order: 0,
+            selected_strategy: 0,
+            notice: None,
+        }
+    }
+}
+
+impl App {
+    /// Handle a key press, returning the action to carry out
+    pub fn on_key(&mut self, key: KeyEvent, dashboard: &Dashboard) -> Option<Action> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(Action::Quit);
+        }
+
+        match std::mem::replace(&mut self.mode, Mode::Normal) {
+            Mode::Normal => self.on_normal_key(key.code, dashboard),
+            Mode::Entry { side, mut input } => match key.code {
+                KeyCode::Esc => None,
+                KeyCode::Enter => match parse_entry(&input) {
+                    Ok((volume, price)) => {
+                        let action = Action::Place {
+                            side,
+                            volume,
+                            price,
+                        };
+                        self.mode = Mode::Confirm {
+                            prompt: format!("{}?", describe(&action, dashboard)),
+                            action,
+                        };
+                        None
+                    }
+                    Err(e) => {
+                        self.notice = Some(e);
+                        self.mode = Mode::Entry { side, input };
+                        None
+                    }
+                },
+                KeyCode::Backspace => {
+                    input.pop();
+                    self.mode = Mode::Entry { side, input };
+                    None
+                }
+                KeyCode::Char(c) if c.is_ascii_digit() || c == '.' || c == ' ' => {
+                    input.push(c);
+                    self.mode = Mode::Entry { side, input };
+                    None
+                }
+                _ => {
+                    self.mode = Mode::Entry { side, input };
+                    None
+                }
+            },
+            Mode::Confirm { action, .. } => match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => Some(action),
+                _ => {
+                    self.notice = Some("Cancelled".to_string());
+                    None
+                }
+            },
+        }
+    }
+
+    fn on_normal_key(&mut self, code: KeyCode, dashboard: &Dashboard) -> Option<Action> {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+            KeyCode::Char('r') => Some(Action::Refresh),
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Focus::Orders => Focus::Strategies,
+                    Focus::Strategies => Focus::Orders,
+                };
+                None
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                let selected = self.selected_mut();
+                *selected = selected.saturating_sub(1);
+                None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                *self.selected_mut() += 1;
+                self.clamp(dashboard);
+                None
+            }
+            KeyCode::Char(c @ ('b' | 's')) => {
+                let side = if c == 'b' {
+                    OrderSide::Buy
+                } else {
+                    OrderSide::Sell
+                };
+                self.mode = Mode::Entry {
+                    side,
+                    input: String::new(),
+                };
+                None
+            }
+            KeyCode::Char('c') => {
+                self.clamp(dashboard);
+                let order = dashboard.orders.get(self.selected_order)?;
+                let action = Action::Cancel {
+                    txid: order.txid.clone(),
+                };
+                self.mode = Mode::Confirm {
+                    prompt: format!("{}?", describe(&action, dashboard)),
+                    action,
+                };
+                None
+            }
+            KeyCode::Char('p') => {
+                self.clamp(dashboard);
+                let strategy = dashboard.strategies.get(self.selected_strategy)?;
+                if strategy.is_running() {
+                    Some(Action::Pause {
+                        id: strategy.id.clone(),
+                    })
+                } else if strategy.is_paused() {
+                    Some(Action::Resume {
+                        id: strategy.id.clone(),
+                    })
+                } else {
+                    self.notice = Some(format!("Strategy {} is {}", strategy.id, strategy.status));
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Keep the selections on existing rows, as the lists change with each refresh
+    pub fn clamp(&mut self, dashboard: &Dashboard) {
+        self.selected_order = self
+            .selected_order
+            .min(dashboard.orders.len().saturating_sub(1));
+        self.selected_strategy = self
+            .selected_strategy
+            .min(dashboard.strategies.len().saturating_sub(1));
+    }
+
+    fn selected_mut(&mut self) -> &mut usize {
+        match self.focus {
+            Focus::Orders => &mut self.selected_order,
+            Focus::Strategies => &mut self.selected_strategy,
+        }
+    }
+}
+
+/// Parse `VOLUME [PRICE]`
+fn parse_entry(input: &str) -> Result<(f64, Option<f64>), String> {
+    let mut words = input.split_whitespace();
+    let volume = words
+        .next()
+        .and_then(|word| word.parse::<f64>().ok())
+        .filter(|volume| *volume > 0.0)
+        .ok_or_else(|| "Enter a volume, then a price for a limit order".to_string())?;
+    let price = match words.next() {
+        Some(word) => Some(
+            word.parse::<f64>()
+                .ok()
+                .filter(|price| *price > 0.0)
+                .ok_or_else(|| format!("Invalid price {}", word))?,
+        ),
+        None => None,
+    };
+    if words.next().is_some() {
+        return Err("Enter a volume, then a price for a limit order".to_string());
+    }
+    Ok((volume, price))
+}
+
+/// One line description of an action, for prompts and notices
+pub fn describe(action: &Action, dashboard: &Dashboard) -> String {
+    match action {
+        Action::Quit => "Quit".to_string(),
+        Action::Refresh => "Refresh".to_string(),
+        Action::Place {
+            side,
+            volume,
+            price,
+        } => {
+            let side = match side {
+                OrderSide::Buy => "Buy",
+                OrderSide::Sell => "Sell",
+            };
+            let volume = format!("{:.*}", dashboard.volume_decimals, volume);
+            match price {
+                Some(price) => format!(
+                    "{} {} {} at {:.*}",
+                    side, volume, dashboard.symbol, dashboard.price_decimals, price
+                ),
+                None => format!("{} {} {} at market", side, volume, dashboard.symbol),
+            }
+        }
+        Action::Cancel { txid } => format!("Cancel order {}", txid),
+        Action::Pause { id } => format!("Pause strategy {}", id),
+        Action::Resume { id } => format!("Resume strategy {}", id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::tui::state::OpenOrderRow;
+
+    fn press(app: &mut App, dashboard: &Dashboard, keys: &str) -> Option<Action> {
+        keys.chars()
+            .map(|c| {
+                let code = match c {
+                    '\n' => KeyCode::Enter,
+                    '\t' => KeyCode::Tab,
+                    c => KeyCode::Char(c),
+                };
+                app.on_key(KeyEvent::new(code, KeyModifiers::NONE), dashboard)
+            })
+            .last()
+            .flatten()
+    }
+
+    #[test]
+    fn test_place_order() {
+        let dashboard = Dashboard::new("BTC/USD", 10);
+        let mut app = App::default();
+
+        assert_eq!(press(&mut app, &dashboard, "b0.5 65000\n"), None);
+        assert!(matches!(&app.mode, Mode::Confirm { prompt, .. }
+            if prompt == "Buy 0.50000000 BTC/USD at 65000.00?"));
+        assert_eq!(
+            press(&mut app, &dashboard, "y"),
+            Some(Action::Place {
+                side: OrderSide::Buy,
+                volume: 0.5,
+                price: Some(65_000.0),
+            })
+        );
+        assert_eq!(app.mode, Mode::Normal);
+
+        // A bad entry keeps the prompt open, anything but `y` abandons the order
+        assert_eq!(press(&mut app, &dashboard, "s\n"), None);
+        assert!(matches!(app.mode, Mode::Entry { .. }));
+        assert_eq!(press(&mut app, &dashboard, "2\nn"), None);
+        assert_eq!(app.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn test_cancel_and_pause() {
+        let mut dashboard = Dashboard::new("BTC/USD", 10);
+        for txid in ["OA", "OB"] {
+            dashboard.orders.push(OpenOrderRow {
+                txid: txid.to_string(),
+                pair: "XBTUSD".to_string(),
+                side: "buy".to_string(),
+                ordertype: "limit".to_string(),
+                price: "60000".to_string(),
+                volume: "0.1".to_string(),
+                filled: "0".to_string(),
+                opened: 0.0,
+            });
+        }
+        dashboard.strategies = serde_json::from_value(serde_json::json!([{
+            "id": "algo-1",
+            "algo": "twap",
+            "request": { "pair": "XBTUSD", "side": "buy", "volume": 1.0 },
+            "status": "paused",
+            "executed_volume": 0.0,
+            "percent_complete": 0.0,
+        }]))
+        .unwrap();
+        let mut app = App::default();
+
+        assert_eq!(
+            press(&mut app, &dashboard, "jjcy"),
+            Some(Action::Cancel {
+                txid: "OB".to_string()
+            })
+        );
+        assert_eq!(
+            press(&mut app, &dashboard, "\tp"),
+            Some(Action::Resume {
+                id: "algo-1".to_string()
+            })
+        );
+        assert_eq!(press(&mut app, &dashboard, "q"), Some(Action::Quit));
+    }
+}
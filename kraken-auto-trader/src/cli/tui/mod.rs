@@ -0,0 +1,440 @@
+//! Interactive dashboard of the `kraken-cli tui` command.
+//!
+//! The order book, trades and ticker of one pair stream from the public WebSocket API, while the
+//! balances and open orders of the account, and the strategies run by the server's execution
+//! engine, are refreshed over REST. Orders are placed and cancelled with the CLI's credentials,
+//! and strategies paused through the server's `/api/algos` endpoints.
+
+pub mod app;
+pub mod state;
+pub mod ui;
+
+use super::CliContext;
+use crate::{
+    client::kraken_apis::PrivateApi,
+    errors::Error,
+    feeds::{ticker::last_price, websocket::MarketDataSocket},
+    instruments::{registry::Instrument, symbols::ws_v2_symbol},
+    middleware::KrakenClientState,
+    models::{
+        market::OHLCData,
+        symbols::{AssetId, Pair},
+        trading::{NewOrder, OrderType},
+    },
+    services::{account_details::Account, market_data::MarketData, trading::Trading},
+};
+use app::{describe, Action, App};
+use crossterm::event::{Event, EventStream, KeyEventKind};
+use futures::StreamExt;
+use state::{Dashboard, OpenOrderRow, StrategyView};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{sync::mpsc, task::JoinHandle};
+
+/// Shortest time between two redraws while market data streams in
+const FRAME: Duration = Duration::from_millis(100);
+
+/// Book depths the WebSocket API accepts
+const BOOK_DEPTHS: [usize; 5] = [10, 25, 100, 500, 1000];
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct TuiArgs {
+    /// Pair to show the market of, in any spelling, e.g. BTC/USD or XBTUSD
+    pub pair: String,
+    /// Price levels shown on each side of the book
+    #[arg(long, default_value_t = 10)]
+    pub depth: usize,
+    /// URL of the trading server running the strategies, from the configured address by default
+    #[arg(long)]
+    pub server: Option<String>,
+    /// Bearer token pausing and resuming strategies, one of the server's KRAKEN_STREAM_TOKENS
+    #[arg(long, env = "KRAKEN_SERVER_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+    /// Seconds between refreshes of balances, orders and strategies
+    #[arg(long, default_value_t = 5)]
+    pub refresh_secs: u64,
+}
+
+/// Balances held, as asset and amount, and open orders, newest first
+type AccountView = (Vec<(String, String)>, Vec<OpenOrderRow>);
+
+/// A result of a background task, applied by the event loop
+enum Update {
+    Account(Result<AccountView, String>),
+    Strategies(Result<(Vec<StrategyView>, HashMap<String, f64>), String>),
+    /// Outcome of an action, after which the account is refreshed
+    Done(String),
+}
+
+/// What the background tasks run against, cheap to clone into each of them
+#[derive(Clone)]
+struct Services {
+    req: KrakenClientState,
+    private_api: Result<PrivateApi, Arc<Error>>,
+    http: reqwest::Client,
+    server: String,
+    token: Option<String>,
+    instrument: Instrument,
+    pair_names: Vec<String>,
+    dry_run: bool,
+}
+
+/// Run the dashboard until the operator quits
+pub async fn run(ctx: &CliContext, args: &TuiArgs, dry_run: bool) -> Result<(), Error> {
+    let req = ctx.req.clone();
+    let market = MarketData::new();
+
+    let pairs = market
+        .get_tradable_asset_pairs(
+            req.clone(),
+            Some(&[Pair::from(args.pair.as_str())]),
+            None,
+            None,
+        )
+        .await?;
+    let (pair, info) = pairs
+        .iter()
+        .next()
+        .ok_or_else(|| Error::InvalidParameter(format!("Unknown asset pair {}", args.pair)))?;
+    let instrument = Instrument::from_asset_pair(pair, info);
+    let wsname = info.wsname.clone().unwrap_or_else(|| info.altname.clone());
+    let symbol = ws_v2_symbol(&wsname);
+
+    let mut dashboard = Dashboard::new(symbol.clone(), args.depth);
+    dashboard.pair_names = vec![pair.to_string(), info.altname.clone(), wsname];
+    dashboard.price_decimals = instrument.pair_decimals;
+    dashboard.volume_decimals = instrument.lot_decimals.min(8);
+
+    // Seed the panels over REST so they are filled before the first WebSocket message
+    let book_depth = BOOK_DEPTHS
+        .into_iter()
+        .find(|depth| *depth >= args.depth)
+        .unwrap_or(1000);
+    if let Some(book) = market
+        .get_order_book(req.clone(), pair.clone(), Some(args.depth as u32))
+        .await?
+        .values()
+        .next()
+    {
+        dashboard.seed_book(book);
+    }
+    let ohlc = market
+        .get_ohlc(req.clone(), pair.clone(), Some(1), None)
+        .await?;
+    dashboard.seed_closes(
+        ohlc.data
+            .values()
+            .flatten()
+            .filter_map(|candle: &OHLCData| Some((candle.time, candle.close.parse().ok()?))),
+    );
+    let trades = market
+        .get_recent_trades(req.clone(), pair.clone(), None, Some(50))
+        .await?;
+    if let Some(trades) = trades.trades.values().next() {
+        dashboard.seed_trades(trades);
+    }
+
+    let mut book = MarketDataSocket::subscription("book", &[symbol.as_str()]);
+    book["params"]["depth"] = book_depth.into();
+    let socket = MarketDataSocket::new(vec![
+        book,
+        MarketDataSocket::subscription("trade", &[symbol.as_str()]),
+        MarketDataSocket::subscription("ticker", &[symbol.as_str()]),
+    ]);
+    let socket_status = socket.status();
+    let stream = socket.spawn();
+
+    let services = Services {
+        req,
+        private_api: ctx.private_api().map_err(Arc::new),
+        http: reqwest::Client::new(),
+        server: args
+            .server
+            .clone()
+            .unwrap_or_else(|| ctx.server_url.clone())
+            .trim_end_matches('/')
+            .to_string(),
+        token: args.token.clone(),
+        instrument,
+        pair_names: dashboard.pair_names.clone(),
+        dry_run,
+    };
+
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(
+        &mut terminal,
+        dashboard,
+        stream,
+        services,
+        || socket_status.is_connected(),
+        Duration::from_secs(args.refresh_secs.max(1)),
+    )
+    .await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(
+    terminal: &mut ratatui::DefaultTerminal,
+    mut dashboard: Dashboard,
+    mut stream: crate::feeds::stream::MarketStream,
+    services: Services,
+    connected: impl Fn() -> bool,
+    refresh_every: Duration,
+) -> Result<(), Error> {
+    let mut app = App::default();
+    let mut events = EventStream::new();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut refresh = tokio::time::interval(refresh_every);
+    let mut frames = tokio::time::interval(FRAME);
+    let mut refreshing: Option<JoinHandle<()>> = None;
+    let mut was_connected = false;
+    let mut dirty = true;
+    let mut last_draw: Option<Instant> = None;
+
+    loop {
+        if dirty && last_draw.is_none_or(|at| at.elapsed() >= FRAME) {
+            let live = connected();
+            terminal.draw(|frame| ui::draw(frame, &dashboard, &app, live))?;
+            was_connected = live;
+            dirty = false;
+            last_draw = Some(Instant::now());
+        }
+
+        let refresh_now = tokio::select! {
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => {
+                    // Answer keys at once rather than on the next frame
+                    dirty = true;
+                    last_draw = None;
+                    match app.on_key(key, &dashboard) {
+                        Some(Action::Quit) => break,
+                        Some(Action::Refresh) => true,
+                        Some(action) => {
+                            let described = describe(&action, &dashboard);
+                            app.notice = Some(format!("{}...", described));
+                            let services = services.clone();
+                            let tx = tx.clone();
+                            tokio::spawn(async move {
+                                let notice = match services.perform(action).await {
+                                    Ok(outcome) => outcome,
+                                    Err(e) => format!("{} failed: {}", described, e),
+                                };
+                                let _ = tx.send(Update::Done(notice));
+                            });
+                            false
+                        }
+                        None => false,
+                    }
+                }
+                Some(Ok(Event::Resize(..))) => {
+                    dirty = true;
+                    false
+                }
+                Some(Ok(_)) => false,
+                Some(Err(e)) => return Err(e.into()),
+                None => break,
+            },
+            Some(message) = stream.next() => {
+                if let Ok(value) = message.json() {
+                    dirty |= dashboard.apply_market(&value);
+                }
+                false
+            }
+            Some(update) = rx.recv() => {
+                dirty = true;
+                match update {
+                    Update::Account(Ok((balances, orders))) => {
+                        dashboard.balances = balances;
+                        dashboard.orders = orders;
+                        dashboard.account_error = None;
+                        app.clamp(&dashboard);
+                        false
+                    }
+                    Update::Account(Err(e)) => {
+                        dashboard.account_error = Some(e);
+                        false
+                    }
+                    Update::Strategies(Ok((strategies, marks))) => {
+                        dashboard.strategies = strategies;
+                        dashboard.marks = marks;
+                        dashboard.strategies_error = None;
+                        app.clamp(&dashboard);
+                        false
+                    }
+                    Update::Strategies(Err(e)) => {
+                        dashboard.strategies_error = Some(e);
+                        false
+                    }
+                    Update::Done(notice) => {
+                        app.notice = Some(notice);
+                        true
+                    }
+                }
+            }
+            _ = refresh.tick() => true,
+            _ = frames.tick() => {
+                dirty |= connected() != was_connected;
+                false
+            }
+        };
+
+        if refresh_now && refreshing.as_ref().is_none_or(JoinHandle::is_finished) {
+            let services = services.clone();
+            let tx = tx.clone();
+            refreshing = Some(tokio::spawn(async move {
+                let (account, strategies) = tokio::join!(services.account(), services.strategies());
+                let _ = tx.send(Update::Account(account.map_err(|e| e.to_string())));
+                let _ = tx.send(Update::Strategies(strategies.map_err(|e| e.to_string())));
+            }));
+        }
+    }
+    Ok(())
+}
+
+impl Services {
+    fn private_api(&self) -> Result<PrivateApi, Error> {
+        self.private_api.clone().map_err(|e| e.duplicate())
+    }
+
+    /// Balances held, and open orders
+    async fn account(&self) -> Result<AccountView, Error> {
+        let account = Account::with_api(self.private_api()?);
+        let balance = account
+            .get_balance(self.req.clone())
+            .await?
+            .unwrap_or_default();
+        let mut balances: Vec<(String, String)> = balance
+            .iter()
+            .filter(|(_, amount)| amount.parse::<f64>() != Ok(0.0))
+            .map(|(asset, amount): (&AssetId, &String)| (asset.to_string(), amount.clone()))
+            .collect();
+        balances.sort();
+
+        let open = account
+            .get_open_orders(self.req.clone(), None, None, None)
+            .await?;
+        let mut orders: Vec<OpenOrderRow> = open
+            .open
+            .iter()
+            .map(|(txid, order)| OpenOrderRow::new(txid, order))
+            .collect();
+        orders.sort_by(|a, b| b.opened.total_cmp(&a.opened));
+        Ok((balances, orders))
+    }
+
+    /// Strategies of the server, and the last price of the pairs they trade other than the
+    /// dashboard's
+    async fn strategies(&self) -> Result<(Vec<StrategyView>, HashMap<String, f64>), Error> {
+        let response = self
+            .http
+            .get(format!("{}/api/algos", self.server))
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::InvalidResponse(format!("{} {}", status, body)));
+        }
+        let strategies: Vec<StrategyView> = response.json().await?;
+
+        let mut marks = HashMap::new();
+        let market = MarketData::new();
+        for strategy in &strategies {
+            let pair = strategy.request.pair.as_str();
+            if marks.contains_key(pair)
+                || self
+                    .pair_names
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(pair))
+            {
+                continue;
+            }
+            let tickers = market
+                .get_ticker(
+                    self.req.clone(),
+                    std::slice::from_ref(&strategy.request.pair),
+                )
+                .await?;
+            if let Some(last) = tickers.values().next().and_then(last_price) {
+                marks.insert(pair.to_string(), last);
+            }
+        }
+        Ok((strategies, marks))
+    }
+
+    /// Carry out an action, returning the notice to show
+    async fn perform(&self, action: Action) -> Result<String, Error> {
+        match action {
+            Action::Place {
+                side,
+                volume,
+                price,
+            } => {
+                let ordertype = if price.is_some() {
+                    OrderType::Limit
+                } else {
+                    OrderType::Market
+                };
+                let mut order = NewOrder::new(
+                    self.instrument.pair.clone(),
+                    side,
+                    ordertype,
+                    self.instrument.round_volume(volume),
+                );
+                order.price = price.map(|price| self.instrument.round_price(price, side));
+                order.validate = self.dry_run;
+                let order = self.instrument.validate(&order)?;
+                let response = Trading::with_api(self.private_api()?)
+                    .add_order(self.req.clone(), &order)
+                    .await?;
+                Ok(if self.dry_run {
+                    format!("Validated, not placed: {}", response.descr.order)
+                } else {
+                    format!(
+                        "Placed {}: {}",
+                        response.txid.join(","),
+                        response.descr.order
+                    )
+                })
+            }
+            Action::Cancel { txid } => {
+                if self.dry_run {
+                    return Ok(format!("Dry run, order {} not cancelled", txid));
+                }
+                let response = Trading::with_api(self.private_api()?)
+                    .cancel_order(self.req.clone(), Some(txid.clone()), None)
+                    .await?;
+                Ok(format!("Cancelled {} ({} order)", txid, response.count))
+            }
+            Action::Pause { id } | Action::Resume { id } if self.dry_run => {
+                Ok(format!("Dry run, strategy {} left as it is", id))
+            }
+            Action::Pause { id } => self.control_strategy(&id, "pause").await,
+            Action::Resume { id } => self.control_strategy(&id, "resume").await,
+            Action::Quit | Action::Refresh => Ok(String::new()),
+        }
+    }
+
+    async fn control_strategy(&self, id: &str, verb: &str) -> Result<String, Error> {
+        let mut request = self
+            .http
+            .post(format!("{}/api/algos/{}/{}", self.server, id, verb))
+            .timeout(Duration::from_secs(5));
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::InvalidResponse(format!("{} {}", status, body)));
+        }
+        let strategy: StrategyView = response.json().await?;
+        Ok(format!("Strategy {} is {}", strategy.id, strategy.status))
+    }
+}
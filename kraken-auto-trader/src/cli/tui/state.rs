@@ -0,0 +1,463 @@
+use crate::{
+    feeds::book::{BookLadder, Level},
+    models::{
+        account::Order,
+        market::{OrderBook, Trade},
+        symbols::Pair,
+        trading::OrderSide,
+    },
+    utils::time::{format_rfc3339, parse_rfc3339},
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+
+/// Trades kept for the recent trades panel
+const MAX_TRADES: usize = 50;
+
+/// One-minute closes kept for the price chart
+const MAX_CLOSES: usize = 240;
+
+/// A trade of the market, as shown in the recent trades panel
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeRow {
+    /// Time of day, `HH:MM:SS` in UTC
+    pub time: String,
+    pub side: OrderSide,
+    pub price: f64,
+    pub volume: f64,
+}
+
+/// An open order of the account
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenOrderRow {
+    pub txid: String,
+    pub pair: String,
+    pub side: String,
+    pub ordertype: String,
+    pub price: String,
+    pub volume: String,
+    pub filled: String,
+    pub opened: f64,
+}
+
+impl OpenOrderRow {
+    pub fn new(txid: &str, order: &Order) -> Self {
+        Self {
+            txid: txid.to_string(),
+            pair: order.descr.pair.to_string(),
+            side: order.descr.r#type.clone(),
+            ordertype: order.descr.ordertype.clone(),
+            price: order.descr.price.clone(),
+            volume: order.vol.clone(),
+            filled: order.vol_exec.clone(),
+            opened: order.opentm,
+        }
+    }
+}
+
+/// The part of a parent order request a strategy is shown with
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StrategyRequest {
+    pub pair: Pair,
+    pub side: OrderSide,
+    pub volume: f64,
+}
+
+/// A strategy run by the server, as listed by `GET /api/algos`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StrategyView {
+    pub id: String,
+    pub algo: String,
+    pub request: StrategyRequest,
+    pub status: String,
+    pub executed_volume: f64,
+    #[serde(default)]
+    pub average_price: Option<f64>,
+    pub percent_complete: f64,
+}
+
+impl StrategyView {
+    pub fn is_running(&self) -> bool {
+        self.status == "running"
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.status == "paused"
+    }
+
+    /// Profit of the executed volume if it were closed at `mark`, in the quote currency
+    pub fn pnl(&self, mark: f64) -> Option<f64> {
+        let average = self.average_price?;
+        let per_unit = match self.request.side {
+            OrderSide::Buy => mark - average,
+            OrderSide::Sell => average - mark,
+        };
+        Some(per_unit * self.executed_volume)
+    }
+}
+
+/// Everything the dashboard shows, updated from the WebSocket feeds and REST refreshes
+#[derive(Debug, Clone, Default)]
+pub struct Dashboard {
+    /// Pair in WebSocket v2 notation, e.g. `BTC/USD`
+    pub symbol: String,
+    /// Names the pair of a strategy may be given by
+    pub pair_names: Vec<String>,
+    pub price_decimals: usize,
+    pub volume_decimals: usize,
+    book: BookLadder,
+    pub trades: VecDeque<TradeRow>,
+    /// Close of each minute, oldest first, keyed by the minute's Unix time
+    closes: VecDeque<(i64, f64)>,
+    pub last: Option<f64>,
+    pub change_pct: Option<f64>,
+    pub volume_24h: Option<f64>,
+    pub balances: Vec<(String, String)>,
+    pub orders: Vec<OpenOrderRow>,
+    pub strategies: Vec<StrategyView>,
+    /// Last price of the pairs strategies trade other than the dashboard's
+    pub marks: HashMap<String, f64>,
+    /// Why the account or strategies could not be loaded
+    pub account_error: Option<String>,
+    pub strategies_error: Option<String>,
+}
+
+impl Dashboard {
+    pub fn new(symbol: impl Into<String>, depth: usize) -> Self {
+        Self {
+            symbol: symbol.into(),
+            book: BookLadder::new(depth),
+            price_decimals: 2,
+            volume_decimals: 8,
+            ..Self::default()
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.book.depth()
+    }
+
+    /// Load the order book returned by the REST API
+    pub fn seed_book(&mut self, book: &OrderBook) {
+        self.book.seed(book);
+    }
+
+    /// Load the recent trades returned by the REST API, oldest first
+    pub fn seed_trades(&mut self, trades: &[Trade]) {
+        for trade in trades {
+            let (Ok(price), Ok(volume)) = (trade.price.parse(), trade.volume.parse()) else {
+                continue;
+            };
+            let side = if trade.buy_sell == "s" {
+                OrderSide::Sell
+            } else {
+                OrderSide::Buy
+            };
+            let time = format_rfc3339(trade.time.max(0.0) as u64);
+            self.push_trade(TradeRow {
+                time: time_of_day(&time),
+                side,
+                price,
+                volume,
+            });
+            self.record_price(trade.time as i64, price);
+        }
+    }
+
+    /// Load one-minute closes, oldest first
+    pub fn seed_closes(&mut self, closes: impl IntoIterator<Item = (i64, f64)>) {
+        for (time, close) in closes {
+            self.record_price(time, close);
+        }
+    }
+
+    /// Apply a message of the WebSocket v2 `book`, `trade` or `ticker` channels. Returns whether
+    /// the dashboard changed.
+    pub fn apply_market(&mut self, message: &Value) -> bool {
+        let Some(entries) = message["data"].as_array() else {
+            return false;
+        };
+        let entries: Vec<&Value> = entries
+            .iter()
+            .filter(|entry| entry["symbol"].as_str() == Some(self.symbol.as_str()))
+            .collect();
+        if entries.is_empty() {
+            return false;
+        }
+
+        match message["channel"].as_str() {
+            Some("book") => {
+                let snapshot = message["type"] == "snapshot";
+                for (i, entry) in entries.into_iter().enumerate() {
+                    self.book.apply(entry, snapshot && i == 0);
+                }
+            }
+            Some("trade") => {
+                for entry in entries {
+                    let (Some(price), Some(volume)) =
+                        (entry["price"].as_f64(), entry["qty"].as_f64())
+                    else {
+                        continue;
+                    };
+                    let side = if entry["side"] == "sell" {
+                        OrderSide::Sell
+                    } else {
+                        OrderSide::Buy
+                    };
+                    let timestamp = entry["timestamp"].as_str().unwrap_or_default();
+                    self.push_trade(TradeRow {
+                        time: time_of_day(timestamp),
+                        side,
+                        price,
+                        volume,
+                    });
+                    if let Some(time) = parse_rfc3339(timestamp) {
+                        self.record_price(time as i64, price);
+                    }
+                }
+            }
+            Some("ticker") => {
+                let entry = entries[0];
+                self.last = entry["last"].as_f64().or(self.last);
+                self.change_pct = entry["change_pct"].as_f64();
+                self.volume_24h = entry["volume"].as_f64();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Best ask levels, nearest to the spread last, and best bid levels, nearest first, as
+    /// `(price, volume)`
+    pub fn ladder(&self) -> (Vec<Level>, Vec<Level>) {
+        let mut asks = self.book.asks();
+        asks.reverse();
+        (asks, self.book.bids())
+    }
+
+    pub fn best_bid(&self) -> Option<f64> {
+        self.book.best_bid()
+    }
+
+    pub fn best_ask(&self) -> Option<f64> {
+        self.book.best_ask()
+    }
+
+    /// Middle of the best bid and ask, or the last trade price when a side is empty
+    pub fn mid(&self) -> Option<f64> {
+        self.book.mid().or(self.last)
+    }
+
+    /// One-minute closes scaled to the 1..=100 range of the chart
+    pub fn sparkline(&self) -> Vec<u64> {
+        let low = self
+            .closes
+            .iter()
+            .map(|(_, close)| *close)
+            .fold(f64::INFINITY, f64::min);
+        let high = self
+            .closes
+            .iter()
+            .map(|(_, close)| *close)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let range = high - low;
+        self.closes
+            .iter()
+            .map(|(_, close)| {
+                if range > 0.0 {
+                    ((close - low) / range * 99.0).round() as u64 + 1
+                } else {
+                    50
+                }
+            })
+            .collect()
+    }
+
+    /// Lowest and highest close on the chart
+    pub fn chart_range(&self) -> Option<(f64, f64)> {
+        let closes = self.closes.iter().map(|(_, close)| *close);
+        let low = closes.clone().reduce(f64::min)?;
+        let high = closes.reduce(f64::max)?;
+        Some((low, high))
+    }
+
+    /// Price the executed volume of a strategy is marked at
+    pub fn mark_of(&self, strategy: &StrategyView) -> Option<f64> {
+        let pair = strategy.request.pair.as_str();
+        if self
+            .pair_names
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(pair))
+        {
+            self.mid()
+        } else {
+            self.marks.get(pair).copied()
+        }
+    }
+
+    /// Sum of the PnL of every strategy with a mark, as `(pnl, strategies counted)`
+    pub fn total_pnl(&self) -> (f64, usize) {
+        self.strategies
+            .iter()
+            .filter_map(|strategy| strategy.pnl(self.mark_of(strategy)?))
+            .fold((0.0, 0), |(total, count), pnl| (total + pnl, count + 1))
+    }
+
+    fn push_trade(&mut self, trade: TradeRow) {
+        self.last = Some(trade.price);
+        self.trades.push_front(trade);
+        self.trades.truncate(MAX_TRADES);
+    }
+
+    fn record_price(&mut self, time: i64, price: f64) {
+        let minute = time - time.rem_euclid(60);
+        match self.closes.back_mut() {
+            Some((last, close)) if *last == minute => *close = price,
+            Some((last, _)) if *last > minute => {}
+            _ => {
+                self.closes.push_back((minute, price));
+                if self.closes.len() > MAX_CLOSES {
+                    self.closes.pop_front();
+                }
+            }
+        }
+    }
+}
+
+/// `HH:MM:SS` of an RFC 3339 timestamp
+fn time_of_day(timestamp: &str) -> String {
+    timestamp.get(11..19).unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn dashboard() -> Dashboard {
+        let mut dashboard = Dashboard::new("BTC/USD", 2);
+        dashboard.apply_market(&json!({
+            "channel": "book",
+            "type": "snapshot",
+            "data": [{
+                "symbol": "BTC/USD",
+                "bids": [
+                    { "price": 100.0, "qty": 1.0 },
+                    { "price": 99.5, "qty": 2.0 },
+                ],
+                "asks": [
+                    { "price": 101.0, "qty": 0.5 },
+                    { "price": 102.0, "qty": 3.0 },
+                ],
+            }],
+        }));
+        dashboard
+    }
+
+    #[test]
+    fn test_book() {
+        let mut dashboard = dashboard();
+        assert_eq!(dashboard.mid(), Some(100.5));
+
+        let changed = dashboard.apply_market(&json!({
+            "channel": "book",
+            "type": "update",
+            "data": [{
+                "symbol": "BTC/USD",
+                "bids": [
+                    { "price": 100.0, "qty": 0.0 },
+                    { "price": 100.5, "qty": 4.0 },
+                ],
+                "asks": [{ "price": 100.8, "qty": 1.0 }],
+            }],
+        }));
+        assert!(changed);
+
+        // Levels beyond the depth are dropped, asks listed from the furthest
+        let (asks, bids) = dashboard.ladder();
+        assert_eq!(asks, vec![(101.0, 0.5), (100.8, 1.0)]);
+        assert_eq!(bids, vec![(100.5, 4.0), (99.5, 2.0)]);
+
+        // Other symbols are ignored
+        assert!(!dashboard.apply_market(&json!({
+            "channel": "book",
+            "type": "snapshot",
+            "data": [{ "symbol": "ETH/USD", "bids": [], "asks": [] }],
+        })));
+        assert_eq!(dashboard.best_bid(), Some(100.5));
+    }
+
+    #[test]
+    fn test_trades_and_chart() {
+        let mut dashboard = dashboard();
+        for (timestamp, price) in [
+            ("2024-01-01T10:00:05.100Z", 100.0),
+            ("2024-01-01T10:00:40.000Z", 101.0),
+            ("2024-01-01T10:01:02.000Z", 99.0),
+        ] {
+            dashboard.apply_market(&json!({
+                "channel": "trade",
+                "type": "update",
+                "data": [{
+                    "symbol": "BTC/USD",
+                    "side": "sell",
+                    "price": price,
+                    "qty": 0.1,
+                    "timestamp": timestamp,
+                }],
+            }));
+        }
+
+        assert_eq!(dashboard.trades.len(), 3);
+        assert_eq!(dashboard.trades[0].time, "10:01:02");
+        assert_eq!(dashboard.trades[0].side, OrderSide::Sell);
+        assert_eq!(dashboard.last, Some(99.0));
+
+        // One point per minute, at its last price
+        assert_eq!(dashboard.sparkline(), vec![100, 1]);
+        assert_eq!(dashboard.chart_range(), Some((99.0, 101.0)));
+    }
+
+    #[test]
+    fn test_strategy_pnl() {
+        let mut dashboard = dashboard();
+        dashboard.pair_names = vec!["XXBTZUSD".into(), "XBTUSD".into()];
+        dashboard.marks.insert("ETHUSD".into(), 2_000.0);
+        dashboard.strategies = serde_json::from_value(json!([
+            {
+                "id": "a",
+                "algo": "twap",
+                "request": { "pair": "XBTUSD", "side": "buy", "volume": 4.0 },
+                "status": "running",
+                "executed_volume": 2.0,
+                "average_price": 99.5,
+                "percent_complete": 50.0,
+            },
+            {
+                "id": "b",
+                "algo": "vwap",
+                "request": { "pair": "ETHUSD", "side": "sell", "volume": 1.0 },
+                "status": "paused",
+                "executed_volume": 1.0,
+                "average_price": 2_100.0,
+                "percent_complete": 100.0,
+            },
+            {
+                "id": "c",
+                "algo": "pov",
+                "request": { "pair": "SOLUSD", "side": "buy", "volume": 1.0 },
+                "status": "running",
+                "executed_volume": 0.0,
+                "percent_complete": 0.0,
+            },
+        ]))
+        .unwrap();
+
+        // Bought 2 at 99.5, marked at the mid of 100.5
+        assert_eq!(dashboard.strategies[0].pnl(100.5), Some(2.0));
+        assert_eq!(dashboard.mark_of(&dashboard.strategies[1]), Some(2_000.0));
+        assert_eq!(dashboard.mark_of(&dashboard.strategies[2]), None);
+        assert_eq!(dashboard.total_pnl(), (102.0, 2));
+    }
+}
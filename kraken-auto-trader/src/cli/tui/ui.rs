@@ -0,0 +1,368 @@
+use super::{
+    app::{App, Focus, Mode},
+    state::Dashboard,
+};
+use crate::models::trading::OrderSide;
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Cell, Paragraph, Row, Sparkline, Table, TableState},
+    Frame,
+};
+
+const HELP: &str =
+    "q quit  b buy  s sell  c cancel order  p pause/resume strategy  tab switch  ↑↓ select  r refresh";
+
+/// Draw the whole dashboard
+pub fn draw(frame: &mut Frame, dashboard: &Dashboard, app: &App, connected: bool) {
+    let [header, market, account, status] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(12),
+        Constraint::Percentage(35),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [book, trades, side] = Layout::horizontal([
+        Constraint::Percentage(30),
+        Constraint::Percentage(30),
+        Constraint::Percentage(40),
+    ])
+    .areas(market);
+    let [chart, balances] =
+        Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(side);
+    let [orders, strategies] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(account);
+
+    draw_header(frame, header, dashboard, connected);
+    draw_book(frame, book, dashboard);
+    draw_trades(frame, trades, dashboard);
+    draw_chart(frame, chart, dashboard);
+    draw_balances(frame, balances, dashboard);
+    draw_orders(frame, orders, dashboard, app);
+    draw_strategies(frame, strategies, dashboard, app);
+    draw_status(frame, status, app);
+}
+
+fn draw_header(frame: &mut Frame, area: Rect, dashboard: &Dashboard, connected: bool) {
+    let mut spans = vec![Span::styled(
+        format!(" {} ", dashboard.symbol),
+        Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED),
+    )];
+    if let Some(last) = dashboard.last {
+        spans.push(Span::raw(format!(
+            "  last {:.*}",
+            dashboard.price_decimals, last
+        )));
+    }
+    if let Some(change) = dashboard.change_pct {
+        spans.push(Span::styled(
+            format!("  {:+.2}%", change),
+            Style::default().fg(signed_color(change)),
+        ));
+    }
+    if let Some(volume) = dashboard.volume_24h {
+        spans.push(Span::raw(format!("  24h vol {:.2}", volume)));
+    }
+    let (pnl, counted) = dashboard.total_pnl();
+    if counted > 0 {
+        spans.push(Span::styled(
+            format!("  strategies PnL {:+.2}", pnl),
+            Style::default().fg(signed_color(pnl)),
+        ));
+    }
+    spans.push(if connected {
+        Span::styled("  ● live", Style::default().fg(Color::Green))
+    } else {
+        Span::styled("  ● reconnecting", Style::default().fg(Color::Yellow))
+    });
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+fn draw_book(frame: &mut Frame, area: Rect, dashboard: &Dashboard) {
+    let (asks, bids) = dashboard.ladder();
+    let level = |(price, volume): (f64, f64), color: Color| {
+        Row::new([
+            Cell::from(format!("{:.*}", dashboard.price_decimals, price)),
+            Cell::from(format!("{:.*}", dashboard.volume_decimals, volume)),
+        ])
+        .style(Style::default().fg(color))
+    };
+    let spread = match (dashboard.best_ask(), dashboard.best_bid()) {
+        (Some(ask), Some(bid)) => format!("spread {:.*}", dashboard.price_decimals, ask - bid),
+        _ => String::new(),
+    };
+
+    // Pad the asks so the spread stays in the middle of the ladder
+    let mut rows: Vec<Row> = (asks.len()..dashboard.depth())
+        .map(|_| Row::default())
+        .collect();
+    rows.extend(asks.into_iter().map(|ask| level(ask, Color::Red)));
+    rows.push(Row::new([Cell::from(spread)]).style(Style::default().fg(Color::DarkGray)));
+    rows.extend(bids.into_iter().map(|bid| level(bid, Color::Green)));
+
+    let table = Table::new(
+        rows,
+        [Constraint::Percentage(55), Constraint::Percentage(45)],
+    )
+    .header(header_row(&["Price", "Volume"]))
+    .block(Block::default().borders(Borders::ALL).title(" Order book "));
+    frame.render_widget(table, area);
+}
+
+fn draw_trades(frame: &mut Frame, area: Rect, dashboard: &Dashboard) {
+    let rows = dashboard.trades.iter().map(|trade| {
+        let color = match trade.side {
+            OrderSide::Buy => Color::Green,
+            OrderSide::Sell => Color::Red,
+        };
+        Row::new([
+            Cell::from(trade.time.clone()),
+            Cell::from(format!("{:.*}", dashboard.price_decimals, trade.price)),
+            Cell::from(format!("{:.*}", dashboard.volume_decimals, trade.volume)),
+        ])
+        .style(Style::default().fg(color))
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(8),
+            Constraint::Percentage(50),
+            Constraint::Percentage(50),
+        ],
+    )
+    .header(header_row(&["Time", "Price", "Volume"]))
+    .block(Block::default().borders(Borders::ALL).title(" Trades "));
+    frame.render_widget(table, area);
+}
+
+fn draw_chart(frame: &mut Frame, area: Rect, dashboard: &Dashboard) {
+    let title = match dashboard.chart_range() {
+        Some((low, high)) => format!(
+            " 1m closes {:.*} - {:.*} ",
+            dashboard.price_decimals, low, dashboard.price_decimals, high
+        ),
+        None => " 1m closes ".to_string(),
+    };
+    let data = dashboard.sparkline();
+    // Show the latest closes the chart has room for
+    let width = area.width.saturating_sub(2) as usize;
+    let data = &data[data.len().saturating_sub(width)..];
+    let sparkline = Sparkline::default()
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .data(data)
+        .max(100)
+        .style(Style::default().fg(Color::Cyan));
+    frame.render_widget(sparkline, area);
+}
+
+fn draw_balances(frame: &mut Frame, area: Rect, dashboard: &Dashboard) {
+    let block = Block::default().borders(Borders::ALL).title(" Balances ");
+    if let Some(error) = &dashboard.account_error {
+        frame.render_widget(unavailable(error).block(block), area);
+        return;
+    }
+    let rows = dashboard
+        .balances
+        .iter()
+        .map(|(asset, amount)| Row::new([asset.clone(), amount.clone()]));
+    let table = Table::new(
+        rows,
+        [Constraint::Percentage(40), Constraint::Percentage(60)],
+    )
+    .header(header_row(&["Asset", "Balance"]))
+    .block(block);
+    frame.render_widget(table, area);
+}
+
+fn draw_orders(frame: &mut Frame, area: Rect, dashboard: &Dashboard, app: &App) {
+    let block = panel(" Open orders ", app.focus == Focus::Orders);
+    if let Some(error) = &dashboard.account_error {
+        frame.render_widget(unavailable(error).block(block), area);
+        return;
+    }
+    let rows = dashboard.orders.iter().map(|order| {
+        Row::new([
+            order.txid.clone(),
+            order.pair.clone(),
+            order.side.clone(),
+            order.ordertype.clone(),
+            order.price.clone(),
+            format!("{}/{}", order.filled, order.volume),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(20),
+            Constraint::Length(10),
+            Constraint::Length(5),
+            Constraint::Length(10),
+            Constraint::Min(8),
+            Constraint::Min(10),
+        ],
+    )
+    .header(header_row(&[
+        "Txid", "Pair", "Side", "Type", "Price", "Filled",
+    ]))
+    .row_highlight_style(highlight(app.focus == Focus::Orders))
+    .block(block);
+    let mut state = TableState::default().with_selected(Some(app.selected_order));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn draw_strategies(frame: &mut Frame, area: Rect, dashboard: &Dashboard, app: &App) {
+    let block = panel(" Strategies ", app.focus == Focus::Strategies);
+    if let Some(error) = &dashboard.strategies_error {
+        frame.render_widget(unavailable(error).block(block), area);
+        return;
+    }
+    let rows = dashboard.strategies.iter().map(|strategy| {
+        let pnl = dashboard
+            .mark_of(strategy)
+            .and_then(|mark| strategy.pnl(mark));
+        let pnl_cell = match pnl {
+            Some(pnl) => {
+                Cell::from(format!("{:+.2}", pnl)).style(Style::default().fg(signed_color(pnl)))
+            }
+            None => Cell::from("-"),
+        };
+        Row::new([
+            Cell::from(strategy.id.chars().take(8).collect::<String>()),
+            Cell::from(strategy.algo.clone()),
+            Cell::from(format!(
+                "{} {}",
+                strategy.request.side.as_str(),
+                strategy.request.pair
+            )),
+            Cell::from(strategy.status.clone()),
+            Cell::from(format!("{:.0}%", strategy.percent_complete)),
+            pnl_cell,
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(8),
+            Constraint::Length(5),
+            Constraint::Min(12),
+            Constraint::Length(9),
+            Constraint::Length(5),
+            Constraint::Min(8),
+        ],
+    )
+    .header(header_row(&[
+        "Id", "Algo", "Order", "Status", "Done", "PnL",
+    ]))
+    .row_highlight_style(highlight(app.focus == Focus::Strategies))
+    .block(block);
+    let mut state = TableState::default().with_selected(Some(app.selected_strategy));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn draw_status(frame: &mut Frame, area: Rect, app: &App) {
+    let line = match &app.mode {
+        Mode::Entry { side, input } => Line::from(vec![
+            Span::styled(
+                format!(" {} VOLUME [PRICE]: ", side.as_str()),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(format!("{}▏", input)),
+            Span::styled(
+                "  enter to review, esc to abandon",
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]),
+        Mode::Confirm { prompt, .. } => Line::from(vec![
+            Span::styled(
+                format!(" {} ", prompt),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("y/n"),
+        ]),
+        Mode::Normal => match &app.notice {
+            Some(notice) => Line::from(format!(" {}", notice)),
+            None => Line::styled(format!(" {}", HELP), Style::default().fg(Color::DarkGray)),
+        },
+    };
+    frame.render_widget(Paragraph::new(line), area);
+}
+
+fn header_row(names: &[&'static str]) -> Row<'static> {
+    Row::new(names.iter().copied()).style(Style::default().add_modifier(Modifier::BOLD))
+}
+
+fn panel(title: &'static str, focused: bool) -> Block<'static> {
+    let block = Block::default().borders(Borders::ALL).title(title);
+    if focused {
+        block.border_style(Style::default().fg(Color::Cyan))
+    } else {
+        block
+    }
+}
+
+fn highlight(focused: bool) -> Style {
+    if focused {
+        Style::default().add_modifier(Modifier::REVERSED)
+    } else {
+        Style::default()
+    }
+}
+
+fn unavailable(reason: &str) -> Paragraph<'static> {
+    Paragraph::new(format!("Unavailable: {}", reason)).style(Style::default().fg(Color::Yellow))
+}
+
+fn signed_color(value: f64) -> Color {
+    if value < 0.0 {
+        Color::Red
+    } else {
+        Color::Green
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{backend::TestBackend, Terminal};
+    use serde_json::json;
+
+    #[test]
+    fn test_draw() {
+        let mut dashboard = Dashboard::new("BTC/USD", 5);
+        dashboard.apply_market(&json!({
+            "channel": "book",
+            "type": "snapshot",
+            "data": [{
+                "symbol": "BTC/USD",
+                "bids": [{ "price": 65000.0, "qty": 1.5 }],
+                "asks": [{ "price": 65010.0, "qty": 0.25 }],
+            }],
+        }));
+        dashboard.balances = vec![("ZUSD".to_string(), "1000.0000".to_string())];
+        dashboard.strategies_error = Some("503 Service Unavailable".to_string());
+
+        let mut terminal = Terminal::new(TestBackend::new(140, 30)).unwrap();
+        terminal
+            .draw(|frame| draw(frame, &dashboard, &App::default(), true))
+            .unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+
+        for text in [
+            "BTC/USD",
+            "65010.00",
+            "spread 10.00",
+            "ZUSD",
+            "Unavailable: 503",
+            "q quit",
+        ] {
+            assert!(screen.contains(text), "{} missing", text);
+        }
+    }
+}
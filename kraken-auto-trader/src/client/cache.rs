@@ -0,0 +1,364 @@
+use crate::{
+    errors::Error, metrics::metrics, utils::config::CacheConfig, utils::endpoints::market::*,
+};
+use futures::{
+    future::{BoxFuture, Shared},
+    FutureExt,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tracing::debug;
+
+type Flight = Shared<BoxFuture<'static, Result<Value, Arc<Error>>>>;
+
+struct Entry {
+    endpoint: String,
+    value: Value,
+    expires: Instant,
+}
+
+/// Counters of how public requests were served
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct CacheStats {
+    /// Served from the cache
+    pub hits: u64,
+    /// Sent to Kraken
+    pub misses: u64,
+    /// Waited for an identical request already sent
+    pub coalesced: u64,
+    /// Responses currently held, fresh or not
+    pub entries: usize,
+}
+
+/// Cache of public endpoint responses, in front of the rate limiter and the client lock.
+///
+/// Responses are kept for a time depending on the endpoint: an hour for assets and asset
+/// pairs, a second for tickers. Server time and system status are never cached, they are what
+/// clock synchronization and health checks measure. A request identical to one in flight waits
+/// for its response instead of being sent again, whether or not the endpoint is cached; a
+/// failure is returned to every caller that waited for it and is not cached.
+pub struct PublicCache {
+    config: CacheConfig,
+    entries: Mutex<HashMap<String, Entry>>,
+    in_flight: Mutex<HashMap<String, (Flight, u64)>>,
+    /// Bumped by every invalidation, so responses to requests sent before it are not kept
+    generation: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+impl PublicCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            entries: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            coalesced: AtomicU64::new(0),
+        }
+    }
+
+    /// How long responses of `endpoint` are kept, `None` when its requests bypass the cache
+    pub fn ttl(&self, endpoint: &str) -> Option<Duration> {
+        if !self.config.enabled {
+            return None;
+        }
+        match endpoint {
+            ASSET_INFO | TRADABLE_ASSET_PAIRS => {
+                Some(Duration::from_secs(self.config.metadata_ttl_secs))
+            }
+            TICKER => Some(Duration::from_millis(self.config.ticker_ttl_ms)),
+            OHLC | ORDER_BOOK | RECENT_TRADES | RECENT_SPREADS => {
+                Some(Duration::from_millis(self.config.market_ttl_ms))
+            }
+            _ => None,
+        }
+    }
+
+    /// The response to `endpoint` with `params`, from the cache, from an identical request in
+    /// flight, or from `fetch`
+    pub async fn get_or_fetch<F>(
+        &self,
+        endpoint: &str,
+        params: &HashMap<String, String>,
+        fetch: F,
+    ) -> Result<Value, Error>
+    where
+        F: Future<Output = Result<Value, Error>> + Send + 'static,
+    {
+        let Some(ttl) = self.ttl(endpoint) else {
+            return fetch.await;
+        };
+        let key = cache_key(endpoint, params);
+
+        if let Some(value) = self.fresh(&key) {
+            self.count(endpoint, "hit", &self.hits);
+            return Ok(value);
+        }
+
+        let (flight, generation) = {
+            let mut in_flight = lock(&self.in_flight);
+            match in_flight.get(&key) {
+                Some(flight) => {
+                    self.count(endpoint, "coalesced", &self.coalesced);
+                    flight.clone()
+                }
+                None => {
+                    self.count(endpoint, "miss", &self.misses);
+                    let flight = (
+                        fetch
+                            .map(|result| result.map_err(Arc::new))
+                            .boxed()
+                            .shared(),
+                        self.generation.load(Ordering::SeqCst),
+                    );
+                    in_flight.insert(key.clone(), flight.clone());
+                    flight
+                }
+            }
+        };
+
+        let result = flight.clone().await;
+
+        // Every caller gets here, the first one to do so settles the request
+        let settled = {
+            let mut in_flight = lock(&self.in_flight);
+            match in_flight.get(&key) {
+                Some((current, _)) if current.ptr_eq(&flight) => {
+                    in_flight.remove(&key);
+                    true
+                }
+                _ => false,
+            }
+        };
+        if settled && !ttl.is_zero() && generation == self.generation.load(Ordering::SeqCst) {
+            if let Ok(value) = &result {
+                lock(&self.entries).insert(
+                    key,
+                    Entry {
+                        endpoint: endpoint.to_string(),
+                        value: value.clone(),
+                        expires: Instant::now() + ttl,
+                    },
+                );
+            }
+        }
+        result.map_err(|e| e.duplicate())
+    }
+
+    fn fresh(&self, key: &str) -> Option<Value> {
+        let mut entries = lock(&self.entries);
+        match entries.get(key) {
+            Some(entry) if entry.expires > Instant::now() => Some(entry.value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn count(&self, endpoint: &str, outcome: &str, counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+        metrics()
+            .public_cache
+            .with_label_values(&[endpoint, outcome])
+            .inc();
+    }
+
+    /// Drop the cached responses of `endpoint`
+    pub fn invalidate(&self, endpoint: &str) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        let mut entries = lock(&self.entries);
+        let before = entries.len();
+        entries.retain(|_, entry| entry.endpoint != endpoint);
+        debug!(
+            "Dropped {} cached responses of {}",
+            before - entries.len(),
+            endpoint
+        );
+    }
+
+    /// Drop every cached response
+    pub fn clear(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        lock(&self.entries).clear();
+        debug!("Dropped every cached response");
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+            entries: lock(&self.entries).len(),
+        }
+    }
+}
+
+impl Default for PublicCache {
+    fn default() -> Self {
+        Self::new(CacheConfig::default())
+    }
+}
+
+/// `endpoint` with its parameters in a fixed order
+fn cache_key(endpoint: &str, params: &HashMap<String, String>) -> String {
+    let params: BTreeMap<&String, &String> = params.iter().collect();
+    let mut key = endpoint.to_string();
+    for (i, (name, value)) in params.into_iter().enumerate() {
+        key.push(if i == 0 { '?' } else { '&' });
+        key.push_str(name);
+        key.push('=');
+        key.push_str(value);
+    }
+    key
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::AtomicUsize;
+
+    fn params(pair: &str) -> HashMap<String, String> {
+        HashMap::from([("pair".to_string(), pair.to_string())])
+    }
+
+    fn counting_fetch(
+        calls: &Arc<AtomicUsize>,
+        value: Value,
+    ) -> impl Future<Output = Result<Value, Error>> + Send + 'static {
+        let calls = calls.clone();
+        async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok(value)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hits_until_invalidated() {
+        let cache = PublicCache::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..3 {
+            let value = cache
+                .get_or_fetch(TICKER, &params("XBTUSD"), counting_fetch(&calls, json!(1)))
+                .await
+                .unwrap();
+            assert_eq!(value, json!(1));
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Other parameters are another entry
+        cache
+            .get_or_fetch(TICKER, &params("ETHUSD"), counting_fetch(&calls, json!(2)))
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        cache.invalidate(TICKER);
+        let value = cache
+            .get_or_fetch(TICKER, &params("XBTUSD"), counting_fetch(&calls, json!(3)))
+            .await
+            .unwrap();
+        assert_eq!(value, json!(3));
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (2, 3, 1));
+    }
+
+    #[tokio::test]
+    async fn test_coalesces_identical_requests() {
+        // With no TTL responses are not kept, but requests in flight are still shared
+        let cache = PublicCache::new(CacheConfig {
+            market_ttl_ms: 0,
+            ..CacheConfig::default()
+        });
+        let calls = Arc::new(AtomicUsize::new(0));
+        let book = params("XBTUSD");
+
+        let requests =
+            (0..5).map(|_| cache.get_or_fetch(ORDER_BOOK, &book, counting_fetch(&calls, json!(1))));
+        let results = futures::future::join_all(requests).await;
+        assert!(results
+            .iter()
+            .all(|result| result.as_ref().unwrap() == &json!(1)));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let stats = cache.stats();
+        assert_eq!((stats.misses, stats.coalesced, stats.entries), (1, 4, 0));
+
+        cache
+            .get_or_fetch(
+                ORDER_BOOK,
+                &params("XBTUSD"),
+                counting_fetch(&calls, json!(1)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_shared_not_cached() {
+        let cache = PublicCache::default();
+        let failing = || async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Err::<Value, _>(Error::RateLimitExceeded(
+                "EAPI:Rate limit exceeded".to_string(),
+            ))
+        };
+
+        let all = HashMap::new();
+        let (a, b) = tokio::join!(
+            cache.get_or_fetch(ASSET_INFO, &all, failing()),
+            cache.get_or_fetch(ASSET_INFO, &all, failing()),
+        );
+        assert!(matches!(a, Err(Error::RateLimitExceeded(_))));
+        assert!(matches!(b, Err(Error::RateLimitExceeded(_))));
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn test_ttls() {
+        let cache = PublicCache::default();
+        assert_eq!(cache.ttl(ASSET_INFO), Some(Duration::from_secs(3600)));
+        assert_eq!(cache.ttl(TICKER), Some(Duration::from_secs(1)));
+        assert_eq!(cache.ttl(SERVER_TIME), None);
+        assert_eq!(cache.ttl(SYSTEM_STATUS), None);
+
+        let disabled = PublicCache::new(CacheConfig {
+            enabled: false,
+            ..CacheConfig::default()
+        });
+        assert_eq!(disabled.ttl(TICKER), None);
+        assert_eq!(
+            cache_key(
+                TICKER,
+                &HashMap::from([
+                    ("b".to_string(), "2".to_string()),
+                    ("a".to_string(), "1".to_string())
+                ])
+            ),
+            "/0/public/Ticker?a=1&b=2"
+        );
+    }
+}
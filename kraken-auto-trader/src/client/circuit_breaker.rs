@@ -0,0 +1,674 @@
+use crate::{
+    errors::Error,
+    metrics::metrics,
+    utils::{
+        config::CircuitBreakerConfig,
+        endpoints::{funding, trading},
+    },
+};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// Endpoints that fail together and share a circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointClass {
+    /// Market data
+    Public,
+    /// Balances, orders, trades and ledgers
+    Account,
+    /// Placing and amending orders
+    Trading,
+    /// Deposits, withdrawals and transfers
+    Funding,
+    /// Cancelling orders and arming the dead man's switch, which must get through while
+    /// placing orders fails. These have no circuit and are always admitted.
+    Cancel,
+}
+
+impl EndpointClass {
+    pub const ALL: [EndpointClass; 5] = [
+        EndpointClass::Public,
+        EndpointClass::Account,
+        EndpointClass::Trading,
+        EndpointClass::Funding,
+        EndpointClass::Cancel,
+    ];
+
+    /// Class of `endpoint`, e.g. `/0/private/AddOrder`
+    pub fn of(endpoint: &str) -> Self {
+        match endpoint {
+            _ if endpoint.starts_with("/0/public/") => EndpointClass::Public,
+            trading::ADD_ORDER
+            | trading::ADD_ORDER_BATCH
+            | trading::AMEND_ORDER
+            | trading::EDIT_ORDER => EndpointClass::Trading,
+            trading::CANCEL_ORDER
+            | trading::CANCEL_ALL_ORDERS
+            | trading::CANCEL_ALL_ORDERS_AFTER_X
+            | trading::CANCEL_ORDER_BATCH => EndpointClass::Cancel,
+            funding::DEPOSIT_METHODS
+            | funding::DEPOSIT_ADDRESSES
+            | funding::RECENT_DEPOSITS_STATUS
+            | funding::WITHDRAWAL_METHODS
+            | funding::WITHDRAWAL_ADDRESSES
+            | funding::WITHDRAWAL_INFO
+            | funding::WITHDRAW_FUNDS
+            | funding::RECENT_WITHDRAWALS_STATUS
+            | funding::WITHDRAWAL_CANCELLATION
+            | funding::WALLET_TRANSFER => EndpointClass::Funding,
+            _ => EndpointClass::Account,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EndpointClass::Public => "public",
+            EndpointClass::Account => "account",
+            EndpointClass::Trading => "trading",
+            EndpointClass::Funding => "funding",
+            EndpointClass::Cancel => "cancel",
+        }
+    }
+
+    /// Whether calls of the class are failed fast while it fails
+    pub fn has_circuit(&self) -> bool {
+        *self != EndpointClass::Cancel
+    }
+}
+
+impl fmt::Display for EndpointClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Calls go through
+    Closed,
+    /// Calls fail fast
+    Open,
+    /// One probe call goes through, the others fail fast
+    HalfOpen,
+}
+
+impl CircuitState {
+    fn gauge(&self) -> i64 {
+        match self {
+            CircuitState::Closed => 0,
+            CircuitState::HalfOpen => 1,
+            CircuitState::Open => 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Circuit {
+    state: CircuitState,
+    consecutive_failures: u32,
+    /// Times the circuit opened since it was last closed
+    trips: u32,
+    open_until: Instant,
+    probe_started: Option<Instant>,
+}
+
+impl Default for Circuit {
+    fn default() -> Self {
+        Self {
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            trips: 0,
+            open_until: Instant::now(),
+            probe_started: None,
+        }
+    }
+}
+
+/// State of the circuit of one class of endpoints
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitStatus {
+    pub class: EndpointClass,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    /// Seconds until an open circuit lets a probe through
+    pub retry_in_secs: Option<f64>,
+}
+
+/// Upstream failures among the recent calls to Kraken
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorBudgetStatus {
+    pub calls: usize,
+    pub failures: usize,
+    pub failure_ratio: f64,
+    /// Whether calls are no longer retried
+    pub exhausted: bool,
+}
+
+/// Everything the breakers report to `/healthz`
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakerReport {
+    pub circuits: Vec<CircuitStatus>,
+    pub error_budget: ErrorBudgetStatus,
+}
+
+/// Whether `error` shows Kraken failing, as opposed to Kraken rejecting a request. Only these
+/// count towards opening a circuit and spending the error budget. Being rate limited is the
+/// caller going too fast, not Kraken failing; a `Retry-After` holds the circuit open instead.
+pub fn is_upstream_failure(error: &Error) -> bool {
+    match error {
+        Error::HttpError(e) => !e.is_decode(),
+        Error::NetworkError(_) | Error::TimeoutError(_) => true,
+        Error::InvalidResponse(status) => status.starts_with('5'),
+        Error::Api(message) => {
+            message.contains("EService:") || message.contains("EGeneral:Internal error")
+        }
+        _ => false,
+    }
+}
+
+/// Whether `error` is Kraken refusing a call for going too fast
+pub fn is_rate_limited(error: &Error) -> bool {
+    match error {
+        Error::RateLimitExceeded(_) => true,
+        Error::InvalidResponse(status) => status.starts_with("429"),
+        _ => false,
+    }
+}
+
+/// Circuit breakers for each class of endpoints, and the error budget shared by all calls.
+///
+/// A class failing `failure_threshold` times in a row opens its circuit: its calls fail fast
+/// with [`Error::CircuitOpen`] for `open_secs`, then a single probe is let through. A successful
+/// probe closes the circuit, a failed one opens it again for twice as long, up to
+/// `max_open_secs`. A `Retry-After` from Kraken holds the circuit open for as long as asked.
+/// Cancellations have no circuit, so that orders can be pulled while placing them fails.
+///
+/// Retries back off exponentially with jitter and stop while the share of failing calls over
+/// the last `budget_window_secs` exceeds the error budget, so an outage is not made worse by
+/// every caller retrying.
+#[derive(Debug)]
+pub struct CircuitBreakers {
+    config: CircuitBreakerConfig,
+    circuits: Mutex<HashMap<EndpointClass, Circuit>>,
+    /// Time and failure of each recent call
+    outcomes: Mutex<VecDeque<(Instant, bool)>>,
+}
+
+impl CircuitBreakers {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        for class in EndpointClass::ALL.into_iter().filter(EndpointClass::has_circuit) {
+            metrics()
+                .circuit_state
+                .with_label_values(&[class.as_str()])
+                .set(CircuitState::Closed.gauge());
+        }
+        Self {
+            config,
+            circuits: Mutex::new(HashMap::new()),
+            outcomes: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn config(&self) -> &CircuitBreakerConfig {
+        &self.config
+    }
+
+    /// Let a call to `endpoint` through, or fail it fast while its circuit is open
+    pub fn admit(&self, endpoint: &str) -> Result<(), Error> {
+        let class = EndpointClass::of(endpoint);
+        if !self.config.enabled || !class.has_circuit() {
+            return Ok(());
+        }
+        let mut circuits = lock(&self.circuits);
+        let circuit = circuits.entry(class).or_default();
+        let now = Instant::now();
+        let admitted = match circuit.state {
+            CircuitState::Closed => true,
+            CircuitState::Open if now >= circuit.open_until => {
+                set_state(class, circuit, CircuitState::HalfOpen);
+                circuit.probe_started = Some(now);
+                true
+            }
+            CircuitState::Open => false,
+            // A probe that never reported back, dropped by its caller for instance, is replaced
+            CircuitState::HalfOpen => match circuit.probe_started {
+                Some(started) if now - started < self.config.open_duration() => false,
+                _ => {
+                    circuit.probe_started = Some(now);
+                    true
+                }
+            },
+        };
+        if admitted {
+            return Ok(());
+        }
+
+        metrics()
+            .circuit_rejections
+            .with_label_values(&[class.as_str()])
+            .inc();
+        let retry_in = circuit.open_until.saturating_duration_since(now);
+        Err(Error::CircuitOpen(format!(
+            "{} endpoints are failing, retry in {:.0}s",
+            class,
+            retry_in.as_secs_f64().ceil()
+        )))
+    }
+
+    /// Count the outcome of a call to `endpoint`. Rate limited calls count neither way: they
+    /// say nothing of whether Kraken is failing, and must not close a circuit held open by a
+    /// `Retry-After`.
+    pub fn record<T>(&self, endpoint: &str, result: &Result<T, Error>) {
+        if result.as_ref().err().is_some_and(is_rate_limited) {
+            return;
+        }
+        let failed = result.as_ref().err().is_some_and(is_upstream_failure);
+        self.record_budget(failed);
+        let class = EndpointClass::of(endpoint);
+        if !self.config.enabled || !class.has_circuit() {
+            return;
+        }
+
+        let mut circuits = lock(&self.circuits);
+        let circuit = circuits.entry(class).or_default();
+        if !failed {
+            circuit.consecutive_failures = 0;
+            circuit.probe_started = None;
+            if circuit.state != CircuitState::Closed {
+                circuit.trips = 0;
+                set_state(class, circuit, CircuitState::Closed);
+                info!(
+                    "Circuit of {} endpoints closed, Kraken answers again",
+                    class
+                );
+            }
+            return;
+        }
+
+        circuit.consecutive_failures += 1;
+        let trip = match circuit.state {
+            CircuitState::Closed => circuit.consecutive_failures >= self.config.failure_threshold,
+            CircuitState::HalfOpen => true,
+            // A call admitted before the circuit opened
+            CircuitState::Open => false,
+        };
+        if trip {
+            circuit.trips += 1;
+            let open_for = self.open_duration(circuit.trips);
+            circuit.open_until = Instant::now() + open_for;
+            circuit.probe_started = None;
+            set_state(class, circuit, CircuitState::Open);
+            warn!(
+                "Circuit of {} endpoints opened for {:?} after {} failures",
+                class, open_for, circuit.consecutive_failures
+            );
+        }
+    }
+
+    /// Hold the circuit of `endpoint` open for `delay`, as asked by a `Retry-After` header
+    pub fn hold_off(&self, endpoint: &str, delay: Duration) {
+        let class = EndpointClass::of(endpoint);
+        if !self.config.enabled || !class.has_circuit() {
+            return;
+        }
+        let mut circuits = lock(&self.circuits);
+        let circuit = circuits.entry(class).or_default();
+        let until = Instant::now() + delay;
+        if circuit.state != CircuitState::Open || until > circuit.open_until {
+            circuit.open_until = until;
+        }
+        circuit.probe_started = None;
+        set_state(class, circuit, CircuitState::Open);
+        warn!("Kraken asked to retry {} endpoints in {:?}", class, delay);
+    }
+
+    /// Delay before retry `attempt` (from 1) of a call to `endpoint`, or `None` when it should
+    /// not be retried: the error budget is spent, or its circuit stays open for longer than the
+    /// longest backoff. Cancellations are retried regardless.
+    pub fn retry_delay(&self, endpoint: &str, attempt: u32, base: Duration) -> Option<Duration> {
+        let class = EndpointClass::of(endpoint);
+        let backoff = backoff(attempt, base, self.config.max_backoff());
+        if !class.has_circuit() {
+            return Some(backoff);
+        }
+        if self.budget_exhausted() {
+            return None;
+        }
+        if !self.config.enabled {
+            return Some(backoff);
+        }
+        let circuits = lock(&self.circuits);
+        let wait = circuits
+            .get(&class)
+            .filter(|circuit| circuit.state == CircuitState::Open)
+            .map(|circuit| circuit.open_until.saturating_duration_since(Instant::now()))
+            .unwrap_or_default();
+        if wait > self.config.max_backoff() {
+            return None;
+        }
+        Some(backoff.max(wait))
+    }
+
+    /// Whether too many recent calls failed for calls to be retried
+    pub fn budget_exhausted(&self) -> bool {
+        self.budget().exhausted
+    }
+
+    pub fn budget(&self) -> ErrorBudgetStatus {
+        let mut outcomes = lock(&self.outcomes);
+        self.prune(&mut outcomes);
+        let calls = outcomes.len();
+        let failures = outcomes.iter().filter(|(_, failed)| *failed).count();
+        let failure_ratio = if calls == 0 {
+            0.0
+        } else {
+            failures as f64 / calls as f64
+        };
+        ErrorBudgetStatus {
+            calls,
+            failures,
+            failure_ratio,
+            exhausted: calls >= self.config.budget_min_calls
+                && failure_ratio > self.config.error_budget,
+        }
+    }
+
+    pub fn report(&self) -> BreakerReport {
+        let now = Instant::now();
+        let circuits = lock(&self.circuits);
+        let circuits = EndpointClass::ALL
+            .into_iter()
+            .filter(EndpointClass::has_circuit)
+            .map(|class| match circuits.get(&class) {
+                Some(circuit) => CircuitStatus {
+                    class,
+                    state: circuit.state,
+                    consecutive_failures: circuit.consecutive_failures,
+                    retry_in_secs: (circuit.state == CircuitState::Open).then(|| {
+                        circuit
+                            .open_until
+                            .saturating_duration_since(now)
+                            .as_secs_f64()
+                    }),
+                },
+                None => CircuitStatus {
+                    class,
+                    state: CircuitState::Closed,
+                    consecutive_failures: 0,
+                    retry_in_secs: None,
+                },
+            })
+            .collect();
+        BreakerReport {
+            circuits,
+            error_budget: self.budget(),
+        }
+    }
+
+    fn record_budget(&self, failed: bool) {
+        let mut outcomes = lock(&self.outcomes);
+        outcomes.push_back((Instant::now(), failed));
+        self.prune(&mut outcomes);
+        let failures = outcomes.iter().filter(|(_, failed)| *failed).count();
+        metrics()
+            .error_ratio
+            .set(failures as f64 / outcomes.len() as f64);
+    }
+
+    fn prune(&self, outcomes: &mut VecDeque<(Instant, bool)>) {
+        let window = self.config.budget_window();
+        while outcomes
+            .front()
+            .is_some_and(|(at, _)| at.elapsed() > window)
+        {
+            outcomes.pop_front();
+        }
+    }
+
+    /// How long the circuit stays open on its `trips`th trip in a row
+    fn open_duration(&self, trips: u32) -> Duration {
+        let doubled = self
+            .config
+            .open_duration()
+            .saturating_mul(2u32.saturating_pow(trips.saturating_sub(1)));
+        doubled.min(Duration::from_secs(self.config.max_open_secs))
+    }
+}
+
+impl Default for CircuitBreakers {
+    fn default() -> Self {
+        Self::new(CircuitBreakerConfig::default())
+    }
+}
+
+/// Exponential backoff from `base` with jitter: a random delay between half and all of
+/// `base * 2^(attempt - 1)`, capped at `max`
+pub fn backoff(attempt: u32, base: Duration, max: Duration) -> Duration {
+    let exponential = base
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(max);
+    let half = exponential / 2;
+    half + half.mul_f64(rand::random::<f64>())
+}
+
+/// Delay asked by a `Retry-After` header given in seconds
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+fn set_state(class: EndpointClass, circuit: &mut Circuit, state: CircuitState) {
+    circuit.state = state;
+    metrics()
+        .circuit_state
+        .with_label_values(&[class.as_str()])
+        .set(state.gauge());
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::endpoints::{account::BALANCE, market::TICKER};
+
+    fn breakers() -> CircuitBreakers {
+        CircuitBreakers::new(CircuitBreakerConfig {
+            failure_threshold: 2,
+            open_secs: 1,
+            ..CircuitBreakerConfig::default()
+        })
+    }
+
+    fn failure() -> Result<(), Error> {
+        Err(Error::NetworkError("connection reset".to_string()))
+    }
+
+    #[test]
+    fn test_classes() {
+        assert_eq!(EndpointClass::of(TICKER), EndpointClass::Public);
+        assert_eq!(EndpointClass::of(BALANCE), EndpointClass::Account);
+        assert_eq!(
+            EndpointClass::of(trading::ADD_ORDER),
+            EndpointClass::Trading
+        );
+        assert_eq!(
+            EndpointClass::of(funding::WITHDRAW_FUNDS),
+            EndpointClass::Funding
+        );
+        for endpoint in [
+            trading::CANCEL_ORDER,
+            trading::CANCEL_ALL_ORDERS,
+            trading::CANCEL_ALL_ORDERS_AFTER_X,
+            trading::CANCEL_ORDER_BATCH,
+        ] {
+            assert_eq!(EndpointClass::of(endpoint), EndpointClass::Cancel);
+        }
+    }
+
+    #[test]
+    fn test_rate_limiting_is_not_an_upstream_failure() {
+        assert!(!is_upstream_failure(&Error::RateLimitExceeded(
+            "EAPI:Rate limit exceeded".to_string()
+        )));
+        assert!(!is_upstream_failure(&Error::InvalidResponse(
+            "429 Too Many Requests".to_string()
+        )));
+        assert!(is_upstream_failure(&Error::InvalidResponse(
+            "503 Service Unavailable".to_string()
+        )));
+
+        let breakers = breakers();
+        for _ in 0..3 {
+            breakers.record::<()>(
+                trading::ADD_ORDER,
+                &Err(Error::RateLimitExceeded("EOrder:Rate limit exceeded".to_string())),
+            );
+        }
+        assert!(breakers.admit(trading::ADD_ORDER).is_ok());
+        assert_eq!(breakers.budget().calls, 0);
+
+        // Nor do they close a circuit Kraken asked to hold open
+        breakers.hold_off(TICKER, Duration::from_secs(60));
+        breakers.record::<()>(
+            TICKER,
+            &Err(Error::InvalidResponse("429 Too Many Requests".to_string())),
+        );
+        assert!(breakers.admit(TICKER).is_err());
+    }
+
+    #[test]
+    fn test_cancels_are_always_admitted() {
+        let breakers = CircuitBreakers::new(CircuitBreakerConfig {
+            failure_threshold: 2,
+            budget_min_calls: 4,
+            ..CircuitBreakerConfig::default()
+        });
+        breakers.record(trading::ADD_ORDER, &failure());
+        breakers.record(trading::ADD_ORDER, &failure());
+        assert!(breakers.admit(trading::ADD_ORDER).is_err());
+        assert!(breakers.admit(trading::CANCEL_ORDER).is_ok());
+        assert!(breakers.admit(trading::CANCEL_ALL_ORDERS_AFTER_X).is_ok());
+
+        // Failing cancellations open no circuit, and are retried even with the budget spent
+        for _ in 0..5 {
+            breakers.record(trading::CANCEL_ORDER, &failure());
+        }
+        breakers.hold_off(trading::CANCEL_ALL_ORDERS, Duration::from_secs(60));
+        assert!(breakers.admit(trading::CANCEL_ALL_ORDERS).is_ok());
+        assert!(breakers.budget_exhausted());
+        assert!(breakers
+            .retry_delay(trading::CANCEL_ORDER, 1, Duration::from_millis(100))
+            .is_some());
+        assert!(breakers
+            .report()
+            .circuits
+            .iter()
+            .all(|circuit| circuit.class != EndpointClass::Cancel));
+    }
+
+    #[test]
+    fn test_circuit_opens_and_probes() {
+        let breakers = breakers();
+        breakers.record(trading::ADD_ORDER, &failure());
+        assert!(breakers.admit(trading::ADD_ORDER).is_ok());
+        breakers.record(trading::ADD_ORDER, &failure());
+
+        let rejected = breakers.admit(trading::AMEND_ORDER);
+        assert!(matches!(rejected, Err(Error::CircuitOpen(_))));
+        // Other classes are not affected
+        assert!(breakers.admit(TICKER).is_ok());
+
+        // Once the circuit has been open long enough, a single probe goes through
+        let mut circuits = lock(&breakers.circuits);
+        circuits
+            .get_mut(&EndpointClass::Trading)
+            .unwrap()
+            .open_until = Instant::now();
+        drop(circuits);
+        assert!(breakers.admit(trading::ADD_ORDER).is_ok());
+        assert!(breakers.admit(trading::ADD_ORDER).is_err());
+
+        // A failed probe opens it for twice as long
+        breakers.record(trading::ADD_ORDER, &failure());
+        let report = breakers.report();
+        let trading = &report.circuits[2];
+        assert_eq!(trading.state, CircuitState::Open);
+        assert!(trading.retry_in_secs.unwrap() > 1.5);
+
+        let mut circuits = lock(&breakers.circuits);
+        circuits
+            .get_mut(&EndpointClass::Trading)
+            .unwrap()
+            .open_until = Instant::now();
+        drop(circuits);
+        assert!(breakers.admit(trading::ADD_ORDER).is_ok());
+        breakers.record::<()>(
+            trading::ADD_ORDER,
+            &Err(Error::Api("EOrder:Insufficient funds".to_string())),
+        );
+        assert_eq!(breakers.report().circuits[2].state, CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_retry_after_holds_circuit_open() {
+        let breakers = breakers();
+        breakers.hold_off(TICKER, Duration::from_secs(60));
+        assert!(breakers.admit(TICKER).is_err());
+        // Longer than the longest backoff: not worth retrying
+        assert_eq!(
+            breakers.retry_delay(TICKER, 1, Duration::from_millis(100)),
+            None
+        );
+
+        breakers.hold_off(BALANCE, Duration::from_secs(2));
+        let delay = breakers
+            .retry_delay(BALANCE, 1, Duration::from_millis(100))
+            .unwrap();
+        assert!(delay > Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_error_budget() {
+        let breakers = CircuitBreakers::new(CircuitBreakerConfig {
+            enabled: false,
+            budget_min_calls: 4,
+            ..CircuitBreakerConfig::default()
+        });
+        for _ in 0..3 {
+            breakers.record(TICKER, &failure());
+        }
+        // Too few calls to judge
+        assert!(!breakers.budget_exhausted());
+        breakers.record(TICKER, &Ok(()));
+        let budget = breakers.budget();
+        assert!(budget.exhausted);
+        assert_eq!((budget.calls, budget.failures), (4, 3));
+        assert_eq!(
+            breakers.retry_delay(TICKER, 1, Duration::from_millis(100)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_backoff() {
+        let base = Duration::from_millis(100);
+        let max = Duration::from_secs(1);
+        for attempt in 1..=6 {
+            let delay = backoff(attempt, base, max);
+            let full = (base * 2u32.pow(attempt - 1)).min(max);
+            assert!(delay >= full / 2 && delay <= full, "{:?}", delay);
+        }
+        assert_eq!(parse_retry_after(" 30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+}
@@ -0,0 +1,289 @@
+use crate::{
+    errors::Error,
+    utils::endpoints::{account::*, funding::*, trading::*},
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, fmt, str::FromStr};
+use zeroize::Zeroizing;
+
+use super::otp::Otp;
+
+/// What an API key is allowed to do, following the permission groups of Kraken's key settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// Query funds, orders, trades and ledgers
+    Query,
+    /// Request and download data exports
+    Export,
+    /// Access the authenticated WebSocket API
+    WebSocket,
+    /// Create, modify and cancel orders
+    Trade,
+    /// Query deposit methods and addresses
+    Deposit,
+    /// Withdraw and transfer funds
+    Withdraw,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 6] = [
+        Permission::Query,
+        Permission::Export,
+        Permission::WebSocket,
+        Permission::Trade,
+        Permission::Deposit,
+        Permission::Withdraw,
+    ];
+
+    /// Permission a private endpoint needs; unknown endpoints need the most sensitive one
+    pub fn for_endpoint(endpoint: &str) -> Permission {
+        match endpoint {
+            BALANCE | BALANCE_EX | TRADE_BALANCE | OPEN_ORDERS | CLOSED_ORDERS | QUERY_ORDERS
+            | ORDER_AMENDS | TRADES_HISTORY | QUERY_TRADES | OPEN_POSITIONS | LEDGERS
+            | QUERY_LEDGERS | TRADE_VOLUME => Permission::Query,
+            REQUEST_EXPORT_REPORT
+            | GET_EXPORT_REPORT_STATUS
+            | RETRIEVE_EXPORT
+            | DELETE_EXPORT_REPORT => Permission::Export,
+            GET_WEBSOCKETS_TOKEN => Permission::WebSocket,
+            ADD_ORDER
+            | ADD_ORDER_BATCH
+            | AMEND_ORDER
+            | EDIT_ORDER
+            | CANCEL_ORDER
+            | CANCEL_ALL_ORDERS
+            | CANCEL_ALL_ORDERS_AFTER_X
+            | CANCEL_ORDER_BATCH => Permission::Trade,
+            DEPOSIT_METHODS | DEPOSIT_ADDRESSES | RECENT_DEPOSITS_STATUS => Permission::Deposit,
+            _ => Permission::Withdraw,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::Query => "query",
+            Permission::Export => "export",
+            Permission::WebSocket => "web_socket",
+            Permission::Trade => "trade",
+            Permission::Deposit => "deposit",
+            Permission::Withdraw => "withdraw",
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Permission {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase().replace('-', "_");
+        Permission::ALL
+            .into_iter()
+            .find(|permission| {
+                permission.as_str() == name
+                    || (name == "websocket" && *permission == Permission::WebSocket)
+            })
+            .ok_or_else(|| Error::InvalidParameter(format!("Unknown API key permission: {}", s)))
+    }
+}
+
+/// API key and base64 secret, wiped from memory when dropped
+#[derive(Clone)]
+pub struct ApiCredentials {
+    key: Zeroizing<String>,
+    secret: Zeroizing<String>,
+}
+
+impl ApiCredentials {
+    /// Check that the secret is the base64 string Kraken issued, so a typo fails here
+    /// instead of as a rejected signature
+    pub fn new(key: impl Into<String>, secret: impl Into<String>) -> Result<Self, Error> {
+        let key = Zeroizing::new(key.into());
+        let secret = Zeroizing::new(secret.into());
+        if key.trim().is_empty() {
+            return Err(Error::Auth("API key is empty".to_string()));
+        }
+        match BASE64.decode(secret.as_bytes()).map(Zeroizing::new) {
+            Ok(decoded) if !decoded.is_empty() => Ok(Self { key, secret }),
+            _ => Err(Error::Auth(
+                "Malformed API secret: expected a non-empty base64 string".to_string(),
+            )),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn secret(&self) -> &str {
+        &self.secret
+    }
+}
+
+impl fmt::Debug for ApiCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiCredentials")
+            .field("key", &self.key.as_str())
+            .field("secret", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Named API key with the permissions it was created with on Kraken
+#[derive(Debug, Clone)]
+pub struct KeyProfile {
+    pub name: String,
+    pub credentials: ApiCredentials,
+    pub permissions: BTreeSet<Permission>,
+    pub otp: Option<Otp>,
+}
+
+impl KeyProfile {
+    /// Create a profile allowed to call every endpoint
+    pub fn new(name: impl Into<String>, credentials: ApiCredentials) -> Self {
+        Self {
+            name: name.into(),
+            credentials,
+            permissions: Permission::ALL.into_iter().collect(),
+            otp: None,
+        }
+    }
+
+    pub fn with_permissions(mut self, permissions: impl IntoIterator<Item = Permission>) -> Self {
+        self.permissions = permissions.into_iter().collect();
+        self
+    }
+
+    pub fn with_otp(mut self, otp: Option<Otp>) -> Self {
+        self.otp = otp;
+        self
+    }
+
+    pub fn permits(&self, endpoint: &str) -> bool {
+        self.permissions
+            .contains(&Permission::for_endpoint(endpoint))
+    }
+}
+
+/// The profile allowed to call `endpoint` with the fewest other permissions
+pub fn least_privileged<'a>(
+    profiles: &'a [KeyProfile],
+    endpoint: &str,
+) -> Result<&'a KeyProfile, Error> {
+    profiles
+        .iter()
+        .filter(|profile| profile.permits(endpoint))
+        .min_by_key(|profile| profile.permissions.len())
+        .ok_or_else(|| {
+            if profiles.is_empty() {
+                Error::Auth("No API credentials configured".to_string())
+            } else {
+                Error::Auth(format!(
+                    "No API key profile has the {} permission required by {}",
+                    Permission::for_endpoint(endpoint),
+                    endpoint
+                ))
+            }
+        })
+}
+
+/// The profile named `name`, selected with `--profile`
+pub fn named_profile<'a>(profiles: &'a [KeyProfile], name: &str) -> Result<&'a KeyProfile, Error> {
+    profiles
+        .iter()
+        .find(|profile| profile.name == name)
+        .ok_or_else(|| {
+            Error::Auth(format!(
+                "No API key profile `{}` in the keystore, it has {}",
+                name,
+                profiles
+                    .iter()
+                    .map(|profile| format!("`{}`", profile.name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(name: &str, permissions: &[Permission]) -> KeyProfile {
+        KeyProfile::new(name, ApiCredentials::new(name, "c2VjcmV0").unwrap())
+            .with_permissions(permissions.iter().copied())
+    }
+
+    #[test]
+    fn test_malformed_secret_is_rejected() {
+        assert!(matches!(
+            ApiCredentials::new("key", "not base64!"),
+            Err(Error::Auth(_))
+        ));
+        assert!(matches!(
+            ApiCredentials::new("key", ""),
+            Err(Error::Auth(_))
+        ));
+        assert!(matches!(
+            ApiCredentials::new("", "c2VjcmV0"),
+            Err(Error::Auth(_))
+        ));
+
+        let credentials = ApiCredentials::new("key", "c2VjcmV0").unwrap();
+        assert!(!format!("{:?}", credentials).contains("c2VjcmV0"));
+    }
+
+    #[test]
+    fn test_least_privileged_profile() {
+        let profiles = vec![
+            profile("everything", &Permission::ALL),
+            profile("trading", &[Permission::Query, Permission::Trade]),
+            profile("read-only", &[Permission::Query]),
+        ];
+        let name = |endpoint| least_privileged(&profiles, endpoint).unwrap().name.as_str();
+        assert_eq!(name(BALANCE), "read-only");
+        assert_eq!(name(ADD_ORDER), "trading");
+        assert_eq!(name(WITHDRAW_FUNDS), "everything");
+
+        let read_only = &profiles[2..];
+        assert!(matches!(
+            least_privileged(read_only, CANCEL_ORDER),
+            Err(Error::Auth(_))
+        ));
+        assert!(matches!(
+            least_privileged(&[], BALANCE),
+            Err(Error::Auth(_))
+        ));
+    }
+
+    #[test]
+    fn test_named_profile() {
+        let profiles = vec![
+            profile("read-only", &[Permission::Query]),
+            profile("trading", &[Permission::Query, Permission::Trade]),
+        ];
+        assert_eq!(
+            named_profile(&profiles, "trading").unwrap().credentials.key(),
+            "trading"
+        );
+        let missing = named_profile(&profiles, "live").unwrap_err().to_string();
+        assert!(missing.contains("`read-only`, `trading`"), "{}", missing);
+    }
+
+    #[test]
+    fn test_parse_permission() {
+        assert_eq!("Trade".parse::<Permission>().unwrap(), Permission::Trade);
+        assert_eq!(
+            "websocket".parse::<Permission>().unwrap(),
+            Permission::WebSocket
+        );
+        assert!("admin".parse::<Permission>().is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{
+    errors::Error,
+    utils::endpoints::{account, funding, trading},
+};
+
+/// Whether sending a call twice has the same effect as sending it once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Idempotency {
+    /// Reads, cancellations and amendments to absolute values
+    Idempotent,
+    /// Calls creating something each time: orders, withdrawals, transfers and export reports
+    NonIdempotent,
+}
+
+impl Idempotency {
+    /// Idempotency of `endpoint`, e.g. `/0/private/AddOrder`
+    pub fn of(endpoint: &str) -> Self {
+        match endpoint {
+            trading::ADD_ORDER
+            | trading::ADD_ORDER_BATCH
+            | trading::EDIT_ORDER
+            | funding::WITHDRAW_FUNDS
+            | funding::WALLET_TRANSFER
+            | account::REQUEST_EXPORT_REPORT => Idempotency::NonIdempotent,
+            _ => Idempotency::Idempotent,
+        }
+    }
+
+    pub fn is_idempotent(&self) -> bool {
+        *self == Idempotency::Idempotent
+    }
+}
+
+/// Whether the request provably never left this host: the connection could not be opened, or
+/// the request could not be built
+pub fn never_sent(error: &Error) -> bool {
+    matches!(error, Error::HttpError(e) if e.is_connect() || e.is_builder())
+}
+
+/// Whether a call failed without a definite answer from Kraken, so it may or may not have been
+/// carried out: timeouts, connections lost after sending, gateway errors, unreadable responses,
+/// and Kraken's own service errors
+pub fn outcome_unknown(error: &Error) -> bool {
+    match error {
+        Error::HttpError(_) => !never_sent(error),
+        Error::NetworkError(_)
+        | Error::TimeoutError(_)
+        | Error::SerializationError(_)
+        | Error::Deserialization(_) => true,
+        Error::InvalidResponse(status) => status.starts_with('5'),
+        Error::Api(message) => {
+            message.contains("EService:") || message.contains("EGeneral:Internal error")
+        }
+        _ => false,
+    }
+}
+
+/// An HTTP status asking to come back later: too many requests, or a gateway or service
+/// unavailable
+pub fn is_transient_status(error: &Error) -> bool {
+    matches!(error, Error::InvalidResponse(status)
+        if ["429", "502", "503", "504"].iter().any(|code| status.starts_with(code)))
+}
+
+/// Whether a call to `endpoint` that failed with `error` may be sent again as is.
+///
+/// A request that never left the host is always sent again. Otherwise idempotent calls are
+/// retried on transient errors, and non-idempotent ones only when Kraken answered that it did
+/// not carry them out, as with a rate limit error; when the outcome is unknown they must be
+/// reconciled against the exchange instead.
+pub fn retry_allowed(endpoint: &str, error: &Error) -> bool {
+    if never_sent(error) {
+        return true;
+    }
+    let transient = error.is_retryable() || is_transient_status(error);
+    transient && (Idempotency::of(endpoint).is_idempotent() || !outcome_unknown(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classification() {
+        assert_eq!(
+            Idempotency::of(trading::ADD_ORDER),
+            Idempotency::NonIdempotent
+        );
+        assert_eq!(
+            Idempotency::of(funding::WITHDRAW_FUNDS),
+            Idempotency::NonIdempotent
+        );
+        assert!(Idempotency::of(trading::CANCEL_ORDER).is_idempotent());
+        assert!(Idempotency::of(account::BALANCE).is_idempotent());
+    }
+
+    #[test]
+    fn test_retry_allowed() {
+        let timeout = Error::TimeoutError("operation timed out".to_string());
+        assert!(retry_allowed(account::BALANCE, &timeout));
+        assert!(!retry_allowed(trading::ADD_ORDER, &timeout));
+        assert!(!retry_allowed(funding::WALLET_TRANSFER, &timeout));
+
+        // Kraken refused the call, it can be sent again
+        let limited = Error::RateLimitExceeded("EAPI:Rate limit exceeded".to_string());
+        assert!(retry_allowed(trading::ADD_ORDER, &limited));
+        let too_many = Error::InvalidResponse("429 Too Many Requests".to_string());
+        assert!(retry_allowed(trading::ADD_ORDER, &too_many));
+
+        let gateway = Error::InvalidResponse("504 Gateway Timeout".to_string());
+        assert!(retry_allowed(account::BALANCE, &gateway));
+        assert!(!retry_allowed(trading::ADD_ORDER, &gateway));
+
+        let rejected = Error::Api("EOrder:Insufficient funds".to_string());
+        assert!(!retry_allowed(account::BALANCE, &rejected));
+        assert!(!outcome_unknown(&rejected));
+        assert!(outcome_unknown(&Error::Api(
+            "EService:Unavailable".to_string()
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_never_sent() {
+        // Nothing listens on port 1, the connection is refused before anything is sent
+        let refused = reqwest::get("http://127.0.0.1:1/0/private/AddOrder")
+            .await
+            .unwrap_err();
+        let error = Error::from(refused);
+        assert!(never_sent(&error));
+        assert!(!outcome_unknown(&error));
+        assert!(retry_allowed(trading::ADD_ORDER, &error));
+    }
+}
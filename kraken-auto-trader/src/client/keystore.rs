@@ -0,0 +1,318 @@
+use crate::errors::Error;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    XChaCha20Poly1305, XNonce,
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use super::{
+    credentials::{ApiCredentials, KeyProfile, Permission},
+    otp::{Otp, Totp},
+};
+
+const KEYSTORE_VERSION: u32 = 1;
+
+/// A key profile as stored in the keystore
+#[derive(Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct StoredProfile {
+    pub name: String,
+    pub api_key: String,
+    pub api_secret: String,
+    #[zeroize(skip)]
+    pub permissions: BTreeSet<Permission>,
+    /// Static password protecting the key
+    #[serde(default)]
+    pub otp_password: Option<String>,
+    /// Base32 seed of the key's TOTP second factor
+    #[serde(default)]
+    pub totp_seed: Option<String>,
+}
+
+impl StoredProfile {
+    pub fn new(
+        name: impl Into<String>,
+        api_key: impl Into<String>,
+        api_secret: impl Into<String>,
+        permissions: impl IntoIterator<Item = Permission>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            api_key: api_key.into(),
+            api_secret: api_secret.into(),
+            permissions: permissions.into_iter().collect(),
+            otp_password: None,
+            totp_seed: None,
+        }
+    }
+
+    /// Validate the secrets and build the profile used to sign requests
+    pub fn to_profile(&self) -> Result<KeyProfile, Error> {
+        let credentials = ApiCredentials::new(self.api_key.as_str(), self.api_secret.as_str())
+            .map_err(|e| Error::Auth(format!("Profile {}: {}", self.name, e)))?;
+        let otp = match (&self.totp_seed, &self.otp_password) {
+            (Some(seed), _) => Some(Otp::Totp(Totp::from_base32(seed)?)),
+            (None, Some(password)) => Some(Otp::Password(password.clone())),
+            (None, None) => None,
+        };
+        Ok(KeyProfile::new(self.name.as_str(), credentials)
+            .with_permissions(self.permissions.iter().copied())
+            .with_otp(otp))
+    }
+}
+
+/// Argon2id parameters the file key was derived with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KdfParams {
+    salt: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u32,
+    kdf: KdfParams,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Named API key profiles, encrypted at rest with a passphrase.
+///
+/// The file holds the profiles as JSON sealed with XChaCha20-Poly1305 under a key derived from
+/// the passphrase with Argon2id. Secrets are wiped from memory when the store is dropped.
+pub struct Keystore {
+    path: PathBuf,
+    profiles: Vec<StoredProfile>,
+    memory_kib: u32,
+    iterations: u32,
+}
+
+impl Keystore {
+    /// Start an empty keystore, written to `path` by [`Keystore::save`]
+    pub fn create(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            profiles: Vec::new(),
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+        }
+    }
+
+    /// Cost of deriving the key on the next save; lower values only make sense in tests
+    pub fn with_work_factor(mut self, memory_kib: u32, iterations: u32) -> Self {
+        self.memory_kib = memory_kib;
+        self.iterations = iterations;
+        self
+    }
+
+    /// Decrypt the keystore at `path`
+    pub fn open(path: impl Into<PathBuf>, passphrase: &str) -> Result<Self, Error> {
+        let path = path.into();
+        let file: KeystoreFile = serde_json::from_slice(&std::fs::read(&path)?)?;
+        if file.version != KEYSTORE_VERSION {
+            return Err(Error::Auth(format!(
+                "Unsupported keystore version {}",
+                file.version
+            )));
+        }
+
+        let key = derive_key(passphrase, &file.kdf)?;
+        let nonce = decode(&file.nonce)?;
+        if nonce.len() != 24 {
+            return Err(Error::Auth("Corrupted keystore nonce".to_string()));
+        }
+        let plaintext = XChaCha20Poly1305::new(key.as_slice().into())
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                decode(&file.ciphertext)?.as_slice(),
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| {
+                Error::Auth("Cannot decrypt keystore: wrong passphrase or corrupted file".into())
+            })?;
+        let profiles = serde_json::from_slice(&plaintext)?;
+
+        Ok(Self {
+            path,
+            profiles,
+            memory_kib: file.kdf.memory_kib,
+            iterations: file.kdf.iterations,
+        })
+    }
+
+    /// Decrypt the keystore once per process and return its validated profiles
+    pub fn load_profiles(path: &Path, passphrase: &str) -> Result<Arc<Vec<KeyProfile>>, Error> {
+        type Cache = Mutex<HashMap<(PathBuf, Vec<u8>), Arc<Vec<KeyProfile>>>>;
+        static CACHE: OnceLock<Cache> = OnceLock::new();
+
+        let cache_key = (
+            path.to_path_buf(),
+            Sha256::digest(passphrase.as_bytes()).to_vec(),
+        );
+        let cache = CACHE.get_or_init(Default::default);
+        if let Some(profiles) = cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&cache_key)
+        {
+            return Ok(profiles.clone());
+        }
+
+        let profiles = Arc::new(Self::open(path, passphrase)?.profiles()?);
+        cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(cache_key, profiles.clone());
+        Ok(profiles)
+    }
+
+    /// Add a profile, replacing the one with the same name
+    pub fn set(&mut self, profile: StoredProfile) -> Result<(), Error> {
+        // Fail before anything malformed reaches the file
+        profile.to_profile()?;
+        self.profiles.retain(|stored| stored.name != profile.name);
+        self.profiles.push(profile);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.profiles.len();
+        self.profiles.retain(|stored| stored.name != name);
+        self.profiles.len() != before
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.profiles
+            .iter()
+            .map(|stored| stored.name.as_str())
+            .collect()
+    }
+
+    pub fn profiles(&self) -> Result<Vec<KeyProfile>, Error> {
+        self.profiles
+            .iter()
+            .map(StoredProfile::to_profile)
+            .collect()
+    }
+
+    /// Encrypt the profiles under `passphrase` with a fresh salt and nonce, replacing the file
+    pub fn save(&self, passphrase: &str) -> Result<(), Error> {
+        let mut salt = [0u8; 16];
+        let mut nonce = [0u8; 24];
+        rand::rng().fill_bytes(&mut salt);
+        rand::rng().fill_bytes(&mut nonce);
+        let kdf = KdfParams {
+            salt: BASE64.encode(salt),
+            memory_kib: self.memory_kib,
+            iterations: self.iterations,
+            parallelism: 1,
+        };
+
+        let key = derive_key(passphrase, &kdf)?;
+        let plaintext = Zeroizing::new(serde_json::to_vec(&self.profiles)?);
+        let ciphertext = XChaCha20Poly1305::new(key.as_slice().into())
+            .encrypt(XNonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| Error::Unknown("Failed to encrypt keystore".to_string()))?;
+        let file = KeystoreFile {
+            version: KEYSTORE_VERSION,
+            kdf,
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+
+        if let Some(parent) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&file)?)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+fn derive_key(passphrase: &str, kdf: &KdfParams) -> Result<Zeroizing<[u8; 32]>, Error> {
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|e| Error::Auth(format!("Invalid keystore parameters: {}", e)))?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &decode(&kdf.salt)?, key.as_mut())
+        .map_err(|e| Error::Auth(format!("Cannot derive keystore key: {}", e)))?;
+    Ok(key)
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, Error> {
+    BASE64
+        .decode(value)
+        .map_err(|e| Error::Auth(format!("Corrupted keystore: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keystore_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "kraken-keystore-{}-{}.json",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_keystore_round_trip() {
+        let path = keystore_path("round-trip");
+        let mut keystore = Keystore::create(&path).with_work_factor(1024, 1);
+        keystore
+            .set(StoredProfile::new(
+                "read-only",
+                "key-1",
+                "c2VjcmV0LTE=",
+                [Permission::Query],
+            ))
+            .unwrap();
+        let mut trading =
+            StoredProfile::new("trading", "key-2", "c2VjcmV0LTI=", [Permission::Trade]);
+        trading.totp_seed = Some("JBSWY3DPEHPK3PXP".to_string());
+        keystore.set(trading).unwrap();
+        keystore.save("correct horse").unwrap();
+
+        let raw = std::fs::read_to_string(&path).unwrap();
+        assert!(!raw.contains("key-1") && !raw.contains("c2VjcmV0LTE="));
+
+        let opened = Keystore::open(&path, "correct horse").unwrap();
+        assert_eq!(opened.names(), vec!["read-only", "trading"]);
+        let profiles = opened.profiles().unwrap();
+        assert_eq!(profiles[0].credentials.key(), "key-1");
+        assert!(matches!(profiles[1].otp, Some(Otp::Totp(_))));
+
+        assert!(matches!(
+            Keystore::open(&path, "wrong horse"),
+            Err(Error::Auth(_))
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_malformed_secret_is_not_stored() {
+        let mut keystore = Keystore::create(keystore_path("malformed"));
+        let profile = StoredProfile::new("broken", "key", "not base64!", Permission::ALL);
+        assert!(matches!(keystore.set(profile), Err(Error::Auth(_))));
+        assert!(keystore.names().is_empty());
+    }
+}
@@ -1,21 +1,34 @@
 use crate::middleware::KrakenClientExt;
-use actix_web::HttpRequest;
 use serde::Deserialize;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::warn;
+use zeroize::Zeroizing;
 
 use crate::errors::Error;
-use crate::utils::crypto::{generate_nonce, get_signature};
+use crate::metrics::metrics;
+use crate::utils::crypto::get_signature;
+use crate::utils::{app_config::AppConfig, config::NonceConfig};
 
+use super::credentials::{least_privileged, named_profile, ApiCredentials, KeyProfile, Permission};
+use super::keystore::Keystore;
 use super::kraken_client::encode_params;
+use super::nonce::{MonotonicNonce, NonceProvider};
+use super::otp::{Otp, Totp};
+
+/// Times a request is re-signed with a later nonce after `EAPI:Invalid nonce`
+const DEFAULT_NONCE_RETRIES: u32 = 3;
+#[allow(async_fn_in_trait)]
 pub trait KrakenRequest {
     fn new() -> Result<Self, Error>
     where
         Self: Sized;
     async fn kraken_request<T: for<'de> Deserialize<'de>>(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         endpoint: &str,
         params: HashMap<String, String>,
     ) -> Result<T, Error>;
@@ -25,6 +38,10 @@ pub trait KrakenRequest {
 pub struct PrivateApiBuilder {
     api_key: Option<String>,
     api_secret: Option<String>,
+    nonce: Option<Arc<dyn NonceProvider>>,
+    nonce_retries: Option<u32>,
+    otp: Option<Otp>,
+    profiles: Vec<KeyProfile>,
 }
 
 impl PrivateApiBuilder {
@@ -38,34 +55,137 @@ impl PrivateApiBuilder {
         self
     }
 
+    /// Use `provider` for nonces instead of the process-wide millisecond generator.
+    /// Every client signing with the same key must share one provider.
+    pub fn with_nonce_provider(mut self, provider: Arc<dyn NonceProvider>) -> Self {
+        self.nonce = Some(provider);
+        self
+    }
+
+    /// Times a request is retried with a later nonce after `EAPI:Invalid nonce`
+    pub fn with_nonce_retries(mut self, retries: u32) -> Self {
+        self.nonce_retries = Some(retries);
+        self
+    }
+
+    /// Second factor of a key protected by a password or TOTP, sent as `otp`
+    pub fn with_otp(mut self, otp: Otp) -> Self {
+        self.otp = Some(otp);
+        self
+    }
+
+    /// Add a named key; each call is signed with the least-privileged profile allowed to make it
+    pub fn with_profile(mut self, profile: KeyProfile) -> Self {
+        self.profiles.push(profile);
+        self
+    }
+
+    pub fn with_profiles(mut self, profiles: impl IntoIterator<Item = KeyProfile>) -> Self {
+        self.profiles.extend(profiles);
+        self
+    }
+
+    /// Read the keys from the keystore at `KRAKEN_KEYSTORE_PATH` and from `KRAKEN_API_KEY` /
+    /// `KRAKEN_API_SECRET`. Missing credentials only fail the private calls that need them.
+    /// With `KRAKEN_PROFILE` set, only the keystore profile of that name signs.
     pub fn from_env() -> Result<Self, Error> {
-        Ok(Self {
-            api_key: Some(
-                env::var("KRAKEN_API_KEY")
-                    .map_err(|e| Error::Auth(format!("Missing API key: {}", e)))?,
-            ),
-            api_secret: Some(
-                env::var("KRAKEN_API_SECRET")
-                    .map_err(|e| Error::Auth(format!("Missing API secret: {}", e)))?,
-            ),
-        })
+        let nonce = NonceConfig::from_env().map_err(|e| Error::InvalidParameter(e.to_string()))?;
+        let keystore = env::var("KRAKEN_KEYSTORE_PATH").ok().map(PathBuf::from);
+        let profile = env::var("KRAKEN_PROFILE").ok();
+        Self::from_settings(&nonce, keystore.as_deref(), profile.as_deref())
+    }
+
+    /// Like [`PrivateApiBuilder::from_env`], with the nonce and keystore settings of `config`
+    /// and the key profile picked by its `--profile`
+    pub fn from_config(config: &AppConfig) -> Result<Self, Error> {
+        Self::from_settings(
+            &config.storage.nonce,
+            config.storage.keystore_path.as_deref(),
+            config.profile.as_deref(),
+        )
+    }
+
+    /// With a keystore and a `profile` name, that key profile alone signs requests, and the
+    /// environment's key is left out
+    fn from_settings(
+        nonce: &NonceConfig,
+        keystore: Option<&Path>,
+        profile: Option<&str>,
+    ) -> Result<Self, Error> {
+        let mut builder = Self {
+            nonce: Some(nonce.provider()),
+            nonce_retries: Some(nonce.retries),
+            ..Self::default()
+        };
+
+        if let Some(path) = keystore {
+            let passphrase =
+                Zeroizing::new(env::var("KRAKEN_KEYSTORE_PASSPHRASE").map_err(|_| {
+                    Error::Auth(
+                        "KRAKEN_KEYSTORE_PATH is set without KRAKEN_KEYSTORE_PASSPHRASE".into(),
+                    )
+                })?);
+            let profiles = Keystore::load_profiles(path, &passphrase)?;
+            if let Some(name) = profile {
+                return Ok(builder.with_profile(named_profile(&profiles, name)?.clone()));
+            }
+            builder = builder.with_profiles(profiles.iter().cloned());
+        }
+
+        if let (Ok(key), Ok(secret)) = (env::var("KRAKEN_API_KEY"), env::var("KRAKEN_API_SECRET")) {
+            let secret = Zeroizing::new(secret);
+            let otp = match (env::var("KRAKEN_API_OTP"), env::var("KRAKEN_API_TOTP_SEED")) {
+                (_, Ok(seed)) => Some(Otp::Totp(Totp::from_base32(&seed)?)),
+                (Ok(password), _) => Some(Otp::Password(password)),
+                _ => None,
+            };
+            let mut profile =
+                KeyProfile::new("env", ApiCredentials::new(key, secret.as_str())?).with_otp(otp);
+            if let Ok(permissions) = env::var("KRAKEN_API_PERMISSIONS") {
+                profile = profile.with_permissions(
+                    permissions
+                        .split(',')
+                        .filter(|name| !name.trim().is_empty())
+                        .map(str::parse)
+                        .collect::<Result<Vec<Permission>, Error>>()?,
+                );
+            }
+            builder = builder.with_profile(profile);
+        }
+
+        Ok(builder)
     }
 
     pub fn build(self) -> Result<PrivateApi, Error> {
+        let mut profiles = self.profiles;
+        match (self.api_key, self.api_secret) {
+            (Some(key), Some(secret)) => {
+                let secret = Zeroizing::new(secret);
+                profiles.push(
+                    KeyProfile::new("default", ApiCredentials::new(key, secret.as_str())?)
+                        .with_otp(self.otp),
+                );
+            }
+            (Some(_), None) => return Err(Error::Auth("API secret not set".to_string())),
+            (None, Some(_)) => return Err(Error::Auth("API key not set".to_string())),
+            (None, None) => {}
+        }
+
         Ok(PrivateApi {
-            api_key: self
-                .api_key
-                .ok_or_else(|| Error::Auth("API key not set".to_string()))?,
-            api_secret: self
-                .api_secret
-                .ok_or_else(|| Error::Auth("API secret not set".to_string()))?,
+            profiles: Arc::new(profiles),
+            nonce: self
+                .nonce
+                .unwrap_or_else(|| MonotonicNonce::shared(Default::default())),
+            nonce_retries: self.nonce_retries.unwrap_or(DEFAULT_NONCE_RETRIES),
         })
     }
 }
 
+#[derive(Clone)]
 pub struct PrivateApi {
-    api_secret: String,
-    api_key: String,
+    profiles: Arc<Vec<KeyProfile>>,
+    nonce: Arc<dyn NonceProvider>,
+    nonce_retries: u32,
 }
 
 impl KrakenRequest for PrivateApi {
@@ -75,37 +195,62 @@ impl KrakenRequest for PrivateApi {
 
     async fn kraken_request<T: for<'de> Deserialize<'de>>(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         endpoint: &str,
         mut params: HashMap<String, String>,
     ) -> Result<T, Error> {
+        // Fail before taking the client or a nonce when no key may make this call
+        let profile = self.profile_for(endpoint)?;
+
         let client = req.get_client().map_err(|e| Error::Auth(e.to_string()))?;
         let mut client = client.lock().await;
 
-        // Generate nonce
-        let nonce = generate_nonce();
-        params.insert("nonce".to_string(), nonce.to_string());
+        // Create the URL
+        let url: String = format!("{}{}", client.config.base_url, endpoint);
 
-        // Create post data
-        let post_data = encode_params(&params);
+        let mut nonce_retries = 0;
+        loop {
+            // Generate nonce
+            let nonce = self.nonce.next_nonce()?;
+            params.insert("nonce".to_string(), nonce.to_string());
+            if let Some(otp) = &profile.otp {
+                // A TOTP code may have rolled over since the previous attempt
+                params.insert("otp".to_string(), otp.code());
+            }
 
-        // Sign the request
-        let signature = get_signature(endpoint, nonce, &post_data, &self.api_secret)?;
+            // Create post data
+            let post_data = encode_params(&params);
 
-        // Create the URL
-        let url: String = format!("{}{}", client.config.base_url, endpoint);
+            // Sign the request
+            let signature =
+                get_signature(endpoint, nonce, &post_data, profile.credentials.secret())?;
 
-        // Create headers for private request
-        let mut headers = HashMap::new();
-        headers.insert("API-Key".to_string(), self.api_key.clone());
-        headers.insert("API-Sign".to_string(), signature.clone());
+            // Create headers for private request
+            let mut headers = HashMap::new();
+            headers.insert("API-Key".to_string(), profile.credentials.key().to_string());
+            headers.insert("API-Sign".to_string(), signature.clone());
 
-        // Make the request with retry logic
-        let response: Value = client
-            .make_request_with_retry(url, headers, post_data, endpoint.to_string())
-            .await?;
+            // Make the request with retry logic
+            let response: Result<Value, Error> = client
+                .make_request_with_retry(url.clone(), headers, post_data, endpoint.to_string())
+                .await;
 
-        serde_json::from_value(response).map_err(|e| Error::SerializationError(e))
+            match response {
+                // Another process or an earlier run used a greater nonce with this key
+                Err(e) if e.is_invalid_nonce() && nonce_retries < self.nonce_retries => {
+                    nonce_retries += 1;
+                    metrics().observe_retry(endpoint, "invalid_nonce");
+                    warn!(
+                        "Nonce {} rejected on {}, retrying with a later one (attempt {}/{})",
+                        nonce, endpoint, nonce_retries, self.nonce_retries
+                    );
+                    self.nonce.bump(nonce)?;
+                }
+                response => {
+                    return serde_json::from_value(response?).map_err(Error::SerializationError)
+                }
+            }
+        }
     }
 }
 
@@ -113,9 +258,20 @@ impl PrivateApi {
     pub fn builder() -> PrivateApiBuilder {
         PrivateApiBuilder::default()
     }
+
+    /// Profile that signs calls to `endpoint`
+    pub fn profile_for(&self, endpoint: &str) -> Result<&KeyProfile, Error> {
+        least_privileged(&self.profiles, endpoint)
+    }
+
+    /// Whether any configured key may call `endpoint`
+    pub fn permits(&self, endpoint: &str) -> bool {
+        self.profile_for(endpoint).is_ok()
+    }
 }
 
 /// Public API client for Kraken
+#[derive(Default)]
 pub struct PublicApi;
 
 impl PublicApi {
@@ -131,27 +287,33 @@ impl KrakenRequest for PublicApi {
 
     async fn kraken_request<T: for<'de> Deserialize<'de>>(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         endpoint: &str,
         params: HashMap<String, String>,
     ) -> Result<T, Error> {
         let client = req.get_client().map_err(|e| Error::Auth(e.to_string()))?;
-        let mut client = client.lock().await;
+        let query = (!params.is_empty()).then(|| encode_params(&params));
+        let path = endpoint.to_string();
+        let fetch = async move {
+            let mut client = client.lock().await;
 
-        // Build the URL with query parameters
-        let mut url = format!("{}{}", client.config.base_url, endpoint);
+            // Build the URL with query parameters
+            let mut url = format!("{}{}", client.config.base_url, path);
+            if let Some(query) = query {
+                url.push('?');
+                url.push_str(&query);
+            }
 
-        // Add query parameters if any
-        if !params.is_empty() {
-            url.push('?');
-            url.push_str(&encode_params(&params));
-        }
+            // Make the GET request
+            client.make_get_request_with_retry(url, path).await
+        };
 
-        // Make the GET request
-        let response: Value = client
-            .make_get_request_with_retry(url, endpoint.to_string())
-            .await?;
+        // The cache is consulted before the client lock and the rate limiter
+        let response: Value = match req.public_cache() {
+            Some(cache) => cache.get_or_fetch(endpoint, &params, fetch).await?,
+            None => fetch.await?,
+        };
 
-        serde_json::from_value(response).map_err(|e| Error::SerializationError(e))
+        serde_json::from_value(response).map_err(Error::SerializationError)
     }
 }
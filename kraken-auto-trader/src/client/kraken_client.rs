@@ -1,12 +1,27 @@
-use crate::{errors::Error, utils::config::KrakenConfig};
-
-use super::rate_limit::RateLimiter;
-
-use reqwest::{Client, ClientBuilder};
+use crate::{
+    errors::Error,
+    feeds::stream::{MarketMessage, MessageSource},
+    metrics::metrics,
+    recording::recorder::Recorder,
+    telemetry::{form_value, redact_form, RedactedHeaders},
+    utils::config::{CircuitBreakerConfig, KrakenConfig, RateLimitConfig},
+};
+
+use super::{
+    circuit_breaker::{parse_retry_after, CircuitBreakers},
+    idempotency::{never_sent, outcome_unknown, retry_allowed, Idempotency},
+    rate_limit::RateLimiter,
+};
+
+use reqwest::{header::RETRY_AFTER, Client, ClientBuilder, Response};
 use serde::Deserialize;
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::time::sleep;
-use tracing::{debug, error, info};
+use tracing::{debug, error, field::Empty, info, info_span, warn, Instrument, Span};
 
 #[derive(Debug, Deserialize)]
 pub struct KrakenResponse<T> {
@@ -19,6 +34,8 @@ pub struct KrakenClient {
     pub config: KrakenConfig,
     pub(crate) client: Client,
     pub(crate) rate_limiter: RateLimiter,
+    pub(crate) recorder: Option<Arc<Recorder>>,
+    pub(crate) breakers: Arc<CircuitBreakers>,
 }
 
 /// Convert a HashMap to a URL encoded string
@@ -45,10 +62,42 @@ impl KrakenClient {
             config,
             client,
             rate_limiter,
+            recorder: None,
+            breakers: Arc::new(CircuitBreakers::default()),
         })
     }
 
+    /// Record the raw responses of public (GET) endpoints
+    pub fn with_recorder(mut self, recorder: Arc<Recorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Replace the default limit of 15 calls per minute
+    pub fn with_rate_limit(mut self, config: &RateLimitConfig) -> Self {
+        self.rate_limiter = RateLimiter::new(config.capacity, config.refill_per_sec);
+        self
+    }
+
+    /// Replace the default circuit breakers and backoff
+    pub fn with_circuit_breaker(mut self, config: &CircuitBreakerConfig) -> Self {
+        self.breakers = Arc::new(CircuitBreakers::new(config.clone()));
+        self
+    }
+
+    /// The circuit breakers shared by every clone of this client
+    pub fn circuit_breakers(&self) -> Arc<CircuitBreakers> {
+        self.breakers.clone()
+    }
+
+    /// Apply a new rate limit to a running client
+    pub fn set_rate_limit(&mut self, config: &RateLimitConfig) {
+        self.rate_limiter
+            .reconfigure(config.capacity, config.refill_per_sec);
+    }
+
     /// Create a new Kraken API client with default configuration
+    #[allow(clippy::should_implement_trait)]
     pub fn default() -> Result<Self, Error> {
         Self::new(KrakenConfig::default())
     }
@@ -75,14 +124,26 @@ impl KrakenClient {
     {
         let mut retries = 0;
         loop {
-            match self.make_single_get_request(&url, &endpoint).await {
+            match self
+                .make_single_get_request(&url, &endpoint, retries + 1)
+                .await
+            {
                 Ok(response) => return Ok(response),
                 Err(e) => {
-                    if !e.is_retryable() || retries >= self.config.max_retries {
+                    if !retry_allowed(&endpoint, &e) || retries >= self.config.max_retries {
                         return Err(e);
                     }
                     retries += 1;
-                    let delay = self.config.retry_delay() * retries as u32;
+                    let Some(delay) = self.breakers.retry_delay(
+                        &endpoint,
+                        retries,
+                        self.config.retry_delay(),
+                    ) else {
+                        debug!("Not retrying GET request to {}: {}", endpoint, e);
+                        return Err(e);
+                    };
+                    let reason = if never_sent(&e) { "not_sent" } else { "retryable" };
+                    metrics().observe_retry(&endpoint, reason);
                     debug!(
                         "Retrying GET request after {:?} delay (attempt {}/{})",
                         delay, retries, self.config.max_retries
@@ -98,13 +159,33 @@ impl KrakenClient {
         &mut self,
         url: &str,
         endpoint: &str,
+        attempt: u32,
     ) -> Result<T, Error>
     where
         T: for<'de> Deserialize<'de>,
     {
+        // Fail fast while the exchange is failing, without spending a rate limit token
+        self.breakers.admit(endpoint)?;
+
         // Wait for rate limit token
         self.rate_limiter.acquire().await;
 
+        let span = kraken_call_span(endpoint, "GET", attempt, None);
+        let started = Instant::now();
+        let result = self
+            .send_get_request(url, endpoint)
+            .instrument(span.clone())
+            .await;
+        record_outcome(&span, started, &result);
+        self.breakers.record(endpoint, &result);
+        metrics().observe_kraken_request(endpoint, "GET", started.elapsed(), &result);
+        result
+    }
+
+    async fn send_get_request<T>(&self, url: &str, endpoint: &str) -> Result<T, Error>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
         info!("Making GET request to {}", endpoint);
         let request = self.client.get(url);
 
@@ -113,13 +194,22 @@ impl KrakenClient {
             .await?;
 
         if !response.status().is_success() {
-            return Err(Error::InvalidResponse(response.status().to_string()));
+            return Err(self.status_error(&response, endpoint));
         }
 
-        let kraken_response: KrakenResponse<T> = response.json().await?;
+        let body = response.text().await?;
+        if let Some(recorder) = &self.recorder {
+            let source = MessageSource::Rest {
+                endpoint: endpoint.to_string(),
+                url: url.to_string(),
+            };
+            recorder.record(&MarketMessage::received(source, body.as_str()));
+        }
+        let kraken_response: KrakenResponse<T> = serde_json::from_str(&body)?;
 
         if !kraken_response.error.is_empty() {
             error!("Kraken API error: {:?}", kraken_response.error);
+            metrics().observe_kraken_errors(endpoint, &kraken_response.error);
             return Err(Error::from(kraken_response.error));
         }
 
@@ -129,7 +219,25 @@ impl KrakenClient {
         }
     }
 
-    /// Make a request with retry logic
+    /// Error for an HTTP error status, holding the circuit open as long as a `Retry-After`
+    /// header asks
+    fn status_error(&self, response: &Response, endpoint: &str) -> Error {
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
+        if let Some(delay) = retry_after {
+            self.breakers.hold_off(endpoint, delay);
+        }
+        Error::InvalidResponse(response.status().to_string())
+    }
+
+    /// Make a request with retry logic.
+    ///
+    /// Non-idempotent calls such as AddOrder or Withdraw are only sent again when Kraken did
+    /// not carry them out; when their outcome is unknown the error is returned, for the caller
+    /// to reconcile against the exchange before resubmitting.
     pub async fn make_request_with_retry<T>(
         &mut self,
         url: String,
@@ -143,16 +251,31 @@ impl KrakenClient {
         let mut retries = 0;
         loop {
             match self
-                .make_single_request(&url, headers.clone(), &post_data, &endpoint)
+                .make_single_request(&url, headers.clone(), &post_data, &endpoint, retries + 1)
                 .await
             {
                 Ok(response) => return Ok(response),
                 Err(e) => {
-                    if !e.is_retryable() || retries >= self.config.max_retries {
+                    if !retry_allowed(&endpoint, &e) || retries >= self.config.max_retries {
+                        if !Idempotency::of(&endpoint).is_idempotent() && outcome_unknown(&e) {
+                            warn!(
+                                "Outcome of {} is unknown, not sending it again: {}",
+                                endpoint, e
+                            );
+                        }
                         return Err(e);
                     }
                     retries += 1;
-                    let delay = self.config.retry_delay() * retries as u32;
+                    let Some(delay) = self.breakers.retry_delay(
+                        &endpoint,
+                        retries,
+                        self.config.retry_delay(),
+                    ) else {
+                        debug!("Not retrying request to {}: {}", endpoint, e);
+                        return Err(e);
+                    };
+                    let reason = if never_sent(&e) { "not_sent" } else { "retryable" };
+                    metrics().observe_retry(&endpoint, reason);
                     debug!(
                         "Retrying request after {:?} delay (attempt {}/{})",
                         delay, retries, self.config.max_retries
@@ -170,14 +293,45 @@ impl KrakenClient {
         headers: HashMap<String, String>,
         post_data: &str,
         endpoint: &str,
+        attempt: u32,
     ) -> Result<T, Error>
     where
         T: for<'de> Deserialize<'de>,
     {
+        // Fail fast while the exchange is failing, without spending a rate limit token
+        self.breakers.admit(endpoint)?;
+
         // Wait for rate limit token
         self.rate_limiter.acquire().await;
 
+        let span = kraken_call_span(endpoint, "POST", attempt, form_value(post_data, "nonce"));
+        let started = Instant::now();
+        let result = self
+            .send_request(url, headers, post_data, endpoint)
+            .instrument(span.clone())
+            .await;
+        record_outcome(&span, started, &result);
+        self.breakers.record(endpoint, &result);
+        metrics().observe_kraken_request(endpoint, "POST", started.elapsed(), &result);
+        result
+    }
+
+    async fn send_request<T>(
+        &self,
+        url: &str,
+        headers: HashMap<String, String>,
+        post_data: &str,
+        endpoint: &str,
+    ) -> Result<T, Error>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
         info!("Making request to {}", endpoint);
+        debug!(
+            headers = ?RedactedHeaders(&headers),
+            body = %redact_form(post_data),
+            "Request details"
+        );
         let mut request = self.client.post(url);
 
         // Add all headers
@@ -192,13 +346,14 @@ impl KrakenClient {
             .await?;
 
         if !response.status().is_success() {
-            return Err(Error::InvalidResponse(response.status().to_string()));
+            return Err(self.status_error(&response, endpoint));
         }
 
         let kraken_response: KrakenResponse<T> = response.json().await?;
 
         if !kraken_response.error.is_empty() {
             error!("Kraken API error: {:?}", kraken_response.error);
+            metrics().observe_kraken_errors(endpoint, &kraken_response.error);
             return Err(Error::from(kraken_response.error));
         }
 
@@ -209,6 +364,26 @@ impl KrakenClient {
     }
 }
 
+/// Span of one attempt at a Kraken call, nested in the span of the request that made it
+fn kraken_call_span(endpoint: &str, method: &str, attempt: u32, nonce: Option<&str>) -> Span {
+    info_span!(
+        "kraken_call",
+        endpoint,
+        method,
+        attempt,
+        nonce,
+        latency_ms = Empty,
+        error = Empty,
+    )
+}
+
+fn record_outcome<T>(span: &Span, started: Instant, result: &Result<T, Error>) {
+    span.record("latency_ms", started.elapsed().as_secs_f64() * 1000.0);
+    if let Err(e) = result {
+        span.record("error", tracing::field::display(e));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
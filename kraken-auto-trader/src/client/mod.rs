@@ -1,3 +1,10 @@
+pub mod cache;
+pub mod circuit_breaker;
+pub mod credentials;
+pub mod idempotency;
+pub mod keystore;
 pub mod kraken_client;
 pub mod kraken_apis;
+pub mod nonce;
+pub mod otp;
 pub mod rate_limit;
@@ -0,0 +1,288 @@
+use crate::{clock::clock, errors::Error};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+/// Source of the `nonce` sent with every private request.
+///
+/// Kraken rejects a nonce that is not greater than the last one it accepted for the API key
+/// with `EAPI:Invalid nonce`, so every provider shared by the users of a key must be strictly
+/// increasing, even when called twice within one clock tick or after the clock steps back.
+pub trait NonceProvider: Send + Sync {
+    /// Next nonce, greater than any nonce returned before
+    fn next_nonce(&self) -> Result<u64, Error>;
+
+    /// Skip ahead after Kraken rejected `rejected`; it has seen a greater nonce for the key
+    fn bump(&self, rejected: u64) -> Result<(), Error>;
+}
+
+/// Unit of time the nonces count in.
+///
+/// Switching a key to a finer resolution is safe, the nonces only get larger. Switching back
+/// requires a new key or waiting out the difference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum NonceResolution {
+    #[default]
+    Millis,
+    Micros,
+    Nanos,
+}
+
+impl NonceResolution {
+    /// The current time in this unit, following Kraken's clock once it is synchronized
+    pub fn now(&self) -> u64 {
+        let elapsed = clock().now();
+        match self {
+            NonceResolution::Millis => elapsed.as_millis() as u64,
+            NonceResolution::Micros => elapsed.as_micros() as u64,
+            NonceResolution::Nanos => elapsed.as_nanos() as u64,
+        }
+    }
+
+    /// Step taken by [`NonceProvider::bump`]
+    pub fn per_second(&self) -> u64 {
+        match self {
+            NonceResolution::Millis => 1_000,
+            NonceResolution::Micros => 1_000_000,
+            NonceResolution::Nanos => 1_000_000_000,
+        }
+    }
+
+    /// Nonce following `last`: the current time, or `last + 1` when the clock has not moved on
+    fn next_after(&self, last: u64) -> u64 {
+        self.now().max(last + 1)
+    }
+
+    fn bumped(&self, last: u64, rejected: u64) -> u64 {
+        last.max(rejected.max(self.now()) + self.per_second())
+    }
+}
+
+impl FromStr for NonceResolution {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ms" | "millis" | "milliseconds" => Ok(NonceResolution::Millis),
+            "us" | "micros" | "microseconds" => Ok(NonceResolution::Micros),
+            "ns" | "nanos" | "nanoseconds" => Ok(NonceResolution::Nanos),
+            _ => Err(Error::InvalidParameter(format!(
+                "Unknown nonce resolution: {}",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for NonceResolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NonceResolution::Millis => "ms",
+            NonceResolution::Micros => "us",
+            NonceResolution::Nanos => "ns",
+        })
+    }
+}
+
+impl Serialize for NonceResolution {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for NonceResolution {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Strictly increasing nonces for one process
+#[derive(Debug, Default)]
+pub struct MonotonicNonce {
+    resolution: NonceResolution,
+    last: AtomicU64,
+}
+
+impl MonotonicNonce {
+    pub fn new(resolution: NonceResolution) -> Self {
+        Self {
+            resolution,
+            last: AtomicU64::new(0),
+        }
+    }
+
+    /// Process-wide generator for `resolution`, shared by every client that does not set its own
+    pub fn shared(resolution: NonceResolution) -> Arc<dyn NonceProvider> {
+        static SHARED: [OnceLock<Arc<MonotonicNonce>>; 3] =
+            [OnceLock::new(), OnceLock::new(), OnceLock::new()];
+        let index = match resolution {
+            NonceResolution::Millis => 0,
+            NonceResolution::Micros => 1,
+            NonceResolution::Nanos => 2,
+        };
+        SHARED[index]
+            .get_or_init(|| Arc::new(MonotonicNonce::new(resolution)))
+            .clone()
+    }
+}
+
+impl NonceProvider for MonotonicNonce {
+    fn next_nonce(&self) -> Result<u64, Error> {
+        let mut last = self.last.load(Ordering::Relaxed);
+        loop {
+            let next = self.resolution.next_after(last);
+            match self
+                .last
+                .compare_exchange_weak(last, next, Ordering::AcqRel, Ordering::Relaxed)
+            {
+                Ok(_) => return Ok(next),
+                Err(current) => last = current,
+            }
+        }
+    }
+
+    fn bump(&self, rejected: u64) -> Result<(), Error> {
+        let bumped = self.resolution.bumped(0, rejected);
+        self.last.fetch_max(bumped, Ordering::AcqRel);
+        Ok(())
+    }
+}
+
+/// Nonces shared by every process using the same file.
+///
+/// The last nonce is stored in the file, which is locked for the read-increment-write, so bots
+/// running side by side with one API key never reuse or reorder nonces.
+#[derive(Debug)]
+pub struct FileNonce {
+    path: PathBuf,
+    resolution: NonceResolution,
+}
+
+impl FileNonce {
+    pub fn new(path: impl Into<PathBuf>, resolution: NonceResolution) -> Self {
+        Self {
+            path: path.into(),
+            resolution,
+        }
+    }
+
+    /// Replace the stored nonce with `update(last)` while holding the file lock
+    fn update(&self, update: impl FnOnce(u64) -> u64) -> Result<u64, Error> {
+        if let Some(parent) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?;
+        file.lock()?;
+        let next = Self::rewrite(&mut file, update);
+        file.unlock()?;
+        next
+    }
+
+    fn rewrite(file: &mut File, update: impl FnOnce(u64) -> u64) -> Result<u64, Error> {
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        // An empty or torn file falls back to the clock, which is ahead of any stored nonce
+        // unless the clock stepped back
+        let last = contents.trim().parse().unwrap_or(0);
+        let next = update(last);
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write!(file, "{}", next)?;
+        file.flush()?;
+        Ok(next)
+    }
+}
+
+impl NonceProvider for FileNonce {
+    fn next_nonce(&self) -> Result<u64, Error> {
+        self.update(|last| self.resolution.next_after(last))
+    }
+
+    fn bump(&self, rejected: u64) -> Result<(), Error> {
+        self.update(|last| self.resolution.bumped(last, rejected))
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_monotonic_nonce_is_strictly_increasing() {
+        let provider = Arc::new(MonotonicNonce::new(NonceResolution::Millis));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let provider = provider.clone();
+                std::thread::spawn(move || {
+                    let nonces: Vec<u64> =
+                        (0..500).map(|_| provider.next_nonce().unwrap()).collect();
+                    assert!(nonces.windows(2).all(|pair| pair[0] < pair[1]));
+                    nonces
+                })
+            })
+            .collect();
+
+        let mut all = HashSet::new();
+        for handle in handles {
+            for nonce in handle.join().unwrap() {
+                assert!(all.insert(nonce), "nonce {} issued twice", nonce);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bump_skips_past_rejected_nonce() {
+        let provider = MonotonicNonce::new(NonceResolution::Micros);
+        let far_ahead = NonceResolution::Micros.now() + 60_000_000;
+        provider.bump(far_ahead).unwrap();
+        assert!(provider.next_nonce().unwrap() > far_ahead);
+    }
+
+    #[test]
+    fn test_file_nonce_is_shared_between_providers() {
+        let path = std::env::temp_dir().join(format!("kraken-nonce-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let first = FileNonce::new(&path, NonceResolution::Nanos);
+        let second = FileNonce::new(&path, NonceResolution::Nanos);
+
+        let a = first.next_nonce().unwrap();
+        let b = second.next_nonce().unwrap();
+        let c = first.next_nonce().unwrap();
+        assert!(a < b && b < c);
+
+        second.bump(c + 5).unwrap();
+        assert!(first.next_nonce().unwrap() > c + 5);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_resolution() {
+        assert_eq!(
+            "us".parse::<NonceResolution>().unwrap(),
+            NonceResolution::Micros
+        );
+        assert_eq!(
+            "Nanos".parse::<NonceResolution>().unwrap(),
+            NonceResolution::Nanos
+        );
+        assert!("hours".parse::<NonceResolution>().is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::metrics::metrics;
 use std::time::{Duration, Instant};
 use tokio::time::sleep;
 use tracing::debug;
@@ -29,13 +30,19 @@ impl RateLimiter {
     /// Wait until a token is available
     pub async fn acquire(&mut self) {
         self.update_tokens();
+        let mut waited = Duration::ZERO;
         if self.tokens == 0 {
             let wait_time = self.calculate_wait_time();
             debug!("Rate limit reached, waiting for {:?}", wait_time);
             sleep(wait_time).await;
+            waited = wait_time;
             self.update_tokens();
         }
         self.tokens -= 1;
+
+        let metrics = metrics();
+        metrics.rate_limiter_wait.observe(waited.as_secs_f64());
+        metrics.rate_limiter_tokens.set(self.tokens as i64);
     }
 
     /// Update the number of tokens in the bucket
@@ -57,6 +64,14 @@ impl RateLimiter {
         Duration::from_secs_f64(wait_seconds)
     }
 
+    /// Change the capacity and rate, keeping the tokens already spent
+    pub fn reconfigure(&mut self, capacity: u32, rate: f64) {
+        self.update_tokens();
+        self.tokens = self.tokens.min(capacity);
+        self.capacity = capacity;
+        self.rate = rate;
+    }
+
     /// Get the current number of tokens in the bucket
     pub fn tokens(&self) -> u32 {
         self.tokens
@@ -111,4 +126,17 @@ mod tests {
         limiter.update_tokens();
         assert_eq!(limiter.tokens(), 3);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_reconfigure_keeps_spent_tokens() {
+        let mut limiter = RateLimiter::new(15, 0.25);
+        limiter.tokens = 4;
+        limiter.reconfigure(20, 1.0);
+        assert_eq!(limiter.tokens(), 4);
+        assert_eq!(limiter.capacity(), 20);
+
+        limiter.reconfigure(2, 1.0);
+        assert_eq!(limiter.tokens(), 2);
+        assert_eq!(limiter.rate(), 1.0);
+    }
+}
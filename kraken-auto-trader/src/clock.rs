@@ -0,0 +1,272 @@
+use crate::{
+    errors::Error, metrics::metrics, middleware::KrakenClientExt,
+    services::market_data::MarketData, utils::config::ClockConfig,
+};
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{task::JoinHandle, time::sleep};
+use tracing::{debug, info, warn};
+
+/// The local clock corrected by the estimated offset to Kraken's clock
+#[derive(Debug)]
+pub struct Clock {
+    offset_micros: AtomicI64,
+}
+
+static CLOCK: Clock = Clock {
+    offset_micros: AtomicI64::new(0),
+};
+
+/// The process-wide corrected clock, used for nonces, order times and candle boundaries
+pub fn clock() -> &'static Clock {
+    &CLOCK
+}
+
+/// Exchange time in seconds since the Unix epoch, as estimated from the local clock
+pub fn unix_time() -> f64 {
+    CLOCK.unix_time()
+}
+
+impl Clock {
+    /// Exchange time since the Unix epoch
+    pub fn now(&self) -> Duration {
+        let local = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let offset = self.offset_micros.load(Ordering::Relaxed);
+        if offset >= 0 {
+            local + Duration::from_micros(offset as u64)
+        } else {
+            local.saturating_sub(Duration::from_micros(offset.unsigned_abs()))
+        }
+    }
+
+    pub fn unix_time(&self) -> f64 {
+        self.now().as_secs_f64()
+    }
+
+    /// Exchange clock minus local clock, in seconds
+    pub fn offset(&self) -> f64 {
+        self.offset_micros.load(Ordering::Relaxed) as f64 / 1e6
+    }
+
+    pub fn set_offset(&self, secs: f64) {
+        self.offset_micros
+            .store((secs * 1e6).round() as i64, Ordering::Relaxed);
+    }
+}
+
+/// One exchange of a server time request, timed with the uncorrected local clock
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ClockSample {
+    /// Local time the request was sent
+    pub sent: f64,
+    /// Local time the response arrived
+    pub received: f64,
+    /// Server time in the response, truncated to the second
+    pub server_time: i64,
+}
+
+impl ClockSample {
+    pub fn rtt(&self) -> f64 {
+        self.received - self.sent
+    }
+
+    /// Range of offsets consistent with this sample: the server read its clock at some point
+    /// between sending and receiving, and truncated it to the second
+    fn bounds(&self) -> (f64, f64) {
+        let server = self.server_time as f64;
+        (server - self.received, server + 1.0 - self.sent)
+    }
+}
+
+/// Offset of the exchange clock derived from recent samples
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ClockEstimate {
+    /// Exchange clock minus local clock, in milliseconds
+    pub offset_ms: f64,
+    /// Half the width of the range the offset lies in, in milliseconds
+    pub uncertainty_ms: f64,
+    /// Shortest round trip among the samples, in milliseconds
+    pub rtt_ms: f64,
+    pub samples: usize,
+}
+
+/// Estimate of the offset from `samples`, NTP-style.
+///
+/// Each sample bounds the offset; the bounds of all samples are intersected, which narrows the
+/// one-second resolution of the server time as samples straddle its second boundaries. When
+/// they disagree, after the local clock stepped for instance, the sample with the shortest
+/// round trip is used alone.
+pub fn estimate(samples: &[ClockSample]) -> Option<ClockEstimate> {
+    let best = samples.iter().min_by(|a, b| a.rtt().total_cmp(&b.rtt()))?;
+    let (lo, hi) = samples
+        .iter()
+        .map(ClockSample::bounds)
+        .fold((f64::MIN, f64::MAX), |(lo, hi), (l, h)| {
+            (lo.max(l), hi.min(h))
+        });
+    let (lo, hi) = if lo <= hi { (lo, hi) } else { best.bounds() };
+    Some(ClockEstimate {
+        offset_ms: (lo + hi) / 2.0 * 1000.0,
+        uncertainty_ms: (hi - lo) / 2.0 * 1000.0,
+        rtt_ms: best.rtt() * 1000.0,
+        samples: samples.len(),
+    })
+}
+
+/// Keeps the process-wide [`Clock`] in line with Kraken's server time
+pub struct ClockSync {
+    config: ClockConfig,
+    market: MarketData,
+    samples: Mutex<VecDeque<ClockSample>>,
+}
+
+impl ClockSync {
+    pub fn new(config: ClockConfig) -> Self {
+        Self {
+            config,
+            market: MarketData::new(),
+            samples: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Query the server time once and correct the clock
+    pub async fn sample(&self, req: impl KrakenClientExt) -> Result<ClockEstimate, Error> {
+        let sent = local_unix_time();
+        let time = self.market.get_server_time(req).await?;
+        let received = local_unix_time();
+        Ok(self.record(ClockSample {
+            sent,
+            received,
+            server_time: time.unixtime,
+        }))
+    }
+
+    /// Add a sample taken elsewhere and correct the clock
+    pub fn record(&self, sample: ClockSample) -> ClockEstimate {
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        samples.push_back(sample);
+        while samples.len() > self.config.window {
+            samples.pop_front();
+        }
+        let estimate = estimate(samples.make_contiguous()).expect("at least one sample");
+
+        clock().set_offset(estimate.offset_ms / 1000.0);
+        metrics().clock_drift.set(estimate.offset_ms / 1000.0);
+        debug!(
+            "Clock offset {:.0}ms ±{:.0}ms over {} samples (best round trip {:.0}ms)",
+            estimate.offset_ms, estimate.uncertainty_ms, estimate.samples, estimate.rtt_ms
+        );
+        estimate
+    }
+
+    /// The current estimate, if any sample was taken
+    pub fn estimate(&self) -> Option<ClockEstimate> {
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        estimate(samples.make_contiguous())
+    }
+
+    /// Sample a burst at startup, spread over a second so the samples straddle a second
+    /// boundary of the server time, then once every `interval_secs`
+    pub fn spawn<R>(self: &Arc<Self>, req: R) -> JoinHandle<()>
+    where
+        R: KrakenClientExt + Clone + Send + 'static,
+    {
+        let sync = self.clone();
+        tokio::spawn(async move {
+            let burst = sync.config.window.min(4) as u32;
+            for _ in 0..burst {
+                if let Err(e) = sync.sample(req.clone()).await {
+                    warn!("Clock sample failed: {}", e);
+                }
+                sleep(Duration::from_secs(1) / burst).await;
+            }
+            if let Some(estimate) = sync.estimate() {
+                info!(
+                    "Clock synchronized with Kraken: offset {:.0}ms ±{:.0}ms",
+                    estimate.offset_ms, estimate.uncertainty_ms
+                );
+            }
+
+            let mut ticker = tokio::time::interval(sync.config.interval());
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = sync.sample(req.clone()).await {
+                    warn!("Clock sample failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+fn local_unix_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(sent: f64, rtt: f64, server_time: i64) -> ClockSample {
+        ClockSample {
+            sent,
+            received: sent + rtt,
+            server_time,
+        }
+    }
+
+    #[test]
+    fn test_estimate_intersects_samples() {
+        // The server runs 2.3s ahead; responses are read halfway through each round trip
+        let offset = 2.3;
+        let samples: Vec<ClockSample> = [100.0, 100.25, 100.5, 100.75]
+            .iter()
+            .map(|&sent: &f64| {
+                let rtt = 0.1;
+                sample(sent, rtt, (sent + rtt / 2.0 + offset).floor() as i64)
+            })
+            .collect();
+
+        let single = estimate(&samples[..1]).unwrap();
+        assert!(single.uncertainty_ms > 500.0);
+
+        let combined = estimate(&samples).unwrap();
+        assert!(combined.uncertainty_ms < 200.0, "{:?}", combined);
+        assert!((combined.offset_ms - 2300.0).abs() <= combined.uncertainty_ms);
+        assert!((combined.rtt_ms - 100.0).abs() < 1e-6);
+        assert_eq!(combined.samples, 4);
+    }
+
+    #[test]
+    fn test_inconsistent_samples_use_shortest_round_trip() {
+        // The local clock stepped back by 10s between the samples
+        let samples = [sample(100.0, 0.5, 102), sample(90.0, 0.05, 102)];
+        let estimate = estimate(&samples).unwrap();
+        assert!((estimate.rtt_ms - 50.0).abs() < 1e-6);
+        assert!((estimate.offset_ms - 12_475.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_clock_applies_offset() {
+        let clock = Clock {
+            offset_micros: AtomicI64::new(0),
+        };
+        let before = clock.unix_time();
+        clock.set_offset(-5.0);
+        assert!((clock.offset() + 5.0).abs() < 1e-9);
+        let after = clock.unix_time();
+        assert!(before - after > 4.9 && before - after < 5.1);
+    }
+}
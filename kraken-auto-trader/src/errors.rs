@@ -10,7 +10,7 @@ pub enum Error {
 
     #[error("Invalid parameter: {0}")]
     InvalidParameter(String),
-    
+
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
 
@@ -32,11 +32,17 @@ pub enum Error {
     #[error("Timeout error: {0}")]
     TimeoutError(String),
 
+    #[error("Circuit open: {0}")]
+    CircuitOpen(String),
+
     #[error("Unknown error: {0}")]
     Unknown(String),
 
     #[error("Deserialization error: {0}")]
     Deserialization(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 impl Error {
@@ -46,6 +52,34 @@ impl Error {
             Error::RateLimitExceeded(_) | Error::NetworkError(_) | Error::TimeoutError(_)
         )
     }
+
+    /// Kraken rejected the request's nonce as not greater than the last one it accepted
+    pub fn is_invalid_nonce(&self) -> bool {
+        matches!(self, Error::Api(message) if message.contains("EAPI:Invalid nonce"))
+    }
+
+    /// A copy of this error for every caller sharing one request. Errors of other crates keep
+    /// their message and are mapped to the variant they are retried and counted as.
+    pub fn duplicate(&self) -> Error {
+        match self {
+            Error::HttpError(e) if e.is_timeout() => Error::TimeoutError(e.to_string()),
+            Error::HttpError(e) if e.is_decode() => Error::Deserialization(e.to_string()),
+            Error::HttpError(e) => Error::NetworkError(e.to_string()),
+            Error::InvalidResponse(message) => Error::InvalidResponse(message.clone()),
+            Error::InvalidParameter(message) => Error::InvalidParameter(message.clone()),
+            Error::SerializationError(e) => Error::Deserialization(e.to_string()),
+            Error::Api(message) => Error::Api(message.clone()),
+            Error::Auth(message) => Error::Auth(message.clone()),
+            Error::RateLimitExceeded(message) => Error::RateLimitExceeded(message.clone()),
+            Error::ValidationError(message) => Error::ValidationError(message.clone()),
+            Error::NetworkError(message) => Error::NetworkError(message.clone()),
+            Error::TimeoutError(message) => Error::TimeoutError(message.clone()),
+            Error::CircuitOpen(message) => Error::CircuitOpen(message.clone()),
+            Error::Unknown(message) => Error::Unknown(message.clone()),
+            Error::Deserialization(message) => Error::Deserialization(message.clone()),
+            Error::Io(e) => Error::Io(std::io::Error::new(e.kind(), e.to_string())),
+        }
+    }
 }
 
 // Implement conversion from Kraken API error responses
@@ -0,0 +1,543 @@
+use super::{
+    pov::Pov,
+    twap::Twap,
+    vwap::{ohlc_interval, volume_profile, Vwap},
+    AlgoContext, AlgoParams, ExecutionAlgo, ParentOrderProgress, ParentOrderRequest,
+    ParentOrderStatus,
+};
+use crate::{
+    errors::Error,
+    feeds::trades::{traded_volume, TradeFeed},
+    metrics::metrics,
+    middleware::KrakenClientState,
+    models::trading::{NewOrder, OrderType},
+    orders::manager::{generate_cl_ord_id, OrderManager},
+    services::market_data::MarketData,
+    streaming::{hub::EventHub, Channel, StreamEvent},
+};
+use rand::Rng;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{watch, Mutex};
+use tracing::{info, warn};
+
+/// Child orders smaller than this are not worth sending
+const MIN_CHILD_VOLUME: f64 = 1e-8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    Run,
+    Pause,
+    Cancel,
+}
+
+struct ParentOrder {
+    progress: Mutex<ParentOrderProgress>,
+    control: watch::Sender<Control>,
+    events: Option<Arc<EventHub>>,
+}
+
+impl ParentOrder {
+    /// Stream a change of progress to the clients following strategies
+    fn publish(&self, progress: &ParentOrderProgress) {
+        if let (Some(events), Ok(data)) = (&self.events, serde_json::to_value(progress)) {
+            events.publish(StreamEvent::new(Channel::Strategies, None, data));
+        }
+    }
+}
+
+/// Runs parent orders, sending their child orders through the [`OrderManager`]
+pub struct ExecutionEngine {
+    orders: Arc<OrderManager>,
+    client: KrakenClientState,
+    parents: Mutex<HashMap<String, Arc<ParentOrder>>>,
+    events: Option<Arc<EventHub>>,
+}
+
+impl ExecutionEngine {
+    pub fn new(orders: Arc<OrderManager>, client: KrakenClientState) -> Self {
+        Self {
+            orders,
+            client,
+            parents: Mutex::new(HashMap::new()),
+            events: None,
+        }
+    }
+
+    /// Stream the progress of parent orders on the hub
+    pub fn with_event_hub(mut self, events: Arc<EventHub>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Validate a parent order and start executing it in the background
+    pub async fn start(&self, request: ParentOrderRequest) -> Result<ParentOrderProgress, Error> {
+        request.validate()?;
+        let min_volume = self
+            .orders
+            .instrument(self.client.clone(), &request.pair)
+            .await?
+            .map_or(0.0, |instrument| instrument.ordermin);
+        if request.volume < min_volume {
+            return Err(Error::InvalidParameter(format!(
+                "Volume must be at least the minimum order of {} for {}",
+                min_volume, request.pair
+            )));
+        }
+        let algo = self.build_algo(&request).await?;
+        let feed = algo
+            .uses_market_volume()
+            .then(|| TradeFeed::new(request.pair.clone()));
+
+        let now = unix_time();
+        let progress = ParentOrderProgress {
+            id: generate_cl_ord_id(),
+            algo: request.algo_name(),
+            request: request.clone(),
+            status: ParentOrderStatus::Running,
+            executed_volume: 0.0,
+            average_price: None,
+            pnl: None,
+            working_volume: 0.0,
+            target_volume: 0.0,
+            percent_complete: 0.0,
+            children: Vec::new(),
+            started_at: now,
+            updated_at: now,
+            next_child_at: Some(now),
+            last_error: None,
+        };
+        let parent = Arc::new(ParentOrder {
+            progress: Mutex::new(progress.clone()),
+            control: watch::channel(Control::Run).0,
+            events: self.events.clone(),
+        });
+        parent.publish(&progress);
+        self.parents
+            .lock()
+            .await
+            .insert(progress.id.clone(), parent.clone());
+
+        info!(
+            "Starting {} parent order {} for {} {}",
+            progress.algo, progress.id, request.volume, request.pair
+        );
+        tokio::spawn(run(
+            self.orders.clone(),
+            self.client.clone(),
+            parent,
+            request,
+            algo,
+            feed,
+            min_volume,
+        ));
+        Ok(progress)
+    }
+
+    /// Get all parent orders, oldest first
+    pub async fn list(&self) -> Vec<ParentOrderProgress> {
+        let parents: Vec<Arc<ParentOrder>> = self.parents.lock().await.values().cloned().collect();
+        let mut list = Vec::with_capacity(parents.len());
+        for parent in parents {
+            list.push(parent.progress.lock().await.clone());
+        }
+        list.sort_by(|a, b| a.started_at.total_cmp(&b.started_at));
+        list
+    }
+
+    /// Get the progress of a parent order
+    pub async fn get(&self, id: &str) -> Option<ParentOrderProgress> {
+        let parent = self.parents.lock().await.get(id).cloned()?;
+        let progress = parent.progress.lock().await.clone();
+        Some(progress)
+    }
+
+    /// Stop sending child orders until the parent order is resumed
+    pub async fn pause(&self, id: &str) -> Result<ParentOrderProgress, Error> {
+        self.control(id, Control::Pause).await
+    }
+
+    /// Resume a paused parent order
+    pub async fn resume(&self, id: &str) -> Result<ParentOrderProgress, Error> {
+        self.control(id, Control::Run).await
+    }
+
+    /// Stop a parent order and cancel its working child orders
+    pub async fn cancel(&self, id: &str) -> Result<ParentOrderProgress, Error> {
+        self.control(id, Control::Cancel).await
+    }
+
+    async fn control(&self, id: &str, control: Control) -> Result<ParentOrderProgress, Error> {
+        let parent = self
+            .parents
+            .lock()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or_else(|| Error::InvalidParameter(format!("Unknown parent order {}", id)))?;
+
+        let mut progress = parent.progress.lock().await;
+        if progress.status.is_finished() {
+            return Err(Error::InvalidParameter(format!(
+                "Parent order {} is already {:?}",
+                id, progress.status
+            )));
+        }
+        match control {
+            Control::Run => progress.status = ParentOrderStatus::Running,
+            Control::Pause => progress.status = ParentOrderStatus::Paused,
+            Control::Cancel => {}
+        }
+        parent.control.send_replace(control);
+        parent.publish(&progress);
+        Ok(progress.clone())
+    }
+
+    async fn build_algo(
+        &self,
+        request: &ParentOrderRequest,
+    ) -> Result<Box<dyn ExecutionAlgo>, Error> {
+        Ok(match request.params {
+            AlgoParams::Twap { slices } => {
+                Box::new(Twap::new(request.volume, slices, request.duration()))
+            }
+            AlgoParams::Vwap { slices } => {
+                let slice_secs = (request.duration_secs / slices as u64).max(1);
+                let interval = ohlc_interval(slice_secs);
+                // Slices shorter than a candle would mostly get no volume at all
+                if slice_secs < interval as u64 * 60 {
+                    info!(
+                        "VWAP slices of {}s are shorter than {}-minute candles, using TWAP",
+                        slice_secs, interval
+                    );
+                    return Ok(Box::new(Twap::new(
+                        request.volume,
+                        slices,
+                        request.duration(),
+                    )));
+                }
+                let response = MarketData::new()
+                    .get_ohlc(
+                        self.client.clone(),
+                        request.pair.clone(),
+                        Some(interval),
+                        None,
+                    )
+                    .await?;
+                let candles = response.data.into_values().next().unwrap_or_default();
+                let profile =
+                    volume_profile(&candles, unix_time() as i64, slice_secs as i64, slices);
+                Box::new(Vwap::new(request.volume, request.duration(), &profile))
+            }
+            AlgoParams::Pov {
+                participation,
+                interval_secs,
+            } => Box::new(Pov::new(
+                request.volume,
+                participation,
+                Duration::from_secs(interval_secs),
+            )),
+        })
+    }
+}
+
+async fn run(
+    orders: Arc<OrderManager>,
+    client: KrakenClientState,
+    parent: Arc<ParentOrder>,
+    request: ParentOrderRequest,
+    algo: Box<dyn ExecutionAlgo>,
+    mut feed: Option<TradeFeed>,
+    min_volume: f64,
+) {
+    let mut control = parent.control.subscribe();
+    let market = MarketData::new();
+    let mut children: Vec<String> = Vec::new();
+    let mut market_volume = 0.0;
+    let mut elapsed_before_pause = Duration::ZERO;
+    let mut running_since = Some(Instant::now());
+
+    if let Some(feed) = feed.as_mut() {
+        // Only trades after the start count towards the participation
+        if let Err(e) = feed.skip_to_latest(client.clone()).await {
+            finish(&parent, ParentOrderStatus::Failed, Some(e.to_string())).await;
+            return;
+        }
+    }
+
+    let status = loop {
+        let state = *control.borrow_and_update();
+        match state {
+            Control::Cancel => break ParentOrderStatus::Cancelled,
+            Control::Pause => {
+                if let Some(since) = running_since.take() {
+                    elapsed_before_pause += since.elapsed();
+                    parent.progress.lock().await.next_child_at = None;
+                }
+                if control.changed().await.is_err() {
+                    break ParentOrderStatus::Cancelled;
+                }
+                continue;
+            }
+            Control::Run => {}
+        }
+        let since = *running_since.get_or_insert_with(Instant::now);
+        let elapsed = elapsed_before_pause + since.elapsed();
+
+        let mut last_error = None;
+        if let Some(feed) = feed.as_mut() {
+            match feed.poll(client.clone()).await {
+                Ok(trades) => market_volume += traded_volume(&trades),
+                Err(e) => last_error = Some(e.to_string()),
+            }
+        }
+        if let Err(e) = orders.sync(client.clone()).await {
+            last_error = Some(e.to_string());
+        }
+
+        let (executed, working, average_price) = tally(&orders, &children).await;
+        let ctx = AlgoContext {
+            elapsed,
+            market_volume,
+            executed,
+        };
+        let target = algo.target_volume(&ctx).min(request.volume);
+
+        if executed >= request.volume - MIN_CHILD_VOLUME {
+            break ParentOrderStatus::Completed;
+        }
+        let remaining = request.volume - executed - working;
+        if working <= 0.0 && remaining < min_volume {
+            info!(
+                "Leaving {} {} unexecuted, below the minimum order of {}",
+                remaining, request.pair, min_volume
+            );
+            break ParentOrderStatus::Completed;
+        }
+        if elapsed >= request.duration() {
+            break ParentOrderStatus::Expired;
+        }
+
+        if let Some(child_volume) = slice_volume(target - executed - working, remaining, min_volume)
+        {
+            match orders
+                .submit(client.clone(), child_order(&request, child_volume))
+                .await
+            {
+                Ok(child) => children.push(child.cl_ord_id),
+                Err(e) => {
+                    warn!("Failed to send child order: {}", e);
+                    last_error = Some(e.to_string());
+                }
+            }
+        }
+
+        let mark = match average_price {
+            Some(_) => market
+                .last_trade_price(client.clone(), &request.pair)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Failed to get the last price of {}: {}", request.pair, e);
+                    None
+                }),
+            None => None,
+        };
+
+        let wait = jittered(algo.interval(), request.jitter)
+            .min(request.duration().saturating_sub(elapsed));
+        {
+            let mut progress = parent.progress.lock().await;
+            progress.executed_volume = executed;
+            progress.average_price = average_price;
+            if let Some(pnl) = mark.and_then(|mark| progress.pnl_at(mark)) {
+                progress.pnl = Some(pnl);
+                metrics().set_strategy_pnl(&progress.id, pnl);
+            }
+            progress.working_volume = working;
+            progress.target_volume = target;
+            progress.percent_complete = executed / request.volume * 100.0;
+            progress.children = children.clone();
+            progress.updated_at = unix_time();
+            progress.next_child_at = Some(unix_time() + wait.as_secs_f64());
+            if last_error.is_some() {
+                progress.last_error = last_error;
+            }
+            parent.publish(&progress);
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            changed = control.changed() => {
+                if changed.is_err() {
+                    break ParentOrderStatus::Cancelled;
+                }
+            }
+        }
+    };
+
+    if status == ParentOrderStatus::Cancelled {
+        for id in &children {
+            if let Some(child) = orders.get(id).await {
+                if !child.state.is_terminal() {
+                    if let Err(e) = orders.cancel(client.clone(), id).await {
+                        warn!("Failed to cancel child order {}: {}", id, e);
+                    }
+                }
+            }
+        }
+    }
+
+    let (executed, working, average_price) = tally(&orders, &children).await;
+    {
+        let mut progress = parent.progress.lock().await;
+        progress.executed_volume = executed;
+        progress.average_price = average_price;
+        progress.working_volume = working;
+        progress.percent_complete = executed / request.volume * 100.0;
+        progress.children = children;
+    }
+    finish(&parent, status, None).await;
+}
+
+async fn finish(parent: &ParentOrder, status: ParentOrderStatus, error: Option<String>) {
+    let mut progress = parent.progress.lock().await;
+    info!("Parent order {} finished as {:?}", progress.id, status);
+    metrics().clear_strategy_pnl(&progress.id);
+    progress.status = status;
+    progress.next_child_at = None;
+    progress.updated_at = unix_time();
+    if error.is_some() {
+        progress.last_error = error;
+    }
+    parent.publish(&progress);
+}
+
+/// Sum the executed and still working volume of child orders, with the average price of the
+/// executed volume
+async fn tally(orders: &OrderManager, children: &[String]) -> (f64, f64, Option<f64>) {
+    let mut executed = 0.0;
+    let mut working = 0.0;
+    let mut priced = 0.0;
+    let mut cost = 0.0;
+    for id in children {
+        if let Some(child) = orders.get(id).await {
+            executed += child.vol_exec;
+            if let Some(price) = child.avg_price {
+                priced += child.vol_exec;
+                cost += child.vol_exec * price;
+            }
+            if !child.state.is_terminal() {
+                let volume: f64 = child.order.volume.parse().unwrap_or(0.0);
+                working += (volume - child.vol_exec).max(0.0);
+            }
+        }
+    }
+    let average_price = (priced > 0.0).then(|| cost / priced);
+    (executed, working, average_price)
+}
+
+/// Volume of the next child order given the volume `due` by the schedule and what is left of
+/// the parent. A slice below the pair's minimum order waits for later slices to make it up, and
+/// a remainder that would be left below the minimum is sent along with the slice
+fn slice_volume(due: f64, remaining: f64, min_volume: f64) -> Option<f64> {
+    let min_volume = min_volume.max(MIN_CHILD_VOLUME);
+    if due < min_volume {
+        None
+    } else if remaining - due < min_volume {
+        Some(remaining)
+    } else {
+        Some(due)
+    }
+}
+
+/// Build a child order, guarded by an immediate-or-cancel limit when the parent has a limit price
+fn child_order(request: &ParentOrderRequest, volume: f64) -> NewOrder {
+    let volume = format!("{:.8}", volume);
+    match request.limit_price {
+        Some(price) => {
+            let mut order =
+                NewOrder::new(request.pair.clone(), request.side, OrderType::Limit, volume);
+            order.price = Some(price.to_string());
+            order.timeinforce = Some("IOC".to_string());
+            order
+        }
+        None => NewOrder::new(
+            request.pair.clone(),
+            request.side,
+            OrderType::Market,
+            volume,
+        ),
+    }
+}
+
+/// Randomly shift an interval by up to `jitter` times its length
+fn jittered(interval: Duration, jitter: f64) -> Duration {
+    if jitter <= 0.0 {
+        return interval;
+    }
+    let factor = 1.0 + rand::rng().random_range(-jitter..jitter);
+    interval.mul_f64(factor.max(0.0))
+}
+
+fn unix_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{symbols::Pair, trading::OrderSide};
+
+    fn request(limit_price: Option<f64>) -> ParentOrderRequest {
+        ParentOrderRequest {
+            pair: Pair::from("XETHZGBP"),
+            side: OrderSide::Sell,
+            volume: 1.0,
+            duration_secs: 60,
+            params: AlgoParams::Twap { slices: 3 },
+            limit_price,
+            allow_market: limit_price.is_none(),
+            jitter: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_child_order_limit_guard() {
+        let order = child_order(&request(Some(1800.0)), 0.333333333);
+        assert_eq!(order.ordertype, OrderType::Limit);
+        assert_eq!(order.volume, "0.33333333");
+        assert_eq!(order.price.as_deref(), Some("1800"));
+        assert_eq!(order.timeinforce.as_deref(), Some("IOC"));
+
+        let order = child_order(&request(None), 0.5);
+        assert_eq!(order.ordertype, OrderType::Market);
+        assert!(order.price.is_none());
+    }
+
+    #[test]
+    fn test_slice_volume_respects_ordermin() {
+        // Below the minimum, the slice is carried forward
+        assert_eq!(slice_volume(0.004, 1.0, 0.01), None);
+        assert_eq!(slice_volume(0.012, 1.0, 0.01), Some(0.012));
+        // A remainder that could not be sent on its own goes with the slice
+        assert_eq!(slice_volume(0.3, 0.305, 0.01), Some(0.305));
+        assert_eq!(slice_volume(0.5, 0.5, 0.0), Some(0.5));
+        assert_eq!(slice_volume(1e-9, 1.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_jittered_stays_in_range() {
+        let interval = Duration::from_secs(10);
+        assert_eq!(jittered(interval, 0.0), interval);
+        for _ in 0..100 {
+            let wait = jittered(interval, 0.2);
+            assert!(wait >= Duration::from_secs(8) && wait <= Duration::from_secs(12));
+        }
+    }
+}
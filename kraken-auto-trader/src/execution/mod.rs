@@ -0,0 +1,273 @@
+pub mod engine;
+pub mod pov;
+pub mod twap;
+pub mod vwap;
+
+use crate::{
+    errors::Error,
+    models::{symbols::Pair, trading::OrderSide},
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Parameters of the algorithm used to split a parent order
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "algo", rename_all = "lowercase")]
+pub enum AlgoParams {
+    /// Equal slices at a fixed interval
+    Twap { slices: u32 },
+    /// Slices weighted by the historical volume profile of the pair
+    Vwap { slices: u32 },
+    /// Child orders sized to a share of the live traded volume
+    Pov {
+        participation: f64,
+        interval_secs: u64,
+    },
+}
+
+/// A large order to be executed as a series of child orders
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ParentOrderRequest {
+    pub pair: Pair,
+    pub side: OrderSide,
+    pub volume: f64,
+    /// Time over which the order is spread (TWAP, VWAP) or after which it stops (POV)
+    pub duration_secs: u64,
+    #[serde(flatten)]
+    pub params: AlgoParams,
+    /// Worst price accepted, child orders are sent as immediate-or-cancel limits at this price
+    pub limit_price: Option<f64>,
+    /// Send unprotected market child orders when there is no limit price
+    #[serde(default)]
+    pub allow_market: bool,
+    /// Fraction of the interval by which each child order is randomly shifted (0 to 1)
+    #[serde(default)]
+    pub jitter: f64,
+}
+
+impl ParentOrderRequest {
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.volume.is_nan() || self.volume <= 0.0 {
+            return Err(Error::InvalidParameter("Volume must be positive".into()));
+        }
+        if self.duration_secs == 0 {
+            return Err(Error::InvalidParameter("Duration must be positive".into()));
+        }
+        if !(0.0..1.0).contains(&self.jitter) {
+            return Err(Error::InvalidParameter(
+                "Jitter must be between 0 and 1".into(),
+            ));
+        }
+        if self
+            .limit_price
+            .is_some_and(|price| price.is_nan() || price <= 0.0)
+        {
+            return Err(Error::InvalidParameter(
+                "Limit price must be positive".into(),
+            ));
+        }
+        if self.limit_price.is_none() && !self.allow_market {
+            return Err(Error::InvalidParameter(
+                "A limit price is required unless market child orders are allowed".into(),
+            ));
+        }
+        match self.params {
+            AlgoParams::Twap { slices } | AlgoParams::Vwap { slices } => {
+                if slices == 0 {
+                    return Err(Error::InvalidParameter("Slices must be positive".into()));
+                }
+            }
+            AlgoParams::Pov {
+                participation,
+                interval_secs,
+            } => {
+                if !(participation > 0.0 && participation < 1.0) {
+                    return Err(Error::InvalidParameter(
+                        "Participation must be between 0 and 1".into(),
+                    ));
+                }
+                if interval_secs == 0 {
+                    return Err(Error::InvalidParameter("Interval must be positive".into()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs(self.duration_secs)
+    }
+
+    pub fn algo_name(&self) -> &'static str {
+        match self.params {
+            AlgoParams::Twap { .. } => "twap",
+            AlgoParams::Vwap { .. } => "vwap",
+            AlgoParams::Pov { .. } => "pov",
+        }
+    }
+}
+
+/// What an algorithm knows when sizing the next child order
+#[derive(Debug, Clone, Copy)]
+pub struct AlgoContext {
+    /// Running time of the parent order, excluding pauses
+    pub elapsed: Duration,
+    /// Volume traded on the market since the parent order started, including its own fills
+    pub market_volume: f64,
+    /// Volume executed so far
+    pub executed: f64,
+}
+
+/// Decides how much of a parent order should have been executed at a point in time
+pub trait ExecutionAlgo: Send + Sync {
+    /// Time between two child orders
+    fn interval(&self) -> Duration;
+
+    /// Cumulative volume that should be executed or working by now
+    fn target_volume(&self, ctx: &AlgoContext) -> f64;
+
+    /// Whether the algorithm needs the live market volume
+    fn uses_market_volume(&self) -> bool {
+        false
+    }
+}
+
+/// Lifecycle of a parent order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParentOrderStatus {
+    Running,
+    Paused,
+    Completed,
+    /// The duration ran out before the full volume could be executed
+    Expired,
+    Cancelled,
+    Failed,
+}
+
+impl ParentOrderStatus {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, ParentOrderStatus::Running | ParentOrderStatus::Paused)
+    }
+}
+
+/// Progress of a parent order as reported by the API
+#[derive(Debug, Clone, Serialize)]
+pub struct ParentOrderProgress {
+    pub id: String,
+    pub algo: &'static str,
+    pub request: ParentOrderRequest,
+    pub status: ParentOrderStatus,
+    pub executed_volume: f64,
+    /// Volume weighted average price of the executed child orders
+    pub average_price: Option<f64>,
+    /// Profit of the executed volume if it were closed at the last trade price, in the quote
+    /// currency
+    pub pnl: Option<f64>,
+    pub working_volume: f64,
+    pub target_volume: f64,
+    pub percent_complete: f64,
+    pub children: Vec<String>,
+    pub started_at: f64,
+    pub updated_at: f64,
+    pub next_child_at: Option<f64>,
+    pub last_error: Option<String>,
+}
+
+impl ParentOrderProgress {
+    /// Profit of the executed volume if it were closed at `mark`
+    pub fn pnl_at(&self, mark: f64) -> Option<f64> {
+        let average = self.average_price?;
+        let per_unit = match self.request.side {
+            OrderSide::Buy => mark - average,
+            OrderSide::Sell => average - mark,
+        };
+        Some(per_unit * self.executed_volume)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(params: AlgoParams) -> ParentOrderRequest {
+        ParentOrderRequest {
+            pair: Pair::from("XETHZGBP"),
+            side: OrderSide::Buy,
+            volume: 10.0,
+            duration_secs: 600,
+            params,
+            limit_price: Some(1800.0),
+            allow_market: false,
+            jitter: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_request_validation() {
+        assert!(request(AlgoParams::Twap { slices: 10 }).validate().is_ok());
+        assert!(request(AlgoParams::Vwap { slices: 0 }).validate().is_err());
+        assert!(request(AlgoParams::Pov {
+            participation: 1.5,
+            interval_secs: 10
+        })
+        .validate()
+        .is_err());
+
+        let mut invalid = request(AlgoParams::Twap { slices: 10 });
+        invalid.jitter = 1.0;
+        assert!(invalid.validate().is_err());
+
+        // Market child orders must be asked for
+        let mut market = request(AlgoParams::Twap { slices: 10 });
+        market.limit_price = None;
+        assert!(market.validate().is_err());
+        market.allow_market = true;
+        assert!(market.validate().is_ok());
+    }
+
+    #[test]
+    fn test_request_deserialization() {
+        let request: ParentOrderRequest = serde_json::from_value(serde_json::json!({
+            "pair": "XETHZGBP",
+            "side": "sell",
+            "volume": 5.0,
+            "duration_secs": 3600,
+            "algo": "pov",
+            "participation": 0.1,
+            "interval_secs": 30,
+            "limit_price": 1800.0
+        }))
+        .unwrap();
+        assert_eq!(request.algo_name(), "pov");
+        assert_eq!(request.jitter, 0.0);
+        assert!(request.validate().is_ok());
+    }
+
+    #[test]
+    fn test_progress_pnl() {
+        let mut progress = ParentOrderProgress {
+            id: "p1".to_string(),
+            algo: "twap",
+            request: request(AlgoParams::Twap { slices: 10 }),
+            status: ParentOrderStatus::Running,
+            executed_volume: 2.0,
+            average_price: None,
+            pnl: None,
+            working_volume: 0.0,
+            target_volume: 2.0,
+            percent_complete: 20.0,
+            children: Vec::new(),
+            started_at: 0.0,
+            updated_at: 0.0,
+            next_child_at: None,
+            last_error: None,
+        };
+        assert_eq!(progress.pnl_at(1810.0), None);
+
+        progress.average_price = Some(1800.0);
+        assert_eq!(progress.pnl_at(1810.0), Some(20.0));
+        progress.request.side = OrderSide::Sell;
+        assert_eq!(progress.pnl_at(1810.0), Some(-20.0));
+    }
+}
@@ -0,0 +1,55 @@
+use super::{AlgoContext, ExecutionAlgo};
+use std::time::Duration;
+
+/// Percentage of volume: child orders keep the parent order at a target share of the market
+pub struct Pov {
+    volume: f64,
+    participation: f64,
+    interval: Duration,
+}
+
+impl Pov {
+    pub fn new(volume: f64, participation: f64, interval: Duration) -> Self {
+        Self {
+            volume,
+            participation,
+            interval,
+        }
+    }
+}
+
+impl ExecutionAlgo for Pov {
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn target_volume(&self, ctx: &AlgoContext) -> f64 {
+        // Our own fills are part of the market volume, so size against everybody else's
+        let others = (ctx.market_volume - ctx.executed).max(0.0);
+        let target = others * self.participation / (1.0 - self.participation);
+        target.min(self.volume)
+    }
+
+    fn uses_market_volume(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pov_target() {
+        let pov = Pov::new(10.0, 0.2, Duration::from_secs(30));
+        let ctx = |market_volume, executed| AlgoContext {
+            elapsed: Duration::from_secs(60),
+            market_volume,
+            executed,
+        };
+        assert_eq!(pov.target_volume(&ctx(0.0, 0.0)), 0.0);
+        // 8 traded by others, 20% participation means 2 for us
+        assert_eq!(pov.target_volume(&ctx(10.0, 2.0)), 2.0);
+        assert_eq!(pov.target_volume(&ctx(1000.0, 2.0)), 10.0);
+    }
+}
@@ -0,0 +1,64 @@
+use super::{AlgoContext, ExecutionAlgo};
+use std::time::Duration;
+
+/// Time-weighted average price: the volume is split into equal slices sent at a fixed interval
+pub struct Twap {
+    volume: f64,
+    slices: u32,
+    interval: Duration,
+}
+
+impl Twap {
+    pub fn new(volume: f64, slices: u32, duration: Duration) -> Self {
+        let slices = slices.max(1);
+        Self {
+            volume,
+            slices,
+            interval: duration / slices,
+        }
+    }
+}
+
+/// Number of slices due `elapsed` into the schedule, the first one being due immediately
+pub(crate) fn slices_due(elapsed: Duration, interval: Duration, slices: u32) -> u32 {
+    if interval.is_zero() {
+        return slices;
+    }
+    let due = (elapsed.as_secs_f64() / interval.as_secs_f64()).floor() as u32 + 1;
+    due.min(slices)
+}
+
+impl ExecutionAlgo for Twap {
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn target_volume(&self, ctx: &AlgoContext) -> f64 {
+        let due = slices_due(ctx.elapsed, self.interval, self.slices);
+        self.volume * due as f64 / self.slices as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(elapsed_secs: u64) -> AlgoContext {
+        AlgoContext {
+            elapsed: Duration::from_secs(elapsed_secs),
+            market_volume: 0.0,
+            executed: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_twap_schedule() {
+        let twap = Twap::new(10.0, 4, Duration::from_secs(400));
+        assert_eq!(twap.interval(), Duration::from_secs(100));
+        assert_eq!(twap.target_volume(&ctx(0)), 2.5);
+        assert_eq!(twap.target_volume(&ctx(99)), 2.5);
+        assert_eq!(twap.target_volume(&ctx(100)), 5.0);
+        assert_eq!(twap.target_volume(&ctx(350)), 10.0);
+        assert_eq!(twap.target_volume(&ctx(1000)), 10.0);
+    }
+}
@@ -0,0 +1,140 @@
+use super::{twap::slices_due, AlgoContext, ExecutionAlgo};
+use crate::models::market::OHLCData;
+use std::time::Duration;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Volume-weighted average price: slices follow the pair's historical intraday volume profile
+pub struct Vwap {
+    volume: f64,
+    interval: Duration,
+    /// Cumulative share of the volume due after each slice
+    cumulative: Vec<f64>,
+}
+
+impl Vwap {
+    /// Create a VWAP schedule from per-slice weights, see [`volume_profile`]
+    pub fn new(volume: f64, duration: Duration, profile: &[f64]) -> Self {
+        let slices = profile.len().max(1) as u32;
+        let total: f64 = profile.iter().sum();
+        let mut cumulative = Vec::with_capacity(profile.len());
+        let mut sum = 0.0;
+        for weight in profile {
+            sum += if total > 0.0 {
+                weight / total
+            } else {
+                1.0 / slices as f64
+            };
+            cumulative.push(sum);
+        }
+        Self {
+            volume,
+            interval: duration / slices,
+            cumulative,
+        }
+    }
+}
+
+impl ExecutionAlgo for Vwap {
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn target_volume(&self, ctx: &AlgoContext) -> f64 {
+        if self.cumulative.is_empty() {
+            return self.volume;
+        }
+        let due = slices_due(ctx.elapsed, self.interval, self.cumulative.len() as u32);
+        self.volume * self.cumulative[due as usize - 1].min(1.0)
+    }
+}
+
+/// OHLC interval (in minutes) used to build a profile for slices of `slice_secs`.
+///
+/// Kraken returns at most 720 candles, so intervals below 5 minutes would not cover a full day.
+pub fn ohlc_interval(slice_secs: u64) -> u32 {
+    let minutes = slice_secs / 60;
+    [5, 15, 30, 60]
+        .into_iter()
+        .rev()
+        .find(|interval| *interval as u64 <= minutes)
+        .unwrap_or(5)
+}
+
+/// Build the weights of `slices` consecutive windows of `slice_secs` starting at `start`
+/// (unix time) from the volume traded at the same time of day in historical candles
+pub fn volume_profile(candles: &[OHLCData], start: i64, slice_secs: i64, slices: u32) -> Vec<f64> {
+    let mut weights = vec![0.0; slices as usize];
+    if slice_secs <= 0 || slice_secs >= SECONDS_PER_DAY {
+        return vec![1.0; slices as usize];
+    }
+
+    for candle in candles {
+        let volume: f64 = candle.volume.parse().unwrap_or(0.0);
+        let time_of_day = candle.time.rem_euclid(SECONDS_PER_DAY);
+        for (i, weight) in weights.iter_mut().enumerate() {
+            let window_start = (start + i as i64 * slice_secs).rem_euclid(SECONDS_PER_DAY);
+            if (time_of_day - window_start).rem_euclid(SECONDS_PER_DAY) < slice_secs {
+                *weight += volume;
+            }
+        }
+    }
+
+    if weights.iter().all(|weight| *weight <= 0.0) {
+        return vec![1.0; slices as usize];
+    }
+    weights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(time: i64, volume: f64) -> OHLCData {
+        OHLCData {
+            time,
+            open: "1".to_string(),
+            high: "1".to_string(),
+            low: "1".to_string(),
+            close: "1".to_string(),
+            vwap: "1".to_string(),
+            volume: volume.to_string(),
+            count: 1,
+        }
+    }
+
+    #[test]
+    fn test_volume_profile_uses_time_of_day() {
+        // Two days of hourly candles, busier in the second hour of each day
+        let day = SECONDS_PER_DAY;
+        let candles = vec![
+            candle(0, 1.0),
+            candle(3600, 3.0),
+            candle(day, 1.0),
+            candle(day + 3600, 3.0),
+        ];
+        let profile = volume_profile(&candles, 2 * day, 3600, 2);
+        assert_eq!(profile, vec![2.0, 6.0]);
+        assert_eq!(volume_profile(&[], 0, 3600, 3), vec![1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_vwap_schedule() {
+        let vwap = Vwap::new(8.0, Duration::from_secs(200), &[1.0, 3.0]);
+        let ctx = |secs| AlgoContext {
+            elapsed: Duration::from_secs(secs),
+            market_volume: 0.0,
+            executed: 0.0,
+        };
+        assert_eq!(vwap.interval(), Duration::from_secs(100));
+        assert_eq!(vwap.target_volume(&ctx(0)), 2.0);
+        assert_eq!(vwap.target_volume(&ctx(150)), 8.0);
+    }
+
+    #[test]
+    fn test_ohlc_interval() {
+        assert_eq!(ohlc_interval(60), 5);
+        assert_eq!(ohlc_interval(20 * 60), 15);
+        assert_eq!(ohlc_interval(6 * 3600), 60);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::models::market::OrderBook;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Price and volume of a level of the book
+pub type Level = (f64, f64);
+
+/// The best levels of an order book, kept up to date from the WebSocket v2 `book` channel
+#[derive(Debug, Clone, Default)]
+pub struct BookLadder {
+    depth: usize,
+    bids: BTreeMap<u64, Level>,
+    asks: BTreeMap<u64, Level>,
+}
+
+impl BookLadder {
+    /// A book keeping `depth` levels on each side
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth: depth.max(1),
+            ..Self::default()
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Replace the levels with the order book returned by the REST API
+    pub fn seed(&mut self, book: &OrderBook) {
+        self.bids.clear();
+        self.asks.clear();
+        for (levels, side) in [(&book.bids, &mut self.bids), (&book.asks, &mut self.asks)] {
+            for level in levels {
+                if let (Ok(price), Ok(volume)) = (level.price.parse(), level.volume.parse()) {
+                    set_level(side, price, volume);
+                }
+            }
+        }
+        self.truncate();
+    }
+
+    /// Apply an entry of a `book` message, replacing every level when it is a snapshot. A level
+    /// with no volume is removed.
+    pub fn apply(&mut self, entry: &Value, snapshot: bool) {
+        if snapshot {
+            self.bids.clear();
+            self.asks.clear();
+        }
+        for (key, side) in [("bids", &mut self.bids), ("asks", &mut self.asks)] {
+            for level in entry[key].as_array().into_iter().flatten() {
+                if let (Some(price), Some(volume)) =
+                    (level["price"].as_f64(), level["qty"].as_f64())
+                {
+                    set_level(side, price, volume);
+                }
+            }
+        }
+        self.truncate();
+    }
+
+    /// Bid levels, best first
+    pub fn bids(&self) -> Vec<Level> {
+        self.bids.values().rev().copied().collect()
+    }
+
+    /// Ask levels, best first
+    pub fn asks(&self) -> Vec<Level> {
+        self.asks.values().copied().collect()
+    }
+
+    pub fn best_bid(&self) -> Option<f64> {
+        self.bids.values().next_back().map(|(price, _)| *price)
+    }
+
+    pub fn best_ask(&self) -> Option<f64> {
+        self.asks.values().next().map(|(price, _)| *price)
+    }
+
+    /// Middle of the best bid and ask
+    pub fn mid(&self) -> Option<f64> {
+        Some((self.best_bid()? + self.best_ask()?) / 2.0)
+    }
+
+    /// Drop the levels beyond the depth, as Kraken stops sending updates for them
+    fn truncate(&mut self) {
+        while self.bids.len() > self.depth {
+            self.bids.pop_first();
+        }
+        while self.asks.len() > self.depth {
+            self.asks.pop_last();
+        }
+    }
+}
+
+/// Key of a price level, exact to the satoshi
+fn level_key(price: f64) -> u64 {
+    (price * 1e8).round() as u64
+}
+
+fn set_level(side: &mut BTreeMap<u64, Level>, price: f64, volume: f64) {
+    if volume > 0.0 {
+        side.insert(level_key(price), (price, volume));
+    } else {
+        side.remove(&level_key(price));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_apply() {
+        let mut book = BookLadder::new(2);
+        book.apply(
+            &json!({
+                "bids": [{ "price": 100.0, "qty": 1.0 }, { "price": 99.5, "qty": 2.0 }],
+                "asks": [{ "price": 101.0, "qty": 0.5 }, { "price": 102.0, "qty": 3.0 }],
+            }),
+            true,
+        );
+        assert_eq!(book.mid(), Some(100.5));
+
+        book.apply(
+            &json!({
+                "bids": [{ "price": 100.0, "qty": 0.0 }, { "price": 100.5, "qty": 4.0 }],
+                "asks": [{ "price": 100.8, "qty": 1.0 }],
+            }),
+            false,
+        );
+        assert_eq!(book.bids(), vec![(100.5, 4.0), (99.5, 2.0)]);
+        // The furthest ask falls beyond the depth
+        assert_eq!(book.asks(), vec![(100.8, 1.0), (101.0, 0.5)]);
+
+        book.apply(&json!({ "bids": [], "asks": [] }), true);
+        assert_eq!(book.mid(), None);
+    }
+}
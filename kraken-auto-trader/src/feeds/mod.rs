@@ -0,0 +1,5 @@
+pub mod book;
+pub mod stream;
+pub mod ticker;
+pub mod trades;
+pub mod websocket;
@@ -0,0 +1,57 @@
+use crate::clock;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+/// Where a market-data message came from
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MessageSource {
+    /// Response of a public REST endpoint
+    Rest { endpoint: String, url: String },
+    /// Message received on a WebSocket connection
+    WebSocket { url: String },
+}
+
+/// A raw market-data message as received from Kraken
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MarketMessage {
+    /// Unix time at which the message was received, in seconds, on the exchange clock
+    pub received_at: f64,
+    pub source: MessageSource,
+    /// Message body exactly as received
+    pub raw: String,
+}
+
+impl MarketMessage {
+    /// A message received now
+    pub fn received(source: MessageSource, raw: impl Into<String>) -> Self {
+        Self {
+            received_at: clock::unix_time(),
+            source,
+            raw: raw.into(),
+        }
+    }
+
+    /// Parse the raw body as JSON
+    pub fn json(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::from_str(&self.raw)
+    }
+}
+
+/// A stream of market-data messages, fed by a live connection or a replay alike
+pub struct MarketStream {
+    rx: mpsc::Receiver<MarketMessage>,
+}
+
+impl MarketStream {
+    /// Create a stream and the sender feeding it
+    pub fn channel(capacity: usize) -> (mpsc::Sender<MarketMessage>, Self) {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        (tx, Self { rx })
+    }
+
+    /// Wait for the next message, `None` once the source is exhausted
+    pub async fn next(&mut self) -> Option<MarketMessage> {
+        self.rx.recv().await
+    }
+}
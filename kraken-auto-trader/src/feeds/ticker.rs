@@ -0,0 +1,77 @@
+use crate::{
+    errors::Error,
+    middleware::KrakenClientExt,
+    models::{market::Ticker, symbols::Pair},
+    services::market_data::MarketData,
+};
+use std::collections::HashMap;
+
+/// Last traded prices of a set of pairs, polled from the Ticker endpoint
+pub struct PriceFeed {
+    market: MarketData,
+    prices: HashMap<Pair, f64>,
+}
+
+impl Default for PriceFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PriceFeed {
+    pub fn new() -> Self {
+        Self {
+            market: MarketData::new(),
+            prices: HashMap::new(),
+        }
+    }
+
+    /// Refresh the last price of every given pair.
+    ///
+    /// Prices are stored under the pair as requested, whatever name Kraken answers with.
+    pub async fn poll(
+        &mut self,
+        req: impl KrakenClientExt + Clone,
+        pairs: &[Pair],
+    ) -> Result<(), Error> {
+        for pair in pairs {
+            let tickers = self.market.get_ticker(req.clone(), std::slice::from_ref(pair)).await?;
+            if let Some(price) = tickers.values().next().and_then(last_price) {
+                self.prices.insert(pair.clone(), price);
+            }
+        }
+        Ok(())
+    }
+
+    /// Last known price of a pair
+    pub fn last(&self, pair: &Pair) -> Option<f64> {
+        self.prices.get(pair).copied()
+    }
+}
+
+/// Price of the last trade in a ticker
+pub fn last_price(ticker: &Ticker) -> Option<f64> {
+    ticker.c.first()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_price() {
+        let ticker: Ticker = serde_json::from_value(serde_json::json!({
+            "a": ["1801.0", "1", "1.000"],
+            "b": ["1800.5", "2", "2.000"],
+            "c": ["1800.75", "0.1"],
+            "v": ["10", "20"],
+            "p": ["1800", "1800"],
+            "t": [5, 10],
+            "l": ["1790", "1780"],
+            "h": ["1810", "1820"],
+            "o": "1795"
+        }))
+        .unwrap();
+        assert_eq!(last_price(&ticker), Some(1800.75));
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{
+    errors::Error,
+    middleware::KrakenClientExt,
+    models::{market::Trade, symbols::Pair},
+    services::market_data::MarketData,
+};
+
+/// Incremental feed of public trades for one pair, polled from the Trades endpoint
+pub struct TradeFeed {
+    market: MarketData,
+    pair: Pair,
+    since: Option<u64>,
+}
+
+impl TradeFeed {
+    pub fn new(pair: Pair) -> Self {
+        Self {
+            market: MarketData::new(),
+            pair,
+            since: None,
+        }
+    }
+
+    /// The pair this feed follows
+    pub fn pair(&self) -> &Pair {
+        &self.pair
+    }
+
+    /// Get the trades that happened since the previous poll.
+    ///
+    /// The first poll returns Kraken's most recent trades.
+    pub async fn poll(&mut self, req: impl KrakenClientExt) -> Result<Vec<Trade>, Error> {
+        let response = self
+            .market
+            .get_recent_trades(req, self.pair.clone(), self.since, None)
+            .await?;
+        self.since = response.last.parse().ok().or(self.since);
+        Ok(response.trades.into_values().flatten().collect())
+    }
+
+    /// Move the cursor to the latest trade without returning anything
+    pub async fn skip_to_latest(&mut self, req: impl KrakenClientExt) -> Result<(), Error> {
+        self.poll(req).await.map(|_| ())
+    }
+}
+
+/// Sum the volume of a batch of trades
+pub fn traded_volume(trades: &[Trade]) -> f64 {
+    trades
+        .iter()
+        .filter_map(|trade| trade.volume.parse::<f64>().ok())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traded_volume() {
+        let trades: Vec<Trade> = serde_json::from_value(serde_json::json!([
+            ["2000.1", "0.5", 1700000000.1, "b", "l", "", 1],
+            ["2000.2", "1.25", 1700000001.2, "s", "m", "", 2]
+        ]))
+        .unwrap();
+        assert_eq!(traded_volume(&trades), 1.75);
+    }
+}
@@ -0,0 +1,321 @@
+use super::stream::{MarketMessage, MarketStream, MessageSource};
+use crate::{
+    metrics::metrics, middleware::KrakenClientState, recording::recorder::Recorder,
+    services::trading::Trading, utils::time::parse_rfc3339,
+};
+use futures::{SinkExt, StreamExt};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{info, warn};
+
+/// Kraken's public WebSocket API (v2)
+pub const PUBLIC_WS_URL: &str = "wss://ws.kraken.com/v2";
+
+/// Kraken's authenticated WebSocket API (v2)
+pub const PRIVATE_WS_URL: &str = "wss://ws-auth.kraken.com/v2";
+
+/// Connection state of a [`MarketDataSocket`] or [`PrivateDataSocket`], shared with health checks
+#[derive(Debug, Default)]
+pub struct SocketStatus {
+    connected: AtomicBool,
+    /// Unix time of the last message as `f64` bits, zero before the first one
+    last_message_at: AtomicU64,
+}
+
+/// Point-in-time view of a [`SocketStatus`]
+#[derive(Debug, Clone, Serialize)]
+pub struct SocketState {
+    pub connected: bool,
+    pub last_message_at: Option<f64>,
+}
+
+impl SocketStatus {
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    pub fn last_message_at(&self) -> Option<f64> {
+        match self.last_message_at.load(Ordering::Relaxed) {
+            0 => None,
+            bits => Some(f64::from_bits(bits)),
+        }
+    }
+
+    pub fn state(&self) -> SocketState {
+        SocketState {
+            connected: self.is_connected(),
+            last_message_at: self.last_message_at(),
+        }
+    }
+
+    fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::Relaxed);
+    }
+
+    fn message_received(&self, at: f64) {
+        self.last_message_at.store(at.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Connection to the public WebSocket API, reconnecting and resubscribing on failure
+pub struct MarketDataSocket {
+    url: String,
+    subscriptions: Vec<Value>,
+    recorder: Option<Arc<Recorder>>,
+    reconnect_delay: Duration,
+    buffer: usize,
+    status: Arc<SocketStatus>,
+}
+
+impl MarketDataSocket {
+    pub fn new(subscriptions: Vec<Value>) -> Self {
+        Self {
+            url: std::env::var("KRAKEN_WS_URL").unwrap_or_else(|_| PUBLIC_WS_URL.to_string()),
+            subscriptions,
+            recorder: None,
+            reconnect_delay: Duration::from_secs(5),
+            buffer: 1024,
+            status: Arc::default(),
+        }
+    }
+
+    /// Connection state, updated while the socket runs
+    pub fn status(&self) -> Arc<SocketStatus> {
+        self.status.clone()
+    }
+
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    /// Record every message received
+    pub fn with_recorder(mut self, recorder: Arc<Recorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    pub fn with_reconnect_delay(mut self, delay: Duration) -> Self {
+        self.reconnect_delay = delay;
+        self
+    }
+
+    /// Subscription request for a channel (`ticker`, `book`, `trade`, `ohlc`, ...) and symbols
+    /// in WebSocket notation such as `BTC/USD`
+    pub fn subscription(channel: &str, symbols: &[&str]) -> Value {
+        json!({
+            "method": "subscribe",
+            "params": {
+                "channel": channel,
+                "symbol": symbols,
+            }
+        })
+    }
+
+    /// Connect in the background; the connection stops once the stream is dropped
+    pub fn spawn(self) -> MarketStream {
+        let (tx, stream) = MarketStream::channel(self.buffer);
+        tokio::spawn(async move {
+            while !tx.is_closed() {
+                if let Err(e) = self.run(&tx).await {
+                    warn!("WebSocket connection to {} failed: {}", self.url, e);
+                }
+                self.status.set_connected(false);
+                if tx.is_closed() {
+                    break;
+                }
+                tokio::time::sleep(self.reconnect_delay).await;
+                metrics()
+                    .ws_reconnects
+                    .with_label_values(&[self.url.as_str()])
+                    .inc();
+            }
+        });
+        stream
+    }
+
+    async fn run(
+        &self,
+        tx: &mpsc::Sender<MarketMessage>,
+    ) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+        relay(
+            &self.url,
+            &self.subscriptions,
+            &self.status,
+            self.recorder.as_deref(),
+            tx,
+        )
+        .await
+    }
+}
+
+/// Connection to the authenticated WebSocket API, subscribing with a fresh token each time it
+/// connects
+pub struct PrivateDataSocket {
+    url: String,
+    trading: Trading,
+    client: KrakenClientState,
+    subscriptions: Vec<Value>,
+    reconnect_delay: Duration,
+    buffer: usize,
+    status: Arc<SocketStatus>,
+}
+
+impl PrivateDataSocket {
+    pub fn new(trading: Trading, client: KrakenClientState, subscriptions: Vec<Value>) -> Self {
+        Self {
+            url: std::env::var("KRAKEN_WS_AUTH_URL")
+                .unwrap_or_else(|_| PRIVATE_WS_URL.to_string()),
+            trading,
+            client,
+            subscriptions,
+            reconnect_delay: Duration::from_secs(5),
+            buffer: 1024,
+            status: Arc::default(),
+        }
+    }
+
+    /// Connection state, updated while the socket runs
+    pub fn status(&self) -> Arc<SocketStatus> {
+        self.status.clone()
+    }
+
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    pub fn with_reconnect_delay(mut self, delay: Duration) -> Self {
+        self.reconnect_delay = delay;
+        self
+    }
+
+    /// Subscription request for a private channel (`executions`, `balances`); the token is
+    /// added when connecting
+    pub fn subscription(channel: &str) -> Value {
+        json!({
+            "method": "subscribe",
+            "params": {
+                "channel": channel,
+            }
+        })
+    }
+
+    /// Connect in the background; the connection stops once the stream is dropped
+    pub fn spawn(self) -> MarketStream {
+        let (tx, stream) = MarketStream::channel(self.buffer);
+        tokio::spawn(async move {
+            while !tx.is_closed() {
+                match self.trading.get_websockets_token(self.client.clone()).await {
+                    Ok(token) => {
+                        let subscriptions: Vec<Value> = self
+                            .subscriptions
+                            .iter()
+                            .cloned()
+                            .map(|mut subscription| {
+                                subscription["params"]["token"] = token.token.clone().into();
+                                subscription
+                            })
+                            .collect();
+                        if let Err(e) =
+                            relay(&self.url, &subscriptions, &self.status, None, &tx).await
+                        {
+                            warn!("WebSocket connection to {} failed: {}", self.url, e);
+                        }
+                    }
+                    Err(e) => warn!("Failed to get a WebSocket token: {}", e),
+                }
+                self.status.set_connected(false);
+                if tx.is_closed() {
+                    break;
+                }
+                tokio::time::sleep(self.reconnect_delay).await;
+                metrics()
+                    .ws_reconnects
+                    .with_label_values(&[self.url.as_str()])
+                    .inc();
+            }
+        });
+        stream
+    }
+}
+
+/// Connect to `url`, send the subscriptions and pass every message received on to `tx` until
+/// the connection closes or the stream is dropped
+async fn relay(
+    url: &str,
+    subscriptions: &[Value],
+    status: &SocketStatus,
+    recorder: Option<&Recorder>,
+    tx: &mpsc::Sender<MarketMessage>,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    let (mut socket, _) = connect_async(url).await?;
+    info!("Connected to {}", url);
+    for subscription in subscriptions {
+        socket
+            .send(Message::Text(subscription.to_string().into()))
+            .await?;
+    }
+    status.set_connected(true);
+
+    let source = MessageSource::WebSocket {
+        url: url.to_string(),
+    };
+    while let Some(message) = socket.next().await {
+        let text = match message? {
+            Message::Text(text) => text.to_string(),
+            Message::Ping(payload) => {
+                socket.send(Message::Pong(payload)).await?;
+                continue;
+            }
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let message = MarketMessage::received(source.clone(), text);
+        status.message_received(message.received_at);
+        if let Some(sent_at) = exchange_timestamp(&message.raw) {
+            metrics()
+                .ws_message_lag
+                .with_label_values(&[url])
+                .observe((message.received_at - sent_at).max(0.0));
+        }
+        if let Some(recorder) = recorder {
+            recorder.record(&message);
+        }
+        if tx.send(message).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Exchange time of a channel update, from the `timestamp` of its first entry
+fn exchange_timestamp(raw: &str) -> Option<f64> {
+    // Heartbeats and acknowledgements carry no timestamp; skip parsing them
+    if !raw.contains("\"timestamp\"") {
+        return None;
+    }
+    let message: Value = serde_json::from_str(raw).ok()?;
+    parse_rfc3339(message["data"][0]["timestamp"].as_str()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exchange_timestamp() {
+        let trade = r#"{"channel":"trade","type":"update","data":[{"symbol":"BTC/USD","price":50000.0,"qty":0.1,"timestamp":"2024-05-01T00:00:00.250Z"}]}"#;
+        assert_eq!(exchange_timestamp(trade), Some(1_714_521_600.25));
+        assert_eq!(exchange_timestamp(r#"{"channel":"heartbeat"}"#), None);
+    }
+}
@@ -0,0 +1,424 @@
+use crate::{
+    client::circuit_breaker::{BreakerReport, CircuitBreakers, CircuitState},
+    clock::{ClockEstimate, ClockSample, ClockSync},
+    feeds::websocket::{SocketState, SocketStatus},
+    metrics::metrics,
+    middleware::KrakenClientExt,
+    orders::{
+        dead_man_switch::{DeadManSwitch, DeadManSwitchStatus},
+        trading_gate::{ExchangeMode, TradingGate},
+    },
+    services::market_data::MarketData,
+    utils::config::HealthConfig,
+};
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::{sync::Mutex, task::JoinHandle};
+use tracing::{debug, info, warn};
+
+/// Outcome of the last check of the exchange
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExchangeHealth {
+    pub reachable: bool,
+    /// Unix time of the last check
+    pub checked_at: Option<f64>,
+    /// Round trip of the server time request, in milliseconds
+    pub latency_ms: Option<f64>,
+    /// Exchange clock minus local clock, in milliseconds
+    pub clock_drift_ms: Option<f64>,
+    pub system_status: Option<ExchangeMode>,
+    pub error: Option<String>,
+}
+
+/// Whether new orders are let through, and under which exchange mode
+#[derive(Debug, Clone, Serialize)]
+pub struct TradingState {
+    pub accepts_orders: bool,
+    pub mode: Option<ExchangeMode>,
+}
+
+/// Everything `/healthz` and `/readyz` report
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub ready: bool,
+    /// Why the process is not ready, empty when it is
+    pub problems: Vec<String>,
+    pub exchange: ExchangeHealth,
+    pub trading: Option<TradingState>,
+    pub clock: Option<ClockEstimate>,
+    pub circuits: Option<BreakerReport>,
+    pub websocket: Option<SocketState>,
+    pub private_websocket: Option<SocketState>,
+    pub dead_man_switch: Option<DeadManSwitchStatus>,
+}
+
+/// Last time each background loop went round, so a wedged loop can be told from a live one.
+///
+/// A loop is stalled once it has missed three beats, and never less than a minute after its
+/// last one, so that a slow round of requests is not mistaken for a hang.
+#[derive(Debug, Default)]
+pub struct Watchdog {
+    loops: std::sync::Mutex<HashMap<&'static str, Beat>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Beat {
+    at: Instant,
+    max_silence: Duration,
+}
+
+impl Watchdog {
+    const MIN_SILENCE: Duration = Duration::from_secs(60);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect `name` to beat every `interval` from now on
+    pub fn watch(&self, name: &'static str, interval: Duration) {
+        let beat = Beat {
+            at: Instant::now(),
+            max_silence: (interval * 3).max(Self::MIN_SILENCE),
+        };
+        self.loops.lock().unwrap().insert(name, beat);
+    }
+
+    /// Record that `name` went round once
+    pub fn beat(&self, name: &'static str) {
+        if let Some(beat) = self.loops.lock().unwrap().get_mut(name) {
+            beat.at = Instant::now();
+        }
+    }
+
+    /// Watched loops that have been silent for too long, by name
+    pub fn stalled(&self) -> Vec<&'static str> {
+        let mut stalled: Vec<_> = self
+            .loops
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, beat)| beat.at.elapsed() > beat.max_silence)
+            .map(|(name, _)| *name)
+            .collect();
+        stalled.sort_unstable();
+        stalled
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.stalled().is_empty()
+    }
+}
+
+/// Checks Kraken reachability, clock drift and system status in the background.
+///
+/// The system status is handed to the trading gate, so new orders stop or are downgraded while
+/// the exchange is in `maintenance`, `cancel_only`, `limit_only` or `post_only` mode, and resume
+/// once it is `online` again.
+pub struct HealthMonitor {
+    config: HealthConfig,
+    market: MarketData,
+    gate: Option<Arc<TradingGate>>,
+    clock_sync: Option<Arc<ClockSync>>,
+    breakers: Option<Arc<CircuitBreakers>>,
+    websocket: Option<Arc<SocketStatus>>,
+    private_websocket: Option<Arc<SocketStatus>>,
+    dead_man_switch: Option<Arc<DeadManSwitch>>,
+    watchdog: Option<Arc<Watchdog>>,
+    exchange: Mutex<ExchangeHealth>,
+}
+
+impl HealthMonitor {
+    pub fn new(config: HealthConfig) -> Self {
+        Self {
+            config,
+            market: MarketData::new(),
+            gate: None,
+            clock_sync: None,
+            breakers: None,
+            websocket: None,
+            private_websocket: None,
+            dead_man_switch: None,
+            watchdog: None,
+            exchange: Mutex::new(ExchangeHealth::default()),
+        }
+    }
+
+    /// Apply the system status to `gate` after every check
+    pub fn with_trading_gate(mut self, gate: Arc<TradingGate>) -> Self {
+        self.gate = Some(gate);
+        self
+    }
+
+    /// Feed every server time read to `sync` and report its offset as the clock drift
+    pub fn with_clock_sync(mut self, sync: Arc<ClockSync>) -> Self {
+        self.clock_sync = Some(sync);
+        self
+    }
+
+    /// Report the circuits and the error budget of the client, and require every circuit to be
+    /// closed or probing
+    pub fn with_circuit_breakers(mut self, breakers: Arc<CircuitBreakers>) -> Self {
+        self.breakers = Some(breakers);
+        self
+    }
+
+    /// Require the market data socket to be connected
+    pub fn with_websocket(mut self, status: Arc<SocketStatus>) -> Self {
+        self.websocket = Some(status);
+        self
+    }
+
+    /// Require the account events socket to be connected
+    pub fn with_private_websocket(mut self, status: Arc<SocketStatus>) -> Self {
+        self.private_websocket = Some(status);
+        self
+    }
+
+    /// Require the dead man's switch to be armed
+    pub fn with_dead_man_switch(mut self, switch: Arc<DeadManSwitch>) -> Self {
+        self.dead_man_switch = Some(switch);
+        self
+    }
+
+    /// Beat `watchdog` after every check, and report the loops it watches that have stalled
+    pub fn with_watchdog(mut self, watchdog: Arc<Watchdog>) -> Self {
+        self.watchdog = Some(watchdog);
+        self
+    }
+
+    /// Check the exchange once
+    pub async fn check(&self, req: impl KrakenClientExt + Clone) -> ExchangeHealth {
+        let mut health = ExchangeHealth {
+            checked_at: Some(unix_time()),
+            ..ExchangeHealth::default()
+        };
+
+        let sent = unix_time();
+        match self.market.get_server_time(req.clone()).await {
+            Ok(time) => {
+                let received = unix_time();
+                health.reachable = true;
+                health.latency_ms = Some((received - sent) * 1000.0);
+                let drift_ms = match &self.clock_sync {
+                    Some(sync) => {
+                        let sample = ClockSample {
+                            sent,
+                            received,
+                            server_time: time.unixtime,
+                        };
+                        sync.record(sample).offset_ms
+                    }
+                    None => {
+                        // The server time is truncated to the second, so its midpoint is
+                        // compared to the local time halfway through the round trip
+                        let drift = time.unixtime as f64 + 0.5 - (sent + received) / 2.0;
+                        metrics().clock_drift.set(drift);
+                        drift * 1000.0
+                    }
+                };
+                if drift_ms.abs() > self.config.max_clock_drift_ms as f64 {
+                    warn!(
+                        "Clock drift of {:.0}ms exceeds {}ms",
+                        drift_ms, self.config.max_clock_drift_ms
+                    );
+                }
+                health.clock_drift_ms = Some(drift_ms);
+            }
+            Err(e) => health.error = Some(e.to_string()),
+        }
+
+        if health.reachable {
+            match self.market.get_system_status(req).await {
+                Ok(status) => match status.status.parse::<ExchangeMode>() {
+                    Ok(mode) => {
+                        health.system_status = Some(mode);
+                        if let Some(gate) = &self.gate {
+                            gate.set_mode(mode);
+                        }
+                    }
+                    Err(e) => health.error = Some(e.to_string()),
+                },
+                Err(e) => health.error = Some(e.to_string()),
+            }
+        }
+
+        metrics().exchange_up.set(health.reachable as i64);
+        *self.exchange.lock().await = health.clone();
+        health
+    }
+
+    /// The current state of every component, and what keeps the process from being ready
+    pub async fn report(&self) -> HealthReport {
+        let exchange = self.exchange.lock().await.clone();
+        let mut problems = Vec::new();
+
+        match exchange.checked_at {
+            None => problems.push("Kraken has not been checked yet".to_string()),
+            Some(at) if unix_time() - at > 3.0 * self.config.interval_secs as f64 => problems.push(
+                format!("Last check of Kraken is {:.0}s old", unix_time() - at),
+            ),
+            Some(_) => {}
+        }
+        if exchange.checked_at.is_some() && !exchange.reachable {
+            problems.push(format!(
+                "Kraken is unreachable: {}",
+                exchange.error.as_deref().unwrap_or("unknown error")
+            ));
+        }
+        let clock = self.clock_sync.as_ref().and_then(|sync| sync.estimate());
+        let drift = clock
+            .map(|estimate| estimate.offset_ms)
+            .or(exchange.clock_drift_ms);
+        if let Some(drift) = drift {
+            if drift.abs() > self.config.max_clock_drift_ms as f64 {
+                problems.push(format!(
+                    "Clock drift of {:.0}ms exceeds {}ms",
+                    drift, self.config.max_clock_drift_ms
+                ));
+            }
+        }
+        if let Some(mode) = exchange.system_status {
+            if mode != ExchangeMode::Online {
+                problems.push(format!("Exchange is in {} mode", mode));
+            }
+        }
+
+        let circuits = self.breakers.as_ref().map(|breakers| breakers.report());
+        if let Some(report) = &circuits {
+            for circuit in &report.circuits {
+                if circuit.state == CircuitState::Open {
+                    problems.push(format!(
+                        "Circuit of {} endpoints is open for {:.0}s",
+                        circuit.class,
+                        circuit.retry_in_secs.unwrap_or_default()
+                    ));
+                }
+            }
+            let budget = &report.error_budget;
+            if budget.exhausted {
+                problems.push(format!(
+                    "Error budget exhausted: {} of the last {} calls failed",
+                    budget.failures, budget.calls
+                ));
+            }
+        }
+
+        let websocket = self.websocket.as_ref().map(|status| status.state());
+        if websocket.as_ref().is_some_and(|state| !state.connected) {
+            problems.push("Market data WebSocket is disconnected".to_string());
+        }
+        let private_websocket = self.private_websocket.as_ref().map(|status| status.state());
+        if private_websocket.as_ref().is_some_and(|state| !state.connected) {
+            problems.push("Account events WebSocket is disconnected".to_string());
+        }
+
+        let dead_man_switch = match &self.dead_man_switch {
+            Some(switch) => Some(switch.status().await),
+            None => None,
+        };
+        if dead_man_switch.as_ref().is_some_and(|status| !status.armed) {
+            problems.push("Dead man's switch is not armed".to_string());
+        }
+        if let Some(watchdog) = &self.watchdog {
+            for name in watchdog.stalled() {
+                problems.push(format!("The {} loop has stalled", name));
+            }
+        }
+
+        HealthReport {
+            ready: problems.is_empty(),
+            problems,
+            exchange,
+            trading: self.gate.as_ref().map(|gate| TradingState {
+                accepts_orders: gate.accepts_orders(),
+                mode: gate.mode(),
+            }),
+            clock,
+            circuits,
+            websocket,
+            private_websocket,
+            dead_man_switch,
+        }
+    }
+
+    /// Check the exchange every `interval_secs` in the background
+    pub fn spawn<R>(self: &Arc<Self>, req: R) -> JoinHandle<()>
+    where
+        R: KrakenClientExt + Clone + Send + 'static,
+    {
+        let monitor = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(monitor.config.interval());
+            info!(
+                "Health checks started ({}s interval)",
+                monitor.config.interval_secs
+            );
+            if let Some(watchdog) = &monitor.watchdog {
+                watchdog.watch("health check", monitor.config.interval());
+            }
+            let mut reachable = true;
+            loop {
+                ticker.tick().await;
+                let health = monitor.check(req.clone()).await;
+                if let Some(watchdog) = &monitor.watchdog {
+                    watchdog.beat("health check");
+                }
+                match (&health.error, health.reachable) {
+                    (Some(e), false) if reachable => warn!("Kraken is unreachable: {}", e),
+                    (Some(e), true) => warn!("Health check failed: {}", e),
+                    (None, true) if !reachable => info!("Kraken is reachable again"),
+                    _ => debug!("Health check: {:?}", health),
+                }
+                reachable = health.reachable;
+            }
+        })
+    }
+}
+
+/// Handler for `GET /healthz`: the process is up, with the state of every component
+pub async fn healthz(monitor: web::Data<HealthMonitor>) -> HttpResponse {
+    HttpResponse::Ok().json(monitor.report().await)
+}
+
+/// Handler for `GET /readyz`: 503 while anything keeps the process from trading normally
+pub async fn readyz(monitor: web::Data<HealthMonitor>) -> HttpResponse {
+    let report = monitor.report().await;
+    if report.ready {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+fn unix_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchdog() {
+        let watchdog = Watchdog::new();
+        assert!(watchdog.is_healthy());
+
+        watchdog.watch("synthetic orders", Duration::from_secs(2));
+        watchdog.beat("unwatched");
+        assert!(watchdog.is_healthy());
+
+        watchdog.loops.lock().unwrap().get_mut("synthetic orders").unwrap().at -=
+            Duration::from_secs(61);
+        assert_eq!(watchdog.stalled(), vec!["synthetic orders"]);
+        watchdog.beat("synthetic orders");
+        assert!(watchdog.is_healthy());
+    }
+}
@@ -0,0 +1,76 @@
+//! Technical indicators over OHLC candles.
+//!
+//! Every indicator is updated one candle at a time in constant (amortized) time, so the same
+//! instance can be warmed up on history with [`Indicator::batch`] and then fed live candles.
+
+pub mod momentum;
+pub mod moving_average;
+pub mod trend;
+pub mod volatility;
+pub mod volume;
+mod window;
+
+pub use momentum::{Macd, MacdOutput, Rsi, Stochastic, StochasticOutput};
+pub use moving_average::{Ema, Sma, Wma};
+pub use trend::{Adx, AdxOutput, Ichimoku, IchimokuOutput};
+pub use volatility::{Atr, BandsOutput, BollingerBands, Donchian};
+pub use volume::{Obv, Vwap};
+
+use crate::{
+    errors::Error,
+    models::market::{Candle, OHLCData},
+};
+
+/// A technical indicator fed one closed candle at a time
+pub trait Indicator {
+    type Output;
+
+    /// Feed the next candle, returning a value once enough candles have been seen
+    fn update(&mut self, candle: &Candle) -> Option<Self::Output>;
+
+    /// Forget every candle seen so far
+    fn reset(&mut self);
+
+    /// Feed a series of candles, oldest first, returning the value after each of them.
+    ///
+    /// Nothing is fed if any candle has a value that does not parse.
+    fn batch(&mut self, candles: &[OHLCData]) -> Result<Vec<Option<Self::Output>>, Error> {
+        let candles = candles
+            .iter()
+            .map(Candle::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(candles.iter().map(|candle| self.update(candle)).collect())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_support {
+    use crate::models::market::Candle;
+
+    /// Candle with the same open, high, low and close
+    pub fn flat(time: i64, price: f64) -> Candle {
+        candle(time, price, price, price, 1.0)
+    }
+
+    pub fn candle(time: i64, high: f64, low: f64, close: f64, volume: f64) -> Candle {
+        Candle {
+            time,
+            open: close,
+            high,
+            low,
+            close,
+            vwap: close,
+            volume,
+            count: 1,
+        }
+    }
+
+    pub fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+}
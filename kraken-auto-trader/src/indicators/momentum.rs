@@ -0,0 +1,208 @@
+use super::{
+    moving_average::{Ema, Sma},
+    window::RollingExtreme,
+    Indicator,
+};
+use crate::models::market::Candle;
+use serde::Serialize;
+
+/// Relative strength index with Wilder's smoothing
+#[derive(Debug, Clone)]
+pub struct Rsi {
+    period: usize,
+    previous: Option<f64>,
+    gain: Ema,
+    loss: Ema,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period,
+            previous: None,
+            gain: Ema::wilder(period),
+            loss: Ema::wilder(period),
+        }
+    }
+
+    /// Feed the next value
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        let previous = self.previous.replace(value)?;
+        let change = value - previous;
+        let gain = self.gain.next(change.max(0.0));
+        let loss = self.loss.next((-change).max(0.0));
+        let (gain, loss) = (gain?, loss?);
+        if loss == 0.0 {
+            return Some(if gain == 0.0 { 50.0 } else { 100.0 });
+        }
+        Some(100.0 - 100.0 / (1.0 + gain / loss))
+    }
+}
+
+impl Indicator for Rsi {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) -> Option<f64> {
+        self.next(candle.close)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.period);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MacdOutput {
+    pub macd: f64,
+    pub signal: f64,
+    pub histogram: f64,
+}
+
+/// Moving average convergence divergence
+#[derive(Debug, Clone)]
+pub struct Macd {
+    periods: (usize, usize, usize),
+    fast: Ema,
+    slow: Ema,
+    signal: Ema,
+}
+
+impl Default for Macd {
+    /// The usual 12, 26, 9 configuration
+    fn default() -> Self {
+        Self::new(12, 26, 9)
+    }
+}
+
+impl Macd {
+    pub fn new(fast: usize, slow: usize, signal: usize) -> Self {
+        Self {
+            periods: (fast, slow, signal),
+            fast: Ema::new(fast),
+            slow: Ema::new(slow),
+            signal: Ema::new(signal),
+        }
+    }
+
+    /// Feed the next value
+    pub fn next(&mut self, value: f64) -> Option<MacdOutput> {
+        let fast = self.fast.next(value);
+        let slow = self.slow.next(value);
+        let macd = fast? - slow?;
+        let signal = self.signal.next(macd)?;
+        Some(MacdOutput {
+            macd,
+            signal,
+            histogram: macd - signal,
+        })
+    }
+}
+
+impl Indicator for Macd {
+    type Output = MacdOutput;
+
+    fn update(&mut self, candle: &Candle) -> Option<MacdOutput> {
+        self.next(candle.close)
+    }
+
+    fn reset(&mut self) {
+        let (fast, slow, signal) = self.periods;
+        *self = Self::new(fast, slow, signal);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct StochasticOutput {
+    /// Position of the close within the high-low range, 0 to 100
+    pub k: f64,
+    /// Simple moving average of `k`
+    pub d: f64,
+}
+
+/// Stochastic oscillator
+#[derive(Debug, Clone)]
+pub struct Stochastic {
+    periods: (usize, usize),
+    highest: RollingExtreme,
+    lowest: RollingExtreme,
+    d: Sma,
+}
+
+impl Stochastic {
+    pub fn new(k_period: usize, d_period: usize) -> Self {
+        Self {
+            periods: (k_period, d_period),
+            highest: RollingExtreme::max(k_period),
+            lowest: RollingExtreme::min(k_period),
+            d: Sma::new(d_period),
+        }
+    }
+}
+
+impl Indicator for Stochastic {
+    type Output = StochasticOutput;
+
+    fn update(&mut self, candle: &Candle) -> Option<StochasticOutput> {
+        let highest = self.highest.push(candle.high);
+        let lowest = self.lowest.push(candle.low);
+        if !self.highest.is_full() {
+            return None;
+        }
+        let k = if highest > lowest {
+            100.0 * (candle.close - lowest) / (highest - lowest)
+        } else {
+            50.0
+        };
+        let d = self.d.next(k)?;
+        Some(StochasticOutput { k, d })
+    }
+
+    fn reset(&mut self) {
+        let (k_period, d_period) = self.periods;
+        *self = Self::new(k_period, d_period);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::test_support::{assert_close, candle};
+
+    #[test]
+    fn test_rsi() {
+        let mut rsi = Rsi::new(2);
+        assert_eq!(rsi.next(10.0), None);
+        assert_eq!(rsi.next(11.0), None);
+        // Average gain 0.5 and loss 0.5 after a +1 and a -1
+        assert_eq!(rsi.next(10.0), Some(50.0));
+        // A +2 move smooths the gain to (0.5 + 2) / 2 and the loss to 0.5 / 2
+        assert_close(rsi.next(12.0).unwrap(), 100.0 - 100.0 / (1.0 + 1.25 / 0.25));
+
+        let mut rising = Rsi::new(3);
+        let last = (0..10).filter_map(|i| rising.next(i as f64)).last();
+        assert_eq!(last, Some(100.0));
+    }
+
+    #[test]
+    fn test_macd() {
+        let mut macd = Macd::new(2, 3, 2);
+        let outputs: Vec<_> = (1..=6).map(|i| macd.next(i as f64)).collect();
+        assert!(outputs[..3].iter().all(Option::is_none));
+        // On a straight line both EMAs lag by a constant, so the signal converges to the MACD
+        let last = outputs[5].unwrap();
+        assert_close(last.macd, 0.5);
+        assert_close(last.histogram, 0.0);
+    }
+
+    #[test]
+    fn test_stochastic() {
+        let mut stochastic = Stochastic::new(3, 2);
+        assert_eq!(stochastic.update(&candle(0, 10.0, 8.0, 9.0, 1.0)), None);
+        assert_eq!(stochastic.update(&candle(1, 12.0, 9.0, 11.0, 1.0)), None);
+        // Close 11 in a 8 to 12 range gives k = 75, but d needs a second k
+        assert_eq!(stochastic.update(&candle(2, 12.0, 10.0, 11.0, 1.0)), None);
+        let output = stochastic.update(&candle(3, 12.0, 9.0, 12.0, 1.0)).unwrap();
+        assert_close(output.k, 100.0);
+        assert_close(output.d, 87.5);
+    }
+}
@@ -0,0 +1,221 @@
+use super::{window::RollingSum, Indicator};
+use crate::models::market::Candle;
+use std::collections::VecDeque;
+
+/// Simple moving average of closing prices
+#[derive(Debug, Clone)]
+pub struct Sma {
+    window: RollingSum,
+}
+
+impl Sma {
+    pub fn new(period: usize) -> Self {
+        Self {
+            window: RollingSum::new(period),
+        }
+    }
+
+    /// Feed the next value
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        self.window.push(value);
+        self.window.is_full().then(|| self.window.mean())
+    }
+}
+
+impl Indicator for Sma {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) -> Option<f64> {
+        self.next(candle.close)
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+    }
+}
+
+/// Exponential moving average of closing prices, seeded with the SMA of the first `period` values
+#[derive(Debug, Clone)]
+pub struct Ema {
+    period: usize,
+    alpha: f64,
+    count: usize,
+    seed: f64,
+    value: Option<f64>,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Self {
+        let period = period.max(1);
+        Self::with_alpha(period, 2.0 / (period as f64 + 1.0))
+    }
+
+    /// EMA with an explicit smoothing factor, e.g. `1 / period` for Wilder's smoothing
+    pub fn with_alpha(period: usize, alpha: f64) -> Self {
+        Self {
+            period: period.max(1),
+            alpha,
+            count: 0,
+            seed: 0.0,
+            value: None,
+        }
+    }
+
+    /// Wilder's smoothed moving average, as used by RSI, ATR and ADX
+    pub fn wilder(period: usize) -> Self {
+        let period = period.max(1);
+        Self::with_alpha(period, 1.0 / period as f64)
+    }
+
+    /// Feed the next value
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        match self.value {
+            Some(previous) => {
+                let current = previous + self.alpha * (value - previous);
+                self.value = Some(current);
+            }
+            None => {
+                self.count += 1;
+                self.seed += value;
+                if self.count == self.period {
+                    self.value = Some(self.seed / self.period as f64);
+                }
+            }
+        }
+        self.value
+    }
+
+    /// Current value, if warmed up
+    pub fn value(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+impl Indicator for Ema {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) -> Option<f64> {
+        self.next(candle.close)
+    }
+
+    fn reset(&mut self) {
+        self.count = 0;
+        self.seed = 0.0;
+        self.value = None;
+    }
+}
+
+/// Linearly weighted moving average of closing prices, the newest value weighing `period`
+#[derive(Debug, Clone)]
+pub struct Wma {
+    period: usize,
+    values: VecDeque<f64>,
+    sum: f64,
+    weighted_sum: f64,
+}
+
+impl Wma {
+    pub fn new(period: usize) -> Self {
+        let period = period.max(1);
+        Self {
+            period,
+            values: VecDeque::with_capacity(period),
+            sum: 0.0,
+            weighted_sum: 0.0,
+        }
+    }
+
+    /// Feed the next value
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        if self.values.len() == self.period {
+            // Every weight drops by one, the oldest value's reaching zero
+            self.weighted_sum -= self.sum;
+            self.sum -= self.values.pop_front().unwrap_or_default();
+        }
+        self.values.push_back(value);
+        self.sum += value;
+        self.weighted_sum += self.values.len() as f64 * value;
+
+        let n = self.period as f64;
+        (self.values.len() == self.period).then(|| self.weighted_sum / (n * (n + 1.0) / 2.0))
+    }
+}
+
+impl Indicator for Wma {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) -> Option<f64> {
+        self.next(candle.close)
+    }
+
+    fn reset(&mut self) {
+        self.values.clear();
+        self.sum = 0.0;
+        self.weighted_sum = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::test_support::{assert_close, flat};
+
+    #[test]
+    fn test_sma() {
+        let mut sma = Sma::new(3);
+        let values: Vec<Option<f64>> = [1.0, 2.0, 3.0, 4.0].iter().map(|v| sma.next(*v)).collect();
+        assert_eq!(values, vec![None, None, Some(2.0), Some(3.0)]);
+    }
+
+    #[test]
+    fn test_ema() {
+        let mut ema = Ema::new(3);
+        assert_eq!(ema.next(2.0), None);
+        assert_eq!(ema.next(4.0), None);
+        assert_eq!(ema.next(6.0), Some(4.0));
+        assert_eq!(ema.next(8.0), Some(6.0));
+    }
+
+    #[test]
+    fn test_wma() {
+        let mut wma = Wma::new(3);
+        wma.next(1.0);
+        wma.next(2.0);
+        assert_close(wma.next(3.0).unwrap(), (1.0 + 4.0 + 9.0) / 6.0);
+        assert_close(wma.next(6.0).unwrap(), (2.0 + 6.0 + 18.0) / 6.0);
+    }
+
+    #[test]
+    fn test_batch_matches_incremental() {
+        let candles: Vec<_> = (0..20)
+            .map(|i| crate::models::market::OHLCData {
+                time: i,
+                open: "0".into(),
+                high: "0".into(),
+                low: "0".into(),
+                close: ((i * 7 % 11) as f64).to_string(),
+                vwap: "0".into(),
+                volume: "1".into(),
+                count: 1,
+            })
+            .collect();
+        let batch = Wma::new(5).batch(&candles).unwrap();
+
+        let mut wma = Wma::new(5);
+        for (data, expected) in candles.iter().zip(batch) {
+            assert_eq!(
+                wma.update(&flat(data.time, data.close.parse().unwrap())),
+                expected
+            );
+        }
+        wma.reset();
+        assert_eq!(wma.next(1.0), None);
+
+        let mut corrupt = candles.clone();
+        corrupt[3].close = "n/a".into();
+        assert!(matches!(
+            Wma::new(5).batch(&corrupt),
+            Err(crate::errors::Error::InvalidResponse(_))
+        ));
+    }
+}
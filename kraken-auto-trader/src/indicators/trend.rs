@@ -0,0 +1,194 @@
+use super::{moving_average::Ema, volatility::true_range, window::RollingExtreme, Indicator};
+use crate::models::market::Candle;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct AdxOutput {
+    pub adx: f64,
+    pub plus_di: f64,
+    pub minus_di: f64,
+}
+
+/// Average directional index with its +DI and -DI lines
+#[derive(Debug, Clone)]
+pub struct Adx {
+    period: usize,
+    previous: Option<Candle>,
+    true_range: Ema,
+    plus_dm: Ema,
+    minus_dm: Ema,
+    adx: Ema,
+}
+
+impl Adx {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period,
+            previous: None,
+            true_range: Ema::wilder(period),
+            plus_dm: Ema::wilder(period),
+            minus_dm: Ema::wilder(period),
+            adx: Ema::wilder(period),
+        }
+    }
+}
+
+impl Indicator for Adx {
+    type Output = AdxOutput;
+
+    fn update(&mut self, candle: &Candle) -> Option<AdxOutput> {
+        let previous = self.previous.replace(*candle)?;
+        let up = candle.high - previous.high;
+        let down = previous.low - candle.low;
+        let plus_dm = if up > down && up > 0.0 { up } else { 0.0 };
+        let minus_dm = if down > up && down > 0.0 { down } else { 0.0 };
+
+        let range = self
+            .true_range
+            .next(true_range(candle, Some(previous.close)));
+        let plus_dm = self.plus_dm.next(plus_dm);
+        let minus_dm = self.minus_dm.next(minus_dm);
+        let (range, plus_dm, minus_dm) = (range?, plus_dm?, minus_dm?);
+
+        let (plus_di, minus_di) = if range > 0.0 {
+            (100.0 * plus_dm / range, 100.0 * minus_dm / range)
+        } else {
+            (0.0, 0.0)
+        };
+        let total = plus_di + minus_di;
+        let dx = if total > 0.0 {
+            100.0 * (plus_di - minus_di).abs() / total
+        } else {
+            0.0
+        };
+        let adx = self.adx.next(dx)?;
+        Some(AdxOutput {
+            adx,
+            plus_di,
+            minus_di,
+        })
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.period);
+    }
+}
+
+/// Ichimoku lines as computed on the latest candle.
+///
+/// The leading spans are plotted `displacement` candles ahead and the lagging span
+/// `displacement` candles behind; shifting them is left to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct IchimokuOutput {
+    pub tenkan_sen: f64,
+    pub kijun_sen: f64,
+    pub senkou_span_a: f64,
+    pub senkou_span_b: f64,
+    pub chikou_span: f64,
+}
+
+/// Ichimoku cloud
+#[derive(Debug, Clone)]
+pub struct Ichimoku {
+    periods: (usize, usize, usize),
+    displacement: usize,
+    conversion: (RollingExtreme, RollingExtreme),
+    base: (RollingExtreme, RollingExtreme),
+    span_b: (RollingExtreme, RollingExtreme),
+}
+
+impl Default for Ichimoku {
+    /// The usual 9, 26, 52 periods with a displacement of 26
+    fn default() -> Self {
+        Self::new(9, 26, 52, 26)
+    }
+}
+
+fn channel(period: usize) -> (RollingExtreme, RollingExtreme) {
+    (RollingExtreme::max(period), RollingExtreme::min(period))
+}
+
+fn midpoint(channel: &mut (RollingExtreme, RollingExtreme), candle: &Candle) -> f64 {
+    (channel.0.push(candle.high) + channel.1.push(candle.low)) / 2.0
+}
+
+impl Ichimoku {
+    pub fn new(conversion: usize, base: usize, span_b: usize, displacement: usize) -> Self {
+        Self {
+            periods: (conversion, base, span_b),
+            displacement,
+            conversion: channel(conversion),
+            base: channel(base),
+            span_b: channel(span_b),
+        }
+    }
+
+    /// Number of candles the spans are shifted by when plotted
+    pub fn displacement(&self) -> usize {
+        self.displacement
+    }
+}
+
+impl Indicator for Ichimoku {
+    type Output = IchimokuOutput;
+
+    fn update(&mut self, candle: &Candle) -> Option<IchimokuOutput> {
+        let tenkan_sen = midpoint(&mut self.conversion, candle);
+        let kijun_sen = midpoint(&mut self.base, candle);
+        let senkou_span_b = midpoint(&mut self.span_b, candle);
+        let ready = self.conversion.0.is_full() && self.base.0.is_full() && self.span_b.0.is_full();
+        ready.then(|| IchimokuOutput {
+            tenkan_sen,
+            kijun_sen,
+            senkou_span_a: (tenkan_sen + kijun_sen) / 2.0,
+            senkou_span_b,
+            chikou_span: candle.close,
+        })
+    }
+
+    fn reset(&mut self) {
+        let (conversion, base, span_b) = self.periods;
+        *self = Self::new(conversion, base, span_b, self.displacement);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::test_support::{assert_close, candle};
+
+    #[test]
+    fn test_adx_trending_market() {
+        let mut adx = Adx::new(3);
+        let outputs: Vec<_> = (0..20)
+            .map(|i| {
+                let price = 100.0 + i as f64;
+                adx.update(&candle(i, price + 0.5, price - 0.5, price, 1.0))
+            })
+            .collect();
+        // The first candle only sets the reference, DI needs 3 moves and ADX 3 DX readings
+        assert!(outputs[..5].iter().all(Option::is_none));
+        assert!(outputs[5].is_some());
+        let last = outputs[19].unwrap();
+        assert_close(last.minus_di, 0.0);
+        assert!(last.plus_di > 0.0);
+        assert_close(last.adx, 100.0);
+    }
+
+    #[test]
+    fn test_ichimoku() {
+        let mut ichimoku = Ichimoku::new(2, 3, 4, 3);
+        let candles: Vec<_> = (0..4)
+            .map(|i| candle(i, 10.0 + i as f64, 8.0 + i as f64, 9.0 + i as f64, 1.0))
+            .collect();
+        let outputs: Vec<_> = candles.iter().map(|c| ichimoku.update(c)).collect();
+        assert!(outputs[2].is_none());
+        let last = outputs[3].unwrap();
+        // Highs 10..13, lows 8..11
+        assert_close(last.tenkan_sen, (13.0 + 10.0) / 2.0);
+        assert_close(last.kijun_sen, (13.0 + 9.0) / 2.0);
+        assert_close(last.senkou_span_a, (11.5 + 11.0) / 2.0);
+        assert_close(last.senkou_span_b, (13.0 + 8.0) / 2.0);
+        assert_eq!(last.chikou_span, 12.0);
+    }
+}
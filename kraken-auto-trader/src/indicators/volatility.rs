@@ -0,0 +1,183 @@
+use super::{
+    moving_average::Ema,
+    window::{RollingExtreme, RollingSum},
+    Indicator,
+};
+use crate::models::market::Candle;
+use serde::Serialize;
+
+/// Upper, middle and lower lines of a channel
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BandsOutput {
+    pub upper: f64,
+    pub middle: f64,
+    pub lower: f64,
+}
+
+/// Bollinger bands: SMA of closes plus and minus a multiple of their standard deviation
+#[derive(Debug, Clone)]
+pub struct BollingerBands {
+    window: RollingSum,
+    multiplier: f64,
+}
+
+impl Default for BollingerBands {
+    /// The usual 20 periods and 2 standard deviations
+    fn default() -> Self {
+        Self::new(20, 2.0)
+    }
+}
+
+impl BollingerBands {
+    pub fn new(period: usize, multiplier: f64) -> Self {
+        Self {
+            window: RollingSum::new(period),
+            multiplier,
+        }
+    }
+
+    /// Feed the next value
+    pub fn next(&mut self, value: f64) -> Option<BandsOutput> {
+        self.window.push(value);
+        if !self.window.is_full() {
+            return None;
+        }
+        let middle = self.window.mean();
+        let width = self.multiplier * self.window.std_dev();
+        Some(BandsOutput {
+            upper: middle + width,
+            middle,
+            lower: middle - width,
+        })
+    }
+}
+
+impl Indicator for BollingerBands {
+    type Output = BandsOutput;
+
+    fn update(&mut self, candle: &Candle) -> Option<BandsOutput> {
+        self.next(candle.close)
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+    }
+}
+
+/// Range of a candle including any gap from the previous close
+pub(crate) fn true_range(candle: &Candle, previous_close: Option<f64>) -> f64 {
+    match previous_close {
+        Some(close) => candle.high.max(close) - candle.low.min(close),
+        None => candle.high - candle.low,
+    }
+}
+
+/// Average true range with Wilder's smoothing
+#[derive(Debug, Clone)]
+pub struct Atr {
+    period: usize,
+    previous_close: Option<f64>,
+    average: Ema,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period,
+            previous_close: None,
+            average: Ema::wilder(period),
+        }
+    }
+}
+
+impl Indicator for Atr {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) -> Option<f64> {
+        let range = true_range(candle, self.previous_close);
+        self.previous_close = Some(candle.close);
+        self.average.next(range)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.period);
+    }
+}
+
+/// Donchian channel: highest high and lowest low of the last `period` candles
+#[derive(Debug, Clone)]
+pub struct Donchian {
+    highest: RollingExtreme,
+    lowest: RollingExtreme,
+}
+
+impl Donchian {
+    pub fn new(period: usize) -> Self {
+        Self {
+            highest: RollingExtreme::max(period),
+            lowest: RollingExtreme::min(period),
+        }
+    }
+}
+
+impl Indicator for Donchian {
+    type Output = BandsOutput;
+
+    fn update(&mut self, candle: &Candle) -> Option<BandsOutput> {
+        let upper = self.highest.push(candle.high);
+        let lower = self.lowest.push(candle.low);
+        self.highest.is_full().then(|| BandsOutput {
+            upper,
+            middle: (upper + lower) / 2.0,
+            lower,
+        })
+    }
+
+    fn reset(&mut self) {
+        self.highest.clear();
+        self.lowest.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::test_support::{assert_close, candle};
+
+    #[test]
+    fn test_bollinger_bands() {
+        let mut bands = BollingerBands::new(4, 2.0);
+        let outputs: Vec<_> = [2.0, 4.0, 4.0, 6.0]
+            .iter()
+            .map(|v| bands.next(*v))
+            .collect();
+        // Mean 4, population standard deviation sqrt(2)
+        let last = outputs[3].unwrap();
+        assert_close(last.middle, 4.0);
+        assert_close(last.upper, 4.0 + 2.0 * 2f64.sqrt());
+        assert_close(last.lower, 4.0 - 2.0 * 2f64.sqrt());
+        assert!(outputs[2].is_none());
+    }
+
+    #[test]
+    fn test_atr_includes_gaps() {
+        let mut atr = Atr::new(2);
+        assert_eq!(atr.update(&candle(0, 11.0, 9.0, 10.0, 1.0)), None);
+        // Gap up from 10 to a 14-15 range: true range 5
+        assert_eq!(atr.update(&candle(1, 15.0, 14.0, 15.0, 1.0)), Some(3.5));
+        assert_eq!(atr.update(&candle(2, 16.0, 15.0, 15.5, 1.0)), Some(2.25));
+    }
+
+    #[test]
+    fn test_donchian() {
+        let mut donchian = Donchian::new(2);
+        assert_eq!(donchian.update(&candle(0, 11.0, 9.0, 10.0, 1.0)), None);
+        let output = donchian.update(&candle(1, 12.0, 10.0, 11.0, 1.0)).unwrap();
+        assert_eq!(
+            (output.upper, output.middle, output.lower),
+            (12.0, 10.5, 9.0)
+        );
+        let output = donchian.update(&candle(2, 11.0, 10.5, 11.0, 1.0)).unwrap();
+        assert_eq!(output.lower, 10.0);
+    }
+}
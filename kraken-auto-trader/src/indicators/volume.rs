@@ -0,0 +1,109 @@
+use super::Indicator;
+use crate::models::market::Candle;
+
+/// On-balance volume: cumulative volume signed by the direction of the close
+#[derive(Debug, Clone, Default)]
+pub struct Obv {
+    previous_close: Option<f64>,
+    value: f64,
+}
+
+impl Obv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Indicator for Obv {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) -> Option<f64> {
+        if let Some(previous) = self.previous_close {
+            if candle.close > previous {
+                self.value += candle.volume;
+            } else if candle.close < previous {
+                self.value -= candle.volume;
+            }
+        }
+        self.previous_close = Some(candle.close);
+        Some(self.value)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Volume weighted average of the typical price `(high + low + close) / 3`, optionally
+/// restarting at the beginning of every session
+#[derive(Debug, Clone, Default)]
+pub struct Vwap {
+    session_secs: Option<i64>,
+    session: Option<i64>,
+    price_volume: f64,
+    volume: f64,
+}
+
+impl Vwap {
+    /// VWAP over every candle since the last reset
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// VWAP restarting every `session_secs`, e.g. 86400 for a daily VWAP (UTC)
+    pub fn with_session(session_secs: i64) -> Self {
+        Self {
+            session_secs: Some(session_secs.max(1)),
+            ..Self::default()
+        }
+    }
+}
+
+impl Indicator for Vwap {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) -> Option<f64> {
+        if let Some(session_secs) = self.session_secs {
+            let session = candle.time.div_euclid(session_secs);
+            if self.session.replace(session) != Some(session) {
+                self.price_volume = 0.0;
+                self.volume = 0.0;
+            }
+        }
+        let typical = (candle.high + candle.low + candle.close) / 3.0;
+        self.price_volume += typical * candle.volume;
+        self.volume += candle.volume;
+        (self.volume > 0.0).then(|| self.price_volume / self.volume)
+    }
+
+    fn reset(&mut self) {
+        *self = Self {
+            session_secs: self.session_secs,
+            ..Self::default()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::test_support::candle;
+
+    #[test]
+    fn test_obv() {
+        let mut obv = Obv::new();
+        assert_eq!(obv.update(&candle(0, 10.0, 10.0, 10.0, 5.0)), Some(0.0));
+        assert_eq!(obv.update(&candle(1, 11.0, 11.0, 11.0, 3.0)), Some(3.0));
+        assert_eq!(obv.update(&candle(2, 9.0, 9.0, 9.0, 4.0)), Some(-1.0));
+        assert_eq!(obv.update(&candle(3, 9.0, 9.0, 9.0, 4.0)), Some(-1.0));
+    }
+
+    #[test]
+    fn test_vwap_sessions() {
+        let mut vwap = Vwap::with_session(100);
+        assert_eq!(vwap.update(&candle(0, 10.0, 10.0, 10.0, 1.0)), Some(10.0));
+        assert_eq!(vwap.update(&candle(50, 20.0, 20.0, 20.0, 3.0)), Some(17.5));
+        // A new session starts from scratch
+        assert_eq!(vwap.update(&candle(100, 30.0, 30.0, 30.0, 1.0)), Some(30.0));
+    }
+}
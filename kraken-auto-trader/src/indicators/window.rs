@@ -0,0 +1,166 @@
+use std::collections::VecDeque;
+
+/// The last `period` values with their running mean and sum of squared deviations.
+///
+/// Both are updated with Welford's algorithm as values enter and leave the window, which stays
+/// accurate over long runs where a running sum of squares would drift.
+#[derive(Debug, Clone)]
+pub(crate) struct RollingSum {
+    period: usize,
+    values: VecDeque<f64>,
+    mean: f64,
+    m2: f64,
+}
+
+impl RollingSum {
+    pub fn new(period: usize) -> Self {
+        let period = period.max(1);
+        Self {
+            period,
+            values: VecDeque::with_capacity(period + 1),
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    /// Add a value, returning the one that left the window
+    pub fn push(&mut self, value: f64) -> Option<f64> {
+        self.values.push_back(value);
+        let n = self.values.len() as f64;
+        let delta = value - self.mean;
+        self.mean += delta / n;
+        self.m2 += delta * (value - self.mean);
+        if self.values.len() > self.period {
+            let evicted = self.values.pop_front()?;
+            let n = self.values.len() as f64;
+            let delta = evicted - self.mean;
+            self.mean -= delta / n;
+            self.m2 = (self.m2 - delta * (evicted - self.mean)).max(0.0);
+            return Some(evicted);
+        }
+        None
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.values.len() == self.period
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Population standard deviation of the window
+    pub fn std_dev(&self) -> f64 {
+        (self.m2 / self.values.len().max(1) as f64).sqrt()
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.mean = 0.0;
+        self.m2 = 0.0;
+    }
+}
+
+/// Highest or lowest of the last `period` values, kept in a monotonic deque
+#[derive(Debug, Clone)]
+pub(crate) struct RollingExtreme {
+    period: usize,
+    highest: bool,
+    index: usize,
+    deque: VecDeque<(usize, f64)>,
+}
+
+impl RollingExtreme {
+    pub fn max(period: usize) -> Self {
+        Self::new(period, true)
+    }
+
+    pub fn min(period: usize) -> Self {
+        Self::new(period, false)
+    }
+
+    fn new(period: usize, highest: bool) -> Self {
+        Self {
+            period: period.max(1),
+            highest,
+            index: 0,
+            deque: VecDeque::new(),
+        }
+    }
+
+    /// Add a value, returning the extreme of the window
+    pub fn push(&mut self, value: f64) -> f64 {
+        while let Some(&(_, last)) = self.deque.back() {
+            let dominated = if self.highest {
+                last <= value
+            } else {
+                last >= value
+            };
+            if !dominated {
+                break;
+            }
+            self.deque.pop_back();
+        }
+        self.deque.push_back((self.index, value));
+        self.index += 1;
+        while let Some(&(index, _)) = self.deque.front() {
+            if index + self.period >= self.index {
+                break;
+            }
+            self.deque.pop_front();
+        }
+        self.deque.front().map(|&(_, value)| value).unwrap_or(value)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.index >= self.period
+    }
+
+    pub fn clear(&mut self) {
+        self.index = 0;
+        self.deque.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_sum() {
+        let mut window = RollingSum::new(3);
+        assert_eq!(window.push(1.0), None);
+        window.push(2.0);
+        window.push(3.0);
+        assert!(window.is_full());
+        assert_eq!(window.push(4.0), Some(1.0));
+        assert_eq!(window.mean(), 3.0);
+        assert!((window.std_dev() - (2.0f64 / 3.0).sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_rolling_sum_does_not_drift() {
+        // Large prices with small moves, where a running sum of squares loses the variance
+        let mut window = RollingSum::new(20);
+        for i in 0..1_000_000 {
+            window.push(1e8 + (i % 7) as f64 * 0.01);
+        }
+        let values: Vec<f64> = window.values.iter().copied().collect();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance =
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+        assert!((window.mean() - mean).abs() < 1e-6);
+        assert!((window.std_dev() - variance.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_rolling_extreme() {
+        let mut max = RollingExtreme::max(3);
+        let mut min = RollingExtreme::min(3);
+        let values = [5.0, 1.0, 3.0, 2.0, 4.0, 0.0];
+        let maxes: Vec<f64> = values.iter().map(|v| max.push(*v)).collect();
+        let mins: Vec<f64> = values.iter().map(|v| min.push(*v)).collect();
+        assert_eq!(maxes, vec![5.0, 5.0, 5.0, 3.0, 4.0, 4.0]);
+        assert_eq!(mins, vec![5.0, 1.0, 1.0, 1.0, 2.0, 0.0]);
+    }
+}
@@ -0,0 +1,464 @@
+use crate::{
+    errors::Error,
+    middleware::KrakenClientExt,
+    models::{
+        market::{Asset, AssetPair},
+        symbols::{AssetId, Pair},
+        trading::{NewOrder, OrderSide, OrderType},
+    },
+    services::market_data::MarketData,
+};
+
+use super::symbols::SymbolResolver;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+use tracing::info;
+
+/// Trading status of an asset pair
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PairStatus {
+    Online,
+    CancelOnly,
+    PostOnly,
+    LimitOnly,
+    ReduceOnly,
+    Other(String),
+}
+
+impl From<&str> for PairStatus {
+    fn from(status: &str) -> Self {
+        match status {
+            "online" => PairStatus::Online,
+            "cancel_only" => PairStatus::CancelOnly,
+            "post_only" => PairStatus::PostOnly,
+            "limit_only" => PairStatus::LimitOnly,
+            "reduce_only" => PairStatus::ReduceOnly,
+            other => PairStatus::Other(other.to_string()),
+        }
+    }
+}
+
+/// Trading rules of an asset pair, parsed from its `AssetPair` metadata
+#[derive(Debug, Clone, Serialize)]
+pub struct Instrument {
+    pub pair: Pair,
+    pub altname: String,
+    pub wsname: Option<String>,
+    pub base: AssetId,
+    pub quote: AssetId,
+    pub pair_decimals: usize,
+    pub lot_decimals: usize,
+    pub cost_decimals: usize,
+    pub ordermin: f64,
+    pub costmin: f64,
+    pub tick_size: f64,
+    pub status: PairStatus,
+    pub leverage_buy: Vec<i32>,
+    pub leverage_sell: Vec<i32>,
+}
+
+impl Instrument {
+    pub fn from_asset_pair(pair: &Pair, info: &AssetPair) -> Self {
+        let pair_decimals = info.pair_decimals.max(0) as usize;
+        Self {
+            pair: pair.clone(),
+            altname: info.altname.clone(),
+            wsname: info.wsname.clone(),
+            base: info.base.clone(),
+            quote: info.quote.clone(),
+            pair_decimals,
+            lot_decimals: info.lot_decimals.max(0) as usize,
+            cost_decimals: info.cost_decimals.max(0) as usize,
+            ordermin: info.ordermin.parse().unwrap_or(0.0),
+            costmin: info.costmin.parse().unwrap_or(0.0),
+            // Older pairs don't publish a tick size, their price precision is the tick
+            tick_size: info
+                .tick_size
+                .parse()
+                .unwrap_or_else(|_| 10f64.powi(-(pair_decimals as i32))),
+            status: PairStatus::from(info.status.as_str()),
+            leverage_buy: info.leverage_buy.clone(),
+            leverage_sell: info.leverage_sell.clone(),
+        }
+    }
+
+    /// Round a price to a tick, down for buys and up for sells, so that an off-tick limit never
+    /// becomes a worse price than the one asked for
+    pub fn round_price(&self, price: f64, side: OrderSide) -> String {
+        let price = if self.tick_size > 0.0 {
+            // Nudge before rounding so that prices already on a tick stay there
+            let ticks = price / self.tick_size;
+            let ticks = match side {
+                OrderSide::Buy => (ticks + 1e-9).floor(),
+                OrderSide::Sell => (ticks - 1e-9).ceil(),
+            };
+            ticks * self.tick_size
+        } else {
+            price
+        };
+        format!("{:.*}", self.pair_decimals, price)
+    }
+
+    /// Round a volume down to the lot precision
+    pub fn round_volume(&self, volume: f64) -> String {
+        let scale = 10f64.powi(self.lot_decimals as i32);
+        // Nudge up before flooring so that values like 0.3 don't become 0.29999
+        let volume = ((volume * scale) + 1e-9).floor() / scale;
+        format!("{:.*}", self.lot_decimals, volume)
+    }
+
+    /// Check an order against the pair's trading rules and return a copy with its price rounded
+    /// to the tick size and its volume to the lot precision
+    pub fn validate(&self, order: &NewOrder) -> Result<NewOrder, Error> {
+        let mut normalized = order.clone();
+        normalized.pair = self.pair.clone();
+
+        match &self.status {
+            PairStatus::Online => {}
+            PairStatus::CancelOnly => {
+                return Err(self.invalid("only accepts cancellations".to_string()));
+            }
+            PairStatus::PostOnly => {
+                let post_only = order
+                    .oflags
+                    .as_deref()
+                    .is_some_and(|flags| flags.split(',').any(|flag| flag == "post"));
+                if order.ordertype != OrderType::Limit || !post_only {
+                    return Err(self.invalid("only accepts post-only limit orders".to_string()));
+                }
+            }
+            PairStatus::LimitOnly => {
+                if order.ordertype != OrderType::Limit {
+                    return Err(self.invalid("only accepts limit orders".to_string()));
+                }
+            }
+            PairStatus::ReduceOnly => {
+                if order.reduce_only != Some(true) {
+                    return Err(self.invalid("only accepts reduce-only orders".to_string()));
+                }
+            }
+            PairStatus::Other(status) => {
+                return Err(self.invalid(format!("is not tradable (status {})", status)));
+            }
+        }
+
+        let volume = parse_amount(&order.volume, "volume")?;
+        normalized.volume = self.round_volume(volume);
+        let volume: f64 = normalized.volume.parse().unwrap_or(0.0);
+        if volume <= 0.0 || volume < self.ordermin {
+            return Err(self.invalid(format!(
+                "requires a volume of at least {} (got {})",
+                self.ordermin, order.volume
+            )));
+        }
+
+        if let Some(price) = &order.price {
+            if let Some(value) = absolute_price(price)? {
+                normalized.price = Some(self.round_price(value, order.side));
+            }
+        }
+        if let Some(price2) = &order.price2 {
+            if let Some(value) = absolute_price(price2)? {
+                normalized.price2 = Some(self.round_price(value, order.side));
+            }
+        }
+
+        // The cost can only be checked locally when the order carries an absolute price
+        if let Some(price) = normalized
+            .price
+            .as_deref()
+            .and_then(|p| p.parse::<f64>().ok())
+        {
+            let cost = price * volume;
+            if cost < self.costmin {
+                return Err(self.invalid(format!(
+                    "requires a cost of at least {} (got {:.*})",
+                    self.costmin, self.cost_decimals, cost
+                )));
+            }
+        }
+
+        if let Some(leverage) = order.leverage.as_deref().filter(|l| *l != "none") {
+            let value: i32 = leverage
+                .split(':')
+                .next()
+                .and_then(|l| l.parse().ok())
+                .ok_or_else(|| Error::ValidationError(format!("Invalid leverage {}", leverage)))?;
+            let allowed = match order.side {
+                OrderSide::Buy => &self.leverage_buy,
+                OrderSide::Sell => &self.leverage_sell,
+            };
+            if !allowed.contains(&value) {
+                return Err(self.invalid(format!(
+                    "does not allow leverage {} for {} orders (allowed: {:?})",
+                    value,
+                    order.side.as_str(),
+                    allowed
+                )));
+            }
+        }
+
+        Ok(normalized)
+    }
+
+    fn invalid(&self, message: String) -> Error {
+        Error::ValidationError(format!("{} {}", self.altname, message))
+    }
+}
+
+fn parse_amount(value: &str, field: &str) -> Result<f64, Error> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| Error::ValidationError(format!("Invalid {} {}", field, value)))
+}
+
+/// Parse a price unless it is relative to the market (`+`, `-` or `#` prefix, `%` suffix)
+fn absolute_price(price: &str) -> Result<Option<f64>, Error> {
+    if price.starts_with(['+', '-', '#']) || price.ends_with('%') {
+        return Ok(None);
+    }
+    parse_amount(price, "price").map(Some)
+}
+
+/// Cache of Kraken instrument metadata used to validate orders before they are sent
+pub struct InstrumentRegistry {
+    instruments: RwLock<HashMap<Pair, Instrument>>,
+    assets: RwLock<HashMap<AssetId, Asset>>,
+    resolver: RwLock<SymbolResolver>,
+    loaded_at: RwLock<Option<Instant>>,
+    ttl: Duration,
+}
+
+impl Default for InstrumentRegistry {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(3600))
+    }
+}
+
+impl InstrumentRegistry {
+    /// Create an empty registry whose metadata is considered stale after `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            instruments: RwLock::new(HashMap::new()),
+            assets: RwLock::new(HashMap::new()),
+            resolver: RwLock::new(SymbolResolver::default()),
+            loaded_at: RwLock::new(None),
+            ttl,
+        }
+    }
+
+    /// Reload asset and asset pair metadata from Kraken
+    pub async fn refresh(&self, req: impl KrakenClientExt + Clone) -> Result<(), Error> {
+        let market = MarketData::new();
+        let pairs = market
+            .get_tradable_asset_pairs(req.clone(), None, None, None)
+            .await?;
+        let assets = market.get_asset_info(req, None, None).await?;
+        info!(
+            "Loaded {} asset pairs and {} assets",
+            pairs.len(),
+            assets.len()
+        );
+        self.load(pairs, assets);
+        Ok(())
+    }
+
+    /// Reload metadata if it is missing or older than the registry's TTL
+    pub async fn ensure_fresh(&self, req: impl KrakenClientExt + Clone) -> Result<(), Error> {
+        let fresh = self
+            .loaded_at
+            .read()
+            .unwrap()
+            .is_some_and(|loaded| loaded.elapsed() < self.ttl);
+        if fresh {
+            return Ok(());
+        }
+        self.refresh(req).await
+    }
+
+    /// Replace the cached metadata
+    pub fn load(&self, pairs: HashMap<Pair, AssetPair>, assets: HashMap<AssetId, Asset>) {
+        let instruments = pairs
+            .iter()
+            .map(|(name, info)| (name.clone(), Instrument::from_asset_pair(name, info)))
+            .collect();
+        *self.resolver.write().unwrap() = SymbolResolver::new(&pairs, &assets);
+        *self.instruments.write().unwrap() = instruments;
+        *self.assets.write().unwrap() = assets;
+        *self.loaded_at.write().unwrap() = Some(Instant::now());
+    }
+
+    /// Get a copy of the symbol resolver built from the cached metadata
+    pub fn resolver(&self) -> SymbolResolver {
+        self.resolver.read().unwrap().clone()
+    }
+
+    /// Resolve any spelling of an asset pair to its canonical name
+    pub fn resolve_pair(&self, name: &str) -> Result<Pair, Error> {
+        self.resolver.read().unwrap().resolve_pair(name)
+    }
+
+    /// Resolve any spelling of an asset to its canonical id
+    pub fn resolve_asset(&self, name: &str) -> Result<AssetId, Error> {
+        self.resolver.read().unwrap().resolve_asset(name)
+    }
+
+    /// Find an instrument by any spelling of its pair name
+    pub fn instrument(&self, pair: &str) -> Option<Instrument> {
+        let pair = self.resolve_pair(pair).ok()?;
+        self.instruments.read().unwrap().get(&pair).cloned()
+    }
+
+    /// Find an asset by any spelling of its name
+    pub fn asset(&self, asset: &str) -> Option<Asset> {
+        let asset = self.resolve_asset(asset).ok()?;
+        self.assets.read().unwrap().get(&asset).cloned()
+    }
+
+    /// Validate an order against the cached metadata and normalize its pair, price and volume
+    pub fn validate(&self, order: &NewOrder) -> Result<NewOrder, Error> {
+        self.instrument(order.pair.as_str())
+            .ok_or_else(|| Error::ValidationError(format!("Unknown asset pair {}", order.pair)))?
+            .validate(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(status: &str) -> InstrumentRegistry {
+        let pair: AssetPair = serde_json::from_value(serde_json::json!({
+            "altname": "ETHGBP",
+            "wsname": "ETH/GBP",
+            "aclass_base": "currency",
+            "base": "XETH",
+            "aclass_quote": "currency",
+            "quote": "ZGBP",
+            "lot": "unit",
+            "pair_decimals": 2,
+            "cost_decimals": 5,
+            "lot_decimals": 8,
+            "lot_multiplier": 1,
+            "leverage_buy": [2, 3],
+            "leverage_sell": [2],
+            "fees": [[0, 0.4]],
+            "fees_maker": [[0, 0.25]],
+            "fee_volume_currency": "ZUSD",
+            "margin_call": 80,
+            "margin_stop": 40,
+            "ordermin": "0.01",
+            "costmin": "0.5",
+            "tick_size": "0.05",
+            "status": status
+        }))
+        .unwrap();
+        let registry = InstrumentRegistry::default();
+        registry.load(
+            HashMap::from([(Pair::from("XETHZGBP"), pair)]),
+            HashMap::new(),
+        );
+        registry
+    }
+
+    fn limit_order(volume: &str, price: &str) -> NewOrder {
+        let mut order = NewOrder::new(
+            Pair::from("ETHGBP"),
+            OrderSide::Buy,
+            OrderType::Limit,
+            volume.to_string(),
+        );
+        order.price = Some(price.to_string());
+        order
+    }
+
+    #[test]
+    fn test_validate_normalizes_order() {
+        let registry = registry("online");
+        let order = registry
+            .validate(&limit_order("0.123456789", "2000.123"))
+            .unwrap();
+        assert_eq!(order.pair.as_str(), "XETHZGBP");
+        assert_eq!(order.volume, "0.12345678");
+        assert_eq!(order.price.as_deref(), Some("2000.10"));
+
+        let mut sell = limit_order("1", "2000.123");
+        sell.side = OrderSide::Sell;
+        assert_eq!(
+            registry.validate(&sell).unwrap().price.as_deref(),
+            Some("2000.15")
+        );
+        sell.price = Some("2000.15".to_string());
+        assert_eq!(
+            registry.validate(&sell).unwrap().price.as_deref(),
+            Some("2000.15")
+        );
+
+        let mut relative = limit_order("1", "+5");
+        relative.ordertype = OrderType::StopLoss;
+        assert_eq!(
+            registry.validate(&relative).unwrap().price.as_deref(),
+            Some("+5")
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_small_orders() {
+        let registry = registry("online");
+        assert!(matches!(
+            registry.validate(&limit_order("0.001", "2000")),
+            Err(Error::ValidationError(_))
+        ));
+        assert!(matches!(
+            registry.validate(&limit_order("0.01", "10")),
+            Err(Error::ValidationError(_))
+        ));
+        assert!(matches!(
+            registry.validate(&limit_order("abc", "2000")),
+            Err(Error::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_pair_status() {
+        let order = limit_order("1", "2000");
+        assert!(registry("cancel_only").validate(&order).is_err());
+        assert!(registry("limit_only").validate(&order).is_ok());
+        assert!(registry("post_only").validate(&order).is_err());
+        assert!(registry("reduce_only").validate(&order).is_err());
+
+        let mut post = order.clone();
+        post.oflags = Some("post".to_string());
+        assert!(registry("post_only").validate(&post).is_ok());
+    }
+
+    #[test]
+    fn test_validate_leverage() {
+        let registry = registry("online");
+        let mut order = limit_order("1", "2000");
+        order.leverage = Some("3".to_string());
+        assert!(registry.validate(&order).is_ok());
+
+        order.side = OrderSide::Sell;
+        assert!(registry.validate(&order).is_err());
+    }
+
+    #[test]
+    fn test_unknown_pair() {
+        let registry = registry("online");
+        let mut order = limit_order("1", "2000");
+        order.pair = Pair::from("DOGEGBP");
+        assert!(matches!(
+            registry.validate(&order),
+            Err(Error::ValidationError(_))
+        ));
+        assert!(registry.instrument("ETH/GBP").is_some());
+    }
+}
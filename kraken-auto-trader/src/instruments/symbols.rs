@@ -0,0 +1,281 @@
+use crate::{
+    errors::Error,
+    models::{
+        market::{Asset, AssetPair},
+        symbols::{AssetId, Pair},
+    },
+};
+use std::collections::HashMap;
+
+/// Common asset names that Kraken lists under a different altname
+const ASSET_ALIASES: &[(&str, &str)] = &[("BTC", "XBT"), ("DOGE", "XDG")];
+
+/// Separators accepted between the base and quote of a pair
+const PAIR_SEPARATORS: &[char] = &['/', '-', '_', ':'];
+
+/// Symbol of a pair on the WebSocket v2 API, which uses the common asset names, e.g. "BTC/USD"
+/// for the WebSocket name "XBT/USD"
+pub fn ws_v2_symbol(wsname: &str) -> String {
+    wsname
+        .split('/')
+        .map(|asset| {
+            ASSET_ALIASES
+                .iter()
+                .find(|(_, altname)| *altname == asset)
+                .map_or(asset, |(alias, _)| alias)
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[derive(Debug, Clone)]
+struct PairNames {
+    altname: String,
+    wsname: Option<String>,
+    base: AssetId,
+    quote: AssetId,
+}
+
+/// Maps the different spellings Kraken uses for assets and pairs to their canonical names.
+///
+/// Pairs can be given by canonical name ("XXBTZUSD"), altname ("XBTUSD"), WebSocket name
+/// ("XBT/USD") or any combination of asset names, including common aliases ("BTC-USD").
+#[derive(Debug, Clone, Default)]
+pub struct SymbolResolver {
+    assets: HashMap<String, AssetId>,
+    asset_altnames: HashMap<AssetId, String>,
+    pairs: HashMap<String, Pair>,
+    pairs_by_assets: HashMap<(AssetId, AssetId), Pair>,
+    pair_names: HashMap<Pair, PairNames>,
+}
+
+impl SymbolResolver {
+    pub fn new(pairs: &HashMap<Pair, AssetPair>, assets: &HashMap<AssetId, Asset>) -> Self {
+        let mut resolver = Self::default();
+
+        for (id, asset) in assets {
+            resolver
+                .assets
+                .insert(id.as_str().to_uppercase(), id.clone());
+            resolver
+                .assets
+                .insert(asset.altname.to_uppercase(), id.clone());
+            resolver
+                .asset_altnames
+                .insert(id.clone(), asset.altname.clone());
+        }
+
+        for (pair, info) in pairs {
+            let mut names = vec![pair.as_str().to_string(), info.altname.clone()];
+            if let Some(wsname) = &info.wsname {
+                names.push(wsname.clone());
+            }
+            for name in names {
+                resolver.pairs.insert(name.to_uppercase(), pair.clone());
+            }
+
+            resolver
+                .pairs_by_assets
+                .insert((info.base.clone(), info.quote.clone()), pair.clone());
+            resolver.pair_names.insert(
+                pair.clone(),
+                PairNames {
+                    altname: info.altname.clone(),
+                    wsname: info.wsname.clone(),
+                    base: info.base.clone(),
+                    quote: info.quote.clone(),
+                },
+            );
+        }
+
+        resolver
+    }
+
+    /// Resolve any spelling of an asset to its canonical id
+    pub fn resolve_asset(&self, name: &str) -> Result<AssetId, Error> {
+        self.find_asset(name)
+            .ok_or_else(|| Error::InvalidParameter(format!("Unknown asset {}", name)))
+    }
+
+    /// Resolve any spelling of an asset pair to its canonical name
+    pub fn resolve_pair(&self, name: &str) -> Result<Pair, Error> {
+        self.find_pair(name)
+            .ok_or_else(|| Error::InvalidParameter(format!("Unknown asset pair {}", name)))
+    }
+
+    /// Get the altname of an asset, e.g. "XBT" for "XXBT"
+    pub fn asset_altname(&self, asset: &AssetId) -> Option<&str> {
+        self.asset_altnames.get(asset).map(String::as_str)
+    }
+
+    /// Get the altname of a pair, e.g. "XBTUSD" for "XXBTZUSD"
+    pub fn pair_altname(&self, pair: &Pair) -> Option<&str> {
+        self.pair_names
+            .get(pair)
+            .map(|names| names.altname.as_str())
+    }
+
+    /// Get the WebSocket name of a pair, e.g. "XBT/USD" for "XXBTZUSD"
+    pub fn pair_wsname(&self, pair: &Pair) -> Option<&str> {
+        self.pair_names
+            .get(pair)
+            .and_then(|names| names.wsname.as_deref())
+    }
+
+    /// Get the base and quote assets of a pair
+    pub fn pair_assets(&self, pair: &Pair) -> Option<(AssetId, AssetId)> {
+        self.pair_names
+            .get(pair)
+            .map(|names| (names.base.clone(), names.quote.clone()))
+    }
+
+    fn find_asset(&self, name: &str) -> Option<AssetId> {
+        let key = name.trim().to_uppercase();
+        let key = ASSET_ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == key)
+            .map(|(_, altname)| altname.to_string())
+            .unwrap_or(key);
+        self.assets.get(&key).cloned()
+    }
+
+    fn find_pair(&self, name: &str) -> Option<Pair> {
+        let key = name.trim().to_uppercase();
+        if let Some(pair) = self.pairs.get(&key) {
+            return Some(pair.clone());
+        }
+
+        if let Some((base, quote)) = key.split_once(PAIR_SEPARATORS) {
+            return self.pair_of(base, quote);
+        }
+
+        // Without a separator, try every split into two known assets
+        (1..key.len())
+            .filter(|i| key.is_char_boundary(*i))
+            .find_map(|i| self.pair_of(&key[..i], &key[i..]))
+    }
+
+    fn pair_of(&self, base: &str, quote: &str) -> Option<Pair> {
+        let base = self.find_asset(base)?;
+        let quote = self.find_asset(quote)?;
+        self.pairs_by_assets.get(&(base, quote)).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolver() -> SymbolResolver {
+        let asset = |altname: &str| -> Asset {
+            serde_json::from_value(serde_json::json!({
+                "aclass": "currency",
+                "altname": altname,
+                "decimals": 8,
+                "display_decimals": 5,
+                "status": "enabled"
+            }))
+            .unwrap()
+        };
+        let pair = |altname: &str, wsname: &str, base: &str, quote: &str| -> AssetPair {
+            serde_json::from_value(serde_json::json!({
+                "altname": altname,
+                "wsname": wsname,
+                "aclass_base": "currency",
+                "base": base,
+                "aclass_quote": "currency",
+                "quote": quote,
+                "lot": "unit",
+                "pair_decimals": 1,
+                "cost_decimals": 5,
+                "lot_decimals": 8,
+                "lot_multiplier": 1,
+                "leverage_buy": [],
+                "leverage_sell": [],
+                "fees": [[0, 0.4]],
+                "fees_maker": [[0, 0.25]],
+                "fee_volume_currency": "ZUSD",
+                "margin_call": 80,
+                "margin_stop": 40,
+                "ordermin": "0.0001",
+                "costmin": "0.5",
+                "tick_size": "0.1",
+                "status": "online"
+            }))
+            .unwrap()
+        };
+
+        let assets = HashMap::from([
+            (AssetId::from("XXBT"), asset("XBT")),
+            (AssetId::from("XETH"), asset("ETH")),
+            (AssetId::from("ZUSD"), asset("USD")),
+            (AssetId::from("ZGBP"), asset("GBP")),
+        ]);
+        let pairs = HashMap::from([
+            (
+                Pair::from("XXBTZUSD"),
+                pair("XBTUSD", "XBT/USD", "XXBT", "ZUSD"),
+            ),
+            (
+                Pair::from("XETHZGBP"),
+                pair("ETHGBP", "ETH/GBP", "XETH", "ZGBP"),
+            ),
+            (
+                Pair::from("XETHXXBT"),
+                pair("ETHXBT", "ETH/XBT", "XETH", "XXBT"),
+            ),
+        ]);
+        SymbolResolver::new(&pairs, &assets)
+    }
+
+    #[test]
+    fn test_resolve_pair() {
+        let resolver = resolver();
+        for name in [
+            "XXBTZUSD", "XBTUSD", "XBT/USD", "BTC/USD", "btcusd", "BTC-USD",
+        ] {
+            assert_eq!(
+                resolver.resolve_pair(name).unwrap().as_str(),
+                "XXBTZUSD",
+                "{}",
+                name
+            );
+        }
+        assert_eq!(
+            resolver.resolve_pair("ETHGBP").unwrap().as_str(),
+            "XETHZGBP"
+        );
+        assert_eq!(
+            resolver.resolve_pair("ETH/BTC").unwrap().as_str(),
+            "XETHXXBT"
+        );
+        assert!(matches!(
+            resolver.resolve_pair("USD/XBT"),
+            Err(Error::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_asset() {
+        let resolver = resolver();
+        assert_eq!(resolver.resolve_asset("BTC").unwrap().as_str(), "XXBT");
+        assert_eq!(resolver.resolve_asset("xbt").unwrap().as_str(), "XXBT");
+        assert_eq!(resolver.resolve_asset("ZGBP").unwrap().as_str(), "ZGBP");
+        assert!(resolver.resolve_asset("DOGE").is_err());
+    }
+
+    #[test]
+    fn test_names_of_pair() {
+        let resolver = resolver();
+        let pair = Pair::from("XXBTZUSD");
+        assert_eq!(resolver.pair_altname(&pair), Some("XBTUSD"));
+        assert_eq!(resolver.pair_wsname(&pair), Some("XBT/USD"));
+        assert_eq!(ws_v2_symbol("XBT/USD"), "BTC/USD");
+        assert_eq!(ws_v2_symbol("ETH/GBP"), "ETH/GBP");
+        assert_eq!(
+            resolver.pair_assets(&pair),
+            Some((AssetId::from("XXBT"), AssetId::from("ZUSD")))
+        );
+        assert_eq!(resolver.asset_altname(&AssetId::from("XXBT")), Some("XBT"));
+    }
+}
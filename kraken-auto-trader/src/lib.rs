@@ -1,7 +1,21 @@
+pub mod candles;
+pub mod cli;
 pub mod client;
+pub mod clock;
 pub mod errors;
+pub mod execution;
+pub mod health;
+pub mod feeds;
+pub mod indicators;
+pub mod instruments;
+pub mod metrics;
 pub mod middleware;
+pub mod mock;
 pub mod models;
+pub mod orders;
+pub mod recording;
 pub mod services;
+pub mod streaming;
+pub mod telemetry;
 pub mod utils;
 pub mod api;
\ No newline at end of file
@@ -1,24 +1,329 @@
-use actix_web::{web, App, HttpServer};
+use actix_web::{middleware::from_fn, web, App, HttpServer};
+use clap::Parser;
 use dotenv::dotenv;
 use kraken_auto_trader::{
-    api, client::kraken_client::KrakenClient, middleware::{KrakenClientMiddleware, KrakenClientState}, utils::config::KrakenConfig
+    api,
+    client::{cache::PublicCache, kraken_apis::PrivateApiBuilder, kraken_client::KrakenClient},
+    clock::ClockSync,
+    execution::engine::ExecutionEngine,
+    feeds::websocket::{MarketDataSocket, PrivateDataSocket},
+    health::{self, HealthMonitor, Watchdog},
+    instruments::registry::InstrumentRegistry,
+    metrics,
+    middleware::{KrakenClientMiddleware, KrakenClientState},
+    orders::{
+        dead_man_switch::DeadManSwitch,
+        manager::OrderManager,
+        synthetic::{SyntheticOrderManager, SyntheticOrderStore},
+        trading_gate::TradingGate,
+    },
+    recording::recorder::Recorder,
+    services::{account_details::Account, trading::Trading},
+    streaming::{
+        hub::EventHub,
+        relay::{market_subscriptions, MarketRelay, PrivateRelay},
+        StreamAuth,
+    },
+    telemetry::{self, Telemetry},
+    utils::{
+        app_config::{AppConfig, ConfigArgs},
+        audit::AuditLog,
+        endpoints::trading::{CANCEL_ALL_ORDERS_AFTER_X, GET_WEBSOCKETS_TOKEN},
+        reload::ConfigReloader,
+    },
 };
+use std::sync::Arc;
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+/// Kraken trading server
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    /// Print the effective configuration as TOML and exit
+    #[arg(long)]
+    print_config: bool,
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
-    env_logger::init();
-    let config = KrakenConfig::default();
-    let client = KrakenClient::new(config).unwrap();
-    let client_state = KrakenClientState::new(client);
-
-    HttpServer::new(move || {
-        App::new()
-            .app_data(web::Data::new(client_state.clone()))
+    let cli = Cli::parse();
+    let config = match AppConfig::load(&cli.config) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    if cli.print_config {
+        print!("{}", config.to_toml().map_err(invalid_input)?);
+        return Ok(());
+    }
+
+    let telemetry = Telemetry::init(&config.logging).map_err(invalid_input)?;
+
+    let mut client = KrakenClient::new(config.client.clone())
+        .map_err(invalid_input)?
+        .with_rate_limit(&config.rate_limit)
+        .with_circuit_breaker(&config.circuit_breaker);
+    let breakers = client.circuit_breakers();
+
+    let recorder_config = &config.storage.recorder;
+    if recorder_config.enabled {
+        let recorder = Recorder::new(&recorder_config.directory)
+            .map_err(|e| std::io::Error::other(e.to_string()))?
+            .with_rotation(recorder_config.rotate_secs);
+        client = client.with_recorder(Arc::new(recorder));
+    }
+    let client_state = KrakenClientState::new(client)
+        .with_public_cache(Arc::new(PublicCache::new(config.cache.clone())));
+
+    // One API shared by every service, so they all draw from the same nonce provider
+    let private_api = PrivateApiBuilder::from_config(&config).and_then(PrivateApiBuilder::build);
+
+    let account = private_api
+        .as_ref()
+        .ok()
+        .map(|api| Arc::new(Account::with_api(api.clone())));
+
+    // New orders stop or are downgraded while the exchange is not fully online
+    let gate = Arc::new(TradingGate::new());
+    let orders = match &private_api {
+        Ok(api) => Some(Arc::new(
+            OrderManager::with_services(Trading::with_api(api.clone()), Account::with_api(api.clone()))
+                .with_registry(Arc::new(InstrumentRegistry::default()))
+                .with_risk_limits(config.risk.clone())
+                .with_trading_gate(gate.clone()),
+        )),
+        Err(e) => {
+            warn!("Order management disabled: {}", e);
+            None
+        }
+    };
+
+    // The dead man's switch stops re-arming once a background loop stalls
+    let watchdog = Arc::new(Watchdog::new());
+    let dms_config = config.dead_man_switch.clone();
+    // Built even without heartbeats, as it also cancels every open order on shutdown
+    let dead_man_switch = match private_api
+        .as_ref()
+        .map(|api| Trading::with_api(api.clone()))
+    {
+        Ok(trading) if !trading.permits(CANCEL_ALL_ORDERS_AFTER_X) => {
+            warn!("Dead man's switch disabled: no API key has the trade permission");
+            None
+        }
+        Ok(trading) => {
+            let healthy = watchdog.clone();
+            let mut switch = DeadManSwitch::new(trading, dms_config)
+                .with_health_check(move || healthy.is_healthy());
+            if let Some(orders) = &orders {
+                switch = switch.with_order_manager(orders.clone());
+            }
+            let switch = Arc::new(switch);
+            if config.dead_man_switch.enabled {
+                switch.spawn(client_state.clone());
+            }
+            Some(switch)
+        }
+        Err(e) => {
+            warn!("Dead man's switch disabled: {}", e);
+            None
+        }
+    };
+
+    // The streaming sockets are built ahead of the health monitor, which is not ready while
+    // they are disconnected
+    let stream_config = &config.streaming;
+    let market_socket = stream_config
+        .enabled
+        .then(|| MarketDataSocket::new(market_subscriptions(stream_config)));
+
+    // Account events come from the authenticated WebSocket API, when the key may use it
+    let stream_trading = private_api
+        .as_ref()
+        .ok()
+        .map(|api| Trading::with_api(api.clone()))
+        .filter(|trading| trading.permits(GET_WEBSOCKETS_TOKEN));
+    let private_events = stream_config.enabled && stream_config.private_events;
+    let private_socket = match stream_trading.filter(|_| private_events) {
+        Some(trading) => Some(PrivateDataSocket::new(
+            trading,
+            client_state.clone(),
+            vec![
+                PrivateDataSocket::subscription("executions"),
+                PrivateDataSocket::subscription("balances"),
+            ],
+        )),
+        None if private_events => {
+            warn!("Account events not streamed: no API key has the WebSocket permission");
+            None
+        }
+        None => None,
+    };
+
+    let mut monitor = HealthMonitor::new(config.health.clone())
+        .with_trading_gate(gate)
+        .with_circuit_breakers(breakers)
+        .with_watchdog(watchdog.clone());
+    if config.clock.enabled {
+        let clock_sync = Arc::new(ClockSync::new(config.clock.clone()));
+        clock_sync.spawn(client_state.clone());
+        monitor = monitor.with_clock_sync(clock_sync);
+    }
+    if let Some(socket) = &market_socket {
+        monitor = monitor.with_websocket(socket.status());
+    }
+    if let Some(socket) = &private_socket {
+        monitor = monitor.with_private_websocket(socket.status());
+    }
+    if let Some(switch) = dead_man_switch.as_ref().filter(|_| config.dead_man_switch.enabled) {
+        monitor = monitor.with_dead_man_switch(switch.clone());
+    }
+    let monitor = Arc::new(monitor);
+    monitor.spawn(client_state.clone());
+
+    // One upstream connection per feed, fanned out to every streaming client
+    let hub = market_socket.map(|socket| {
+        let hub = Arc::new(EventHub::new(stream_config));
+        MarketRelay::new(hub.clone(), stream_config.book_depth).spawn(socket.spawn());
+        if let Some(socket) = private_socket {
+            let mut relay = PrivateRelay::new(hub.clone());
+            if let Some(orders) = &orders {
+                relay = relay.with_order_manager(orders.clone());
+            }
+            relay.spawn(socket.spawn());
+        }
+        info!(
+            "Streaming market data for {}",
+            stream_config.symbols.join(", ")
+        );
+        hub
+    });
+    let stream_auth = Arc::new(StreamAuth::from_env());
+
+    let engine = orders.clone().map(|orders| {
+        let mut engine = ExecutionEngine::new(orders, client_state.clone());
+        if let Some(hub) = &hub {
+            engine = engine.with_event_hub(hub.clone());
+        }
+        Arc::new(engine)
+    });
+
+    let synthetic_config = &config.storage.synthetic_orders;
+    let synthetic = match orders.clone() {
+        Some(orders) => {
+            let synthetic = Arc::new(SyntheticOrderManager::new(
+                orders,
+                client_state.clone(),
+                SyntheticOrderStore::new(&synthetic_config.state_path),
+            )
+            .with_watchdog(watchdog.clone()));
+            if let Err(e) = synthetic.restore().await {
+                error!("Failed to restore synthetic orders: {}", e);
+            }
+            synthetic.spawn(synthetic_config.poll_interval());
+            Some(synthetic)
+        }
+        None => None,
+    };
+
+    if config.reload.enabled {
+        let mut reloader = ConfigReloader::new(cli.config.clone(), config.clone());
+        match AuditLog::open(&config.storage.audit_log) {
+            Ok(audit) => reloader = reloader.with_audit_log(Arc::new(audit)),
+            Err(e) => warn!("Configuration changes will not be audited: {}", e),
+        }
+        let reloader = Arc::new(reloader);
+        match reloader.spawn(config.reload.poll_interval()) {
+            Some(_) => apply_live_settings(
+                reloader.subscribe(),
+                telemetry.clone(),
+                client_state.clone(),
+                orders,
+            ),
+            None => info!("No configuration file to watch, live reload disabled"),
+        }
+    }
+
+    let app_switch = dead_man_switch.clone();
+    let app_state = client_state.clone();
+    let mut server = HttpServer::new(move || {
+        let mut app = App::new()
+            .app_data(web::Data::new(app_state.clone()))
+            .app_data(web::Data::from(monitor.clone()))
+            .app_data(web::Data::from(stream_auth.clone()))
             .wrap(KrakenClientMiddleware::new())
+            .wrap(from_fn(metrics::track_http))
+            .wrap(from_fn(telemetry::trace_request));
+        if let Some(account) = &account {
+            app = app.app_data(web::Data::from(account.clone()));
+        }
+        if let Some(switch) = &app_switch {
+            app = app.app_data(web::Data::from(switch.clone()));
+        }
+        if let Some(engine) = &engine {
+            app = app.app_data(web::Data::from(engine.clone()));
+        }
+        if let Some(synthetic) = &synthetic {
+            app = app.app_data(web::Data::from(synthetic.clone()));
+        }
+        if let Some(hub) = &hub {
+            app = app.app_data(web::Data::from(hub.clone()));
+        }
+        app.route("/metrics", web::get().to(metrics::export))
+            .route("/healthz", web::get().to(health::healthz))
+            .route("/readyz", web::get().to(health::readyz))
             .service(web::scope("/api").configure(api::config))
-    })
-    .bind(("127.0.0.1", 8080))?
-    .run()
-    .await
+    });
+    if let Some(workers) = config.server.workers {
+        server = server.workers(workers);
+    }
+    info!("Listening on {}:{}", config.server.host, config.server.port);
+    server
+        .bind((config.server.host.as_str(), config.server.port))?
+        .run()
+        .await?;
+
+    if let Some(switch) = dead_man_switch {
+        match switch.shutdown(client_state).await {
+            Ok(cancelled) => info!("Shutdown complete, {} orders cancelled", cancelled),
+            Err(e) => error!("Failed to cancel orders on shutdown: {}", e),
+        }
+    }
+    telemetry.shutdown();
+    Ok(())
+}
+
+/// Hand reloaded settings to the running components
+fn apply_live_settings(
+    mut updates: watch::Receiver<Arc<AppConfig>>,
+    telemetry: Telemetry,
+    client_state: KrakenClientState,
+    orders: Option<Arc<OrderManager>>,
+) {
+    tokio::spawn(async move {
+        while updates.changed().await.is_ok() {
+            let config = updates.borrow_and_update().clone();
+            if let Err(e) = telemetry.set_level(&config.logging) {
+                warn!("Keeping the running log level: {}", e);
+            }
+            if let Some(orders) = &orders {
+                orders.set_risk_limits(config.risk.clone());
+            }
+            let mut client = client_state.client.lock().await;
+            client.set_rate_limit(&config.rate_limit);
+            client.config.max_retries = config.client.max_retries;
+            client.config.retry_delay_ms = config.client.retry_delay_ms;
+            client.config.rate_limit_delay_ms = config.client.rate_limit_delay_ms;
+        }
+    });
+}
+
+fn invalid_input(e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string())
 }
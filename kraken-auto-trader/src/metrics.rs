@@ -0,0 +1,431 @@
+use crate::errors::Error;
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    HttpResponse,
+};
+use prometheus::{
+    exponential_buckets, Encoder, Gauge, GaugeVec, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec,
+    IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+/// Process-wide metrics, exported in the Prometheus text format on `/metrics`
+pub struct Metrics {
+    registry: Registry,
+    /// Calls to the Kraken REST API by `endpoint`, `method` and `status`
+    pub kraken_requests: IntCounterVec,
+    pub kraken_request_duration: HistogramVec,
+    /// Calls sent again by `endpoint` and `reason`
+    pub kraken_retries: IntCounterVec,
+    /// Errors returned by Kraken by `endpoint` and `code`, e.g. `EAPI:Invalid nonce`
+    pub kraken_errors: IntCounterVec,
+    /// Public requests by `endpoint` and `outcome`: `hit`, `miss` or `coalesced`
+    pub public_cache: IntCounterVec,
+    /// State of the circuit of each endpoint `class`: 0 closed, 1 half-open, 2 open
+    pub circuit_state: IntGaugeVec,
+    /// Calls failed fast by an open circuit, by endpoint `class`
+    pub circuit_rejections: IntCounterVec,
+    /// Share of the Kraken calls of the error budget window that failed upstream
+    pub error_ratio: Gauge,
+    /// 1 when the last health check reached Kraken, 0 otherwise
+    pub exchange_up: IntGauge,
+    /// Exchange clock minus local clock, as of the last health check
+    pub clock_drift: Gauge,
+    pub rate_limiter_tokens: IntGauge,
+    pub rate_limiter_wait: Histogram,
+    pub ws_reconnects: IntCounterVec,
+    /// Delay between the exchange timestamp of a WebSocket message and its receipt
+    pub ws_message_lag: HistogramVec,
+    /// Clients of the streaming endpoints by `transport`: `sse` or `ws`
+    pub stream_clients: IntGaugeVec,
+    /// Events not delivered to a streaming client that fell behind, by `channel`
+    pub stream_events_dropped: IntCounterVec,
+    /// Streaming clients disconnected for falling too far behind
+    pub stream_slow_disconnects: IntCounter,
+    /// Time from sending an order to Kraken acknowledging it
+    pub order_ack_latency: Histogram,
+    pub order_fills: IntCounterVec,
+    /// Account equity by reference `currency`, as of the last trade balance query
+    pub portfolio_equity: GaugeVec,
+    /// Unrealized profit of each running parent order by `strategy` id, marked at the last trade
+    pub strategy_pnl: GaugeVec,
+    /// Requests served by this process by `method`, `route` and `status`
+    pub http_requests: IntCounterVec,
+    pub http_request_duration: HistogramVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// The process-wide metrics
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let latency = || exponential_buckets(0.005, 2.0, 12).expect("valid buckets");
+
+        let metrics = Self {
+            kraken_requests: IntCounterVec::new(
+                Opts::new("kraken_requests_total", "Kraken REST API calls"),
+                &["endpoint", "method", "status"],
+            )
+            .expect("valid metric"),
+            kraken_request_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "kraken_request_duration_seconds",
+                    "Latency of Kraken REST API calls",
+                )
+                .buckets(latency()),
+                &["endpoint", "method"],
+            )
+            .expect("valid metric"),
+            kraken_retries: IntCounterVec::new(
+                Opts::new("kraken_retries_total", "Kraken REST API calls sent again"),
+                &["endpoint", "reason"],
+            )
+            .expect("valid metric"),
+            kraken_errors: IntCounterVec::new(
+                Opts::new("kraken_errors_total", "Errors returned by the Kraken API"),
+                &["endpoint", "code"],
+            )
+            .expect("valid metric"),
+            public_cache: IntCounterVec::new(
+                Opts::new(
+                    "kraken_public_cache_requests_total",
+                    "Public Kraken requests served from the cache, sent, or coalesced with one in flight",
+                ),
+                &["endpoint", "outcome"],
+            )
+            .expect("valid metric"),
+            circuit_state: IntGaugeVec::new(
+                Opts::new(
+                    "kraken_circuit_state",
+                    "Circuit of each class of endpoints: 0 closed, 1 half-open, 2 open",
+                ),
+                &["class"],
+            )
+            .expect("valid metric"),
+            circuit_rejections: IntCounterVec::new(
+                Opts::new(
+                    "kraken_circuit_rejections_total",
+                    "Kraken calls failed fast by an open circuit",
+                ),
+                &["class"],
+            )
+            .expect("valid metric"),
+            error_ratio: Gauge::new(
+                "kraken_error_ratio",
+                "Share of recent Kraken calls that failed upstream",
+            )
+            .expect("valid metric"),
+            exchange_up: IntGauge::new(
+                "kraken_up",
+                "Whether the last health check reached the Kraken API",
+            )
+            .expect("valid metric"),
+            clock_drift: Gauge::new(
+                "kraken_clock_drift_seconds",
+                "Kraken server time minus local time",
+            )
+            .expect("valid metric"),
+            rate_limiter_tokens: IntGauge::new(
+                "kraken_rate_limiter_tokens",
+                "Calls the client-side rate limiter allows right away",
+            )
+            .expect("valid metric"),
+            rate_limiter_wait: Histogram::with_opts(
+                HistogramOpts::new(
+                    "kraken_rate_limiter_wait_seconds",
+                    "Time spent waiting for the rate limiter",
+                )
+                .buckets(vec![0.0, 0.1, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0]),
+            )
+            .expect("valid metric"),
+            ws_reconnects: IntCounterVec::new(
+                Opts::new(
+                    "kraken_ws_reconnects_total",
+                    "WebSocket connections lost and opened again",
+                ),
+                &["url"],
+            )
+            .expect("valid metric"),
+            ws_message_lag: HistogramVec::new(
+                HistogramOpts::new(
+                    "kraken_ws_message_lag_seconds",
+                    "Delay between the exchange timestamp of a WebSocket message and its receipt",
+                )
+                .buckets(latency()),
+                &["url"],
+            )
+            .expect("valid metric"),
+            stream_clients: IntGaugeVec::new(
+                Opts::new("stream_clients", "Clients of the streaming endpoints"),
+                &["transport"],
+            )
+            .expect("valid metric"),
+            stream_events_dropped: IntCounterVec::new(
+                Opts::new(
+                    "stream_events_dropped_total",
+                    "Events not delivered to streaming clients that fell behind",
+                ),
+                &["channel"],
+            )
+            .expect("valid metric"),
+            stream_slow_disconnects: IntCounter::new(
+                "stream_slow_disconnects_total",
+                "Streaming clients disconnected for falling too far behind",
+            )
+            .expect("valid metric"),
+            order_ack_latency: Histogram::with_opts(
+                HistogramOpts::new(
+                    "order_submit_ack_seconds",
+                    "Time from submitting an order to Kraken acknowledging it",
+                )
+                .buckets(latency()),
+            )
+            .expect("valid metric"),
+            order_fills: IntCounterVec::new(
+                Opts::new(
+                    "order_fills_total",
+                    "Fills of orders placed by this process",
+                ),
+                &["pair", "side"],
+            )
+            .expect("valid metric"),
+            portfolio_equity: GaugeVec::new(
+                Opts::new(
+                    "portfolio_equity",
+                    "Account equity as of the last trade balance query",
+                ),
+                &["currency"],
+            )
+            .expect("valid metric"),
+            strategy_pnl: GaugeVec::new(
+                Opts::new("strategy_pnl", "Profit and loss of each strategy"),
+                &["strategy"],
+            )
+            .expect("valid metric"),
+            http_requests: IntCounterVec::new(
+                Opts::new("http_requests_total", "Requests served by the API"),
+                &["method", "route", "status"],
+            )
+            .expect("valid metric"),
+            http_request_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "http_request_duration_seconds",
+                    "Time taken to serve API requests",
+                )
+                .buckets(latency()),
+                &["method", "route"],
+            )
+            .expect("valid metric"),
+            registry,
+        };
+
+        let collectors: [Box<dyn prometheus::core::Collector>; 23] = [
+            Box::new(metrics.kraken_requests.clone()),
+            Box::new(metrics.kraken_request_duration.clone()),
+            Box::new(metrics.kraken_retries.clone()),
+            Box::new(metrics.kraken_errors.clone()),
+            Box::new(metrics.public_cache.clone()),
+            Box::new(metrics.circuit_state.clone()),
+            Box::new(metrics.circuit_rejections.clone()),
+            Box::new(metrics.error_ratio.clone()),
+            Box::new(metrics.exchange_up.clone()),
+            Box::new(metrics.clock_drift.clone()),
+            Box::new(metrics.rate_limiter_tokens.clone()),
+            Box::new(metrics.rate_limiter_wait.clone()),
+            Box::new(metrics.ws_reconnects.clone()),
+            Box::new(metrics.ws_message_lag.clone()),
+            Box::new(metrics.stream_clients.clone()),
+            Box::new(metrics.stream_events_dropped.clone()),
+            Box::new(metrics.stream_slow_disconnects.clone()),
+            Box::new(metrics.order_ack_latency.clone()),
+            Box::new(metrics.order_fills.clone()),
+            Box::new(metrics.portfolio_equity.clone()),
+            Box::new(metrics.strategy_pnl.clone()),
+            Box::new(metrics.http_requests.clone()),
+            Box::new(metrics.http_request_duration.clone()),
+        ];
+        for collector in collectors {
+            metrics
+                .registry
+                .register(collector)
+                .expect("metric registered once");
+        }
+        metrics
+    }
+
+    /// All metrics in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("text encoding does not fail");
+        String::from_utf8(buffer).expect("text encoding is UTF-8")
+    }
+
+    /// Count and time one Kraken call, whatever its outcome
+    pub fn observe_kraken_request<T>(
+        &self,
+        endpoint: &str,
+        method: &str,
+        elapsed: Duration,
+        result: &Result<T, Error>,
+    ) {
+        let status = match result {
+            Ok(_) => "ok".to_string(),
+            Err(e) => status_label(e),
+        };
+        self.kraken_requests
+            .with_label_values(&[endpoint, method, status.as_str()])
+            .inc();
+        self.kraken_request_duration
+            .with_label_values(&[endpoint, method])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Count the error codes of a Kraken error response
+    pub fn observe_kraken_errors(&self, endpoint: &str, errors: &[String]) {
+        for error in errors {
+            self.kraken_errors
+                .with_label_values(&[endpoint, error_code(error).as_str()])
+                .inc();
+        }
+    }
+
+    pub fn observe_retry(&self, endpoint: &str, reason: &str) {
+        self.kraken_retries
+            .with_label_values(&[endpoint, reason])
+            .inc();
+    }
+
+    pub fn set_strategy_pnl(&self, strategy: &str, pnl: f64) {
+        self.strategy_pnl.with_label_values(&[strategy]).set(pnl);
+    }
+
+    /// Stop reporting the PnL of a strategy that is no longer running
+    pub fn clear_strategy_pnl(&self, strategy: &str) {
+        let _ = self.strategy_pnl.remove_label_values(&[strategy]);
+    }
+}
+
+/// Outcome of a failed call as a low-cardinality label
+fn status_label(error: &Error) -> String {
+    match error {
+        // The HTTP status as formatted by reqwest, e.g. "503 Service Unavailable"
+        Error::InvalidResponse(status)
+            if status.len() >= 3 && status.as_bytes()[..3].iter().all(u8::is_ascii_digit) =>
+        {
+            format!("http_{}", &status[..3])
+        }
+        Error::HttpError(e) if e.is_timeout() => "timeout".to_string(),
+        Error::HttpError(e) if e.is_decode() => "decode_error".to_string(),
+        Error::HttpError(_) | Error::NetworkError(_) => "network_error".to_string(),
+        Error::TimeoutError(_) => "timeout".to_string(),
+        Error::SerializationError(_) | Error::Deserialization(_) => "decode_error".to_string(),
+        Error::RateLimitExceeded(_) => "rate_limited".to_string(),
+        Error::CircuitOpen(_) => "circuit_open".to_string(),
+        Error::Api(_) | Error::Auth(_) | Error::ValidationError(_) => "kraken_error".to_string(),
+        _ => "error".to_string(),
+    }
+}
+
+/// Category and message of a Kraken error without its details, e.g. `EGeneral:Invalid arguments`
+fn error_code(error: &str) -> String {
+    error.splitn(3, ':').take(2).collect::<Vec<_>>().join(":")
+}
+
+/// Handler for `GET /metrics`
+pub async fn export() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics().render())
+}
+
+/// Middleware counting and timing the requests served, labelled by route pattern
+pub async fn track_http(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let response = next.call(req).await;
+
+    let (route, status) = match &response {
+        Ok(response) => (
+            response
+                .request()
+                .match_pattern()
+                .unwrap_or_else(|| "unmatched".to_string()),
+            response.status().as_u16().to_string(),
+        ),
+        Err(e) => (
+            "unmatched".to_string(),
+            e.as_response_error().status_code().as_u16().to_string(),
+        ),
+    };
+    let metrics = metrics();
+    metrics
+        .http_requests
+        .with_label_values(&[method.as_str(), route.as_str(), status.as_str()])
+        .inc();
+    metrics
+        .http_request_duration
+        .with_label_values(&[method.as_str(), route.as_str()])
+        .observe(started.elapsed().as_secs_f64());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels() {
+        assert_eq!(
+            status_label(&Error::InvalidResponse(
+                "503 Service Unavailable".to_string()
+            )),
+            "http_503"
+        );
+        assert_eq!(
+            status_label(&Error::Api("EOrder:Insufficient funds".to_string())),
+            "kraken_error"
+        );
+        assert_eq!(
+            error_code("EGeneral:Invalid arguments:volume"),
+            "EGeneral:Invalid arguments"
+        );
+        assert_eq!(error_code("EAPI:Invalid nonce"), "EAPI:Invalid nonce");
+    }
+
+    #[test]
+    fn test_render() {
+        let metrics = metrics();
+        metrics.observe_kraken_request::<()>(
+            "/0/public/Time",
+            "GET",
+            Duration::from_millis(12),
+            &Ok(()),
+        );
+        metrics.set_strategy_pnl("grid", -1.5);
+
+        let text = metrics.render();
+        assert!(text.contains(
+            r#"kraken_requests_total{endpoint="/0/public/Time",method="GET",status="ok"}"#
+        ));
+        assert!(text.contains("kraken_request_duration_seconds_bucket"));
+        assert!(text.contains(r#"strategy_pnl{strategy="grid"} -1.5"#));
+
+        metrics.clear_strategy_pnl("grid");
+        assert!(!metrics.render().contains(r#"strategy_pnl{strategy="grid"}"#));
+    }
+}
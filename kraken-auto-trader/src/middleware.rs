@@ -1,25 +1,35 @@
 use actix_web::{dev::ServiceRequest, Error, error::ErrorUnauthorized};
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use crate::client::kraken_client::KrakenClient;
+use crate::client::{cache::PublicCache, kraken_client::KrakenClient};
 
 #[derive(Clone)]
 pub struct KrakenClientState {
     pub client: Arc<Mutex<KrakenClient>>,
+    /// Public responses shared by every request made through this state
+    pub public_cache: Option<Arc<PublicCache>>,
 }
 
 impl KrakenClientState {
     pub fn new(client: KrakenClient) -> Self {
         Self {
             client: Arc::new(Mutex::new(client)),
+            public_cache: None,
         }
     }
 
+    /// Serve public requests from `cache` and coalesce identical ones
+    pub fn with_public_cache(mut self, cache: Arc<PublicCache>) -> Self {
+        self.public_cache = Some(cache);
+        self
+    }
+
     pub async fn get_client(&self) -> KrakenClient {
         self.client.lock().await.clone()
     }
 }
 
+#[derive(Default)]
 pub struct KrakenClientMiddleware;
 
 impl KrakenClientMiddleware {
@@ -77,6 +87,21 @@ where
 // Helper trait to extract client from request
 pub trait KrakenClientExt {
     fn get_client(&self) -> Result<Arc<Mutex<KrakenClient>>, Error>;
+
+    /// Cache public requests go through, if any
+    fn public_cache(&self) -> Option<Arc<PublicCache>> {
+        None
+    }
+}
+
+impl KrakenClientExt for KrakenClientState {
+    fn get_client(&self) -> Result<Arc<Mutex<KrakenClient>>, Error> {
+        Ok(self.client.clone())
+    }
+
+    fn public_cache(&self) -> Option<Arc<PublicCache>> {
+        self.public_cache.clone()
+    }
 }
 
 impl KrakenClientExt for actix_web::HttpRequest {
@@ -85,4 +110,9 @@ impl KrakenClientExt for actix_web::HttpRequest {
             .map(|data| data.client.clone())
             .ok_or_else(|| ErrorUnauthorized("Kraken client not found"))
     }
+
+    fn public_cache(&self) -> Option<Arc<PublicCache>> {
+        self.app_data::<actix_web::web::Data<KrakenClientState>>()
+            .and_then(|data| data.public_cache.clone())
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,958 @@
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+use super::unix_time;
+use crate::utils::time::format_rfc3339;
+
+/// Kraken error strings returned by the simulated account
+pub const UNKNOWN_PAIR: &str = "EQuery:Unknown asset pair";
+pub const UNKNOWN_ORDER: &str = "EOrder:Unknown order";
+pub const INSUFFICIENT_FUNDS: &str = "EOrder:Insufficient funds";
+pub const ORDER_MINIMUM: &str = "EOrder:Order minimum not met";
+pub const INSUFFICIENT_FUNDS_WITHDRAWAL: &str = "EFunding:Insufficient funds";
+
+/// Trading rules of a pair, taken from the `AssetPairs` fixture
+#[derive(Debug, Clone)]
+pub struct PairInfo {
+    pub name: String,
+    pub altname: String,
+    pub wsname: Option<String>,
+    pub base: String,
+    pub quote: String,
+    pub ordermin: f64,
+    /// Fees of the first tier, in percent
+    pub taker_fee: f64,
+    pub maker_fee: f64,
+}
+
+impl PairInfo {
+    /// Parse the result of an `AssetPairs` response
+    pub fn from_fixture(asset_pairs: &Value) -> HashMap<String, PairInfo> {
+        let first_tier = |pair: &Value, key: &str| pair[key][0][1].as_f64().unwrap_or(0.0);
+        asset_pairs
+            .as_object()
+            .map(|pairs| {
+                pairs
+                    .iter()
+                    .map(|(name, pair)| {
+                        let info = PairInfo {
+                            name: name.clone(),
+                            altname: pair["altname"].as_str().unwrap_or(name).to_string(),
+                            wsname: pair["wsname"].as_str().map(str::to_string),
+                            base: pair["base"].as_str().unwrap_or_default().to_string(),
+                            quote: pair["quote"].as_str().unwrap_or_default().to_string(),
+                            ordermin: pair["ordermin"]
+                                .as_str()
+                                .and_then(|min| min.parse().ok())
+                                .unwrap_or(0.0),
+                            taker_fee: first_tier(pair, "fees"),
+                            maker_fee: first_tier(pair, "fees_maker"),
+                        };
+                        (name.clone(), info)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.name == name || self.altname == name || self.wsname.as_deref() == Some(name)
+    }
+}
+
+/// Top of the book of a pair
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quote {
+    pub bid: f64,
+    pub ask: f64,
+    pub last: f64,
+}
+
+impl Quote {
+    /// Read the best bid, best ask and last trade of a `Ticker` entry
+    pub fn from_ticker(ticker: &Value) -> Option<Self> {
+        let price = |key: &str| ticker[key][0].as_str()?.parse::<f64>().ok();
+        Some(Self {
+            bid: price("b")?,
+            ask: price("a")?,
+            last: price("c")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MockOrder {
+    txid: String,
+    pair: String,
+    side: Side,
+    ordertype: String,
+    volume: f64,
+    vol_exec: f64,
+    cost: f64,
+    fee: f64,
+    price: f64,
+    price2: f64,
+    userref: Option<i64>,
+    cl_ord_id: Option<String>,
+    oflags: String,
+    status: &'static str,
+    reason: Option<String>,
+    opentm: f64,
+    closetm: Option<f64>,
+    /// Stop and take-profit orders only match once their trigger price is crossed
+    triggered: bool,
+    trades: Vec<String>,
+    descr: String,
+}
+
+impl MockOrder {
+    fn is_open(&self) -> bool {
+        self.status == "open"
+    }
+
+    fn close(&mut self, status: &'static str, reason: Option<&str>) {
+        self.status = status;
+        self.reason = reason.map(str::to_string);
+        self.closetm = Some(unix_time());
+    }
+
+    fn to_json(&self, pair: &PairInfo, trades: bool) -> Value {
+        let mut order = json!({
+            "refid": null,
+            "userref": self.userref,
+            "cl_ord_id": self.cl_ord_id,
+            "status": self.status,
+            "reason": self.reason,
+            "opentm": self.opentm,
+            "closetm": self.closetm,
+            "starttm": 0,
+            "expiretm": 0,
+            "descr": {
+                "pair": pair.altname,
+                "type": self.side.as_str(),
+                "ordertype": self.ordertype,
+                "price": format_price(self.price),
+                "price2": format_price(self.price2),
+                "leverage": "none",
+                "order": self.descr,
+                "close": "",
+            },
+            "vol": format_volume(self.volume),
+            "vol_exec": format_volume(self.vol_exec),
+            "cost": format_price(self.cost),
+            "fee": format_price(self.fee),
+            "price": format_price(if self.vol_exec > 0.0 { self.cost / self.vol_exec } else { 0.0 }),
+            "stopprice": format_price(0.0),
+            "limitprice": format_price(0.0),
+            "misc": "",
+            "oflags": self.oflags,
+        });
+        if trades && !self.trades.is_empty() {
+            order["trades"] = json!(self.trades);
+        }
+        order
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MockTrade {
+    id: String,
+    ordertxid: String,
+    pair: String,
+    side: Side,
+    ordertype: String,
+    time: f64,
+    price: f64,
+    volume: f64,
+    cost: f64,
+    fee: f64,
+    maker: bool,
+}
+
+#[derive(Debug, Clone)]
+struct MockWithdrawal {
+    refid: String,
+    asset: String,
+    key: String,
+    amount: f64,
+    time: f64,
+}
+
+impl MockWithdrawal {
+    fn to_json(&self) -> Value {
+        json!({
+            "method": "Mock",
+            "aclass": "currency",
+            "asset": self.asset,
+            "refid": self.refid,
+            "txid": null,
+            "info": self.key,
+            "amount": format_volume(self.amount),
+            "fee": format_volume(0.0),
+            "time": self.time as i64,
+            "status": "Initial",
+        })
+    }
+}
+
+/// Simulated Kraken account: balances, resting orders and fills against the current quotes.
+///
+/// The book has unlimited depth, so every order that matches fills in full at the best bid or ask.
+/// Fees are charged in the quote currency at the first tier of the pair.
+#[derive(Debug, Default)]
+pub struct MockExchange {
+    pairs: HashMap<String, PairInfo>,
+    quotes: HashMap<String, Quote>,
+    balances: HashMap<String, f64>,
+    orders: Vec<MockOrder>,
+    trades: Vec<MockTrade>,
+    withdrawals: Vec<MockWithdrawal>,
+    seq: u64,
+    cancel_at: Option<f64>,
+}
+
+impl MockExchange {
+    pub fn new(pairs: HashMap<String, PairInfo>, quotes: HashMap<String, Quote>) -> Self {
+        Self {
+            pairs,
+            quotes,
+            ..Default::default()
+        }
+    }
+
+    pub fn resolve_pair(&self, name: &str) -> Option<&PairInfo> {
+        self.pairs.values().find(|pair| pair.matches(name))
+    }
+
+    pub fn set_balance(&mut self, asset: &str, amount: f64) {
+        self.balances.insert(asset.to_string(), amount);
+    }
+
+    pub fn balance(&self, asset: &str) -> f64 {
+        self.balances.get(asset).copied().unwrap_or(0.0)
+    }
+
+    pub fn quote(&self, pair: &str) -> Option<Quote> {
+        self.resolve_pair(pair)
+            .and_then(|info| self.quotes.get(&info.name))
+            .copied()
+    }
+
+    /// Move the market of `pair` and fill the resting orders it crosses
+    pub fn set_quote(&mut self, pair: &str, quote: Quote) -> Result<(), String> {
+        let name = self.resolve_pair(pair).ok_or(UNKNOWN_PAIR)?.name.clone();
+        self.quotes.insert(name.clone(), quote);
+        for index in 0..self.orders.len() {
+            if self.orders[index].is_open() && self.orders[index].pair == name {
+                self.try_fill(index, true);
+            }
+        }
+        Ok(())
+    }
+
+    /// Cancel every open order once the `CancelAllOrdersAfterX` countdown has expired
+    pub fn check_dead_man_switch(&mut self) {
+        if self
+            .cancel_at
+            .is_some_and(|deadline| unix_time() >= deadline)
+        {
+            self.cancel_at = None;
+            self.cancel_where(|_| true);
+        }
+    }
+
+    pub fn get_balance(&self) -> Value {
+        let balances: Map<String, Value> = self
+            .balances
+            .iter()
+            .map(|(asset, amount)| (asset.clone(), json!(format!("{:.10}", amount))))
+            .collect();
+        Value::Object(balances)
+    }
+
+    pub fn trade_balance(&self, params: &HashMap<String, String>) -> Value {
+        let asset = params.get("asset").map(String::as_str).unwrap_or("ZUSD");
+        let equivalent: f64 = self
+            .balances
+            .iter()
+            .filter_map(|(held, amount)| self.convert(*amount, held, asset))
+            .sum();
+        let zero = format!("{:.4}", 0.0);
+        json!({
+            "eb": format!("{:.4}", equivalent),
+            "tb": format!("{:.4}", equivalent),
+            "m": zero,
+            "n": zero,
+            "c": zero,
+            "v": zero,
+            "e": format!("{:.4}", equivalent),
+            "mf": format!("{:.4}", equivalent),
+            "ml": zero,
+        })
+    }
+
+    pub fn add_order(&mut self, params: &HashMap<String, String>) -> Result<Value, String> {
+        let pair = params
+            .get("pair")
+            .and_then(|pair| self.resolve_pair(pair))
+            .ok_or(UNKNOWN_PAIR)?
+            .clone();
+        let side = match params.get("type").map(String::as_str) {
+            Some("buy") => Side::Buy,
+            Some("sell") => Side::Sell,
+            _ => return Err("EGeneral:Invalid arguments:type".to_string()),
+        };
+        let ordertype = params
+            .get("ordertype")
+            .map(String::as_str)
+            .filter(|ordertype| {
+                matches!(
+                    *ordertype,
+                    "market"
+                        | "limit"
+                        | "stop-loss"
+                        | "take-profit"
+                        | "stop-loss-limit"
+                        | "take-profit-limit"
+                )
+            })
+            .ok_or("EGeneral:Invalid arguments:ordertype")?
+            .to_string();
+        let volume = parse_param(params, "volume")?.ok_or("EGeneral:Invalid arguments:volume")?;
+        if volume <= 0.0 {
+            return Err("EGeneral:Invalid arguments:volume".to_string());
+        }
+        if volume < pair.ordermin {
+            return Err(ORDER_MINIMUM.to_string());
+        }
+        let price = parse_param(params, "price")?;
+        let price2 = parse_param(params, "price2")?;
+        if ordertype != "market" && price.is_none() {
+            return Err("EGeneral:Invalid arguments:price".to_string());
+        }
+        if ordertype.ends_with("-limit") && price2.is_none() {
+            return Err("EGeneral:Invalid arguments:price2".to_string());
+        }
+        let userref = match params.get("userref") {
+            Some(userref) => Some(
+                userref
+                    .parse::<i64>()
+                    .map_err(|_| "EGeneral:Invalid arguments:userref")?,
+            ),
+            None => None,
+        };
+        if userref.is_some() && params.contains_key("cl_ord_id") {
+            return Err("EGeneral:Invalid arguments:userref and cl_ord_id are exclusive".to_string());
+        }
+
+        let quote = self.quotes.get(&pair.name).copied().ok_or(UNKNOWN_PAIR)?;
+        let mut order = MockOrder {
+            txid: String::new(),
+            pair: pair.name.clone(),
+            side,
+            descr: format!(
+                "{} {} {} @ {} {}",
+                side.as_str(),
+                format_volume(volume),
+                pair.altname,
+                ordertype,
+                price.map(format_price).unwrap_or_default()
+            )
+            .trim_end()
+            .to_string(),
+            ordertype,
+            volume,
+            vol_exec: 0.0,
+            cost: 0.0,
+            fee: 0.0,
+            price: price.unwrap_or(0.0),
+            price2: price2.unwrap_or(0.0),
+            userref,
+            cl_ord_id: params.get("cl_ord_id").cloned(),
+            oflags: params.get("oflags").cloned().unwrap_or_default(),
+            status: "open",
+            reason: None,
+            opentm: unix_time(),
+            closetm: None,
+            triggered: false,
+            trades: Vec::new(),
+        };
+
+        let (asset, needed) = self.reserved(&order, &pair, &quote);
+        if needed > self.available(asset) + 1e-12 {
+            return Err(INSUFFICIENT_FUNDS.to_string());
+        }
+
+        let descr = json!({ "order": order.descr });
+        if params
+            .get("validate")
+            .is_some_and(|validate| validate == "true")
+        {
+            return Ok(json!({ "descr": descr }));
+        }
+
+        self.seq += 1;
+        order.txid = format!("O{:05}-MOCK-{:06}", self.seq, self.seq);
+        let txid = order.txid.clone();
+
+        let post_only = order.oflags.split(',').any(|flag| flag == "post");
+        self.orders.push(order);
+        let index = self.orders.len() - 1;
+        if post_only && self.marketable(&self.orders[index], &quote) {
+            self.orders[index].close("canceled", Some("Post only order"));
+        } else {
+            self.try_fill(index, false);
+        }
+        let immediate = params.get("timeinforce").is_some_and(|tif| tif == "IOC");
+        if immediate && self.orders[index].is_open() {
+            self.orders[index].close("canceled", Some("Immediate or cancel"));
+        }
+
+        Ok(json!({ "descr": descr, "txid": [txid] }))
+    }
+
+    /// Cancel by txid, user reference or client order id
+    pub fn cancel_order(&mut self, params: &HashMap<String, String>) -> Result<Value, String> {
+        let count = if let Some(txid) = params.get("txid") {
+            let ids: Vec<&str> = txid.split(',').collect();
+            let userref = txid.parse::<i64>().ok();
+            self.cancel_where(|order| {
+                ids.contains(&order.txid.as_str())
+                    || (userref.is_some() && order.userref == userref)
+            })
+        } else if let Some(cl_ord_id) = params.get("cl_ord_id") {
+            self.cancel_where(|order| order.cl_ord_id.as_ref() == Some(cl_ord_id))
+        } else {
+            return Err("EGeneral:Invalid arguments:txid".to_string());
+        };
+        if count == 0 {
+            return Err(UNKNOWN_ORDER.to_string());
+        }
+        Ok(json!({ "count": count }))
+    }
+
+    pub fn cancel_all(&mut self) -> Value {
+        json!({ "count": self.cancel_where(|_| true) })
+    }
+
+    pub fn cancel_all_after(&mut self, params: &HashMap<String, String>) -> Result<Value, String> {
+        let timeout: u64 = params
+            .get("timeout")
+            .and_then(|timeout| timeout.parse().ok())
+            .ok_or("EGeneral:Invalid arguments:timeout")?;
+        let now = unix_time();
+        let trigger_time = if timeout == 0 {
+            self.cancel_at = None;
+            "0".to_string()
+        } else {
+            let deadline = now + timeout as f64;
+            self.cancel_at = Some(deadline);
+            format_rfc3339(deadline as u64)
+        };
+        Ok(json!({
+            "currentTime": format_rfc3339(now as u64),
+            "triggerTime": trigger_time,
+        }))
+    }
+
+    pub fn open_orders(&self, params: &HashMap<String, String>) -> Value {
+        let userref = params
+            .get("userref")
+            .and_then(|userref| userref.parse().ok());
+        let cl_ord_id = params.get("cl_ord_id");
+        let open = self.orders_json(params, |order| {
+            order.is_open()
+                && userref.is_none_or(|userref| order.userref == Some(userref))
+                && cl_ord_id.is_none_or(|id| order.cl_ord_id.as_ref() == Some(id))
+        });
+        json!({ "count": open.len(), "open": open })
+    }
+
+    pub fn closed_orders(&self, params: &HashMap<String, String>) -> Value {
+        let userref = params
+            .get("userref")
+            .and_then(|userref| userref.parse().ok());
+        let cl_ord_id = params.get("cl_ord_id");
+        let closed = self.orders_json(params, |order| {
+            !order.is_open()
+                && userref.is_none_or(|userref| order.userref == Some(userref))
+                && cl_ord_id.is_none_or(|id| order.cl_ord_id.as_ref() == Some(id))
+        });
+        json!({ "count": closed.len(), "closed": closed })
+    }
+
+    /// Withdraw from the balance of `asset` to the address saved as `key`, free of fees
+    pub fn withdraw(&mut self, params: &HashMap<String, String>) -> Result<Value, String> {
+        let asset = params
+            .get("asset")
+            .ok_or("EGeneral:Invalid arguments:asset")?;
+        let key = params.get("key").ok_or("EGeneral:Invalid arguments:key")?;
+        let amount = parse_param(params, "amount")?
+            .filter(|amount| *amount > 0.0)
+            .ok_or("EGeneral:Invalid arguments:amount")?;
+        if self.available(asset) < amount {
+            return Err(INSUFFICIENT_FUNDS_WITHDRAWAL.to_string());
+        }
+        *self.balances.entry(asset.clone()).or_default() -= amount;
+        self.seq += 1;
+        let refid = format!("W{:05}-MOCK", self.seq);
+        self.withdrawals.push(MockWithdrawal {
+            refid: refid.clone(),
+            asset: asset.clone(),
+            key: key.clone(),
+            amount,
+            time: unix_time(),
+        });
+        Ok(json!({ "refid": refid }))
+    }
+
+    pub fn withdraw_status(&self, params: &HashMap<String, String>) -> Value {
+        let asset = params.get("asset");
+        Value::Array(
+            self.withdrawals
+                .iter()
+                .filter(|withdrawal| asset.is_none_or(|asset| &withdrawal.asset == asset))
+                .map(MockWithdrawal::to_json)
+                .collect(),
+        )
+    }
+
+    pub fn query_orders(&self, params: &HashMap<String, String>) -> Result<Value, String> {
+        let ids: Vec<&str> = params
+            .get("txid")
+            .map(|txid| txid.split(',').filter(|id| !id.is_empty()).collect())
+            .unwrap_or_default();
+        if let Some(unknown) = ids
+            .iter()
+            .find(|id| !self.orders.iter().any(|order| order.txid == **id))
+        {
+            return Err(format!("{}: {}", UNKNOWN_ORDER, unknown));
+        }
+        Ok(Value::Object(self.orders_json(params, |order| {
+            ids.contains(&order.txid.as_str())
+        })))
+    }
+
+    pub fn trades_history(&self) -> Value {
+        let trades: Map<String, Value> = self
+            .trades
+            .iter()
+            .map(|trade| {
+                let value = json!({
+                    "ordertxid": trade.ordertxid,
+                    "pair": trade.pair,
+                    "time": trade.time,
+                    "type": trade.side.as_str(),
+                    "ordertype": trade.ordertype,
+                    "price": format_price(trade.price),
+                    "cost": format_price(trade.cost),
+                    "fee": format_price(trade.fee),
+                    "vol": format_volume(trade.volume),
+                    "margin": format_price(0.0),
+                    "misc": "",
+                    "ledgers": "",
+                    "maker": trade.maker,
+                });
+                (trade.id.clone(), value)
+            })
+            .collect();
+        json!({ "count": trades.len(), "trades": trades })
+    }
+
+    pub fn trade_volume(&self, params: &HashMap<String, String>) -> Value {
+        let volume: f64 = self
+            .trades
+            .iter()
+            .filter_map(|trade| {
+                let pair = self.pairs.get(&trade.pair)?;
+                self.convert(trade.cost, &pair.quote, "ZUSD")
+            })
+            .sum();
+        let fees: Map<String, Value> = params
+            .get("pair")
+            .map(|pairs| {
+                pairs
+                    .split(',')
+                    .filter_map(|pair| self.resolve_pair(pair))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+            .into_iter()
+            .map(|pair| {
+                let tier = json!({
+                    "fee": format!("{:.4}", pair.taker_fee),
+                    "minfee": format!("{:.4}", pair.taker_fee),
+                    "maxfee": format!("{:.4}", pair.taker_fee),
+                    "nextfee": null,
+                    "nextvolume": null,
+                    "tiervolume": format!("{:.4}", 0.0),
+                });
+                (pair.name.clone(), tier)
+            })
+            .collect();
+        json!({
+            "currency": "ZUSD",
+            "volume": format!("{:.4}", volume),
+            "fees": fees,
+        })
+    }
+
+    fn orders_json(
+        &self,
+        params: &HashMap<String, String>,
+        filter: impl Fn(&MockOrder) -> bool,
+    ) -> Map<String, Value> {
+        let trades = params.get("trades").is_some_and(|trades| trades == "true");
+        self.orders
+            .iter()
+            .filter(|order| filter(order))
+            .filter_map(|order| {
+                let pair = self.pairs.get(&order.pair)?;
+                Some((order.txid.clone(), order.to_json(pair, trades)))
+            })
+            .collect()
+    }
+
+    fn cancel_where(&mut self, filter: impl Fn(&MockOrder) -> bool) -> usize {
+        let mut count = 0;
+        for order in self.orders.iter_mut().filter(|order| order.is_open()) {
+            if filter(order) {
+                order.close("canceled", Some("User requested"));
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Asset and amount an open order keeps on hold
+    fn reserved<'a>(&self, order: &MockOrder, pair: &'a PairInfo, quote: &Quote) -> (&'a str, f64) {
+        let remaining = order.volume - order.vol_exec;
+        match order.side {
+            Side::Sell => (pair.base.as_str(), remaining),
+            Side::Buy => {
+                let price = match order.ordertype.as_str() {
+                    "market" => quote.ask,
+                    "limit" | "stop-loss" | "take-profit" => order.price,
+                    _ => order.price2,
+                };
+                let cost = remaining * price;
+                (pair.quote.as_str(), cost + cost * pair.taker_fee / 100.0)
+            }
+        }
+    }
+
+    fn available(&self, asset: &str) -> f64 {
+        let held: f64 = self
+            .orders
+            .iter()
+            .filter(|order| order.is_open())
+            .filter_map(|order| {
+                let pair = self.pairs.get(&order.pair)?;
+                let quote = self.quotes.get(&order.pair)?;
+                let (held_asset, amount) = self.reserved(order, pair, quote);
+                (held_asset == asset).then_some(amount)
+            })
+            .sum();
+        self.balance(asset) - held
+    }
+
+    fn marketable(&self, order: &MockOrder, quote: &Quote) -> bool {
+        match order.side {
+            Side::Buy => order.price >= quote.ask,
+            Side::Sell => order.price <= quote.bid,
+        }
+    }
+
+    /// Fill the order at `index` if the current quote crosses it
+    fn try_fill(&mut self, index: usize, resting: bool) {
+        let order = &self.orders[index];
+        let Some(quote) = self.quotes.get(&order.pair).copied() else {
+            return;
+        };
+        let touch = match order.side {
+            Side::Buy => quote.ask,
+            Side::Sell => quote.bid,
+        };
+        let triggered = order.triggered
+            || match (order.ordertype.as_str(), order.side) {
+                ("stop-loss" | "stop-loss-limit", Side::Buy) => touch >= order.price,
+                ("stop-loss" | "stop-loss-limit", Side::Sell) => touch <= order.price,
+                ("take-profit" | "take-profit-limit", Side::Buy) => touch <= order.price,
+                ("take-profit" | "take-profit-limit", Side::Sell) => touch >= order.price,
+                _ => true,
+            };
+        if !triggered {
+            return;
+        }
+        self.orders[index].triggered = true;
+
+        let order = &self.orders[index];
+        let limit = match order.ordertype.as_str() {
+            "limit" => Some(order.price),
+            "stop-loss-limit" | "take-profit-limit" => Some(order.price2),
+            _ => None,
+        };
+        let fills = limit.is_none_or(|limit| match order.side {
+            Side::Buy => limit >= touch,
+            Side::Sell => limit <= touch,
+        });
+        if fills {
+            // A resting limit order adds liquidity, anything else takes it
+            let maker = resting && order.ordertype == "limit";
+            self.fill(index, touch, maker);
+        }
+    }
+
+    fn fill(&mut self, index: usize, price: f64, maker: bool) {
+        let Some(pair) = self.pairs.get(&self.orders[index].pair).cloned() else {
+            return;
+        };
+        let order = &mut self.orders[index];
+        let volume = order.volume - order.vol_exec;
+        let cost = volume * price;
+        let fee =
+            cost * if maker {
+                pair.maker_fee
+            } else {
+                pair.taker_fee
+            } / 100.0;
+
+        self.seq += 1;
+        let trade = MockTrade {
+            id: format!("T{:05}-MOCK-{:06}", self.seq, self.seq),
+            ordertxid: order.txid.clone(),
+            pair: pair.name.clone(),
+            side: order.side,
+            ordertype: order.ordertype.clone(),
+            time: unix_time(),
+            price,
+            volume,
+            cost,
+            fee,
+            maker,
+        };
+        order.vol_exec += volume;
+        order.cost += cost;
+        order.fee += fee;
+        order.trades.push(trade.id.clone());
+        order.close("closed", None);
+
+        let (base_delta, quote_delta) = match order.side {
+            Side::Buy => (volume, -(cost + fee)),
+            Side::Sell => (-volume, cost - fee),
+        };
+        *self.balances.entry(pair.base).or_default() += base_delta;
+        *self.balances.entry(pair.quote).or_default() += quote_delta;
+        self.trades.push(trade);
+    }
+
+    /// Value `amount` of `from` in `to` using the last trade of a pair between them
+    fn convert(&self, amount: f64, from: &str, to: &str) -> Option<f64> {
+        if from == to {
+            return Some(amount);
+        }
+        self.pairs.values().find_map(|pair| {
+            let last = self.quotes.get(&pair.name)?.last;
+            if pair.base == from && pair.quote == to {
+                Some(amount * last)
+            } else if pair.base == to && pair.quote == from && last > 0.0 {
+                Some(amount / last)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+fn parse_param(params: &HashMap<String, String>, key: &str) -> Result<Option<f64>, String> {
+    params
+        .get(key)
+        .map(|value| {
+            value
+                .parse::<f64>()
+                .map_err(|_| format!("EGeneral:Invalid arguments:{}", key))
+        })
+        .transpose()
+}
+
+fn format_price(price: f64) -> String {
+    format!("{:.5}", price)
+}
+
+fn format_volume(volume: f64) -> String {
+    format!("{:.8}", volume)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange() -> MockExchange {
+        let pairs = PairInfo::from_fixture(
+            &serde_json::from_str(include_str!("fixtures/asset_pairs.json")).unwrap(),
+        );
+        let quotes = HashMap::from([(
+            "XXBTZUSD".to_string(),
+            Quote {
+                bid: 99.0,
+                ask: 101.0,
+                last: 100.0,
+            },
+        )]);
+        let mut exchange = MockExchange::new(pairs, quotes);
+        exchange.set_balance("ZUSD", 1000.0);
+        exchange.set_balance("XXBT", 1.0);
+        exchange
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_market_order_fills_and_charges_fees() {
+        let mut exchange = exchange();
+        let order = params(&[
+            ("pair", "XBTUSD"),
+            ("type", "buy"),
+            ("ordertype", "market"),
+            ("volume", "2"),
+        ]);
+        exchange.add_order(&order).unwrap();
+
+        // 2 @ 101 plus the 0.4% taker fee
+        assert!((exchange.balance("ZUSD") - (1000.0 - 202.0 * 1.004)).abs() < 1e-9);
+        assert_eq!(exchange.balance("XXBT"), 3.0);
+        assert_eq!(exchange.trades_history()["count"], 1);
+    }
+
+    #[test]
+    fn test_userref_and_cl_ord_id_are_exclusive() {
+        let mut exchange = exchange();
+        let order = params(&[
+            ("pair", "XXBTZUSD"),
+            ("type", "buy"),
+            ("ordertype", "limit"),
+            ("price", "90"),
+            ("volume", "1"),
+            ("userref", "7"),
+            ("cl_ord_id", "abc"),
+        ]);
+        assert!(exchange.add_order(&order).is_err());
+        assert_eq!(exchange.open_orders(&params(&[]))["count"], 0);
+    }
+
+    #[test]
+    fn test_limit_order_rests_until_crossed() {
+        let mut exchange = exchange();
+        let order = params(&[
+            ("pair", "XXBTZUSD"),
+            ("type", "sell"),
+            ("ordertype", "limit"),
+            ("price", "110"),
+            ("volume", "1"),
+            ("userref", "7"),
+        ]);
+        let response = exchange.add_order(&order).unwrap();
+        let txid = response["txid"][0].as_str().unwrap().to_string();
+        assert_eq!(
+            exchange.open_orders(&params(&[("userref", "7")]))["count"],
+            1
+        );
+
+        // The base asset is on hold, so it cannot be sold twice
+        assert_eq!(
+            exchange.add_order(&order),
+            Err(INSUFFICIENT_FUNDS.to_string())
+        );
+
+        let crossed = Quote {
+            bid: 111.0,
+            ask: 112.0,
+            last: 111.0,
+        };
+        exchange.set_quote("XBTUSD", crossed).unwrap();
+        let orders = exchange.query_orders(&params(&[("txid", &txid)])).unwrap();
+        assert_eq!(orders[&txid]["status"], "closed");
+        assert_eq!(orders[&txid]["price"], "111.00000");
+        assert_eq!(exchange.balance("XXBT"), 0.0);
+        // Filled as a maker at 0.25%
+        assert!((exchange.balance("ZUSD") - (1000.0 + 111.0 * 0.9975)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_order_validation() {
+        let mut exchange = exchange();
+        let mut order = params(&[
+            ("pair", "XBTUSD"),
+            ("type", "buy"),
+            ("ordertype", "limit"),
+            ("price", "100"),
+            ("volume", "0.00001"),
+        ]);
+        assert_eq!(exchange.add_order(&order), Err(ORDER_MINIMUM.to_string()));
+
+        order.insert("volume".to_string(), "1".to_string());
+        order.insert("validate".to_string(), "true".to_string());
+        let response = exchange.add_order(&order).unwrap();
+        assert!(response.get("txid").is_none());
+        assert_eq!(exchange.open_orders(&HashMap::new())["count"], 0);
+
+        order.insert("pair".to_string(), "DOGEUSD".to_string());
+        assert_eq!(exchange.add_order(&order), Err(UNKNOWN_PAIR.to_string()));
+    }
+
+    #[test]
+    fn test_stop_loss_and_cancel() {
+        let mut exchange = exchange();
+        let stop = params(&[
+            ("pair", "XBTUSD"),
+            ("type", "sell"),
+            ("ordertype", "stop-loss"),
+            ("price", "90"),
+            ("volume", "0.5"),
+            ("cl_ord_id", "stop-1"),
+        ]);
+        exchange.add_order(&stop).unwrap();
+        let resting = params(&[
+            ("pair", "XBTUSD"),
+            ("type", "buy"),
+            ("ordertype", "limit"),
+            ("price", "50"),
+            ("volume", "1"),
+        ]);
+        exchange.add_order(&resting).unwrap();
+
+        let falling = Quote {
+            bid: 89.0,
+            ask: 90.0,
+            last: 89.5,
+        };
+        exchange.set_quote("XBTUSD", falling).unwrap();
+        assert_eq!(exchange.balance("XXBT"), 0.5);
+        assert_eq!(
+            exchange.cancel_order(&params(&[("cl_ord_id", "stop-1")])),
+            Err(UNKNOWN_ORDER.to_string())
+        );
+        assert_eq!(exchange.cancel_all()["count"], 1);
+        assert_eq!(exchange.closed_orders(&HashMap::new())["count"], 2);
+    }
+}
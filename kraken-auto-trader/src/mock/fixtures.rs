@@ -0,0 +1,27 @@
+use crate::utils::endpoints::market::*;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Results served by the public endpoints until a test replaces them
+pub fn default_fixtures() -> HashMap<String, Value> {
+    [
+        (SYSTEM_STATUS, include_str!("fixtures/system_status.json")),
+        (ASSET_INFO, include_str!("fixtures/assets.json")),
+        (
+            TRADABLE_ASSET_PAIRS,
+            include_str!("fixtures/asset_pairs.json"),
+        ),
+        (TICKER, include_str!("fixtures/ticker.json")),
+        (OHLC, include_str!("fixtures/ohlc.json")),
+        (ORDER_BOOK, include_str!("fixtures/depth.json")),
+        (RECENT_TRADES, include_str!("fixtures/trades.json")),
+        (RECENT_SPREADS, include_str!("fixtures/spread.json")),
+    ]
+    .into_iter()
+    .map(|(endpoint, fixture)| {
+        let result = serde_json::from_str(fixture)
+            .unwrap_or_else(|e| panic!("Invalid fixture for {}: {}", endpoint, e));
+        (endpoint.to_string(), result)
+    })
+    .collect()
+}
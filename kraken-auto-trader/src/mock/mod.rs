@@ -0,0 +1,678 @@
+//! Local mock of the Kraken REST API.
+//!
+//! [`MockKraken`] binds an HTTP server to a free local port. Point a client at it through
+//! [`MockKraken::config`] to exercise every service end to end without network access:
+//! public endpoints answer from JSON fixtures, private endpoints check `API-Key`/`API-Sign`
+//! and nonces like Kraken does and trade against a simulated account, and [`Fault`]s can be
+//! queued to reproduce rate limits, outages, timeouts and malformed responses.
+
+pub mod exchange;
+pub mod fixtures;
+
+use crate::{
+    client::credentials::Permission,
+    client::kraken_apis::PrivateApi,
+    client::kraken_client::KrakenClient,
+    client::otp::Otp,
+    errors::Error,
+    middleware::KrakenClientState,
+    utils::{
+        config::KrakenConfig,
+        crypto::get_signature,
+        endpoints,
+        time::civil,
+    },
+};
+use actix_web::{
+    dev::ServerHandle, http::StatusCode, web, App, HttpRequest, HttpResponse, HttpServer,
+};
+use exchange::{MockExchange, PairInfo, Quote, UNKNOWN_PAIR};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Credentials accepted by a mock started with the default builder
+pub const MOCK_API_KEY: &str = "mock-api-key";
+pub const MOCK_API_SECRET: &str = "bW9jay1rcmFrZW4tYXBpLXNlY3JldA==";
+
+/// Failure injected in place of, or before, the next response of an endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    /// `EAPI:Rate limit exceeded`
+    RateLimit,
+    /// `EService:Unavailable`
+    Unavailable,
+    /// Answer normally after a delay; use a delay above the client timeout to simulate a timeout
+    Delay(Duration),
+    /// A truncated JSON body
+    MalformedJson,
+    /// An HTTP error status with an empty body
+    Status(u16),
+    /// `429 Too Many Requests` with a `Retry-After` header, in whole seconds
+    RetryAfter(Duration),
+    /// Any other Kraken error string
+    Error(String),
+}
+
+/// A request received by the mock, for assertions on what the client sent
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub params: HashMap<String, String>,
+    /// `API-Key` header of a private request
+    pub api_key: Option<String>,
+}
+
+struct MockState {
+    /// API key to base64 secret
+    credentials: HashMap<String, String>,
+    /// Last nonce accepted per API key
+    nonces: HashMap<String, u64>,
+    /// Second factor required by API key
+    otps: HashMap<String, Otp>,
+    /// Permissions of restricted API keys; keys not listed may call anything
+    permissions: HashMap<String, BTreeSet<Permission>>,
+    fixtures: HashMap<String, Value>,
+    /// Faults per endpoint, `None` applying to any endpoint
+    faults: HashMap<Option<String>, VecDeque<Fault>>,
+    exchange: MockExchange,
+    requests: Vec<RecordedRequest>,
+}
+
+pub struct MockKrakenBuilder {
+    credentials: HashMap<String, String>,
+    otps: HashMap<String, Otp>,
+    permissions: HashMap<String, BTreeSet<Permission>>,
+    balances: HashMap<String, f64>,
+    fixtures: HashMap<String, Value>,
+}
+
+impl Default for MockKrakenBuilder {
+    fn default() -> Self {
+        Self {
+            credentials: HashMap::from([(MOCK_API_KEY.to_string(), MOCK_API_SECRET.to_string())]),
+            otps: HashMap::new(),
+            permissions: HashMap::new(),
+            balances: HashMap::from([
+                ("ZUSD".to_string(), 100_000.0),
+                ("ZGBP".to_string(), 100_000.0),
+                ("XXBT".to_string(), 2.0),
+                ("XETH".to_string(), 50.0),
+            ]),
+            fixtures: fixtures::default_fixtures(),
+        }
+    }
+}
+
+impl MockKrakenBuilder {
+    /// Accept another API key, signed with the base64 encoded `secret`
+    pub fn with_credentials(mut self, key: &str, secret: &str) -> Self {
+        self.credentials.insert(key.to_string(), secret.to_string());
+        self
+    }
+
+    /// Require the `otp` parameter on requests signed with `key`
+    pub fn with_otp(mut self, key: &str, otp: Otp) -> Self {
+        self.otps.insert(key.to_string(), otp);
+        self
+    }
+
+    /// Restrict `key` to `permissions`, rejecting other calls with `EGeneral:Permission denied`
+    pub fn with_permissions(
+        mut self,
+        key: &str,
+        permissions: impl IntoIterator<Item = Permission>,
+    ) -> Self {
+        self.permissions
+            .insert(key.to_string(), permissions.into_iter().collect());
+        self
+    }
+
+    pub fn with_balance(mut self, asset: &str, amount: f64) -> Self {
+        self.balances.insert(asset.to_string(), amount);
+        self
+    }
+
+    /// Replace the `result` served by a public endpoint, e.g. `endpoints::market::TICKER`
+    pub fn with_fixture(mut self, endpoint: &str, result: Value) -> Self {
+        self.fixtures.insert(endpoint.to_string(), result);
+        self
+    }
+
+    pub async fn start(self) -> Result<MockKraken, Error> {
+        let pairs = self
+            .fixtures
+            .get(endpoints::market::TRADABLE_ASSET_PAIRS)
+            .map(PairInfo::from_fixture)
+            .unwrap_or_default();
+        let quotes = self
+            .fixtures
+            .get(endpoints::market::TICKER)
+            .and_then(Value::as_object)
+            .map(|tickers| {
+                tickers
+                    .iter()
+                    .filter_map(|(pair, ticker)| Some((pair.clone(), Quote::from_ticker(ticker)?)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut exchange = MockExchange::new(pairs, quotes);
+        for (asset, amount) in &self.balances {
+            exchange.set_balance(asset, *amount);
+        }
+
+        let state = web::Data::new(Mutex::new(MockState {
+            credentials: self.credentials,
+            nonces: HashMap::new(),
+            otps: self.otps,
+            permissions: self.permissions,
+            fixtures: self.fixtures,
+            faults: HashMap::new(),
+            exchange,
+            requests: Vec::new(),
+        }));
+
+        let app_state = state.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_state.clone())
+                .default_service(web::to(handle))
+        })
+        .workers(1)
+        .disable_signals()
+        .bind(("127.0.0.1", 0))?;
+        let url = server
+            .addrs()
+            .first()
+            .map(|addr| format!("http://{}", addr))
+            .ok_or_else(|| Error::Unknown("Mock server has no address".to_string()))?;
+        let server = server.run();
+        let handle = server.handle();
+        tokio::spawn(server);
+
+        Ok(MockKraken { url, state, handle })
+    }
+}
+
+/// Running mock server, stopped when dropped
+pub struct MockKraken {
+    url: String,
+    state: web::Data<Mutex<MockState>>,
+    handle: ServerHandle,
+}
+
+impl MockKraken {
+    pub fn builder() -> MockKrakenBuilder {
+        MockKrakenBuilder::default()
+    }
+
+    /// Start a mock with the default fixtures, balances and credentials
+    pub async fn start() -> Result<Self, Error> {
+        Self::builder().start().await
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Client configuration pointing at the mock, with short timeouts and retry delays
+    pub fn config(&self) -> KrakenConfig {
+        KrakenConfig {
+            base_url: self.url.clone(),
+            timeout: 5,
+            retry_delay_ms: 10,
+            rate_limit_delay_ms: 10,
+            ..KrakenConfig::default()
+        }
+    }
+
+    pub fn client_state(&self) -> Result<KrakenClientState, Error> {
+        KrakenClient::new(self.config()).map(KrakenClientState::new)
+    }
+
+    /// Private API signed with the default mock credentials
+    pub fn private_api(&self) -> Result<PrivateApi, Error> {
+        PrivateApi::builder()
+            .with_api_key(MOCK_API_KEY.to_string())
+            .with_api_secret(MOCK_API_SECRET.to_string())
+            .build()
+    }
+
+    /// Fail the next request to `endpoint` with `fault`; faults queue up in order
+    pub fn inject(&self, endpoint: &str, fault: Fault) {
+        self.push_fault(Some(endpoint.to_string()), fault);
+    }
+
+    /// Fail the next request to any endpoint with `fault`
+    pub fn inject_any(&self, fault: Fault) {
+        self.push_fault(None, fault);
+    }
+
+    fn push_fault(&self, endpoint: Option<String>, fault: Fault) {
+        self.lock()
+            .faults
+            .entry(endpoint)
+            .or_default()
+            .push_back(fault);
+    }
+
+    /// Move the market of `pair`; resting orders it crosses are filled
+    pub fn set_quote(&self, pair: &str, quote: Quote) -> Result<(), Error> {
+        self.lock()
+            .exchange
+            .set_quote(pair, quote)
+            .map_err(Error::InvalidParameter)
+    }
+
+    pub fn set_balance(&self, asset: &str, amount: f64) {
+        self.lock().exchange.set_balance(asset, amount);
+    }
+
+    /// Replace the `result` served by a public endpoint from now on
+    pub fn set_fixture(&self, endpoint: &str, result: Value) {
+        self.lock().fixtures.insert(endpoint.to_string(), result);
+    }
+
+    pub fn balance(&self, asset: &str) -> f64 {
+        self.lock().exchange.balance(asset)
+    }
+
+    /// Requests received so far, oldest first
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().requests.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub async fn stop(self) {
+        self.handle.stop(false).await;
+    }
+}
+
+impl Drop for MockKraken {
+    fn drop(&mut self) {
+        // The stop command is sent right away, the returned future only waits for completion
+        std::mem::drop(self.handle.stop(false));
+    }
+}
+
+impl MockState {
+    fn next_fault(&mut self, path: &str) -> Option<Fault> {
+        [Some(path.to_string()), None]
+            .into_iter()
+            .find_map(|key| self.faults.get_mut(&key)?.pop_front())
+    }
+
+    fn authenticate(
+        &mut self,
+        req: &HttpRequest,
+        path: &str,
+        params: &HashMap<String, String>,
+        body: &str,
+    ) -> Result<(), String> {
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let key = header("API-Key").ok_or("EAPI:Invalid key")?;
+        let secret = self.credentials.get(&key).ok_or("EAPI:Invalid key")?;
+        let nonce: u64 = params
+            .get("nonce")
+            .and_then(|nonce| nonce.parse().ok())
+            .ok_or("EAPI:Invalid nonce")?;
+
+        let expected = get_signature(path, nonce, body, secret).map_err(|e| e.to_string())?;
+        if header("API-Sign").as_deref() != Some(expected.as_str()) {
+            return Err("EAPI:Invalid signature".to_string());
+        }
+        if self.nonces.get(&key).is_some_and(|last| nonce <= *last) {
+            return Err("EAPI:Invalid nonce".to_string());
+        }
+        if let Some(otp) = self.otps.get(&key) {
+            if !params.get("otp").is_some_and(|code| otp.matches(code)) {
+                return Err("EAPI:Invalid key".to_string());
+            }
+        }
+        if self
+            .permissions
+            .get(&key)
+            .is_some_and(|allowed| !allowed.contains(&Permission::for_endpoint(path)))
+        {
+            return Err("EGeneral:Permission denied".to_string());
+        }
+        self.nonces.insert(key, nonce);
+        Ok(())
+    }
+
+    fn public(&self, path: &str, params: &HashMap<String, String>) -> Result<Value, String> {
+        use endpoints::market::*;
+
+        if path == SERVER_TIME {
+            let now = unix_time() as u64;
+            return Ok(json!({ "unixtime": now, "rfc1123": format_rfc1123(now) }));
+        }
+        let mut result = self
+            .fixtures
+            .get(path)
+            .cloned()
+            .ok_or("EGeneral:Unknown method")?;
+        match path {
+            ASSET_INFO => select_keys(result, params.get("asset"), |asset, info| {
+                info.0 == asset || Some(asset) == info.1["altname"].as_str()
+            })
+            .ok_or_else(|| "EQuery:Unknown asset".to_string()),
+            TICKER => {
+                if let Some(tickers) = result.as_object_mut() {
+                    for (pair, ticker) in tickers.iter_mut() {
+                        if let Some(quote) = self.exchange.quote(pair) {
+                            ticker["a"][0] = json!(format!("{:.5}", quote.ask));
+                            ticker["b"][0] = json!(format!("{:.5}", quote.bid));
+                            ticker["c"][0] = json!(format!("{:.5}", quote.last));
+                        }
+                    }
+                }
+                self.select_pairs(result, params)
+            }
+            TRADABLE_ASSET_PAIRS | OHLC | ORDER_BOOK | RECENT_TRADES | RECENT_SPREADS => {
+                let mut result = self.select_pairs(result, params)?;
+                if let Some(count) = params.get("count").and_then(|count| count.parse().ok()) {
+                    truncate(&mut result, count);
+                }
+                Ok(result)
+            }
+            _ => Ok(result),
+        }
+    }
+
+    /// Keep the entries of the requested pairs, and keys such as `last` that are not pairs
+    fn select_pairs(
+        &self,
+        result: Value,
+        params: &HashMap<String, String>,
+    ) -> Result<Value, String> {
+        let Some(requested) = params.get("pair") else {
+            return Ok(result);
+        };
+        let names = requested
+            .split(',')
+            .map(|pair| {
+                self.exchange
+                    .resolve_pair(pair)
+                    .map(|info| info.name.clone())
+                    .ok_or(UNKNOWN_PAIR)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let Value::Object(entries) = result else {
+            return Ok(result);
+        };
+        Ok(Value::Object(
+            entries
+                .into_iter()
+                .filter(|(key, _)| names.contains(key) || self.exchange.resolve_pair(key).is_none())
+                .collect(),
+        ))
+    }
+
+    fn private(&mut self, path: &str, params: &HashMap<String, String>) -> Result<Value, String> {
+        use endpoints::{account::*, funding::*, trading::*};
+
+        self.exchange.check_dead_man_switch();
+        let exchange = &mut self.exchange;
+        match path {
+            BALANCE => Ok(exchange.get_balance()),
+            TRADE_BALANCE => Ok(exchange.trade_balance(params)),
+            OPEN_ORDERS => Ok(exchange.open_orders(params)),
+            CLOSED_ORDERS => Ok(exchange.closed_orders(params)),
+            QUERY_ORDERS => exchange.query_orders(params),
+            TRADES_HISTORY => Ok(exchange.trades_history()),
+            TRADE_VOLUME => Ok(exchange.trade_volume(params)),
+            ADD_ORDER => exchange.add_order(params),
+            CANCEL_ORDER => exchange.cancel_order(params),
+            CANCEL_ALL_ORDERS => Ok(exchange.cancel_all()),
+            CANCEL_ALL_ORDERS_AFTER_X => exchange.cancel_all_after(params),
+            WITHDRAW_FUNDS => exchange.withdraw(params),
+            RECENT_WITHDRAWALS_STATUS => Ok(exchange.withdraw_status(params)),
+            GET_WEBSOCKETS_TOKEN => Ok(json!({
+                "token": format!("mock-token-{}", params.get("nonce").map(String::as_str).unwrap_or("0")),
+                "expires": 900,
+            })),
+            _ => Err("EGeneral:Unknown method".to_string()),
+        }
+    }
+}
+
+async fn handle(
+    req: HttpRequest,
+    body: web::Bytes,
+    state: web::Data<Mutex<MockState>>,
+) -> HttpResponse {
+    let lock = || state.lock().unwrap_or_else(|e| e.into_inner());
+    let path = req.path().to_string();
+    let body = String::from_utf8_lossy(&body).into_owned();
+    let private = path.starts_with("/0/private/");
+    let params = parse_params(if private { &body } else { req.query_string() });
+
+    let fault = {
+        let mut state = lock();
+        state.requests.push(RecordedRequest {
+            method: req.method().to_string(),
+            path: path.clone(),
+            params: params.clone(),
+            api_key: req
+                .headers()
+                .get("API-Key")
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+        });
+        state.next_fault(&path)
+    };
+    let authenticated = match fault {
+        Some(Fault::Delay(delay)) => {
+            // Kraken checks the nonce on receipt, however long the call then takes
+            let authenticated =
+                private.then(|| lock().authenticate(&req, &path, &params, &body));
+            tokio::time::sleep(delay).await;
+            authenticated
+        }
+        Some(Fault::MalformedJson) => {
+            return HttpResponse::Ok()
+                .content_type("application/json")
+                .body(r#"{"error":[],"result":{"#)
+        }
+        Some(Fault::Status(status)) => {
+            let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            return HttpResponse::build(status).finish();
+        }
+        Some(Fault::RetryAfter(delay)) => {
+            return HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", delay.as_secs().to_string()))
+                .finish();
+        }
+        Some(Fault::RateLimit) => {
+            return kraken_response(Err("EAPI:Rate limit exceeded".to_string()))
+        }
+        Some(Fault::Unavailable) => {
+            return kraken_response(Err("EService:Unavailable".to_string()))
+        }
+        Some(Fault::Error(error)) => return kraken_response(Err(error)),
+        None => None,
+    };
+
+    let mut state = lock();
+    let result = if private {
+        authenticated
+            .unwrap_or_else(|| state.authenticate(&req, &path, &params, &body))
+            .and_then(|_| state.private(&path, &params))
+    } else if path.starts_with("/0/public/") {
+        state.public(&path, &params)
+    } else {
+        return HttpResponse::NotFound().finish();
+    };
+    kraken_response(result)
+}
+
+fn kraken_response(result: Result<Value, String>) -> HttpResponse {
+    let body = match result {
+        Ok(result) => json!({ "error": [], "result": result }),
+        Err(error) => json!({ "error": [error] }),
+    };
+    HttpResponse::Ok().json(body)
+}
+
+/// Parse `key=value&...` pairs; the client does not percent-encode its parameters
+fn parse_params(encoded: &str) -> HashMap<String, String> {
+    encoded
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => (pair.to_string(), String::new()),
+        })
+        .collect()
+}
+
+/// Keep the entries whose key matches one of the comma separated `requested` names
+fn select_keys(
+    result: Value,
+    requested: Option<&String>,
+    matches: impl Fn(&str, (&String, &Value)) -> bool,
+) -> Option<Value> {
+    let Some(requested) = requested else {
+        return Some(result);
+    };
+    let entries = result.as_object()?;
+    let mut selected = Map::new();
+    for name in requested.split(',') {
+        let (key, value) = entries.iter().find(|entry| matches(name, *entry))?;
+        selected.insert(key.clone(), value.clone());
+    }
+    Some(Value::Object(selected))
+}
+
+/// Keep the `count` most recent entries of each pair, or the `count` best levels of a book
+fn truncate(result: &mut Value, count: usize) {
+    let Some(entries) = result.as_object_mut() else {
+        return;
+    };
+    for value in entries.values_mut() {
+        match value {
+            Value::Array(rows) => {
+                let excess = rows.len().saturating_sub(count);
+                rows.drain(..excess);
+            }
+            Value::Object(book) => {
+                for side in ["asks", "bids"] {
+                    if let Some(Value::Array(levels)) = book.get_mut(side) {
+                        levels.truncate(count);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn unix_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs_f64())
+        .unwrap_or_default()
+}
+
+/// e.g. "Thu, 1 Jan 26 00:00:00 +0000", as in `Time` responses
+fn format_rfc1123(secs: u64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (year, month, day, hour, minute, second) = civil(secs);
+    format!(
+        "{}, {} {} {:02} {:02}:{:02}:{:02} +0000",
+        WEEKDAYS[(secs / 86_400 % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year % 100,
+        hour,
+        minute,
+        second
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::endpoints::{account::BALANCE, market::TICKER};
+
+    /// Send a signed private request with an explicit nonce
+    async fn post(server: &MockKraken, path: &str, nonce: u64, secret: &str) -> Value {
+        let body = format!("nonce={}", nonce);
+        let signature = get_signature(path, nonce, &body, secret).unwrap();
+        reqwest::Client::new()
+            .post(format!("{}{}", server.url(), path))
+            .header("API-Key", MOCK_API_KEY)
+            .header("API-Sign", signature)
+            .body(body)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_private_requests_require_signature_and_increasing_nonce() {
+        let server = MockKraken::start().await.unwrap();
+
+        let response = post(&server, BALANCE, 10, MOCK_API_SECRET).await;
+        assert_eq!(response["error"], json!([]));
+        assert_eq!(response["result"]["XXBT"], "2.0000000000");
+
+        let replayed = post(&server, BALANCE, 10, MOCK_API_SECRET).await;
+        assert_eq!(replayed["error"], json!(["EAPI:Invalid nonce"]));
+
+        let forged = post(&server, BALANCE, 11, "b3RoZXItc2VjcmV0").await;
+        assert_eq!(forged["error"], json!(["EAPI:Invalid signature"]));
+
+        // A rejected signature does not consume the nonce
+        let response = post(&server, BALANCE, 11, MOCK_API_SECRET).await;
+        assert_eq!(response["error"], json!([]));
+    }
+
+    #[actix_web::test]
+    async fn test_faults_are_served_in_order() {
+        let server = MockKraken::start().await.unwrap();
+        server.inject(TICKER, Fault::Unavailable);
+        server.inject(TICKER, Fault::MalformedJson);
+        server.inject_any(Fault::Status(502));
+
+        let url = format!("{}{}?pair=XBTUSD", server.url(), TICKER);
+        let get = || async { reqwest::get(&url).await.unwrap() };
+
+        let unavailable: Value = get().await.json().await.unwrap();
+        assert_eq!(unavailable["error"], json!(["EService:Unavailable"]));
+        assert!(get().await.json::<Value>().await.is_err());
+        assert_eq!(get().await.status(), 502);
+
+        let ticker: Value = get().await.json().await.unwrap();
+        let pairs: Vec<&String> = ticker["result"].as_object().unwrap().keys().collect();
+        assert_eq!(pairs, vec!["XXBTZUSD"]);
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[test]
+    fn test_time_formats() {
+        assert_eq!(
+            format_rfc1123(1_767_225_600),
+            "Thu, 1 Jan 26 00:00:00 +0000"
+        );
+    }
+}
@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-pub type Balance = Option<HashMap<String, String>>;
+use super::symbols::{AssetId, Pair};
+
+pub type Balance = Option<HashMap<AssetId, String>>;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ExtendedBalance {
@@ -26,7 +28,7 @@ pub struct TradeBalance {
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct OrderDescription {
-    pub pair: String,
+    pub pair: Pair,
     pub r#type: String,
     pub ordertype: String,
     pub price: String,
@@ -38,8 +40,9 @@ pub struct OrderDescription {
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Order {
-    pub refid: String,
-    pub userref: Option<String>,
+    pub refid: Option<String>,
+    pub userref: Option<i64>,
+    pub cl_ord_id: Option<String>,
     pub status: String,
     pub opentm: f64,
     pub starttm: Option<f64>,
@@ -59,7 +62,7 @@ pub struct Order {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Trade {
     pub ordertxid: String,
-    pub pair: String,
+    pub pair: Pair,
     pub time: f64,
     pub r#type: String,
     pub ordertype: String,
@@ -76,7 +79,7 @@ pub struct Trade {
 pub struct Position {
     pub ordertxid: String,
     pub posstatus: String,
-    pub pair: String,
+    pub pair: Pair,
     pub time: f64,
     pub r#type: String,
     pub ordertype: String,
@@ -96,7 +99,7 @@ pub struct Ledger {
     pub time: f64,
     pub r#type: String,
     pub aclass: String,
-    pub asset: String,
+    pub asset: AssetId,
     pub amount: String,
     pub fee: String,
     pub balance: String,
@@ -116,7 +119,7 @@ pub struct FeeTier {
 pub struct TradeVolume {
     pub currency: String,
     pub volume: String,
-    pub fees: Option<HashMap<String, FeeTier>>,
+    pub fees: Option<HashMap<Pair, FeeTier>>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -1,11 +1,17 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::symbols::AssetId;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct DepositMethod {
     pub method: String,
+    /// Maximum net amount that can be deposited now, `false` when there is none
+    #[serde(default, deserialize_with = "string_or_false")]
     pub limit: Option<String>,
     pub fee: String,
+    #[serde(rename = "address-setup-fee")]
     pub address_setup_fee: Option<String>,
+    #[serde(rename = "gen-address", default)]
     pub gen_address: bool,
 }
 
@@ -20,7 +26,7 @@ pub struct DepositAddress {
 pub struct DepositStatus {
     pub method: String,
     pub aclass: String,
-    pub asset: String,
+    pub asset: AssetId,
     pub refid: String,
     pub txid: String,
     pub info: String,
@@ -42,7 +48,7 @@ pub struct WithdrawalMethod {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct WithdrawalAddress {
     pub address: String,
-    pub asset: String,
+    pub asset: AssetId,
     pub method: String,
     pub key: Option<String>,
     pub verified: bool,
@@ -65,7 +71,7 @@ pub struct WithdrawalResponse {
 pub struct WithdrawalStatus {
     pub method: String,
     pub aclass: String,
-    pub asset: String,
+    pub asset: AssetId,
     pub refid: String,
     pub txid: Option<String>,
     pub info: String,
@@ -79,4 +85,35 @@ pub struct WithdrawalStatus {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct WalletTransfer {
     pub refid: String,
-} 
\ No newline at end of file
+}
+
+/// A string, or `false` standing for no value
+fn string_or_false<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Option::<serde_json::Value>::deserialize(deserializer)? {
+        Some(serde_json::Value::String(value)) => Some(value),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deposit_method() {
+        let method: DepositMethod = serde_json::from_str(
+            r#"{"method":"Bitcoin","limit":false,"fee":"0.0000000000","gen-address":true}"#,
+        )
+        .unwrap();
+        assert_eq!(method.limit, None);
+        assert!(method.gen_address);
+
+        let method: DepositMethod =
+            serde_json::from_str(r#"{"method":"SEPA","limit":"25000.00","fee":"0"}"#).unwrap();
+        assert_eq!(method.limit.as_deref(), Some("25000.00"));
+        assert!(!method.gen_address);
+    }
+}
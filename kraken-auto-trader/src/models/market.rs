@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use super::symbols::{AssetId, Pair};
+use crate::errors::Error;
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ServerTime {
     pub unixtime: i64,
@@ -13,12 +16,13 @@ pub struct SystemStatus {
     pub timestamp: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Asset {
     pub aclass: String,
     pub altname: String,
     pub decimals: i32,
     pub display_decimals: i32,
+    pub status: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -26,9 +30,9 @@ pub struct AssetPair {
     pub altname: String,
     pub wsname: Option<String>,
     pub aclass_base: String,
-    pub base: String,
+    pub base: AssetId,
     pub aclass_quote: String,
-    pub quote: String,
+    pub quote: AssetId,
     #[deprecated]
     pub lot: String,
     pub pair_decimals: i32,
@@ -37,8 +41,8 @@ pub struct AssetPair {
     pub lot_multiplier: i32,
     pub leverage_buy: Vec<i32>,
     pub leverage_sell: Vec<i32>,
-    pub fees: Vec<Vec<String>>,
-    pub fees_maker: Option<Vec<Vec<String>>>,
+    pub fees: Vec<Vec<f64>>,
+    pub fees_maker: Option<Vec<Vec<f64>>>,
     pub fee_volume_currency: String,
     pub margin_call: i32,
     pub margin_stop: i32,
@@ -63,7 +67,7 @@ pub struct Ticker {
     pub o: String,      // Today's opening price
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OHLCData {
     pub time: i64,      // Unix timestamp
     pub open: String,   // Opening price
@@ -75,10 +79,53 @@ pub struct OHLCData {
     pub count: i32,     // Number of trades
 }
 
+/// OHLC candle with numeric values
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+pub struct Candle {
+    pub time: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub vwap: f64,
+    pub volume: f64,
+    pub count: i32,
+}
+
+impl TryFrom<&OHLCData> for Candle {
+    type Error = Error;
+
+    fn try_from(data: &OHLCData) -> Result<Self, Error> {
+        let parse = |field: &str, value: &str| {
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| {
+                    Error::InvalidResponse(format!(
+                        "Invalid {} `{}` in candle at {}",
+                        field, value, data.time
+                    ))
+                })
+        };
+        Ok(Self {
+            time: data.time,
+            open: parse("open", &data.open)?,
+            high: parse("high", &data.high)?,
+            low: parse("low", &data.low)?,
+            close: parse("close", &data.close)?,
+            vwap: parse("vwap", &data.vwap)?,
+            volume: parse("volume", &data.volume)?,
+            count: data.count,
+        })
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct OHLCResponse {
     pub last: i64, // ID to be used as since when polling for new data
-    pub data: Vec<OHLCData>,
+    #[serde(flatten)]
+    pub data: HashMap<Pair, Vec<OHLCData>>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -116,13 +163,14 @@ pub struct Spread {
 pub struct RecentTradesResponse {
     pub last: String,
     #[serde(flatten)]
-    pub trades: HashMap<String, Vec<Trade>>,
+    pub trades: HashMap<Pair, Vec<Trade>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RecentSpreadsResponse {
     pub last: u64, // ID to be used as since when polling for new spread data
-    pub spreads: HashMap<String, Vec<SpreadEntry>>, // Spread data for each asset pair
+    #[serde(flatten)]
+    pub spreads: HashMap<Pair, Vec<SpreadEntry>>, // Spread data for each asset pair
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
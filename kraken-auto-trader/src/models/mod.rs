@@ -2,5 +2,6 @@ pub mod account;
 pub mod market;
 pub mod trading;
 pub mod funding;
+pub mod symbols;
 
 pub use account::{Balance, TradeBalance};
\ No newline at end of file
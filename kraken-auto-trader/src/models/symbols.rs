@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use std::{borrow::Borrow, fmt};
+
+/// Name of an asset pair, e.g. "XXBTZUSD"
+///
+/// Kraken returns canonical pair names as keys of market data responses and accepts most
+/// spellings in requests. Names are trimmed and upper-cased on construction; use
+/// `SymbolResolver` to turn any spelling into the canonical name. A pair is a single name,
+/// methods taking several pairs take a slice of them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Pair(String);
+
+/// Name of an asset, e.g. "XXBT"
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct AssetId(String);
+
+macro_rules! symbol_impls {
+    ($name:ident) => {
+        impl $name {
+            pub fn new(name: impl Into<String>) -> Self {
+                let name = name.into();
+                let trimmed = name.trim();
+                if trimmed.len() == name.len() && !name.bytes().any(|b| b.is_ascii_lowercase()) {
+                    Self(name)
+                } else {
+                    Self(trimmed.to_ascii_uppercase())
+                }
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(name: &str) -> Self {
+                Self::new(name)
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(name: String) -> Self {
+                Self::new(name)
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+    };
+}
+
+symbol_impls!(Pair);
+symbol_impls!(AssetId);
+
+/// Join symbols into the comma separated list Kraken expects
+pub fn join_symbols<T: AsRef<str>>(symbols: &[T]) -> String {
+    symbols
+        .iter()
+        .map(|symbol| symbol.as_ref())
+        .collect::<Vec<&str>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_are_normalized() {
+        assert_eq!(Pair::from(" xbtusd ").as_str(), "XBTUSD");
+        assert_eq!(Pair::from("XETHZGBP".to_string()), Pair::new("xethzgbp"));
+        assert_eq!(AssetId::from("xbt.f").as_str(), "XBT.F");
+        assert_eq!(
+            join_symbols(&[Pair::from("eth/gbp"), Pair::from("XXBTZUSD")]),
+            "ETH/GBP,XXBTZUSD"
+        );
+    }
+}
@@ -1,8 +1,172 @@
 use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Duration};
+
+use super::symbols::Pair;
+use crate::clock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+impl OrderSide {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderSide::Buy => "buy",
+            OrderSide::Sell => "sell",
+        }
+    }
+
+    /// The side that closes a position opened on this side
+    pub fn opposite(&self) -> Self {
+        match self {
+            OrderSide::Buy => OrderSide::Sell,
+            OrderSide::Sell => OrderSide::Buy,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OrderType {
+    Market,
+    Limit,
+    Iceberg,
+    StopLoss,
+    TakeProfit,
+    StopLossLimit,
+    TakeProfitLimit,
+    TrailingStop,
+    TrailingStopLimit,
+    SettlePosition,
+}
+
+impl OrderType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderType::Market => "market",
+            OrderType::Limit => "limit",
+            OrderType::Iceberg => "iceberg",
+            OrderType::StopLoss => "stop-loss",
+            OrderType::TakeProfit => "take-profit",
+            OrderType::StopLossLimit => "stop-loss-limit",
+            OrderType::TakeProfitLimit => "take-profit-limit",
+            OrderType::TrailingStop => "trailing-stop",
+            OrderType::TrailingStopLimit => "trailing-stop-limit",
+            OrderType::SettlePosition => "settle-position",
+        }
+    }
+}
+
+/// Parameters of an AddOrder request
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NewOrder {
+    pub pair: Pair,
+    pub side: OrderSide,
+    pub ordertype: OrderType,
+    pub volume: String,
+    pub price: Option<String>,
+    pub price2: Option<String>,
+    pub leverage: Option<String>,
+    pub oflags: Option<String>,
+    pub timeinforce: Option<String>,
+    pub starttm: Option<String>,
+    pub expiretm: Option<String>,
+    pub reduce_only: Option<bool>,
+    /// Not sent along with `cl_ord_id`, as Kraken accepts only one of the two
+    pub userref: Option<i32>,
+    pub cl_ord_id: Option<String>,
+    #[serde(default)]
+    pub validate: bool,
+}
+
+impl NewOrder {
+    pub fn new(pair: Pair, side: OrderSide, ordertype: OrderType, volume: String) -> Self {
+        Self {
+            pair,
+            side,
+            ordertype,
+            volume,
+            price: None,
+            price2: None,
+            leverage: None,
+            oflags: None,
+            timeinforce: None,
+            starttm: None,
+            expiretm: None,
+            reduce_only: None,
+            userref: None,
+            cl_ord_id: None,
+            validate: false,
+        }
+    }
+
+    /// Schedule the order to start `delay` from now, as an absolute time on Kraken's clock
+    pub fn start_after(mut self, delay: Duration) -> Self {
+        self.starttm = Some(unix_time_after(delay));
+        self
+    }
+
+    /// Let the order expire `delay` from now, as an absolute time on Kraken's clock
+    pub fn expire_after(mut self, delay: Duration) -> Self {
+        self.expiretm = Some(unix_time_after(delay));
+        self
+    }
+
+    /// Convert the order into AddOrder form parameters
+    pub fn to_params(&self) -> HashMap<String, String> {
+        let mut params = HashMap::new();
+        params.insert("pair".to_string(), self.pair.to_string());
+        params.insert("type".to_string(), self.side.as_str().to_string());
+        params.insert("ordertype".to_string(), self.ordertype.as_str().to_string());
+        params.insert("volume".to_string(), self.volume.clone());
+        if let Some(price) = &self.price {
+            params.insert("price".to_string(), price.clone());
+        }
+        if let Some(price2) = &self.price2 {
+            params.insert("price2".to_string(), price2.clone());
+        }
+        if let Some(leverage) = &self.leverage {
+            params.insert("leverage".to_string(), leverage.clone());
+        }
+        if let Some(oflags) = &self.oflags {
+            params.insert("oflags".to_string(), oflags.clone());
+        }
+        if let Some(timeinforce) = &self.timeinforce {
+            params.insert("timeinforce".to_string(), timeinforce.clone());
+        }
+        if let Some(starttm) = &self.starttm {
+            params.insert("starttm".to_string(), starttm.clone());
+        }
+        if let Some(expiretm) = &self.expiretm {
+            params.insert("expiretm".to_string(), expiretm.clone());
+        }
+        if let Some(reduce_only) = self.reduce_only {
+            params.insert("reduce_only".to_string(), reduce_only.to_string());
+        }
+        match (&self.cl_ord_id, self.userref) {
+            (Some(cl_ord_id), _) => {
+                params.insert("cl_ord_id".to_string(), cl_ord_id.clone());
+            }
+            (None, Some(userref)) => {
+                params.insert("userref".to_string(), userref.to_string());
+            }
+            (None, None) => {}
+        }
+        if self.validate {
+            params.insert("validate".to_string(), "true".to_string());
+        }
+        params
+    }
+}
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AddOrderResponse {
     pub descr: OrderDescription,
+    /// Empty when the order was only validated
+    #[serde(default)]
     pub txid: Vec<String>,
 }
 
@@ -24,6 +188,7 @@ pub struct CancelAllOrdersResponse {
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CancelAllOrdersAfterXResponse {
     pub current_time: String,
     pub trigger_time: String,
@@ -56,4 +221,22 @@ pub struct OrderStatus {
     pub reason: Option<String>,
     pub closetm: Option<f64>,
     pub trades: Option<Vec<String>>,
-} 
\ No newline at end of file
+}
+
+/// Entry of the WebSocket v2 `executions` channel
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExecutionReport {
+    pub exec_type: String,
+    pub order_id: String,
+    pub cl_ord_id: Option<String>,
+    pub order_userref: Option<i64>,
+    pub order_status: Option<String>,
+    pub cum_qty: Option<f64>,
+    pub avg_price: Option<f64>,
+    pub reason: Option<String>,
+    pub timestamp: Option<String>,
+}
+
+fn unix_time_after(delay: Duration) -> String {
+    ((clock::unix_time() + delay.as_secs_f64()).ceil() as u64).to_string()
+}
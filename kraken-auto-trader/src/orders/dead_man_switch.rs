@@ -0,0 +1,229 @@
+use super::manager::OrderManager;
+use crate::{
+    errors::Error, middleware::KrakenClientExt, services::trading::Trading,
+    utils::config::DeadManSwitchConfig,
+};
+use serde::Serialize;
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    sync::{watch, Mutex},
+    task::JoinHandle,
+};
+use tracing::{debug, info, warn};
+
+/// State of the dead man's switch as reported by the health endpoint
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadManSwitchStatus {
+    pub armed: bool,
+    pub timeout_secs: u32,
+    pub interval_secs: u64,
+    pub last_heartbeat: Option<u64>,
+    pub trigger_time: Option<String>,
+    pub last_error: Option<String>,
+}
+
+/// Keeps Kraken's CancelAllOrdersAfterX countdown armed while the bot is healthy.
+///
+/// Each heartbeat pushes the countdown back by `timeout_secs`. If the process hangs, loses
+/// connectivity or its health check fails, heartbeats stop and Kraken cancels every open order
+/// once the countdown expires, including orders placed from elsewhere on the same account.
+/// A clean shutdown cancels every open order on the account and disarms the countdown, whether or
+/// not heartbeats were sent.
+pub struct DeadManSwitch {
+    trading: Trading,
+    config: DeadManSwitchConfig,
+    status: Mutex<DeadManSwitchStatus>,
+    stop: watch::Sender<bool>,
+    health_check: Option<Box<dyn Fn() -> bool + Send + Sync>>,
+    orders: Option<Arc<OrderManager>>,
+}
+
+impl DeadManSwitch {
+    pub fn new(trading: Trading, config: DeadManSwitchConfig) -> Self {
+        let status = DeadManSwitchStatus {
+            armed: false,
+            timeout_secs: config.timeout_secs,
+            interval_secs: config.interval_secs,
+            last_heartbeat: None,
+            trigger_time: None,
+            last_error: None,
+        };
+        Self {
+            trading,
+            config,
+            status: Mutex::new(status),
+            stop: watch::channel(false).0,
+            health_check: None,
+            orders: None,
+        }
+    }
+
+    /// Only re-arm the countdown while `check` returns true
+    pub fn with_health_check<F>(mut self, check: F) -> Self
+    where
+        F: Fn() -> bool + Send + Sync + 'static,
+    {
+        self.health_check = Some(Box::new(check));
+        self
+    }
+
+    /// Refresh the working orders of `orders` once they are cancelled on shutdown
+    pub fn with_order_manager(mut self, orders: Arc<OrderManager>) -> Self {
+        self.orders = Some(orders);
+        self
+    }
+
+    /// Re-arm the countdown once
+    pub async fn heartbeat(&self, req: impl KrakenClientExt) -> Result<(), Error> {
+        let result = self
+            .trading
+            .cancel_all_orders_after(req, self.config.timeout_secs)
+            .await;
+
+        let mut status = self.status.lock().await;
+        match result {
+            Ok(response) => {
+                debug!("Dead man's switch armed until {}", response.trigger_time);
+                status.armed = true;
+                status.last_heartbeat = Some(unix_time());
+                status.trigger_time = Some(response.trigger_time);
+                status.last_error = None;
+                Ok(())
+            }
+            Err(e) => {
+                status.last_error = Some(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    /// Disable the countdown without cancelling any order
+    pub async fn disarm(&self, req: impl KrakenClientExt) -> Result<(), Error> {
+        self.trading.cancel_all_orders_after(req, 0).await?;
+
+        let mut status = self.status.lock().await;
+        status.armed = false;
+        status.trigger_time = None;
+        Ok(())
+    }
+
+    /// Start sending heartbeats every `interval_secs` in the background
+    pub fn spawn<R>(self: &Arc<Self>, req: R) -> JoinHandle<()>
+    where
+        R: KrakenClientExt + Clone + Send + 'static,
+    {
+        let switch = self.clone();
+        let mut stop = self.stop.subscribe();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(switch.config.interval());
+            info!(
+                "Dead man's switch started ({}s timeout, {}s interval)",
+                switch.config.timeout_secs, switch.config.interval_secs
+            );
+            while !*stop.borrow() {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = stop.changed() => break,
+                }
+                if !switch.is_healthy() {
+                    warn!("Health check failed, letting the dead man's switch countdown run");
+                    continue;
+                }
+                if let Err(e) = switch.heartbeat(req.clone()).await {
+                    warn!("Dead man's switch heartbeat failed: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Stop the heartbeat, cancel every open order on the account and disarm the countdown.
+    /// Returns how many orders were cancelled. The countdown is disarmed even if the cancel fails
+    pub async fn shutdown(&self, req: impl KrakenClientExt + Clone) -> Result<usize, Error> {
+        self.stop.send_replace(true);
+        let cancelled = self.trading.cancel_all_orders(req.clone()).await;
+        match &cancelled {
+            Ok(response) => info!("Cancelled {} open orders on shutdown", response.count),
+            Err(e) => warn!("Failed to cancel open orders: {}", e),
+        }
+        if let (Ok(_), Some(orders)) = (&cancelled, &self.orders) {
+            if let Err(e) = orders.sync(req.clone()).await {
+                warn!("Failed to refresh the tracked orders: {}", e);
+            }
+        }
+        if let Err(e) = self.disarm(req).await {
+            warn!("Failed to disarm dead man's switch: {}", e);
+        }
+        cancelled.map(|response| response.count.max(0) as usize)
+    }
+
+    /// Get the current state of the switch
+    pub async fn status(&self) -> DeadManSwitchStatus {
+        let mut status = self.status.lock().await.clone();
+        // The countdown has fired if no heartbeat landed within the timeout
+        let expired = status
+            .last_heartbeat
+            .is_none_or(|last| last + self.config.timeout_secs as u64 <= unix_time());
+        status.armed = status.armed && !expired;
+        status
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.health_check.as_ref().is_none_or(|check| check())
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::kraken_apis::PrivateApi;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn switch() -> DeadManSwitch {
+        let api = PrivateApi::builder()
+            .with_api_key("key".to_string())
+            .with_api_secret("c2VjcmV0".to_string())
+            .build()
+            .unwrap();
+        DeadManSwitch::new(Trading::with_api(api), DeadManSwitchConfig::default())
+    }
+
+    #[tokio::test]
+    async fn test_status_expires_without_heartbeat() {
+        let switch = switch();
+        assert!(!switch.status().await.armed);
+
+        {
+            let mut status = switch.status.lock().await;
+            status.armed = true;
+            status.last_heartbeat = Some(unix_time());
+        }
+        assert!(switch.status().await.armed);
+
+        switch.status.lock().await.last_heartbeat = Some(unix_time() - 61);
+        let status = switch.status().await;
+        assert!(!status.armed);
+        assert_eq!(status.timeout_secs, 60);
+    }
+
+    #[test]
+    fn test_health_check() {
+        assert!(switch().is_healthy());
+
+        let healthy = Arc::new(AtomicBool::new(false));
+        let flag = healthy.clone();
+        let switch = switch().with_health_check(move || flag.load(Ordering::Relaxed));
+        assert!(!switch.is_healthy());
+        healthy.store(true, Ordering::Relaxed);
+        assert!(switch.is_healthy());
+    }
+}
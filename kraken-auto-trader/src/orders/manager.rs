@@ -0,0 +1,885 @@
+use crate::{
+    client::idempotency::outcome_unknown,
+    errors::Error,
+    instruments::registry::{Instrument, InstrumentRegistry},
+    metrics::metrics,
+    middleware::KrakenClientExt,
+    models::{
+        account::Order,
+        symbols::Pair,
+        trading::{ExecutionReport, NewOrder},
+    },
+    services::{account_details::Account, market_data::MarketData, trading::Trading},
+    utils::config::RiskConfig,
+};
+
+use super::trading_gate::TradingGate;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+/// Lifecycle state of a locally tracked order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderState {
+    PendingNew,
+    Open,
+    PartiallyFilled,
+    Filled,
+    CancelPending,
+    Cancelled,
+    Rejected,
+    Expired,
+}
+
+impl OrderState {
+    /// Whether the order can no longer change
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            OrderState::Filled | OrderState::Cancelled | OrderState::Rejected | OrderState::Expired
+        )
+    }
+
+    /// Map a REST order status (`pending`, `open`, `closed`, `canceled`, `expired`)
+    pub fn from_rest_status(status: &str, vol_exec: f64) -> Option<Self> {
+        match status {
+            "pending" => Some(OrderState::PendingNew),
+            "open" if vol_exec > 0.0 => Some(OrderState::PartiallyFilled),
+            "open" => Some(OrderState::Open),
+            "closed" => Some(OrderState::Filled),
+            "canceled" => Some(OrderState::Cancelled),
+            "expired" => Some(OrderState::Expired),
+            _ => None,
+        }
+    }
+
+    /// Map a WebSocket v2 `order_status` or `exec_type` value
+    pub fn from_ws_status(status: &str) -> Option<Self> {
+        match status {
+            "pending_new" => Some(OrderState::PendingNew),
+            "new" => Some(OrderState::Open),
+            "partially_filled" | "trade" => Some(OrderState::PartiallyFilled),
+            "filled" => Some(OrderState::Filled),
+            "canceled" => Some(OrderState::Cancelled),
+            "expired" => Some(OrderState::Expired),
+            _ => None,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            OrderState::PendingNew => 0,
+            OrderState::Open => 1,
+            OrderState::PartiallyFilled => 2,
+            OrderState::CancelPending => 3,
+            _ => 4,
+        }
+    }
+
+    /// Apply an update, ignoring stale updates that would move the order backwards
+    pub fn advance(self, next: OrderState) -> OrderState {
+        if self.is_terminal() || next.rank() < self.rank() {
+            self
+        } else {
+            next
+        }
+    }
+}
+
+/// An order submitted through the [`OrderManager`]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TrackedOrder {
+    pub cl_ord_id: String,
+    pub txid: Option<String>,
+    pub order: NewOrder,
+    pub state: OrderState,
+    pub vol_exec: f64,
+    /// Average price of the executed volume
+    #[serde(default)]
+    pub avg_price: Option<f64>,
+    pub reason: Option<String>,
+    pub created_at: f64,
+    pub updated_at: f64,
+}
+
+impl TrackedOrder {
+    fn new(cl_ord_id: String, order: NewOrder) -> Self {
+        let now = unix_time();
+        Self {
+            cl_ord_id,
+            txid: None,
+            order,
+            state: OrderState::PendingNew,
+            vol_exec: 0.0,
+            avg_price: None,
+            reason: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Raise the executed volume, counting a fill when it grows
+    fn record_fill(&mut self, vol_exec: f64) {
+        if vol_exec > self.vol_exec {
+            metrics()
+                .order_fills
+                .with_label_values(&[self.order.pair.as_str(), self.order.side.as_str()])
+                .inc();
+            self.vol_exec = vol_exec;
+        }
+    }
+
+    fn transition(&mut self, next: OrderState) {
+        let state = self.state.advance(next);
+        if state != self.state {
+            debug!(
+                "Order {} moved from {:?} to {:?}",
+                self.cl_ord_id, self.state, state
+            );
+            self.state = state;
+        }
+        self.updated_at = unix_time();
+    }
+}
+
+/// Tracks the orders placed by this process and keeps their state in sync with Kraken
+pub struct OrderManager {
+    trading: Trading,
+    account: Account,
+    /// Prices orders without a limit price for the notional risk limit
+    market: MarketData,
+    registry: Option<Arc<InstrumentRegistry>>,
+    gate: Option<Arc<TradingGate>>,
+    risk: std::sync::RwLock<RiskConfig>,
+    orders: Mutex<HashMap<String, TrackedOrder>>,
+    /// Submissions in progress by `cl_ord_id`, so that a concurrent submission of the same order
+    /// waits for the first one instead of placing it again
+    in_flight: std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>,
+    max_submit_attempts: u32,
+}
+
+/// A submission in progress, forgotten once the last submitter of its `cl_ord_id` is done
+struct InFlight<'a> {
+    manager: &'a OrderManager,
+    cl_ord_id: String,
+    slot: Arc<Mutex<()>>,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self
+            .manager
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        // The map and this submission hold the slot; anyone else is still waiting on it
+        if Arc::strong_count(&self.slot) <= 2 {
+            in_flight.remove(&self.cl_ord_id);
+        }
+    }
+}
+
+impl OrderManager {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self::with_services(Trading::new()?, Account::new()?))
+    }
+
+    pub fn with_services(trading: Trading, account: Account) -> Self {
+        Self {
+            trading,
+            account,
+            market: MarketData::new(),
+            registry: None,
+            gate: None,
+            risk: std::sync::RwLock::new(RiskConfig::default()),
+            orders: Mutex::new(HashMap::new()),
+            in_flight: std::sync::Mutex::new(HashMap::new()),
+            max_submit_attempts: 3,
+        }
+    }
+
+    /// Validate and normalize orders against instrument metadata before they are sent
+    pub fn with_registry(mut self, registry: Arc<InstrumentRegistry>) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Hold orders back, or adapt them, while the exchange is not fully online
+    pub fn with_trading_gate(mut self, gate: Arc<TradingGate>) -> Self {
+        self.gate = Some(gate);
+        self
+    }
+
+    /// Refuse orders breaking `risk` before they are sent
+    pub fn with_risk_limits(self, risk: RiskConfig) -> Self {
+        self.set_risk_limits(risk);
+        self
+    }
+
+    /// Replace the risk limits checked by later submissions
+    pub fn set_risk_limits(&self, risk: RiskConfig) {
+        *self.risk.write().unwrap_or_else(|e| e.into_inner()) = risk;
+    }
+
+    pub fn risk_limits(&self) -> RiskConfig {
+        self.risk.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Trading rules of `pair`, when orders are validated against an instrument registry
+    pub async fn instrument(
+        &self,
+        req: impl KrakenClientExt + Clone,
+        pair: &Pair,
+    ) -> Result<Option<Instrument>, Error> {
+        match &self.registry {
+            Some(registry) => {
+                registry.ensure_fresh(req).await?;
+                Ok(registry.instrument(pair.as_str()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Set how many times a submission is attempted when its outcome is unknown
+    pub fn with_max_submit_attempts(mut self, attempts: u32) -> Self {
+        self.max_submit_attempts = attempts.max(1);
+        self
+    }
+
+    /// Submit an order, assigning it a `cl_ord_id` if it has none. Orders are identified by their
+    /// `cl_ord_id` alone, so they may not carry a `userref`.
+    ///
+    /// When a submission fails without a definite answer from Kraken (timeouts, network errors,
+    /// `EService:Unavailable`), the order is looked up by `cl_ord_id` before it is sent again,
+    /// so a retry never places it twice. Submitting a `cl_ord_id` that is already tracked returns
+    /// the tracked order instead of placing a new one, after waiting for a submission of it still
+    /// in progress. Orders with `validate` set are sent but not
+    /// tracked. With an instrument registry, invalid orders fail before anything is sent, as do
+    /// orders breaking the risk limits and, with a trading gate, orders the exchange does not
+    /// currently accept. An order counts against `max_open_orders` from the moment it is checked,
+    /// so concurrent submissions cannot exceed it.
+    pub async fn submit(
+        &self,
+        req: impl KrakenClientExt + Clone,
+        mut order: NewOrder,
+    ) -> Result<TrackedOrder, Error> {
+        if let Some(registry) = &self.registry {
+            registry.ensure_fresh(req.clone()).await?;
+            order = registry.validate(&order)?;
+        }
+        if let Some(gate) = &self.gate {
+            gate.check(&mut order)?;
+        }
+        let risk = self.risk_limits();
+        let reference_price = if risk.needs_reference_price(&order) {
+            self.market
+                .last_trade_price(req.clone(), &order.pair)
+                .await?
+        } else {
+            None
+        };
+        let resolver = self.registry.as_ref().map(|registry| registry.resolver());
+        risk.check(&order, resolver.as_ref(), reference_price)?;
+        if order.userref.is_some() {
+            return Err(Error::InvalidParameter(
+                "Orders placed through the order manager are identified by cl_ord_id and cannot \
+                 carry a userref"
+                    .to_string(),
+            ));
+        }
+
+        let cl_ord_id = order
+            .cl_ord_id
+            .get_or_insert_with(generate_cl_ord_id)
+            .clone();
+
+        if order.validate {
+            risk.check_open_orders(self.working_orders().await.len())?;
+            self.trading.add_order(req, &order).await?;
+            return Ok(TrackedOrder::new(cl_ord_id, order));
+        }
+
+        // Concurrent submissions of one cl_ord_id go one at a time; later ones find it tracked
+        let in_flight = self.in_flight(&cl_ord_id);
+        let _submitting = in_flight.slot.lock().await;
+
+        let mut needs_lookup = {
+            let mut orders = self.orders.lock().await;
+            match orders.get(&cl_ord_id) {
+                Some(existing)
+                    if existing.state != OrderState::PendingNew || existing.txid.is_some() =>
+                {
+                    return Ok(existing.clone());
+                }
+                // A previous submission never got a definite answer
+                Some(_) => true,
+                None => {
+                    // Counted and reserved under the lock, so concurrent submissions see the slot
+                    // taken
+                    let working = orders
+                        .values()
+                        .filter(|tracked| !tracked.state.is_terminal())
+                        .count();
+                    self.risk_limits().check_open_orders(working)?;
+                    orders.insert(
+                        cl_ord_id.clone(),
+                        TrackedOrder::new(cl_ord_id.clone(), order.clone()),
+                    );
+                    false
+                }
+            }
+        };
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            if needs_lookup {
+                if let Some((txid, remote)) = self
+                    .find_on_exchange(req.clone(), &cl_ord_id)
+                    .await?
+                {
+                    debug!("Order {} found on exchange as {}", cl_ord_id, txid);
+                    return self
+                        .apply_order_update(&txid, &remote)
+                        .await
+                        .ok_or_else(|| {
+                            Error::Unknown(format!("Order {} is no longer tracked", cl_ord_id))
+                        });
+                }
+            }
+
+            let sent = Instant::now();
+            match self.trading.add_order(req.clone(), &order).await {
+                Ok(response) => {
+                    metrics()
+                        .order_ack_latency
+                        .observe(sent.elapsed().as_secs_f64());
+                    return self
+                        .update(&cl_ord_id, |tracked| {
+                            tracked.txid = response.txid.first().cloned();
+                            tracked.transition(OrderState::Open);
+                        })
+                        .await
+                        .ok_or_else(|| {
+                            Error::Unknown(format!("Order {} is no longer tracked", cl_ord_id))
+                        });
+                }
+                Err(e) if outcome_unknown(&e) => {
+                    if attempt >= self.max_submit_attempts {
+                        // Left pending, a later sync or resubmission will resolve it
+                        return Err(e);
+                    }
+                    warn!(
+                        "Submission of order {} failed with unknown outcome: {} (attempt {}/{})",
+                        cl_ord_id, e, attempt, self.max_submit_attempts
+                    );
+                    needs_lookup = true;
+                }
+                Err(e) => {
+                    let reason = e.to_string();
+                    self.update(&cl_ord_id, |tracked| {
+                        tracked.reason = Some(reason);
+                        tracked.transition(OrderState::Rejected);
+                    })
+                    .await;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Request cancellation of a tracked order
+    pub async fn cancel(
+        &self,
+        req: impl KrakenClientExt + Clone,
+        cl_ord_id: &str,
+    ) -> Result<TrackedOrder, Error> {
+        let (txid, previous) = {
+            let mut orders = self.orders.lock().await;
+            let tracked = orders
+                .get_mut(cl_ord_id)
+                .ok_or_else(|| Error::InvalidParameter(format!("Unknown order {}", cl_ord_id)))?;
+            if tracked.state.is_terminal() {
+                return Err(Error::InvalidParameter(format!(
+                    "Order {} is already {:?}",
+                    cl_ord_id, tracked.state
+                )));
+            }
+            let previous = tracked.state;
+            tracked.transition(OrderState::CancelPending);
+            (tracked.txid.clone(), previous)
+        };
+
+        let result = match txid {
+            Some(txid) => self.trading.cancel_order(req, Some(txid), None).await,
+            None => {
+                self.trading
+                    .cancel_order(req, None, Some(cl_ord_id.to_string()))
+                    .await
+            }
+        };
+
+        match result {
+            Ok(response) => self
+                .update(cl_ord_id, |tracked| {
+                    if response.count > 0 && response.pending != Some(true) {
+                        tracked.transition(OrderState::Cancelled);
+                    }
+                })
+                .await
+                .ok_or_else(|| Error::Unknown(format!("Order {} is no longer tracked", cl_ord_id))),
+            Err(e) => {
+                self.update(cl_ord_id, |tracked| {
+                    if tracked.state == OrderState::CancelPending {
+                        tracked.state = previous;
+                    }
+                })
+                .await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Refresh every working order from the REST API
+    pub async fn sync(&self, req: impl KrakenClientExt + Clone) -> Result<(), Error> {
+        let (txids, unresolved): (Vec<_>, Vec<_>) = {
+            let orders = self.orders.lock().await;
+            orders
+                .values()
+                .filter(|tracked| !tracked.state.is_terminal())
+                .map(|tracked| (tracked.txid.clone(), tracked.cl_ord_id.clone()))
+                .partition(|(txid, _)| txid.is_some())
+        };
+
+        for (_, cl_ord_id) in unresolved {
+            if let Some((txid, remote)) = self.find_on_exchange(req.clone(), &cl_ord_id).await?
+            {
+                self.apply_order_update(&txid, &remote).await;
+            }
+        }
+
+        if !txids.is_empty() {
+            let txids = txids.into_iter().filter_map(|(txid, _)| txid).collect();
+            let remote = self
+                .account
+                .query_orders(req, None, None, txids, None)
+                .await?;
+            for (txid, order) in remote {
+                self.apply_order_update(&txid, &order).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply an order as returned by the OpenOrders, ClosedOrders or QueryOrders endpoints
+    pub async fn apply_order_update(&self, txid: &str, order: &Order) -> Option<TrackedOrder> {
+        let vol_exec = order.vol_exec.parse::<f64>().unwrap_or(0.0);
+        let state = OrderState::from_rest_status(&order.status, vol_exec);
+
+        let mut orders = self.orders.lock().await;
+        let tracked = orders.values_mut().find(|tracked| {
+            tracked.txid.as_deref() == Some(txid)
+                || order.cl_ord_id.as_deref() == Some(tracked.cl_ord_id.as_str())
+        })?;
+        tracked.txid = Some(txid.to_string());
+        tracked.record_fill(vol_exec);
+        if vol_exec > 0.0 {
+            tracked.avg_price = order.price.parse().ok().filter(|price: &f64| *price > 0.0);
+        }
+        if let Some(state) = state {
+            tracked.transition(state);
+        }
+        Some(tracked.clone())
+    }
+
+    /// Apply an update from the WebSocket `executions` channel
+    pub async fn apply_execution(&self, report: &ExecutionReport) -> Option<TrackedOrder> {
+        let state = report
+            .order_status
+            .as_deref()
+            .and_then(OrderState::from_ws_status)
+            .or_else(|| OrderState::from_ws_status(&report.exec_type));
+
+        let mut orders = self.orders.lock().await;
+        let tracked = orders.values_mut().find(|tracked| {
+            tracked.txid.as_deref() == Some(report.order_id.as_str())
+                || report.cl_ord_id.as_deref() == Some(tracked.cl_ord_id.as_str())
+        })?;
+        tracked.txid = Some(report.order_id.clone());
+        if let Some(cum_qty) = report.cum_qty {
+            tracked.record_fill(cum_qty);
+        }
+        if report.avg_price.is_some() {
+            tracked.avg_price = report.avg_price;
+        }
+        if report.reason.is_some() {
+            tracked.reason = report.reason.clone();
+        }
+        if let Some(state) = state {
+            tracked.transition(state);
+        }
+        Some(tracked.clone())
+    }
+
+    /// Resume tracking an order placed before a restart, keeping the current entry if it is
+    /// already tracked
+    pub async fn track(&self, order: TrackedOrder) -> TrackedOrder {
+        self.orders
+            .lock()
+            .await
+            .entry(order.cl_ord_id.clone())
+            .or_insert(order)
+            .clone()
+    }
+
+    /// Get a tracked order by its client order id
+    pub async fn get(&self, cl_ord_id: &str) -> Option<TrackedOrder> {
+        self.orders.lock().await.get(cl_ord_id).cloned()
+    }
+
+    /// Get all orders that are not in a terminal state, oldest first
+    pub async fn working_orders(&self) -> Vec<TrackedOrder> {
+        let orders = self.orders.lock().await;
+        let mut working: Vec<TrackedOrder> = orders
+            .values()
+            .filter(|tracked| !tracked.state.is_terminal())
+            .cloned()
+            .collect();
+        working.sort_by(|a, b| a.created_at.total_cmp(&b.created_at));
+        working
+    }
+
+    /// Look an order up on Kraken by its `cl_ord_id`. Only an order carrying that `cl_ord_id` counts
+    /// as found; anything else leaves the order unknown.
+    async fn find_on_exchange(
+        &self,
+        req: impl KrakenClientExt + Clone,
+        cl_ord_id: &str,
+    ) -> Result<Option<(String, Order)>, Error> {
+        let is_match = |order: &Order| order.cl_ord_id.as_deref() == Some(cl_ord_id);
+        let open = self
+            .account
+            .get_open_orders(req.clone(), None, None, Some(cl_ord_id.to_string()))
+            .await?;
+        if let Some(found) = open.open.into_iter().find(|(_, order)| is_match(order)) {
+            return Ok(Some(found));
+        }
+
+        let closed = self
+            .account
+            .get_closed_orders(
+                req,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(cl_ord_id.to_string()),
+            )
+            .await?;
+        Ok(closed.closed.into_iter().find(|(_, order)| is_match(order)))
+    }
+
+    fn in_flight(&self, cl_ord_id: &str) -> InFlight<'_> {
+        let slot = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(cl_ord_id.to_string())
+            .or_default()
+            .clone();
+        InFlight {
+            manager: self,
+            cl_ord_id: cl_ord_id.to_string(),
+            slot,
+        }
+    }
+
+    async fn update<F>(&self, cl_ord_id: &str, f: F) -> Option<TrackedOrder>
+    where
+        F: FnOnce(&mut TrackedOrder),
+    {
+        let mut orders = self.orders.lock().await;
+        let tracked = orders.get_mut(cl_ord_id)?;
+        f(tracked);
+        Some(tracked.clone())
+    }
+}
+
+/// Generate a client order id in Kraken's 32 hex digit short UUID format
+pub fn generate_cl_ord_id() -> String {
+    static SEQUENCE: AtomicU32 = AtomicU32::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_nanos() as u64;
+    format!(
+        "{:016x}{:08x}{:08x}",
+        nanos,
+        std::process::id(),
+        SEQUENCE.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+fn unix_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::kraken_apis::PrivateApi,
+        models::{
+            symbols::Pair,
+            trading::{OrderSide, OrderType},
+        },
+    };
+    use std::collections::HashSet;
+
+    fn private_api() -> PrivateApi {
+        PrivateApi::builder()
+            .with_api_key("key".to_string())
+            .with_api_secret("c2VjcmV0".to_string())
+            .build()
+            .unwrap()
+    }
+
+    async fn manager_with_order(cl_ord_id: &str, txid: Option<&str>) -> OrderManager {
+        let manager = OrderManager::with_services(
+            Trading::with_api(private_api()),
+            Account::with_api(private_api()),
+        );
+        let order = NewOrder::new(
+            Pair::from("XBTUSD"),
+            OrderSide::Buy,
+            OrderType::Limit,
+            "1.0".to_string(),
+        );
+        let mut tracked = TrackedOrder::new(cl_ord_id.to_string(), order);
+        tracked.txid = txid.map(str::to_string);
+        tracked.state = OrderState::Open;
+        manager
+            .orders
+            .lock()
+            .await
+            .insert(cl_ord_id.to_string(), tracked);
+        manager
+    }
+
+    fn execution(
+        order_id: &str,
+        exec_type: &str,
+        order_status: Option<&str>,
+        cum_qty: Option<f64>,
+    ) -> ExecutionReport {
+        ExecutionReport {
+            exec_type: exec_type.to_string(),
+            order_id: order_id.to_string(),
+            cl_ord_id: None,
+            order_userref: None,
+            order_status: order_status.map(str::to_string),
+            cum_qty,
+            avg_price: None,
+            reason: None,
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn test_state_from_rest_status() {
+        assert_eq!(
+            OrderState::from_rest_status("pending", 0.0),
+            Some(OrderState::PendingNew)
+        );
+        assert_eq!(
+            OrderState::from_rest_status("open", 0.0),
+            Some(OrderState::Open)
+        );
+        assert_eq!(
+            OrderState::from_rest_status("open", 0.5),
+            Some(OrderState::PartiallyFilled)
+        );
+        assert_eq!(
+            OrderState::from_rest_status("closed", 1.0),
+            Some(OrderState::Filled)
+        );
+        assert_eq!(
+            OrderState::from_rest_status("canceled", 0.0),
+            Some(OrderState::Cancelled)
+        );
+        assert_eq!(
+            OrderState::from_rest_status("expired", 0.0),
+            Some(OrderState::Expired)
+        );
+        assert_eq!(OrderState::from_rest_status("unknown", 0.0), None);
+    }
+
+    #[test]
+    fn test_state_advance_ignores_stale_updates() {
+        assert_eq!(
+            OrderState::PendingNew.advance(OrderState::Open),
+            OrderState::Open
+        );
+        assert_eq!(
+            OrderState::PartiallyFilled.advance(OrderState::Open),
+            OrderState::PartiallyFilled
+        );
+        assert_eq!(
+            OrderState::CancelPending.advance(OrderState::PartiallyFilled),
+            OrderState::CancelPending
+        );
+        assert_eq!(
+            OrderState::CancelPending.advance(OrderState::Filled),
+            OrderState::Filled
+        );
+        assert_eq!(
+            OrderState::Cancelled.advance(OrderState::Open),
+            OrderState::Cancelled
+        );
+    }
+
+    #[test]
+    fn test_generate_cl_ord_id() {
+        let ids: HashSet<String> = (0..1000).map(|_| generate_cl_ord_id()).collect();
+        assert_eq!(ids.len(), 1000);
+        assert!(ids
+            .iter()
+            .all(|id| id.len() == 32 && id.chars().all(|c| c.is_ascii_hexdigit())));
+    }
+
+    #[tokio::test]
+    async fn test_apply_execution() {
+        let manager = manager_with_order("abc", Some("OTXID-1")).await;
+
+        let tracked = manager
+            .apply_execution(&execution(
+                "OTXID-1",
+                "trade",
+                Some("partially_filled"),
+                Some(0.4),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(tracked.state, OrderState::PartiallyFilled);
+        assert_eq!(tracked.vol_exec, 0.4);
+        assert_eq!(manager.working_orders().await.len(), 1);
+
+        let mut filled = execution("OTXID-1", "filled", None, Some(1.0));
+        filled.avg_price = Some(30_010.5);
+        let tracked = manager.apply_execution(&filled).await.unwrap();
+        assert_eq!(tracked.state, OrderState::Filled);
+        assert_eq!(tracked.avg_price, Some(30_010.5));
+        assert!(manager.working_orders().await.is_empty());
+
+        assert!(manager
+            .apply_execution(&execution("OTHER", "new", None, None))
+            .await
+            .is_none());
+    }
+
+    fn rest_order(cl_ord_id: Option<&str>) -> Order {
+        serde_json::from_value(serde_json::json!({
+            "refid": null,
+            "userref": 42,
+            "cl_ord_id": cl_ord_id,
+            "status": "canceled",
+            "opentm": 1700000000.0,
+            "starttm": 0,
+            "expiretm": 0,
+            "descr": {
+                "pair": "XBTUSD",
+                "type": "buy",
+                "ordertype": "limit",
+                "price": "30000.0",
+                "price2": "0",
+                "leverage": "none",
+                "order": "buy 1.0 XBTUSD @ limit 30000.0",
+                "close": ""
+            },
+            "vol": "1.0",
+            "vol_exec": "0.0",
+            "cost": "0",
+            "fee": "0",
+            "price": "0",
+            "stopprice": "0",
+            "limitprice": "0",
+            "misc": "",
+            "oflags": "fciq"
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_apply_order_update_matches_cl_ord_id() {
+        let manager = manager_with_order("abc", None).await;
+        let tracked = manager
+            .apply_order_update("OTXID-2", &rest_order(Some("abc")))
+            .await
+            .unwrap();
+        assert_eq!(tracked.txid.as_deref(), Some("OTXID-2"));
+        assert_eq!(tracked.state, OrderState::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_apply_order_update_ignores_userref() {
+        let manager = manager_with_order("abc", None).await;
+        // Another order sharing a userref is not this one
+        assert!(manager
+            .apply_order_update("OTXID-3", &rest_order(None))
+            .await
+            .is_none());
+        assert!(manager
+            .apply_order_update("OTXID-3", &rest_order(Some("def")))
+            .await
+            .is_none());
+        assert!(manager.working_orders().await[0].txid.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_terminal_order_is_rejected() {
+        let manager = manager_with_order("abc", Some("OTXID-1")).await;
+        manager
+            .apply_execution(&execution("OTXID-1", "filled", None, Some(1.0)))
+            .await;
+
+        let req = actix_web::test::TestRequest::default().to_http_request();
+        let result = manager.cancel(req, "abc").await;
+        assert!(matches!(result, Err(Error::InvalidParameter(_))));
+    }
+
+    #[tokio::test]
+    async fn test_risk_limits_apply_to_later_submissions() {
+        let manager = manager_with_order("abc", Some("OTXID-1")).await;
+        manager.set_risk_limits(RiskConfig {
+            max_open_orders: Some(1),
+            ..RiskConfig::default()
+        });
+
+        let req = actix_web::test::TestRequest::default().to_http_request();
+        let order = NewOrder::new(
+            Pair::from("XBTUSD"),
+            OrderSide::Sell,
+            OrderType::Market,
+            "0.5".to_string(),
+        );
+        let result = manager.submit(req, order).await;
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+        assert_eq!(manager.working_orders().await.len(), 1);
+    }
+}
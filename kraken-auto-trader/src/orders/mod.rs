@@ -0,0 +1,4 @@
+pub mod dead_man_switch;
+pub mod manager;
+pub mod synthetic;
+pub mod trading_gate;
@@ -0,0 +1,861 @@
+use super::manager::{generate_cl_ord_id, OrderManager, OrderState, TrackedOrder};
+use crate::{
+    client::circuit_breaker::backoff,
+    errors::Error,
+    feeds::ticker::PriceFeed,
+    health::Watchdog,
+    middleware::KrakenClientState,
+    models::{
+        symbols::Pair,
+        trading::{NewOrder, OrderSide, OrderType},
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{sync::Mutex, task::JoinHandle};
+use tracing::{info, warn};
+
+/// Volumes below this are considered filled
+const VOLUME_EPSILON: f64 = 1e-8;
+
+/// Shortest and longest wait before placing a bracket exit that failed again
+const EXIT_RETRY_BASE: Duration = Duration::from_secs(1);
+const EXIT_RETRY_MAX: Duration = Duration::from_secs(60);
+
+/// An order type Kraken doesn't offer, emulated with native child orders
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SyntheticOrderRequest {
+    /// Two orders where any fill on one cancels the other
+    Oco { first: NewOrder, second: NewOrder },
+    /// An entry order whose fills are closed by take-profit limits or a stop watched on the last
+    /// traded price, whichever comes first
+    Bracket {
+        entry: NewOrder,
+        take_profit: f64,
+        stop_loss: f64,
+    },
+    /// A limit order shown to the book one slice at a time, the next slice being sent when
+    /// the previous one is filled
+    Iceberg {
+        order: NewOrder,
+        display_volume: f64,
+    },
+    /// An order sent at `activate_at` and cancelled at `cancel_at` (unix times) if still working
+    Timed {
+        order: NewOrder,
+        activate_at: Option<f64>,
+        cancel_at: Option<f64>,
+    },
+}
+
+impl SyntheticOrderRequest {
+    pub fn validate(&self) -> Result<(), Error> {
+        match self {
+            SyntheticOrderRequest::Oco { first, second } => {
+                order_volume(first)?;
+                order_volume(second)?;
+            }
+            SyntheticOrderRequest::Bracket {
+                entry,
+                take_profit,
+                stop_loss,
+            } => {
+                order_volume(entry)?;
+                if *take_profit <= 0.0 || *stop_loss <= 0.0 {
+                    return Err(Error::InvalidParameter(
+                        "Take profit and stop loss must be positive".into(),
+                    ));
+                }
+                let ordered = match entry.side {
+                    OrderSide::Buy => take_profit > stop_loss,
+                    OrderSide::Sell => take_profit < stop_loss,
+                };
+                if !ordered {
+                    return Err(Error::InvalidParameter(format!(
+                        "Take profit must be on the profitable side of the stop loss for a {} entry",
+                        entry.side.as_str()
+                    )));
+                }
+            }
+            SyntheticOrderRequest::Iceberg {
+                order,
+                display_volume,
+            } => {
+                let volume = order_volume(order)?;
+                if order.ordertype != OrderType::Limit || order.price.is_none() {
+                    return Err(Error::InvalidParameter(
+                        "Iceberg orders must be limit orders with a price".into(),
+                    ));
+                }
+                if *display_volume <= 0.0 || *display_volume > volume {
+                    return Err(Error::InvalidParameter(
+                        "Display volume must be positive and at most the order volume".into(),
+                    ));
+                }
+            }
+            SyntheticOrderRequest::Timed {
+                order,
+                activate_at,
+                cancel_at,
+            } => {
+                order_volume(order)?;
+                if let (Some(activate_at), Some(cancel_at)) = (activate_at, cancel_at) {
+                    if cancel_at <= activate_at {
+                        return Err(Error::InvalidParameter(
+                            "Cancel time must be after the activation time".into(),
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Name of the synthetic order type
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SyntheticOrderRequest::Oco { .. } => "oco",
+            SyntheticOrderRequest::Bracket { .. } => "bracket",
+            SyntheticOrderRequest::Iceberg { .. } => "iceberg",
+            SyntheticOrderRequest::Timed { .. } => "timed",
+        }
+    }
+}
+
+fn order_volume(order: &NewOrder) -> Result<f64, Error> {
+    match order.volume.parse::<f64>() {
+        Ok(volume) if volume > 0.0 => Ok(volume),
+        _ => Err(Error::InvalidParameter(format!(
+            "Invalid order volume {}",
+            order.volume
+        ))),
+    }
+}
+
+/// Lifecycle of a synthetic order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyntheticOrderStatus {
+    /// Waiting for its activation time
+    Pending,
+    Working,
+    Completed,
+    Cancelled,
+    /// The cancel time of a timed order passed before it was filled
+    Expired,
+    Failed,
+}
+
+impl SyntheticOrderStatus {
+    pub fn is_finished(&self) -> bool {
+        !matches!(
+            self,
+            SyntheticOrderStatus::Pending | SyntheticOrderStatus::Working
+        )
+    }
+}
+
+/// A synthetic order and the native child orders it sent
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SyntheticOrder {
+    pub id: String,
+    pub request: SyntheticOrderRequest,
+    pub status: SyntheticOrderStatus,
+    /// Child orders in the order they were sent. For brackets: the entry, then take-profit
+    /// limits and stop market exits
+    pub children: Vec<TrackedOrder>,
+    /// Whether the stop of a bracket was hit
+    #[serde(default)]
+    pub stop_triggered: bool,
+    /// Failed attempts in a row to place a bracket exit
+    #[serde(default)]
+    pub exit_attempts: u32,
+    /// Unix time before which a failed bracket exit is not placed again
+    #[serde(default)]
+    pub retry_at: Option<f64>,
+    pub created_at: f64,
+    pub updated_at: f64,
+    pub last_error: Option<String>,
+}
+
+impl SyntheticOrder {
+    fn new(request: SyntheticOrderRequest) -> Self {
+        let now = unix_time();
+        Self {
+            id: generate_cl_ord_id(),
+            request,
+            status: SyntheticOrderStatus::Pending,
+            children: Vec::new(),
+            stop_triggered: false,
+            exit_attempts: 0,
+            retry_at: None,
+            created_at: now,
+            updated_at: now,
+            last_error: None,
+        }
+    }
+
+    fn finish(&mut self, status: SyntheticOrderStatus) {
+        info!(
+            "Synthetic {} order {} finished as {:?}",
+            self.request.kind(),
+            self.id,
+            status
+        );
+        self.status = status;
+    }
+
+    /// Pair whose last price the order watches, if any
+    fn watched_pair(&self) -> Option<&Pair> {
+        match &self.request {
+            SyntheticOrderRequest::Bracket { entry, .. } if !self.children.is_empty() => {
+                Some(&entry.pair)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// JSON file holding the synthetic orders that are still working
+pub struct SyntheticOrderStore {
+    path: PathBuf,
+}
+
+impl SyntheticOrderStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Load the persisted orders, a missing file meaning there are none
+    pub fn load(&self) -> Result<Vec<SyntheticOrder>, Error> {
+        match fs::read(&self.path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replace the persisted orders, writing to a temporary file first so that a crash
+    /// never leaves a truncated file behind
+    pub fn save(&self, orders: &[&SyntheticOrder]) -> Result<(), Error> {
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(orders)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// A synthetic order and the copy of it readers see.
+///
+/// The order is only moved forward under its own lock, so that requests made for one order
+/// never hold up another, and readers take the copy rather than wait for those requests.
+struct Entry {
+    order: Mutex<SyntheticOrder>,
+    view: std::sync::Mutex<SyntheticOrder>,
+}
+
+impl Entry {
+    fn new(order: SyntheticOrder) -> Arc<Self> {
+        Arc::new(Self {
+            view: std::sync::Mutex::new(order.clone()),
+            order: Mutex::new(order),
+        })
+    }
+
+    fn view(&self) -> SyntheticOrder {
+        self.view.lock().unwrap().clone()
+    }
+
+    fn publish(&self, order: &SyntheticOrder) {
+        *self.view.lock().unwrap() = order.clone();
+    }
+}
+
+/// Emulates synthetic orders on top of the [`OrderManager`] and the last traded prices
+pub struct SyntheticOrderManager {
+    orders: Arc<OrderManager>,
+    client: KrakenClientState,
+    store: SyntheticOrderStore,
+    synthetic: std::sync::Mutex<HashMap<String, Arc<Entry>>>,
+    prices: Mutex<PriceFeed>,
+    watchdog: Option<Arc<Watchdog>>,
+}
+
+impl SyntheticOrderManager {
+    pub fn new(
+        orders: Arc<OrderManager>,
+        client: KrakenClientState,
+        store: SyntheticOrderStore,
+    ) -> Self {
+        Self {
+            orders,
+            client,
+            store,
+            synthetic: std::sync::Mutex::new(HashMap::new()),
+            prices: Mutex::new(PriceFeed::new()),
+            watchdog: None,
+        }
+    }
+
+    /// Beat `watchdog` after every tick of the background loop
+    pub fn with_watchdog(mut self, watchdog: Arc<Watchdog>) -> Self {
+        self.watchdog = Some(watchdog);
+        self
+    }
+
+    /// Reload the orders persisted by a previous run and resume tracking their children
+    pub async fn restore(&self) -> Result<usize, Error> {
+        let restored = self.store.load()?;
+        for order in &restored {
+            for child in &order.children {
+                if !child.state.is_terminal() {
+                    self.orders.track(child.clone()).await;
+                }
+            }
+        }
+        let mut synthetic = self.synthetic.lock().unwrap();
+        for order in restored {
+            synthetic.insert(order.id.clone(), Entry::new(order));
+        }
+        info!("Restored {} synthetic orders", synthetic.len());
+        Ok(synthetic.len())
+    }
+
+    /// Validate a synthetic order and send its first child orders
+    pub async fn submit(&self, request: SyntheticOrderRequest) -> Result<SyntheticOrder, Error> {
+        request.validate()?;
+        let mut order = SyntheticOrder::new(request);
+        self.step(&mut order, None).await;
+        self.synthetic
+            .lock()
+            .unwrap()
+            .insert(order.id.clone(), Entry::new(order.clone()));
+        self.persist();
+        Ok(order)
+    }
+
+    /// Cancel a synthetic order and its working child orders
+    pub async fn cancel(&self, id: &str) -> Result<SyntheticOrder, Error> {
+        let entry = self
+            .entry(id)
+            .ok_or_else(|| Error::InvalidParameter(format!("Unknown synthetic order {}", id)))?;
+        // Waits for a tick moving this order forward, and only this one
+        let mut order = entry.order.lock().await;
+        if order.status.is_finished() {
+            return Err(Error::InvalidParameter(format!(
+                "Synthetic order {} is already {:?}",
+                id, order.status
+            )));
+        }
+        self.cancel_children(&mut order, |_| true).await;
+        order.finish(SyntheticOrderStatus::Cancelled);
+        order.updated_at = unix_time();
+        entry.publish(&order);
+        let order = order.clone();
+        self.persist();
+        Ok(order)
+    }
+
+    /// Get a synthetic order by id
+    pub async fn get(&self, id: &str) -> Option<SyntheticOrder> {
+        self.entry(id).map(|entry| entry.view())
+    }
+
+    /// Get all synthetic orders, oldest first
+    pub async fn list(&self) -> Vec<SyntheticOrder> {
+        self.sorted(|_| true)
+    }
+
+    /// Get the synthetic orders that are still pending or working, oldest first
+    pub async fn open_orders(&self) -> Vec<SyntheticOrder> {
+        self.sorted(|order| !order.status.is_finished())
+    }
+
+    /// Refresh child orders and prices, then move every working synthetic order forward.
+    ///
+    /// No lock is held across requests but that of the order being moved forward.
+    pub async fn tick(&self) -> Result<(), Error> {
+        let working: Vec<(Arc<Entry>, SyntheticOrder)> = self
+            .synthetic
+            .lock()
+            .unwrap()
+            .values()
+            .map(|entry| (entry.clone(), entry.view()))
+            .filter(|(_, order)| !order.status.is_finished())
+            .collect();
+        if working.is_empty() {
+            return Ok(());
+        }
+        self.orders.sync(self.client.clone()).await?;
+
+        let mut pairs: Vec<Pair> = working
+            .iter()
+            .filter_map(|(_, order)| order.watched_pair().cloned())
+            .collect();
+        pairs.sort();
+        pairs.dedup();
+        let mut prices = self.prices.lock().await;
+        if let Err(e) = prices.poll(self.client.clone(), &pairs).await {
+            warn!("Failed to refresh prices for synthetic orders: {}", e);
+        }
+
+        for (entry, _) in working {
+            let mut order = entry.order.lock().await;
+            if !order.status.is_finished() {
+                let price = order.watched_pair().and_then(|pair| prices.last(pair));
+                self.step(&mut order, price).await;
+                entry.publish(&order);
+            }
+        }
+        self.persist();
+        Ok(())
+    }
+
+    /// Evaluate the synthetic orders every `interval` in the background
+    pub fn spawn(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            if let Some(watchdog) = &manager.watchdog {
+                watchdog.watch("synthetic orders", interval);
+            }
+            loop {
+                ticker.tick().await;
+                if let Err(e) = manager.tick().await {
+                    warn!("Failed to update synthetic orders: {}", e);
+                }
+                if let Some(watchdog) = &manager.watchdog {
+                    watchdog.beat("synthetic orders");
+                }
+            }
+        })
+    }
+
+    fn entry(&self, id: &str) -> Option<Arc<Entry>> {
+        self.synthetic.lock().unwrap().get(id).cloned()
+    }
+
+    fn sorted<F>(&self, filter: F) -> Vec<SyntheticOrder>
+    where
+        F: Fn(&SyntheticOrder) -> bool,
+    {
+        let synthetic = self.synthetic.lock().unwrap();
+        let mut orders: Vec<SyntheticOrder> = synthetic
+            .values()
+            .map(|entry| entry.view())
+            .filter(|order| filter(order))
+            .collect();
+        orders.sort_by(|a, b| a.created_at.total_cmp(&b.created_at));
+        orders
+    }
+
+    /// Save the orders that are still working. The map stays locked while writing, so that
+    /// two saves never interleave
+    fn persist(&self) {
+        let synthetic = self.synthetic.lock().unwrap();
+        let open: Vec<SyntheticOrder> = synthetic
+            .values()
+            .map(|entry| entry.view())
+            .filter(|order| !order.status.is_finished())
+            .collect();
+        if let Err(e) = self.store.save(&open.iter().collect::<Vec<_>>()) {
+            warn!("Failed to persist synthetic orders: {}", e);
+        }
+    }
+
+    async fn step(&self, order: &mut SyntheticOrder, price: Option<f64>) {
+        for child in order.children.iter_mut() {
+            if let Some(tracked) = self.orders.get(&child.cl_ord_id).await {
+                *child = tracked;
+            }
+        }
+
+        let now = unix_time();
+        let result = match order.request.clone() {
+            SyntheticOrderRequest::Oco { first, second } => {
+                self.step_oco(order, first, second).await
+            }
+            SyntheticOrderRequest::Bracket {
+                entry,
+                take_profit,
+                stop_loss,
+            } => {
+                self.step_bracket(order, entry, take_profit, stop_loss, price)
+                    .await
+            }
+            SyntheticOrderRequest::Iceberg {
+                order: template,
+                display_volume,
+            } => self.step_iceberg(order, template, display_volume).await,
+            SyntheticOrderRequest::Timed {
+                order: template,
+                activate_at,
+                cancel_at,
+            } => {
+                self.step_timed(order, template, activate_at, cancel_at, now)
+                    .await
+            }
+        };
+
+        if let Err(e) = result {
+            warn!("Synthetic order {} failed: {}", order.id, e);
+            order.last_error = Some(e.to_string());
+            self.cancel_children(order, |_| true).await;
+            order.finish(SyntheticOrderStatus::Failed);
+        }
+        order.updated_at = now;
+    }
+
+    async fn step_oco(
+        &self,
+        order: &mut SyntheticOrder,
+        first: NewOrder,
+        second: NewOrder,
+    ) -> Result<(), Error> {
+        if order.children.is_empty() {
+            self.place(order, first).await?;
+            self.place(order, second).await?;
+            order.status = SyntheticOrderStatus::Working;
+            return Ok(());
+        }
+
+        let filled = order.children.iter().any(|child| child.vol_exec > 0.0);
+        if filled {
+            self.cancel_children(order, |child| child.vol_exec <= 0.0)
+                .await;
+        }
+        if order.children.iter().all(|child| child.state.is_terminal()) {
+            order.finish(if filled {
+                SyntheticOrderStatus::Completed
+            } else {
+                SyntheticOrderStatus::Cancelled
+            });
+        }
+        Ok(())
+    }
+
+    /// Move a bracket forward. Every fill of the entry is protected by a take profit as it comes
+    /// in; once the stop is hit the entry and take profits are pulled and what they leave open is
+    /// closed at market. Exits that fail to be placed are retried rather than failing the bracket,
+    /// as that would leave the filled volume unprotected.
+    async fn step_bracket(
+        &self,
+        order: &mut SyntheticOrder,
+        entry: NewOrder,
+        take_profit: f64,
+        stop_loss: f64,
+        price: Option<f64>,
+    ) -> Result<(), Error> {
+        let Some(placed_entry) = order.children.first().cloned() else {
+            self.place(order, entry).await?;
+            order.status = SyntheticOrderStatus::Working;
+            return Ok(());
+        };
+        let filled = placed_entry.vol_exec;
+        if filled <= VOLUME_EPSILON {
+            if placed_entry.state.is_terminal() {
+                order.finish(SyntheticOrderStatus::Cancelled);
+            }
+            return Ok(());
+        }
+        let exits = &order.children[1..];
+        let (exited, closing) = exit_volumes(exits);
+        if placed_entry.state.is_terminal() && exited >= filled - VOLUME_EPSILON {
+            order.finish(SyntheticOrderStatus::Completed);
+            return Ok(());
+        }
+        let exit_side = entry.side.opposite();
+
+        if !order.stop_triggered {
+            let stop_hit = price.is_some_and(|price| match entry.side {
+                OrderSide::Buy => price <= stop_loss,
+                OrderSide::Sell => price >= stop_loss,
+            });
+            if stop_hit {
+                info!("Stop of synthetic bracket {} hit at {:?}", order.id, price);
+                order.stop_triggered = true;
+            } else if exits
+                .iter()
+                .any(|exit| exit.state.is_terminal() && exit.state != OrderState::Filled)
+            {
+                // A take profit was cancelled outside of the bracket
+                self.cancel_children(order, |_| true).await;
+                order.finish(SyntheticOrderStatus::Cancelled);
+                return Ok(());
+            } else {
+                let unprotected = filled - exited - closing;
+                if unprotected > VOLUME_EPSILON {
+                    let mut exit = NewOrder::new(
+                        entry.pair.clone(),
+                        exit_side,
+                        OrderType::Limit,
+                        format_volume(unprotected),
+                    );
+                    exit.price = Some(take_profit.to_string());
+                    self.place_exit(order, exit).await;
+                }
+                return Ok(());
+            }
+        }
+
+        // Stop adding to the position and pull the take profits, then close whatever is left
+        let take_profit_or_entry = |child: &TrackedOrder| {
+            child.cl_ord_id == placed_entry.cl_ord_id || child.order.ordertype != OrderType::Market
+        };
+        self.cancel_children(order, take_profit_or_entry).await;
+        if order
+            .children
+            .iter()
+            .any(|child| take_profit_or_entry(child) && !child.state.is_terminal())
+        {
+            return Ok(());
+        }
+        let (exited, closing) = exit_volumes(&order.children[1..]);
+        let remaining = order.children[0].vol_exec - exited - closing;
+        if remaining > VOLUME_EPSILON {
+            let stop_exit = NewOrder::new(
+                entry.pair.clone(),
+                exit_side,
+                OrderType::Market,
+                format_volume(remaining),
+            );
+            self.place_exit(order, stop_exit).await;
+        }
+        Ok(())
+    }
+
+    async fn step_iceberg(
+        &self,
+        order: &mut SyntheticOrder,
+        template: NewOrder,
+        display_volume: f64,
+    ) -> Result<(), Error> {
+        let total = order_volume(&template)?;
+        let executed: f64 = order.children.iter().map(|child| child.vol_exec).sum();
+        if let Some(slice) = order.children.last() {
+            if !slice.state.is_terminal() {
+                return Ok(());
+            }
+            if slice.state != OrderState::Filled && executed < total - VOLUME_EPSILON {
+                order.finish(SyntheticOrderStatus::Cancelled);
+                return Ok(());
+            }
+        }
+
+        let remaining = total - executed;
+        if remaining <= VOLUME_EPSILON {
+            order.finish(SyntheticOrderStatus::Completed);
+            return Ok(());
+        }
+        let mut slice = template;
+        slice.volume = format_volume(display_volume.min(remaining));
+        slice.cl_ord_id = None;
+        slice.userref = None;
+        self.place(order, slice).await?;
+        order.status = SyntheticOrderStatus::Working;
+        Ok(())
+    }
+
+    async fn step_timed(
+        &self,
+        order: &mut SyntheticOrder,
+        template: NewOrder,
+        activate_at: Option<f64>,
+        cancel_at: Option<f64>,
+        now: f64,
+    ) -> Result<(), Error> {
+        let expired = cancel_at.is_some_and(|cancel_at| now >= cancel_at);
+        let Some(child) = order.children.first().cloned() else {
+            if expired {
+                order.finish(SyntheticOrderStatus::Expired);
+            } else if activate_at.is_none_or(|activate_at| now >= activate_at) {
+                self.place(order, template).await?;
+                order.status = SyntheticOrderStatus::Working;
+            }
+            return Ok(());
+        };
+
+        if child.state.is_terminal() {
+            order.finish(match child.state {
+                OrderState::Filled => SyntheticOrderStatus::Completed,
+                _ if expired => SyntheticOrderStatus::Expired,
+                _ => SyntheticOrderStatus::Cancelled,
+            });
+        } else if expired {
+            self.cancel_children(order, |_| true).await;
+        }
+        Ok(())
+    }
+
+    /// Send a child order.
+    ///
+    /// A submission whose outcome is unknown is kept as a child, the order manager resolving
+    /// it on the next sync.
+    async fn place(&self, order: &mut SyntheticOrder, mut child: NewOrder) -> Result<(), Error> {
+        let cl_ord_id = child
+            .cl_ord_id
+            .get_or_insert_with(generate_cl_ord_id)
+            .clone();
+        match self.orders.submit(self.client.clone(), child).await {
+            Ok(tracked) => {
+                order.children.push(tracked);
+                Ok(())
+            }
+            Err(e) => match self.orders.get(&cl_ord_id).await {
+                Some(pending) if !pending.state.is_terminal() => {
+                    warn!("Child order {} outcome unknown: {}", cl_ord_id, e);
+                    order.last_error = Some(e.to_string());
+                    order.children.push(pending);
+                    Ok(())
+                }
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Send an exit of a bracket, waiting out the backoff of the last failure first. A failure
+    /// leaves the bracket working, with the exit tried again once the backoff passes
+    async fn place_exit(&self, order: &mut SyntheticOrder, exit: NewOrder) {
+        if order.retry_at.is_some_and(|retry_at| unix_time() < retry_at) {
+            return;
+        }
+        match self.place(order, exit).await {
+            Ok(()) => {
+                order.exit_attempts = 0;
+                order.retry_at = None;
+            }
+            Err(e) => {
+                order.exit_attempts += 1;
+                let delay = backoff(order.exit_attempts, EXIT_RETRY_BASE, EXIT_RETRY_MAX);
+                warn!(
+                    "Failed to place an exit of synthetic bracket {}, retrying in {:?}: {}",
+                    order.id, delay, e
+                );
+                order.last_error = Some(e.to_string());
+                order.retry_at = Some(unix_time() + delay.as_secs_f64());
+            }
+        }
+    }
+
+    /// Cancel the working children matching a filter, errors being retried on the next tick
+    async fn cancel_children<F>(&self, order: &mut SyntheticOrder, filter: F)
+    where
+        F: Fn(&TrackedOrder) -> bool,
+    {
+        for child in order.children.iter_mut() {
+            if child.state.is_terminal()
+                || child.state == OrderState::CancelPending
+                || !filter(child)
+            {
+                continue;
+            }
+            match self
+                .orders
+                .cancel(self.client.clone(), &child.cl_ord_id)
+                .await
+            {
+                Ok(cancelled) => *child = cancelled,
+                Err(e) => {
+                    warn!("Failed to cancel child order {}: {}", child.cl_ord_id, e);
+                    order.last_error = Some(e.to_string());
+                }
+            }
+        }
+    }
+}
+
+/// Volume the exits of a bracket have closed, and volume those still working will close
+fn exit_volumes(exits: &[TrackedOrder]) -> (f64, f64) {
+    let exited = exits.iter().map(|exit| exit.vol_exec).sum();
+    let closing = exits
+        .iter()
+        .filter(|exit| !exit.state.is_terminal())
+        .map(|exit| (order_volume(&exit.order).unwrap_or(0.0) - exit.vol_exec).max(0.0))
+        .sum();
+    (exited, closing)
+}
+
+fn format_volume(volume: f64) -> String {
+    format!("{:.8}", volume)
+}
+
+fn unix_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(side: OrderSide, volume: &str, price: &str) -> NewOrder {
+        let mut order = NewOrder::new(
+            Pair::from("XETHZGBP"),
+            side,
+            OrderType::Limit,
+            volume.to_string(),
+        );
+        order.price = Some(price.to_string());
+        order
+    }
+
+    #[test]
+    fn test_request_validation() {
+        let bracket = SyntheticOrderRequest::Bracket {
+            entry: limit(OrderSide::Buy, "1.0", "1800"),
+            take_profit: 1900.0,
+            stop_loss: 1750.0,
+        };
+        assert!(bracket.validate().is_ok());
+
+        let inverted = SyntheticOrderRequest::Bracket {
+            entry: limit(OrderSide::Sell, "1.0", "1800"),
+            take_profit: 1900.0,
+            stop_loss: 1750.0,
+        };
+        assert!(inverted.validate().is_err());
+
+        let iceberg = SyntheticOrderRequest::Iceberg {
+            order: limit(OrderSide::Sell, "10", "1800"),
+            display_volume: 11.0,
+        };
+        assert!(iceberg.validate().is_err());
+
+        let timed = SyntheticOrderRequest::Timed {
+            order: limit(OrderSide::Buy, "1", "1800"),
+            activate_at: Some(200.0),
+            cancel_at: Some(100.0),
+        };
+        assert!(timed.validate().is_err());
+    }
+
+    #[test]
+    fn test_store_round_trip() {
+        let path = std::env::temp_dir().join(format!("synthetic-{}.json", generate_cl_ord_id()));
+        let store = SyntheticOrderStore::new(&path);
+        assert!(store.load().unwrap().is_empty());
+
+        let order = SyntheticOrder::new(SyntheticOrderRequest::Oco {
+            first: limit(OrderSide::Sell, "1", "1900"),
+            second: limit(OrderSide::Sell, "1", "1700"),
+        });
+        store.save(&[&order]).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].id, order.id);
+        assert_eq!(loaded[0].request.kind(), "oco");
+        assert_eq!(loaded[0].status, SyntheticOrderStatus::Pending);
+        fs::remove_file(path).unwrap();
+    }
+}
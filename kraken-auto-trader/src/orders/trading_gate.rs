@@ -0,0 +1,194 @@
+use crate::{
+    errors::Error,
+    models::trading::{NewOrder, OrderType},
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr, sync::RwLock};
+use tracing::{info, warn};
+
+/// Trading mode of the exchange as reported by the SystemStatus endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExchangeMode {
+    /// All orders are accepted
+    Online,
+    /// Only cancellations are accepted
+    CancelOnly,
+    /// Only limit orders are accepted
+    LimitOnly,
+    /// Only post-only limit orders are accepted
+    PostOnly,
+    /// Nothing is accepted
+    Maintenance,
+}
+
+impl ExchangeMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExchangeMode::Online => "online",
+            ExchangeMode::CancelOnly => "cancel_only",
+            ExchangeMode::LimitOnly => "limit_only",
+            ExchangeMode::PostOnly => "post_only",
+            ExchangeMode::Maintenance => "maintenance",
+        }
+    }
+}
+
+impl fmt::Display for ExchangeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ExchangeMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "online" => Ok(ExchangeMode::Online),
+            "cancel_only" => Ok(ExchangeMode::CancelOnly),
+            "limit_only" => Ok(ExchangeMode::LimitOnly),
+            "post_only" => Ok(ExchangeMode::PostOnly),
+            "maintenance" => Ok(ExchangeMode::Maintenance),
+            other => Err(Error::InvalidParameter(format!(
+                "Unknown system status `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+/// Holds new orders back while the exchange does not accept them.
+///
+/// Until a status is known every order is let through. In `post_only` mode limit orders are
+/// sent with the `post` flag and other order types are refused; in `limit_only` mode only limit
+/// orders pass; `cancel_only` and `maintenance` refuse every new order. Orders pass again as
+/// soon as the exchange is back `online`.
+#[derive(Debug, Default)]
+pub struct TradingGate {
+    mode: RwLock<Option<ExchangeMode>>,
+}
+
+impl TradingGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The last status reported by the exchange
+    pub fn mode(&self) -> Option<ExchangeMode> {
+        *self.mode.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record the status reported by the exchange
+    pub fn set_mode(&self, mode: ExchangeMode) {
+        let previous = self
+            .mode
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .replace(mode);
+        match (previous, mode) {
+            (Some(previous), _) if previous == mode => {}
+            (_, ExchangeMode::Online) if previous.is_some() => {
+                info!("Exchange is back online, resuming new orders")
+            }
+            (_, ExchangeMode::Online) => {}
+            _ => warn!("Exchange is in {} mode, restricting new orders", mode),
+        }
+    }
+
+    /// Whether new orders can be placed at all
+    pub fn accepts_orders(&self) -> bool {
+        !matches!(
+            self.mode(),
+            Some(ExchangeMode::CancelOnly | ExchangeMode::Maintenance)
+        )
+    }
+
+    /// Refuse `order` or adapt it to what the exchange currently accepts
+    pub fn check(&self, order: &mut NewOrder) -> Result<(), Error> {
+        let Some(mode) = self.mode() else {
+            return Ok(());
+        };
+        let refuse = || {
+            Err(Error::ValidationError(format!(
+                "Exchange is in {} mode, {} orders are not accepted",
+                mode,
+                order.ordertype.as_str()
+            )))
+        };
+        match mode {
+            ExchangeMode::Online => Ok(()),
+            ExchangeMode::CancelOnly | ExchangeMode::Maintenance => Err(Error::ValidationError(
+                format!("Exchange is in {} mode, new orders are paused", mode),
+            )),
+            ExchangeMode::LimitOnly if order.ordertype != OrderType::Limit => refuse(),
+            ExchangeMode::LimitOnly => Ok(()),
+            ExchangeMode::PostOnly if order.ordertype != OrderType::Limit => refuse(),
+            ExchangeMode::PostOnly => {
+                let flags = order.oflags.get_or_insert_with(String::new);
+                if !flags.split(',').any(|flag| flag == "post") {
+                    if !flags.is_empty() {
+                        flags.push(',');
+                    }
+                    flags.push_str("post");
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{symbols::Pair, trading::OrderSide};
+
+    fn order(ordertype: OrderType) -> NewOrder {
+        let mut order = NewOrder::new(
+            Pair::from("XBTUSD"),
+            OrderSide::Buy,
+            ordertype,
+            "0.01".to_string(),
+        );
+        order.price = Some("50000".to_string());
+        order
+    }
+
+    #[test]
+    fn test_modes_restrict_orders() {
+        let gate = TradingGate::new();
+        assert!(gate.check(&mut order(OrderType::Market)).is_ok());
+
+        gate.set_mode(ExchangeMode::CancelOnly);
+        assert!(!gate.accepts_orders());
+        assert!(gate.check(&mut order(OrderType::Limit)).is_err());
+
+        gate.set_mode(ExchangeMode::PostOnly);
+        assert!(gate.check(&mut order(OrderType::Market)).is_err());
+        let mut limit = order(OrderType::Limit);
+        limit.oflags = Some("fciq".to_string());
+        gate.check(&mut limit).unwrap();
+        assert_eq!(limit.oflags.as_deref(), Some("fciq,post"));
+        gate.check(&mut limit).unwrap();
+        assert_eq!(limit.oflags.as_deref(), Some("fciq,post"));
+
+        gate.set_mode(ExchangeMode::LimitOnly);
+        assert!(gate.check(&mut order(OrderType::StopLoss)).is_err());
+        let mut limit = order(OrderType::Limit);
+        gate.check(&mut limit).unwrap();
+        assert_eq!(limit.oflags, None);
+
+        gate.set_mode(ExchangeMode::Online);
+        assert!(gate.accepts_orders());
+        assert!(gate.check(&mut order(OrderType::Market)).is_ok());
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(
+            "post_only".parse::<ExchangeMode>().unwrap(),
+            ExchangeMode::PostOnly
+        );
+        assert!("closed".parse::<ExchangeMode>().is_err());
+    }
+}
@@ -0,0 +1,14 @@
+//! Recording of raw market data and its deterministic replay.
+//!
+//! Recordings are directories of gzip compressed JSON lines files, one [`MarketMessage`] per
+//! line, written append-only and rotated periodically. Replays read them back, in order,
+//! into a [`MarketStream`] like the one a live connection produces.
+//!
+//! [`MarketMessage`]: crate::feeds::stream::MarketMessage
+//! [`MarketStream`]: crate::feeds::stream::MarketStream
+
+pub mod recorder;
+pub mod replay;
+
+/// Extension of recording files
+pub const RECORDING_EXTENSION: &str = "jsonl.gz";
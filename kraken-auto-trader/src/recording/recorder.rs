@@ -0,0 +1,133 @@
+use super::RECORDING_EXTENSION;
+use crate::{errors::Error, feeds::stream::MarketMessage};
+use flate2::{write::GzEncoder, Compression};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// Interval between two flushes of the compressed stream to disk
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+struct RecordingFile {
+    encoder: GzEncoder<File>,
+    period: u64,
+    last_flush: Instant,
+}
+
+/// Writes market-data messages to compressed, append-only files.
+///
+/// A new file is started every `rotate_secs` (of receive time) and at every start, so an
+/// existing file is never reopened. Data is flushed at most a second after being recorded.
+#[derive(Debug)]
+pub struct Recorder {
+    directory: PathBuf,
+    prefix: String,
+    rotate_secs: u64,
+    file: Mutex<Option<RecordingFile>>,
+}
+
+impl Recorder {
+    /// Record into `directory`, creating it if needed
+    pub fn new(directory: impl Into<PathBuf>) -> Result<Self, Error> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory,
+            prefix: "market".to_string(),
+            rotate_secs: 3600,
+            file: Mutex::new(None),
+        })
+    }
+
+    /// Start a new file every `secs`
+    pub fn with_rotation(mut self, secs: u64) -> Self {
+        self.rotate_secs = secs.max(1);
+        self
+    }
+
+    /// Prefix of the file names, `market` by default
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Append a message; failures are logged rather than interrupting the data flow
+    pub fn record(&self, message: &MarketMessage) {
+        if let Err(e) = self.write(message) {
+            warn!("Failed to record market data: {}", e);
+        }
+    }
+
+    /// Flush buffered messages to disk
+    pub fn flush(&self) -> Result<(), Error> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(file) = file.as_mut() {
+            file.encoder.flush()?;
+            file.last_flush = Instant::now();
+        }
+        Ok(())
+    }
+
+    fn write(&self, message: &MarketMessage) -> Result<(), Error> {
+        let period = (message.received_at.max(0.0) as u64) / self.rotate_secs;
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if file.as_ref().is_none_or(|file| file.period != period) {
+            if let Some(previous) = file.take() {
+                previous.encoder.finish()?;
+            }
+            *file = Some(self.open(period)?);
+        }
+        let Some(file) = file.as_mut() else {
+            return Ok(());
+        };
+        file.encoder.write_all(&line)?;
+        if file.last_flush.elapsed() >= FLUSH_INTERVAL {
+            file.encoder.flush()?;
+            file.last_flush = Instant::now();
+        }
+        Ok(())
+    }
+
+    fn open(&self, period: u64) -> Result<RecordingFile, Error> {
+        let start = period * self.rotate_secs;
+        for sequence in 0.. {
+            let path = self.directory.join(format!(
+                "{}-{}-{:03}.{}",
+                self.prefix, start, sequence, RECORDING_EXTENSION
+            ));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => {
+                    info!("Recording market data to {}", path.display());
+                    return Ok(RecordingFile {
+                        encoder: GzEncoder::new(file, Compression::default()),
+                        period,
+                        last_flush: Instant::now(),
+                    });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        unreachable!("file sequence exhausted")
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let file = self.file.get_mut().unwrap_or_else(|e| e.into_inner());
+        if let Some(file) = file.take() {
+            if let Err(e) = file.encoder.finish() {
+                warn!("Failed to close market data recording: {}", e);
+            }
+        }
+    }
+}
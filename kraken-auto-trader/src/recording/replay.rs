@@ -0,0 +1,224 @@
+use super::RECORDING_EXTENSION;
+use crate::{
+    errors::Error,
+    feeds::stream::{MarketMessage, MarketStream},
+};
+use flate2::read::MultiGzDecoder;
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, ErrorKind},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+/// Pace at which recorded messages are replayed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Messages are spaced as they were received
+    RealTime,
+    /// Spacing is divided by the given factor
+    Accelerated(f64),
+    /// Messages are sent as fast as the stream is consumed
+    Max,
+    /// One message per step granted through [`ReplayControl::step`]
+    Stepped,
+}
+
+/// Read every message of a recording file, in order.
+///
+/// A file left truncated by a recorder that didn't shut down cleanly is read up to the
+/// last complete message.
+pub fn read_recording(path: &Path) -> Result<Vec<MarketMessage>, Error> {
+    let decoder = MultiGzDecoder::new(BufReader::new(File::open(path)?));
+    let mut messages = Vec::new();
+    for line in BufReader::new(decoder).lines() {
+        match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => messages.push(serde_json::from_str(&line)?),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                warn!("Recording {} is truncated", path.display());
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(messages)
+}
+
+/// Grants steps to a replay running at [`ReplaySpeed::Stepped`]
+#[derive(Clone)]
+pub struct ReplayControl {
+    steps: Arc<Semaphore>,
+}
+
+impl ReplayControl {
+    /// Let `count` more messages through
+    pub fn step(&self, count: usize) {
+        self.steps.add_permits(count);
+    }
+}
+
+/// Replays recording files into a [`MarketStream`]
+pub struct Replayer {
+    files: Vec<PathBuf>,
+    speed: ReplaySpeed,
+    buffer: usize,
+}
+
+impl Replayer {
+    /// Replay the given files in order
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        Self {
+            files,
+            speed: ReplaySpeed::RealTime,
+            buffer: 1024,
+        }
+    }
+
+    /// Replay every recording file of a directory, in name (and so time) order
+    pub fn from_directory(directory: impl AsRef<Path>) -> Result<Self, Error> {
+        let suffix = format!(".{}", RECORDING_EXTENSION);
+        let mut files: Vec<PathBuf> = fs::read_dir(directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.ends_with(&suffix))
+            })
+            .collect();
+        files.sort();
+        Ok(Self::new(files))
+    }
+
+    pub fn with_speed(mut self, speed: ReplaySpeed) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Start the replay in the background
+    pub fn spawn(self) -> (MarketStream, ReplayControl) {
+        let (tx, stream) = MarketStream::channel(self.buffer);
+        let control = ReplayControl {
+            steps: Arc::new(Semaphore::new(0)),
+        };
+        let steps = control.steps.clone();
+        let speed = self.speed;
+
+        tokio::spawn(async move {
+            let mut previous: Option<f64> = None;
+            for path in self.files {
+                let name = path.display().to_string();
+                let messages =
+                    match tokio::task::spawn_blocking(move || read_recording(&path)).await {
+                        Ok(Ok(messages)) => messages,
+                        Ok(Err(e)) => {
+                            warn!("Skipping recording {}: {}", name, e);
+                            continue;
+                        }
+                        Err(e) => {
+                            warn!("Skipping recording {}: {}", name, e);
+                            continue;
+                        }
+                    };
+
+                for message in messages {
+                    match speed {
+                        ReplaySpeed::Stepped => match steps.acquire().await {
+                            Ok(permit) => permit.forget(),
+                            Err(_) => return,
+                        },
+                        ReplaySpeed::RealTime | ReplaySpeed::Accelerated(_) => {
+                            let factor = match speed {
+                                ReplaySpeed::Accelerated(factor) if factor > 0.0 => factor,
+                                _ => 1.0,
+                            };
+                            if let Some(previous) = previous {
+                                let gap = (message.received_at - previous).max(0.0) / factor;
+                                tokio::time::sleep(Duration::from_secs_f64(gap)).await;
+                            }
+                        }
+                        ReplaySpeed::Max => {}
+                    }
+                    previous = Some(message.received_at);
+                    if tx.send(message).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        (stream, control)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{feeds::stream::MessageSource, recording::recorder::Recorder};
+
+    fn message(received_at: f64, raw: &str) -> MarketMessage {
+        MarketMessage {
+            received_at,
+            source: MessageSource::WebSocket {
+                url: "wss://ws.kraken.com/v2".to_string(),
+            },
+            raw: raw.to_string(),
+        }
+    }
+
+    fn recording(name: &str, messages: &[MarketMessage]) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "recording-{}-{}",
+            name,
+            crate::orders::manager::generate_cl_ord_id()
+        ));
+        let recorder = Recorder::new(&directory).unwrap().with_rotation(100);
+        for message in messages {
+            recorder.record(message);
+        }
+        drop(recorder);
+        directory
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let messages = vec![
+            message(10.0, r#"{"channel":"heartbeat"}"#),
+            message(50.0, r#"{"channel":"ticker"}"#),
+            // Rotates into a second file
+            message(150.0, r#"{"channel":"trade"}"#),
+        ];
+        let directory = recording("replay", &messages);
+
+        let replayer = Replayer::from_directory(&directory).unwrap();
+        assert_eq!(replayer.files.len(), 2);
+        let (mut stream, _) = replayer.with_speed(ReplaySpeed::Max).spawn();
+        let mut replayed = Vec::new();
+        while let Some(message) = stream.next().await {
+            replayed.push(message);
+        }
+        assert_eq!(replayed, messages);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stepped_replay() {
+        let messages = vec![message(1.0, "a"), message(2.0, "b")];
+        let directory = recording("stepped", &messages);
+
+        let (mut stream, control) = Replayer::from_directory(&directory)
+            .unwrap()
+            .with_speed(ReplaySpeed::Stepped)
+            .spawn();
+        let pending = tokio::time::timeout(Duration::from_millis(50), stream.next()).await;
+        assert!(pending.is_err());
+
+        control.step(1);
+        assert_eq!(stream.next().await.unwrap().raw, "a");
+        control.step(1);
+        assert_eq!(stream.next().await.unwrap().raw, "b");
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
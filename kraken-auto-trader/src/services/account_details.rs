@@ -1,12 +1,14 @@
 use crate::{
     client::kraken_apis::{KrakenRequest, PrivateApi, PrivateApiBuilder},
     errors::Error,
+    metrics::metrics,
     models::account::{
         Balance, TradeVolume, TradeBalance, OpenOrders, ClosedOrders, TradesHistory,
         OpenPositions, Ledgers, ExportReport, Order, Trade, Ledger
     },
+    models::symbols::{join_symbols, AssetId, Pair},
 };
-use actix_web::HttpRequest;
+use crate::middleware::KrakenClientExt;
 use std::collections::HashMap;
 use crate::utils::endpoints::account::*;
 
@@ -22,29 +24,46 @@ impl Account {
         Ok(Self { private_api: api })
     }
 
+    pub fn with_api(private_api: PrivateApi) -> Self {
+        Self { private_api }
+    }
+
     /// Get account balance
-    pub async fn get_balance(&self, req: HttpRequest) -> Result<Balance, Error> {
+    pub async fn get_balance(&self, req: impl KrakenClientExt) -> Result<Balance, Error> {
         PrivateApi::kraken_request(&self.private_api, req, BALANCE, HashMap::new()).await
     }
 
     /// Get extended account balance
-    pub async fn get_balance_ex(&self, req: HttpRequest) -> Result<Balance, Error> {
+    pub async fn get_balance_ex(&self, req: impl KrakenClientExt) -> Result<Balance, Error> {
         PrivateApi::kraken_request(&self.private_api, req, BALANCE_EX, HashMap::new()).await
     }
 
     /// Get trade balance
-    pub async fn get_trade_balance(&self, req: HttpRequest, asset: Option<String>) -> Result<TradeBalance, Error> {
+    pub async fn get_trade_balance(&self, req: impl KrakenClientExt, asset: Option<AssetId>) -> Result<TradeBalance, Error> {
         let mut params = HashMap::new();
-        if let Some(asset) = asset {
-            params.insert("asset".to_string(), asset);
-        }
-        PrivateApi::kraken_request(&self.private_api, req, TRADE_BALANCE, params).await
+        // Kraken reports in ZUSD unless asked otherwise
+        let currency = match asset {
+            Some(asset) => {
+                params.insert("asset".to_string(), asset.to_string());
+                asset.to_string()
+            }
+            None => "ZUSD".to_string(),
+        };
+        let balance: TradeBalance =
+            PrivateApi::kraken_request(&self.private_api, req, TRADE_BALANCE, params).await?;
+        if let Ok(equity) = balance.e.parse::<f64>() {
+            metrics()
+                .portfolio_equity
+                .with_label_values(&[currency.as_str()])
+                .set(equity);
+        }
+        Ok(balance)
     }
 
     /// Get open orders
     pub async fn get_open_orders(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         trades: Option<bool>,
         userref: Option<String>,
         cl_ord_id: Option<String>,
@@ -63,9 +82,10 @@ impl Account {
     }
 
     /// Get closed orders
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_closed_orders(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         trades: Option<bool>,
         userref: Option<String>,
         start: Option<i64>,
@@ -74,6 +94,7 @@ impl Account {
         closetime: Option<String>,
         consolidate_taker: Option<bool>,
         without_count: Option<bool>,
+        cl_ord_id: Option<String>,
     ) -> Result<ClosedOrders, Error> {
         let mut params = HashMap::new();
         if let Some(trades) = trades {
@@ -100,13 +121,16 @@ impl Account {
         if let Some(without_count) = without_count {
             params.insert("without_count".to_string(), without_count.to_string());
         }
+        if let Some(cl_ord_id) = cl_ord_id {
+            params.insert("cl_ord_id".to_string(), cl_ord_id);
+        }
         PrivateApi::kraken_request(&self.private_api, req, CLOSED_ORDERS, params).await
     }
 
     /// Query orders info
     pub async fn query_orders(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         trades: Option<bool>,
         userref: Option<String>,
         txid: Vec<String>,
@@ -129,7 +153,7 @@ impl Account {
     /// Get order amends
     pub async fn get_order_amends(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         order_id: String,
     ) -> Result<HashMap<String, Vec<Order>>, Error> {
         let mut params = HashMap::new();
@@ -138,9 +162,10 @@ impl Account {
     }
 
     /// Get trades history
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_trades_history(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         trades: Option<bool>,
         type_param: Option<String>,
         start: Option<i64>,
@@ -173,7 +198,7 @@ impl Account {
     /// Query trades info
     pub async fn query_trades(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         trades: Option<bool>,
         txid: Vec<String>,
         consolidate_taker: Option<bool>,
@@ -192,7 +217,7 @@ impl Account {
     /// Get open positions
     pub async fn get_open_positions(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         trades: Option<bool>,
         docalcs: Option<bool>,
     ) -> Result<OpenPositions, Error> {
@@ -207,10 +232,11 @@ impl Account {
     }
 
     /// Get ledgers
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_ledgers(
         &self,
-        req: HttpRequest,
-        asset: Option<String>,
+        req: impl KrakenClientExt,
+        assets: Option<&[AssetId]>,
         aclass: Option<String>,
         type_param: Option<String>,
         start: Option<i64>,
@@ -219,8 +245,8 @@ impl Account {
         consolidate_taker: Option<bool>,
     ) -> Result<Ledgers, Error> {
         let mut params = HashMap::new();
-        if let Some(asset) = asset {
-            params.insert("asset".to_string(), asset);
+        if let Some(assets) = assets {
+            params.insert("asset".to_string(), join_symbols(assets));
         }
         if let Some(aclass) = aclass {
             params.insert("aclass".to_string(), aclass);
@@ -246,7 +272,7 @@ impl Account {
     /// Query ledgers
     pub async fn query_ledgers(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         id: Vec<String>,
     ) -> Result<HashMap<String, Ledger>, Error> {
         let mut params = HashMap::new();
@@ -257,12 +283,12 @@ impl Account {
     /// Get trade volume
     pub async fn get_trade_volume(
         &self,
-        req: HttpRequest,
-        pair: Option<String>,
+        req: impl KrakenClientExt,
+        pairs: Option<&[Pair]>,
     ) -> Result<TradeVolume, Error> {
         let mut params = HashMap::new();
-        if let Some(pair) = pair {
-            params.insert("pair".to_string(), pair);
+        if let Some(pairs) = pairs {
+            params.insert("pair".to_string(), join_symbols(pairs));
         }
         PrivateApi::kraken_request(&self.private_api, req, TRADE_VOLUME, params).await
     }
@@ -270,7 +296,7 @@ impl Account {
     /// Request export report
     pub async fn request_export_report(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         report_type: String,
         description: String,
         format: Option<String>,
@@ -295,7 +321,7 @@ impl Account {
     /// Get export report status
     pub async fn get_export_report_status(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         report_id: String,
     ) -> Result<ExportReport, Error> {
         let mut params = HashMap::new();
@@ -306,7 +332,7 @@ impl Account {
     /// Retrieve export
     pub async fn retrieve_export(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         report_id: String,
     ) -> Result<ExportReport, Error> {
         let mut params = HashMap::new();
@@ -317,7 +343,7 @@ impl Account {
     /// Delete export report
     pub async fn delete_export_report(
         &self,
-        req: HttpRequest,
+        req: impl KrakenClientExt,
         report_id: String,
     ) -> Result<(), Error> {
         let mut params = HashMap::new();
@@ -330,7 +356,7 @@ impl Account {
 mod tests {
     use super::*;
     use crate::{
-        client::kraken_client::KrakenClient, utils::config::KrakenConfig, middleware::KrakenClientState,
+        client::kraken_client::KrakenClient, middleware::KrakenClientState, mock::MockKraken,
     };
     use actix_web::test;
 
@@ -342,7 +368,8 @@ mod tests {
         std::env::remove_var("KRAKEN_API_KEY");
         std::env::remove_var("KRAKEN_API_SECRET");
 
-        let config = KrakenConfig::default();
+        let server = MockKraken::start().await.unwrap();
+        let config = server.config();
         let client = KrakenClient::new(config).unwrap();
 
         let req = test::TestRequest::default()
@@ -370,7 +397,8 @@ mod tests {
         std::env::remove_var("KRAKEN_API_KEY");
         std::env::remove_var("KRAKEN_API_SECRET");
 
-        let config = KrakenConfig::default();
+        let server = MockKraken::start().await.unwrap();
+        let config = server.config();
         let client = KrakenClient::new(config).unwrap();
 
         let req = test::TestRequest::default()
@@ -0,0 +1,351 @@
+use crate::{
+    client::idempotency::outcome_unknown,
+    client::kraken_apis::{KrakenRequest, PrivateApi, PrivateApiBuilder},
+    clock::unix_time,
+    errors::Error,
+    middleware::KrakenClientExt,
+    models::funding::{
+        DepositAddress, DepositMethod, WalletTransfer, WithdrawalResponse, WithdrawalStatus,
+    },
+    models::symbols::AssetId,
+    utils::endpoints::funding::*,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::Duration,
+};
+use tracing::{info, warn};
+
+/// How far back, in seconds, a withdrawal found while reconciling may predate the request,
+/// covering the difference between our clock and Kraken's
+const RECONCILE_SLACK_SECS: f64 = 5.0;
+
+/// Shortest time a withdrawal with an unknown outcome must stay missing before it is resent
+const MIN_WITHDRAW_RETRY_WINDOW: Duration = Duration::from_secs(60);
+
+/// When a withdrawal whose outcome stays unknown may be sent again
+#[derive(Debug, Clone, Copy)]
+struct WithdrawRetry {
+    attempts: u32,
+    window: Duration,
+}
+
+pub struct Funding {
+    private_api: PrivateApi,
+    /// Resending withdrawals is opt-in, they are sent once by default
+    withdraw_retry: Option<WithdrawRetry>,
+    reconcile_delay: Duration,
+    /// Refids of the withdrawals made through this instance, never taken for another request
+    claimed: Mutex<HashSet<String>>,
+}
+
+impl Funding {
+    pub fn new() -> Result<Self, Error> {
+        let api = PrivateApiBuilder::from_env()?.build()?;
+
+        Ok(Self::with_api(api))
+    }
+
+    pub fn with_api(private_api: PrivateApi) -> Self {
+        Self {
+            private_api,
+            withdraw_retry: None,
+            reconcile_delay: Duration::from_secs(5),
+            claimed: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Send a withdrawal whose outcome stays unknown up to `attempts` times in all. It is only
+    /// sent again once no matching withdrawal has shown up on Kraken for `window`, at least a
+    /// minute, since it was first sent
+    pub fn with_withdraw_retry(mut self, attempts: u32, window: Duration) -> Self {
+        self.withdraw_retry = Some(WithdrawRetry {
+            attempts: attempts.max(1),
+            window: window.max(MIN_WITHDRAW_RETRY_WINDOW),
+        });
+        self
+    }
+
+    /// Wait `delay` after a withdrawal with an unknown outcome before looking it up, so a request
+    /// still being carried out by Kraken shows up
+    pub fn with_reconcile_delay(mut self, delay: Duration) -> Self {
+        self.reconcile_delay = delay;
+        self
+    }
+
+    /// Whether a configured API key may call `endpoint`
+    pub fn permits(&self, endpoint: &str) -> bool {
+        self.private_api.permits(endpoint)
+    }
+
+    /// Get the methods available to deposit `asset`
+    pub async fn get_deposit_methods(
+        &self,
+        req: impl KrakenClientExt,
+        asset: AssetId,
+    ) -> Result<Vec<DepositMethod>, Error> {
+        let mut params = HashMap::new();
+        params.insert("asset".to_string(), asset.to_string());
+        PrivateApi::kraken_request(&self.private_api, req, DEPOSIT_METHODS, params).await
+    }
+
+    /// Get the addresses to deposit `asset` with `method`
+    ///
+    /// # Parameters
+    ///
+    /// * `new` - Generate a new address instead of listing the existing ones
+    pub async fn get_deposit_addresses(
+        &self,
+        req: impl KrakenClientExt,
+        asset: AssetId,
+        method: String,
+        new: bool,
+    ) -> Result<Vec<DepositAddress>, Error> {
+        let mut params = HashMap::new();
+        params.insert("asset".to_string(), asset.to_string());
+        params.insert("method".to_string(), method);
+        if new {
+            params.insert("new".to_string(), "true".to_string());
+        }
+        PrivateApi::kraken_request(&self.private_api, req, DEPOSIT_ADDRESSES, params).await
+    }
+
+    /// Get the status of recent withdrawals
+    pub async fn get_withdrawal_status(
+        &self,
+        req: impl KrakenClientExt,
+        asset: Option<AssetId>,
+        method: Option<String>,
+    ) -> Result<Vec<WithdrawalStatus>, Error> {
+        let mut params = HashMap::new();
+        if let Some(asset) = asset {
+            params.insert("asset".to_string(), asset.to_string());
+        }
+        if let Some(method) = method {
+            params.insert("method".to_string(), method);
+        }
+        PrivateApi::kraken_request(&self.private_api, req, RECENT_WITHDRAWALS_STATUS, params).await
+    }
+
+    /// Withdraw `amount` of `asset` to the address saved under `key`
+    ///
+    /// A withdrawal is sent once. When its outcome is unknown, as after a timeout, the recent
+    /// withdrawals of the asset are queried once the reconcile delay has passed, and the one
+    /// made since the request for the same amount and address is taken as this one. Withdrawals
+    /// already returned by this instance are never taken, and several candidates are an error
+    /// to resolve by hand. Without a match the error is returned, unless a retry was set with
+    /// [`with_withdraw_retry`](Self::with_withdraw_retry).
+    pub async fn withdraw(
+        &self,
+        req: impl KrakenClientExt + Clone,
+        asset: AssetId,
+        key: &str,
+        amount: &str,
+    ) -> Result<WithdrawalResponse, Error> {
+        let params = HashMap::from([
+            ("asset".to_string(), asset.to_string()),
+            ("key".to_string(), key.to_string()),
+            ("amount".to_string(), amount.to_string()),
+        ]);
+
+        let sent = unix_time();
+        let first_sent = tokio::time::Instant::now();
+        let mut attempt = 1;
+        loop {
+            let result: Result<WithdrawalResponse, Error> = PrivateApi::kraken_request(
+                &self.private_api,
+                req.clone(),
+                WITHDRAW_FUNDS,
+                params.clone(),
+            )
+            .await;
+            let error = match result {
+                Ok(response) => {
+                    self.claim(&response.refid);
+                    return Ok(response);
+                }
+                Err(e) if outcome_unknown(&e) => e,
+                Err(e) => return Err(e),
+            };
+
+            warn!(
+                "Outcome of withdrawing {} {} is unknown ({}), checking recent withdrawals",
+                amount, asset, error
+            );
+            tokio::time::sleep(self.reconcile_delay).await;
+            if let Some(found) = self.reconcile(req.clone(), &asset, key, amount, sent).await? {
+                return Ok(found);
+            }
+            let Some(retry) = self.withdraw_retry.filter(|retry| attempt < retry.attempts) else {
+                return Err(error);
+            };
+
+            // Only a withdrawal still missing after the whole window is taken as not made
+            tokio::time::sleep_until(first_sent + retry.window * attempt).await;
+            if let Some(found) = self.reconcile(req.clone(), &asset, key, amount, sent).await? {
+                return Ok(found);
+            }
+            attempt += 1;
+            warn!(
+                "No withdrawal of {} {} found after {:?}, sending it again (attempt {}/{})",
+                amount,
+                asset,
+                first_sent.elapsed(),
+                attempt,
+                retry.attempts
+            );
+        }
+    }
+
+    /// Look for the withdrawal of a request sent at `sent` among the recent ones
+    async fn reconcile(
+        &self,
+        req: impl KrakenClientExt,
+        asset: &AssetId,
+        key: &str,
+        amount: &str,
+        sent: f64,
+    ) -> Result<Option<WithdrawalResponse>, Error> {
+        let recent = self
+            .get_withdrawal_status(req, Some(asset.clone()), None)
+            .await?;
+        let claimed = self.claimed.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let Some(found) = find_withdrawal(&recent, asset, key, amount, sent, &claimed)? else {
+            return Ok(None);
+        };
+        info!("Withdrawal {} was received by Kraken", found.refid);
+        self.claim(&found.refid);
+        Ok(Some(WithdrawalResponse {
+            refid: found.refid.clone(),
+        }))
+    }
+
+    fn claim(&self, refid: &str) {
+        self.claimed
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(refid.to_string());
+    }
+
+    /// Transfer `amount` of `asset` from the spot wallet to the futures wallet
+    ///
+    /// Kraken offers no way to look a transfer up, so one whose outcome is unknown is not sent
+    /// again and its error is returned.
+    pub async fn wallet_transfer(
+        &self,
+        req: impl KrakenClientExt,
+        asset: AssetId,
+        amount: &str,
+    ) -> Result<WalletTransfer, Error> {
+        let params = HashMap::from([
+            ("asset".to_string(), asset.to_string()),
+            ("from".to_string(), "Spot Wallet".to_string()),
+            ("to".to_string(), "Futures Wallet".to_string()),
+            ("amount".to_string(), amount.to_string()),
+        ]);
+        PrivateApi::kraken_request(&self.private_api, req, WALLET_TRANSFER, params).await
+    }
+}
+
+/// The withdrawal in `recent` matching a request sent at `sent`, other than the `claimed`
+/// ones. Kraken reports the amount either as requested or net of its fee. Several matches
+/// cannot be told apart and are an error.
+fn find_withdrawal<'a>(
+    recent: &'a [WithdrawalStatus],
+    asset: &AssetId,
+    key: &str,
+    amount: &str,
+    sent: f64,
+    claimed: &HashSet<String>,
+) -> Result<Option<&'a WithdrawalStatus>, Error> {
+    let Ok(requested) = amount.parse::<f64>() else {
+        return Ok(None);
+    };
+    let same = |a: f64, b: f64| (a - b).abs() < 1e-9;
+    let matches: Vec<&WithdrawalStatus> = recent
+        .iter()
+        .filter(|withdrawal| {
+            let reported = withdrawal.amount.parse::<f64>().unwrap_or(f64::NAN);
+            let fee = withdrawal.fee.parse::<f64>().unwrap_or(0.0);
+            withdrawal.asset == *asset
+                && withdrawal.info == key
+                && withdrawal.time as f64 >= sent - RECONCILE_SLACK_SECS
+                && (same(reported, requested) || same(reported + fee, requested))
+                && !claimed.contains(&withdrawal.refid)
+        })
+        .collect();
+    match matches.as_slice() {
+        [] => Ok(None),
+        [found] => Ok(Some(found)),
+        _ => Err(Error::Unknown(format!(
+            "Withdrawals {} all match {} {} to {}, check which one was requested before \
+             withdrawing again",
+            matches
+                .iter()
+                .map(|withdrawal| withdrawal.refid.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            amount,
+            asset,
+            key
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn withdrawal(refid: &str, amount: &str, fee: &str, time: i64) -> WithdrawalStatus {
+        WithdrawalStatus {
+            method: "Bitcoin".to_string(),
+            aclass: "currency".to_string(),
+            asset: AssetId::from("XXBT"),
+            refid: refid.to_string(),
+            txid: None,
+            info: "cold-storage".to_string(),
+            amount: amount.to_string(),
+            fee: fee.to_string(),
+            time,
+            status: "Initial".to_string(),
+            status_prop: None,
+        }
+    }
+
+    #[test]
+    fn test_find_withdrawal() {
+        let xbt = AssetId::from("XXBT");
+        let recent = vec![
+            withdrawal("OLD", "0.5", "0", 1_000),
+            withdrawal("NET", "0.4995", "0.0005", 2_000),
+        ];
+
+        let none = HashSet::new();
+        let find = |key: &str, sent: f64, claimed: &HashSet<String>| {
+            find_withdrawal(&recent, &xbt, key, "0.5", sent, claimed)
+                .unwrap()
+                .map(|w| w.refid.clone())
+        };
+
+        // Net of its fee, and made after the request
+        assert_eq!(find("cold-storage", 1_999.0, &none).as_deref(), Some("NET"));
+
+        assert!(find("cold-storage", 2_100.0, &none).is_none());
+        assert!(find("hot-wallet", 1_999.0, &none).is_none());
+        assert!(
+            find_withdrawal(&recent, &AssetId::from("XETH"), "cold-storage", "0.5", 0.0, &none)
+                .unwrap()
+                .is_none()
+        );
+
+        // Both match a request older than either, and can't be told apart
+        assert!(matches!(
+            find_withdrawal(&recent, &xbt, "cold-storage", "0.5", 0.0, &none),
+            Err(Error::Unknown(_))
+        ));
+        // Unless one of them was already returned for another request
+        let claimed = HashSet::from(["OLD".to_string()]);
+        assert_eq!(find("cold-storage", 0.0, &claimed).as_deref(), Some("NET"));
+    }
+}
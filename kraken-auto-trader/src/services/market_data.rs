@@ -1,44 +1,63 @@
 use crate::{
     client::kraken_apis::{KrakenRequest, PublicApi},
     errors::Error,
-    models::market::*,
+    instruments::symbols::SymbolResolver,
+    models::{
+        market::*,
+        symbols::{join_symbols, AssetId, Pair},
+    },
     utils::endpoints::market::*,
 };
-use actix_web::HttpRequest;
-use serde_json::Value;
+use crate::middleware::KrakenClientExt;
 use std::collections::HashMap;
 
 pub struct MarketData {
     public_api: PublicApi,
+    resolver: Option<SymbolResolver>,
+}
+
+impl Default for MarketData {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MarketData {
     pub fn new() -> Self {
         Self {
             public_api: PublicApi::new(),
+            resolver: None,
         }
     }
 
+    /// Resolve pairs and assets through `resolver` before sending them, so that any spelling is
+    /// accepted and responses are keyed by canonical names. Without a resolver names are sent
+    /// as given
+    pub fn with_resolver(mut self, resolver: SymbolResolver) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
     /// Get server time
-    pub async fn get_server_time(&self, req: HttpRequest) -> Result<ServerTime, Error> {
+    pub async fn get_server_time(&self, req: impl KrakenClientExt) -> Result<ServerTime, Error> {
         PublicApi::kraken_request(&self.public_api, req, SERVER_TIME, HashMap::new()).await
     }
 
     /// Get system status
-    pub async fn get_system_status(&self, req: HttpRequest) -> Result<SystemStatus, Error> {
+    pub async fn get_system_status(&self, req: impl KrakenClientExt) -> Result<SystemStatus, Error> {
         PublicApi::kraken_request(&self.public_api, req, SYSTEM_STATUS, HashMap::new()).await
     }
 
     /// Get asset info
     pub async fn get_asset_info(
         &self,
-        req: HttpRequest,
-        asset: Option<String>,
+        req: impl KrakenClientExt,
+        assets: Option<&[AssetId]>,
         aclass: Option<String>,
-    ) -> Result<HashMap<String, Asset>, Error> {
+    ) -> Result<HashMap<AssetId, Asset>, Error> {
         let mut params = HashMap::new();
-        if let Some(asset) = asset {
-            params.insert("asset".to_string(), asset);
+        if let Some(assets) = assets {
+            params.insert("asset".to_string(), self.assets(assets)?);
         }
         if let Some(aclass) = aclass {
             params.insert("aclass".to_string(), aclass);
@@ -50,24 +69,24 @@ impl MarketData {
     ///
     /// # Parameters
     ///
-    /// * `pair` - Asset pairs to get data for (e.g. "BTC/USD,ETH/BTC")
+    /// * `pairs` - Asset pairs to get data for (e.g. "BTC/USD", "ETH/BTC")
     /// * `info` - Info to retrieve:
     ///   * info = all info (default)
     ///   * leverage = leverage info
     ///   * fees = fees schedule
     ///   * margin = margin info
     /// * `country_code` - Filter for response to only include pairs available in provided countries/regions
-    ///                   (e.g. "US:TX,GB,CA")
+    ///   (e.g. "US:TX,GB,CA")
     pub async fn get_tradable_asset_pairs(
         &self,
-        req: HttpRequest,
-        pair: Option<String>,
+        req: impl KrakenClientExt,
+        pairs: Option<&[Pair]>,
         info: Option<String>,
         country_code: Option<String>,
-    ) -> Result<HashMap<String, AssetPair>, Error> {
+    ) -> Result<HashMap<Pair, AssetPair>, Error> {
         let mut params = HashMap::new();
-        if let Some(pair) = pair {
-            params.insert("pair".to_string(), pair);
+        if let Some(pairs) = pairs {
+            params.insert("pair".to_string(), self.pairs(pairs)?);
         }
         if let Some(info) = info {
             // Validate info parameter
@@ -84,17 +103,36 @@ impl MarketData {
         PublicApi::kraken_request(&self.public_api, req, TRADABLE_ASSET_PAIRS, params).await
     }
 
-    /// Get ticker information
+    /// Get ticker information of `pairs`, or of every pair when empty
     pub async fn get_ticker(
         &self,
-        req: HttpRequest,
-        pair: String,
-    ) -> Result<HashMap<String, Ticker>, Error> {
+        req: impl KrakenClientExt,
+        pairs: &[Pair],
+    ) -> Result<HashMap<Pair, Ticker>, Error> {
         let mut params = HashMap::new();
-        params.insert("pair".to_string(), pair);
+        if !pairs.is_empty() {
+            params.insert("pair".to_string(), self.pairs(pairs)?);
+        }
         PublicApi::kraken_request(&self.public_api, req, TICKER, params).await
     }
 
+    /// Price of the last trade on `pair`, if Kraken reports one
+    pub async fn last_trade_price(
+        &self,
+        req: impl KrakenClientExt,
+        pair: &Pair,
+    ) -> Result<Option<f64>, Error> {
+        let tickers = self.get_ticker(req, std::slice::from_ref(pair)).await?;
+        // Kraken keys the response by the canonical name, which may not be the one asked for
+        let ticker = tickers
+            .get(pair)
+            .or_else(|| tickers.values().next().filter(|_| tickers.len() == 1));
+        Ok(ticker
+            .and_then(|ticker| ticker.c.first())
+            .and_then(|price| price.parse::<f64>().ok())
+            .filter(|price| price.is_finite() && *price > 0.0))
+    }
+
     /// Get OHLC data
     ///
     /// # Parameters
@@ -104,13 +142,13 @@ impl MarketData {
     /// * `since` - Return OHLC entries since the given timestamp (intended for incremental updates)
     pub async fn get_ohlc(
         &self,
-        req: HttpRequest,
-        pair: String,
+        req: impl KrakenClientExt,
+        pair: Pair,
         interval: Option<u32>,
         since: Option<u64>,
     ) -> Result<OHLCResponse, Error> {
         let mut params = HashMap::new();
-        params.insert("pair".to_string(), pair);
+        params.insert("pair".to_string(), self.pair(&pair)?.to_string());
 
         if let Some(interval) = interval {
             // Validate interval values
@@ -132,12 +170,12 @@ impl MarketData {
     /// Get order book
     pub async fn get_order_book(
         &self,
-        req: HttpRequest,
-        pair: String,
+        req: impl KrakenClientExt,
+        pair: Pair,
         count: Option<u32>,
-    ) -> Result<HashMap<String, OrderBook>, Error> {
+    ) -> Result<HashMap<Pair, OrderBook>, Error> {
         let mut params = HashMap::new();
-        params.insert("pair".to_string(), pair);
+        params.insert("pair".to_string(), self.pair(&pair)?.to_string());
         if let Some(count) = count {
             params.insert("count".to_string(), count.to_string());
         }
@@ -153,13 +191,13 @@ impl MarketData {
     /// * `count` - Number of trades to return (optional)
     pub async fn get_recent_trades(
         &self,
-        req: HttpRequest,
-        pair: String,
+        req: impl KrakenClientExt,
+        pair: Pair,
         since: Option<u64>,
         count: Option<u32>,
     ) -> Result<RecentTradesResponse, Error> {
         let mut params = HashMap::new();
-        params.insert("pair".to_string(), pair);
+        params.insert("pair".to_string(), self.pair(&pair)?.to_string());
 
         if let Some(since) = since {
             params.insert("since".to_string(), since.to_string());
@@ -178,36 +216,57 @@ impl MarketData {
     /// Get recent spreads
     pub async fn get_recent_spreads(
         &self,
-        
-        req: HttpRequest,
-        pair: String,
+        req: impl KrakenClientExt,
+        pair: Pair,
         since: Option<u64>,
     ) -> Result<RecentSpreadsResponse, Error> {
         let mut params = HashMap::new();
-        params.insert("pair".to_string(), pair);
+        params.insert("pair".to_string(), self.pair(&pair)?.to_string());
         if let Some(since) = since {
             params.insert("since".to_string(), since.to_string());
         }
         PublicApi::kraken_request(&self.public_api, req, RECENT_SPREADS, params).await
     }
+
+    fn pair(&self, pair: &Pair) -> Result<Pair, Error> {
+        match &self.resolver {
+            Some(resolver) => resolver.resolve_pair(pair.as_str()),
+            None => Ok(pair.clone()),
+        }
+    }
+
+    fn pairs(&self, pairs: &[Pair]) -> Result<String, Error> {
+        let pairs = pairs
+            .iter()
+            .map(|pair| self.pair(pair))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(join_symbols(&pairs))
+    }
+
+    fn assets(&self, assets: &[AssetId]) -> Result<String, Error> {
+        let assets = match &self.resolver {
+            Some(resolver) => assets
+                .iter()
+                .map(|asset| resolver.resolve_asset(asset.as_str()))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => assets.to_vec(),
+        };
+        Ok(join_symbols(&assets))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{
-        client::kraken_client::KrakenClient, middleware::KrakenClientState,
-        utils::config::KrakenConfig,
-    };
-    use actix_web::test;
+    use crate::{instruments::registry::InstrumentRegistry, mock::MockKraken};
+    use actix_web::test::TestRequest;
 
     #[actix_web::test]
     async fn test_get_server_time() {
-        let config = KrakenConfig::default();
-        let client = KrakenClient::new(config).unwrap();
+        let server = MockKraken::start().await.unwrap();
 
-        let req = test::TestRequest::default()
-            .app_data(actix_web::web::Data::new(KrakenClientState::new(client)))
+        let req = TestRequest::default()
+            .app_data(actix_web::web::Data::new(server.client_state().unwrap()))
             .to_http_request();
 
         let market = MarketData::new();
@@ -221,21 +280,40 @@ mod tests {
 
     #[actix_web::test]
     async fn test_get_recent_trades() {
-        let config = KrakenConfig::default();
-        let client = KrakenClient::new(config).unwrap();
+        let server = MockKraken::start().await.unwrap();
+        let state = server.client_state().unwrap();
+        let registry = InstrumentRegistry::default();
+        registry.refresh(state.clone()).await.unwrap();
 
-        let req = test::TestRequest::default()
-            .app_data(actix_web::web::Data::new(KrakenClientState::new(client)))
+        let req = TestRequest::default()
+            .app_data(actix_web::web::Data::new(state))
             .to_http_request();
 
-        let market = MarketData::new();
+        // Any spelling of the pair is sent, and answered, under its canonical name
+        let market = MarketData::new().with_resolver(registry.resolver());
         let result = market
-            .get_recent_trades(req, "ETHGBP".to_string(), None, Some(10))
+            .get_recent_trades(req, Pair::from("eth/gbp"), None, Some(10))
             .await;
         assert!(result.is_ok());
 
         let trades = result.unwrap();
+        let pair = registry.resolve_pair("ETHGBP").unwrap();
         assert!(!trades.last.is_empty());
-        assert!(trades.trades["XETHZGBP"].len() == 10);
+        assert!(trades.trades[&pair].len() == 10);
+    }
+
+    #[test]
+    fn test_unknown_pair_is_not_sent() {
+        let market = MarketData::new().with_resolver(SymbolResolver::default());
+        assert!(matches!(
+            market.pairs(&[Pair::from("XBTUSD")]),
+            Err(Error::InvalidParameter(_))
+        ));
+        assert_eq!(
+            MarketData::new()
+                .pairs(&[Pair::from("xbtusd"), Pair::from("ETHGBP")])
+                .unwrap(),
+            "XBTUSD,ETHGBP"
+        );
     }
 }
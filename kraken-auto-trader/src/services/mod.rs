@@ -1,2 +1,4 @@
 pub mod account_details;
-pub mod market_data;
\ No newline at end of file
+pub mod funding;
+pub mod market_data;
+pub mod trading;
\ No newline at end of file
@@ -0,0 +1,121 @@
+use crate::{
+    client::kraken_apis::{KrakenRequest, PrivateApi, PrivateApiBuilder},
+    errors::Error,
+    models::trading::{
+        AddOrderResponse, CancelAllOrdersAfterXResponse, CancelAllOrdersResponse,
+        CancelOrderResponse, NewOrder, WebSocketToken,
+    },
+    utils::endpoints::trading::*,
+};
+use crate::middleware::KrakenClientExt;
+use std::collections::HashMap;
+
+pub struct Trading {
+    private_api: PrivateApi,
+}
+
+impl Trading {
+    pub fn new() -> Result<Self, Error> {
+        let api = PrivateApiBuilder::from_env()?.build()?;
+
+        Ok(Self { private_api: api })
+    }
+
+    pub fn with_api(private_api: PrivateApi) -> Self {
+        Self { private_api }
+    }
+
+    /// Whether a configured API key may call `endpoint`
+    pub fn permits(&self, endpoint: &str) -> bool {
+        self.private_api.permits(endpoint)
+    }
+
+    /// Place a new order
+    pub async fn add_order(
+        &self,
+        req: impl KrakenClientExt,
+        order: &NewOrder,
+    ) -> Result<AddOrderResponse, Error> {
+        PrivateApi::kraken_request(&self.private_api, req, ADD_ORDER, order.to_params()).await
+    }
+
+    /// Cancel an open order
+    ///
+    /// # Parameters
+    ///
+    /// * `txid` - Kraken transaction id or user reference of the order(s) to cancel
+    /// * `cl_ord_id` - Client order id of the order to cancel
+    pub async fn cancel_order(
+        &self,
+        req: impl KrakenClientExt,
+        txid: Option<String>,
+        cl_ord_id: Option<String>,
+    ) -> Result<CancelOrderResponse, Error> {
+        let mut params = HashMap::new();
+        if let Some(txid) = txid {
+            params.insert("txid".to_string(), txid);
+        }
+        if let Some(cl_ord_id) = cl_ord_id {
+            params.insert("cl_ord_id".to_string(), cl_ord_id);
+        }
+        if params.is_empty() {
+            return Err(Error::InvalidParameter(
+                "Either txid or cl_ord_id must be provided".into(),
+            ));
+        }
+        PrivateApi::kraken_request(&self.private_api, req, CANCEL_ORDER, params).await
+    }
+
+    /// Cancel all open orders
+    pub async fn cancel_all_orders(
+        &self,
+        req: impl KrakenClientExt,
+    ) -> Result<CancelAllOrdersResponse, Error> {
+        PrivateApi::kraken_request(&self.private_api, req, CANCEL_ALL_ORDERS, HashMap::new()).await
+    }
+
+    /// Cancel all open orders after `timeout` seconds unless the call is repeated before then.
+    /// A timeout of 0 disables the countdown.
+    pub async fn cancel_all_orders_after(
+        &self,
+        req: impl KrakenClientExt,
+        timeout: u32,
+    ) -> Result<CancelAllOrdersAfterXResponse, Error> {
+        let mut params = HashMap::new();
+        params.insert("timeout".to_string(), timeout.to_string());
+        PrivateApi::kraken_request(&self.private_api, req, CANCEL_ALL_ORDERS_AFTER_X, params).await
+    }
+
+    /// Get a token for the authenticated WebSocket API
+    pub async fn get_websockets_token(&self, req: impl KrakenClientExt) -> Result<WebSocketToken, Error> {
+        PrivateApi::kraken_request(&self.private_api, req, GET_WEBSOCKETS_TOKEN, HashMap::new())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::kraken_client::KrakenClient, middleware::KrakenClientState,
+        utils::config::KrakenConfig,
+    };
+    use actix_web::test;
+
+    #[actix_web::test]
+    async fn test_cancel_order_requires_identifier() {
+        let client = KrakenClient::new(KrakenConfig::default()).unwrap();
+        let req = test::TestRequest::default()
+            .app_data(actix_web::web::Data::new(KrakenClientState::new(client)))
+            .to_http_request();
+
+        let api = PrivateApi::builder()
+            .with_api_key("key".to_string())
+            .with_api_secret("c2VjcmV0".to_string())
+            .build()
+            .unwrap();
+        let trading = Trading::with_api(api);
+        let result = trading.cancel_order(req, None, None).await;
+        assert!(matches!(result, Err(Error::InvalidParameter(_))));
+    }
+}
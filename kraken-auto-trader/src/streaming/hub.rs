@@ -0,0 +1,440 @@
+use super::{Channel, StreamEvent, Topic};
+use crate::{errors::Error, metrics::metrics, utils::config::StreamingConfig};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// What the hub hands a client
+#[derive(Debug, Clone, PartialEq)]
+pub enum Delivery {
+    /// An event of a subscribed topic, serialized once for every client
+    Event { channel: Channel, payload: Arc<str> },
+    /// Events dropped because the client's queue was full
+    Lagged { missed: u64 },
+}
+
+impl Delivery {
+    /// Name of the event on a Server-Sent Events stream
+    pub fn name(&self) -> &'static str {
+        match self {
+            Delivery::Event { channel, .. } => channel.as_str(),
+            Delivery::Lagged { .. } => "lagged",
+        }
+    }
+
+    /// JSON message sent to the client
+    pub fn payload(&self) -> String {
+        match self {
+            Delivery::Event { payload, .. } => payload.to_string(),
+            Delivery::Lagged { missed } => ServerMessage::Lagged { missed: *missed }.to_json(),
+        }
+    }
+}
+
+/// Message sent to streaming clients
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage<'a> {
+    Event(&'a StreamEvent),
+    Lagged {
+        missed: u64,
+    },
+    /// Topics the client is subscribed to after a change
+    Subscribed {
+        topics: Vec<String>,
+    },
+    Error {
+        message: String,
+    },
+    Pong,
+}
+
+impl ServerMessage<'_> {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Message sent by WebSocket clients, e.g. `{"method": "subscribe", "topics": ["ticker:BTC/USD"]}`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+enum ClientCommand {
+    Subscribe { topics: Vec<String> },
+    Unsubscribe { topics: Vec<String> },
+    Ping,
+}
+
+struct Client {
+    tx: mpsc::Sender<Delivery>,
+    topics: HashSet<Topic>,
+    /// May subscribe to account events
+    authorized: bool,
+    transport: &'static str,
+    /// Events dropped since the last one delivered
+    missed: u64,
+}
+
+/// Fans events out to the streaming clients subscribed to them.
+///
+/// Publishing never waits: each client has a bounded queue, and an event that does not fit is
+/// dropped for that client only. The client is told how many it missed before its next event,
+/// and is disconnected once `max_dropped` are dropped in a row.
+pub struct EventHub {
+    clients: Mutex<HashMap<u64, Client>>,
+    next_id: AtomicU64,
+    symbols: Vec<String>,
+    buffer: usize,
+    max_dropped: u64,
+    max_clients: usize,
+    keepalive: Duration,
+}
+
+impl EventHub {
+    pub fn new(config: &StreamingConfig) -> Self {
+        Self {
+            clients: Mutex::default(),
+            next_id: AtomicU64::new(1),
+            symbols: config.symbols.iter().map(|s| s.to_uppercase()).collect(),
+            buffer: config.client_buffer.max(1),
+            max_dropped: config.max_dropped.max(1),
+            max_clients: config.max_clients,
+            keepalive: config.keepalive(),
+        }
+    }
+
+    /// Interval between keep-alive messages to idle clients
+    pub fn keepalive(&self) -> Duration {
+        self.keepalive
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.clients.lock().expect("hub lock").len()
+    }
+
+    /// Register a client streaming over `transport`; `authorized` clients may subscribe to
+    /// account events
+    pub fn connect(
+        self: &Arc<Self>,
+        authorized: bool,
+        transport: &'static str,
+    ) -> Result<Subscriber, Error> {
+        let mut clients = self.clients.lock().expect("hub lock");
+        if clients.len() >= self.max_clients {
+            return Err(Error::RateLimitExceeded(format!(
+                "Already streaming to {} clients",
+                clients.len()
+            )));
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = mpsc::channel(self.buffer);
+        clients.insert(
+            id,
+            Client {
+                tx,
+                topics: HashSet::new(),
+                authorized,
+                transport,
+                missed: 0,
+            },
+        );
+        metrics()
+            .stream_clients
+            .with_label_values(&[transport])
+            .inc();
+        debug!("Streaming client {} connected over {}", id, transport);
+        Ok(Subscriber {
+            id,
+            hub: self.clone(),
+            rx,
+        })
+    }
+
+    /// Hand `event` to every client subscribed to it
+    pub fn publish(&self, event: StreamEvent) {
+        let mut clients = self.clients.lock().expect("hub lock");
+        let mut payload: Option<Arc<str>> = None;
+        let mut slow = Vec::new();
+        for (id, client) in clients.iter_mut() {
+            if !client.topics.iter().any(|topic| topic.matches(&event)) {
+                continue;
+            }
+            let payload = payload
+                .get_or_insert_with(|| ServerMessage::Event(&event).to_json().into())
+                .clone();
+            let delivery = Delivery::Event {
+                channel: event.channel,
+                payload,
+            };
+            // A client that missed events is told so first, which takes a second slot
+            let room = client.tx.capacity() >= if client.missed > 0 { 2 } else { 1 };
+            if room {
+                if client.missed > 0 {
+                    let _ = client.tx.try_send(Delivery::Lagged {
+                        missed: client.missed,
+                    });
+                    client.missed = 0;
+                }
+                let _ = client.tx.try_send(delivery);
+            } else {
+                client.missed += 1;
+                metrics()
+                    .stream_events_dropped
+                    .with_label_values(&[event.channel.as_str()])
+                    .inc();
+                if client.missed >= self.max_dropped {
+                    slow.push(*id);
+                }
+            }
+        }
+        for id in slow {
+            if let Some(client) = clients.remove(&id) {
+                warn!(
+                    "Disconnecting streaming client {} after dropping {} events",
+                    id, client.missed
+                );
+                metrics().stream_slow_disconnects.inc();
+                disconnected(&client);
+            }
+        }
+    }
+
+    fn subscribe(&self, id: u64, topics: &[Topic]) -> Result<Vec<Topic>, Error> {
+        let mut clients = self.clients.lock().expect("hub lock");
+        let client = clients
+            .get_mut(&id)
+            .ok_or_else(|| Error::InvalidParameter("Client is disconnected".to_string()))?;
+        for topic in topics {
+            if topic.channel.is_private() && !client.authorized {
+                return Err(Error::Auth(format!(
+                    "Channel `{}` requires a stream token",
+                    topic.channel.as_str()
+                )));
+            }
+            if let Some(symbol) = &topic.symbol {
+                if !self.symbols.contains(symbol) {
+                    return Err(Error::InvalidParameter(format!(
+                        "`{}` is not streamed; streamed symbols are {}",
+                        symbol,
+                        self.symbols.join(", ")
+                    )));
+                }
+            }
+        }
+        client.topics.extend(topics.iter().cloned());
+        Ok(sorted(&client.topics))
+    }
+
+    fn unsubscribe(&self, id: u64, topics: &[Topic]) -> Vec<Topic> {
+        let mut clients = self.clients.lock().expect("hub lock");
+        match clients.get_mut(&id) {
+            Some(client) => {
+                for topic in topics {
+                    client.topics.remove(topic);
+                }
+                sorted(&client.topics)
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn topics(&self, id: u64) -> Vec<Topic> {
+        let clients = self.clients.lock().expect("hub lock");
+        clients
+            .get(&id)
+            .map(|client| sorted(&client.topics))
+            .unwrap_or_default()
+    }
+
+    fn remove(&self, id: u64) {
+        let removed = self.clients.lock().expect("hub lock").remove(&id);
+        if let Some(client) = removed {
+            debug!("Streaming client {} disconnected", id);
+            disconnected(&client);
+        }
+    }
+}
+
+fn disconnected(client: &Client) {
+    metrics()
+        .stream_clients
+        .with_label_values(&[client.transport])
+        .dec();
+}
+
+fn sorted(topics: &HashSet<Topic>) -> Vec<Topic> {
+    let mut topics: Vec<Topic> = topics.iter().cloned().collect();
+    topics.sort_by_key(Topic::to_string);
+    topics
+}
+
+/// A client of the [`EventHub`], removed from it when dropped
+pub struct Subscriber {
+    id: u64,
+    hub: Arc<EventHub>,
+    rx: mpsc::Receiver<Delivery>,
+}
+
+impl Subscriber {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Wait for the next delivery, `None` once the hub disconnected the client for falling
+    /// behind
+    pub async fn recv(&mut self) -> Option<Delivery> {
+        self.rx.recv().await
+    }
+
+    /// Add topics, all or none of them; returns every topic subscribed to
+    pub fn subscribe(&self, topics: &[Topic]) -> Result<Vec<Topic>, Error> {
+        self.hub.subscribe(self.id, topics)
+    }
+
+    /// Remove topics; returns the topics left
+    pub fn unsubscribe(&self, topics: &[Topic]) -> Vec<Topic> {
+        self.hub.unsubscribe(self.id, topics)
+    }
+
+    pub fn topics(&self) -> Vec<Topic> {
+        self.hub.topics(self.id)
+    }
+
+    /// Apply a message of a WebSocket client and return the reply
+    pub fn handle(&self, text: &str) -> String {
+        let reply = serde_json::from_str::<ClientCommand>(text)
+            .map_err(|e| Error::InvalidParameter(format!("Invalid message: {}", e)))
+            .and_then(|command| match command {
+                ClientCommand::Subscribe { topics } => {
+                    self.subscribe(&parse_topics(&topics)?).map(Some)
+                }
+                ClientCommand::Unsubscribe { topics } => {
+                    Ok(Some(self.unsubscribe(&parse_topics(&topics)?)))
+                }
+                ClientCommand::Ping => Ok(None),
+            });
+        match reply {
+            Ok(Some(topics)) => ServerMessage::Subscribed {
+                topics: topics.iter().map(Topic::to_string).collect(),
+            },
+            Ok(None) => ServerMessage::Pong,
+            Err(e) => ServerMessage::Error {
+                message: e.to_string(),
+            },
+        }
+        .to_json()
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.hub.remove(self.id);
+    }
+}
+
+fn parse_topics(topics: &[String]) -> Result<Vec<Topic>, Error> {
+    topics.iter().map(|topic| Topic::parse(topic)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn hub(buffer: usize, max_dropped: u64) -> Arc<EventHub> {
+        Arc::new(EventHub::new(&StreamingConfig {
+            symbols: vec!["BTC/USD".to_string(), "ETH/USD".to_string()],
+            client_buffer: buffer,
+            max_dropped,
+            max_clients: 2,
+            ..StreamingConfig::default()
+        }))
+    }
+
+    fn ticker(symbol: &str, last: f64) -> StreamEvent {
+        StreamEvent::new(
+            Channel::Ticker,
+            Some(symbol.to_string()),
+            json!({ "last": last }),
+        )
+    }
+
+    fn data(delivery: Delivery) -> Value {
+        serde_json::from_str::<Value>(&delivery.payload()).unwrap()["data"].clone()
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions() {
+        let hub = hub(8, 8);
+        let mut public = hub.connect(false, "ws").unwrap();
+        let private = hub.connect(true, "sse").unwrap();
+        assert!(matches!(
+            hub.connect(true, "ws"),
+            Err(Error::RateLimitExceeded(_))
+        ));
+
+        let reply: Value = serde_json::from_str(
+            &public.handle(r#"{"method":"subscribe","topics":["ticker:btc/usd"]}"#),
+        )
+        .unwrap();
+        assert_eq!(
+            reply,
+            json!({ "type": "subscribed", "topics": ["ticker:BTC/USD"] })
+        );
+        assert!(public
+            .handle(r#"{"method":"subscribe","topics":["fills"]}"#)
+            .contains("token"));
+        assert!(public
+            .handle(r#"{"method":"subscribe","topics":["trade:SOL/USD"]}"#)
+            .contains("not streamed"));
+        assert_eq!(public.handle(r#"{"method":"ping"}"#), r#"{"type":"pong"}"#);
+        private
+            .subscribe(&[Topic::parse("fills").unwrap()])
+            .unwrap();
+
+        hub.publish(ticker("ETH/USD", 1.0));
+        hub.publish(ticker("BTC/USD", 2.0));
+        let delivery = public.recv().await.unwrap();
+        assert_eq!(delivery.name(), "ticker");
+        assert_eq!(data(delivery), json!({ "last": 2.0 }));
+
+        public.unsubscribe(&[Topic::parse("ticker:BTC/USD").unwrap()]);
+        assert!(public.topics().is_empty());
+
+        drop(private);
+        assert_eq!(hub.client_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_backpressure() {
+        let hub = hub(2, 4);
+        let mut slow = hub.connect(false, "ws").unwrap();
+        slow.subscribe(&[Topic::parse("ticker").unwrap()]).unwrap();
+
+        for last in 1..=4 {
+            hub.publish(ticker("BTC/USD", last as f64));
+        }
+        // The queue held the first two; the client is told it missed the next two
+        assert_eq!(data(slow.recv().await.unwrap()), json!({ "last": 1.0 }));
+        assert_eq!(data(slow.recv().await.unwrap()), json!({ "last": 2.0 }));
+        hub.publish(ticker("BTC/USD", 5.0));
+        assert_eq!(slow.recv().await, Some(Delivery::Lagged { missed: 2 }));
+        assert_eq!(data(slow.recv().await.unwrap()), json!({ "last": 5.0 }));
+
+        // Falling behind for good ends the stream once the queue is drained
+        for last in 6..=11 {
+            hub.publish(ticker("BTC/USD", last as f64));
+        }
+        assert_eq!(hub.client_count(), 0);
+        assert!(slow.recv().await.is_some());
+        assert!(slow.recv().await.is_some());
+        assert_eq!(slow.recv().await, None);
+    }
+}
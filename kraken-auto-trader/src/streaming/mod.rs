@@ -0,0 +1,234 @@
+//! Re-broadcast of live market data and account events to API clients.
+//!
+//! One connection to Kraken's public WebSocket API carries the ticker, trades, book and candles
+//! of the configured symbols, and one to the authenticated API carries the account's executions
+//! and balances. The [`relay`]s turn their messages into [`StreamEvent`]s and publish them on the
+//! [`EventHub`](hub::EventHub), which hands each event to the clients subscribed to its topic.
+//! Every client has a queue of its own, so one that falls behind loses events, is told how many,
+//! and is eventually disconnected, without slowing the others down.
+
+pub mod hub;
+pub mod relay;
+
+use crate::{clock::unix_time, errors::Error};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// Kind of event a client can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Ticker,
+    Trade,
+    /// Best levels of the book after each change
+    Book,
+    Ohlc,
+    /// Executions of the account's orders
+    Fills,
+    /// Every other change of state of the account's orders
+    Orders,
+    Balances,
+    /// Progress of the parent orders run by the execution engine
+    Strategies,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 8] = [
+        Channel::Ticker,
+        Channel::Trade,
+        Channel::Book,
+        Channel::Ohlc,
+        Channel::Fills,
+        Channel::Orders,
+        Channel::Balances,
+        Channel::Strategies,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Channel::Ticker => "ticker",
+            Channel::Trade => "trade",
+            Channel::Book => "book",
+            Channel::Ohlc => "ohlc",
+            Channel::Fills => "fills",
+            Channel::Orders => "orders",
+            Channel::Balances => "balances",
+            Channel::Strategies => "strategies",
+        }
+    }
+
+    /// Whether the channel carries account events, only streamed to clients with a token
+    pub fn is_private(&self) -> bool {
+        matches!(
+            self,
+            Channel::Fills | Channel::Orders | Channel::Balances | Channel::Strategies
+        )
+    }
+}
+
+/// What a client subscribes to: a channel, and for market channels a symbol
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Topic {
+    pub channel: Channel,
+    /// Symbol in WebSocket notation, every symbol when `None`
+    pub symbol: Option<String>,
+}
+
+impl Topic {
+    /// Parse `channel` or `channel:SYMBOL`, e.g. `ticker:BTC/USD`, `trade:*` or `fills`
+    pub fn parse(topic: &str) -> Result<Self, Error> {
+        let (name, symbol) = match topic.trim().split_once(':') {
+            Some((name, symbol)) => (name, Some(symbol.trim())),
+            None => (topic.trim(), None),
+        };
+        let channel = Channel::ALL
+            .into_iter()
+            .find(|channel| channel.as_str().eq_ignore_ascii_case(name))
+            .ok_or_else(|| Error::InvalidParameter(format!("Unknown channel `{}`", name)))?;
+        let symbol = symbol
+            .filter(|symbol| *symbol != "*")
+            .map(str::to_uppercase);
+        if channel.is_private() && symbol.is_some() {
+            return Err(Error::InvalidParameter(format!(
+                "Channel `{}` takes no symbol",
+                channel.as_str()
+            )));
+        }
+        Ok(Self { channel, symbol })
+    }
+
+    /// Parse a comma separated list of topics
+    pub fn parse_list(topics: &str) -> Result<Vec<Self>, Error> {
+        topics
+            .split(',')
+            .filter(|topic| !topic.trim().is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    pub fn matches(&self, event: &StreamEvent) -> bool {
+        self.channel == event.channel && (self.symbol.is_none() || self.symbol == event.symbol)
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.symbol {
+            Some(symbol) => write!(f, "{}:{}", self.channel.as_str(), symbol),
+            None => write!(f, "{}", self.channel.as_str()),
+        }
+    }
+}
+
+/// An event streamed to clients
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StreamEvent {
+    pub channel: Channel,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    /// Unix time the event was published, on the exchange clock
+    pub time: f64,
+    pub data: Value,
+}
+
+impl StreamEvent {
+    pub fn new(channel: Channel, symbol: Option<String>, data: Value) -> Self {
+        Self {
+            channel,
+            symbol,
+            time: unix_time(),
+            data,
+        }
+    }
+}
+
+/// Tokens clients stream with, from the comma separated `KRAKEN_STREAM_TOKENS` variable.
+///
+/// Without tokens anyone may stream market data and account events are not streamed. With
+/// tokens every client must present one, and may then subscribe to every channel. The same
+/// tokens guard the API endpoints that change state, which refuse every request without them.
+#[derive(Clone, Default)]
+pub struct StreamAuth {
+    tokens: Vec<String>,
+}
+
+impl StreamAuth {
+    pub fn new(tokens: Vec<String>) -> Self {
+        Self {
+            tokens: tokens
+                .into_iter()
+                .map(|token| token.trim().to_string())
+                .filter(|token| !token.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            std::env::var("KRAKEN_STREAM_TOKENS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::to_string)
+                .collect(),
+        )
+    }
+
+    /// Whether a client presenting `token` may stream, and if so whether it may receive account
+    /// events
+    pub fn authorize(&self, token: Option<&str>) -> Result<bool, Error> {
+        if self.tokens.is_empty() {
+            return Ok(false);
+        }
+        let token = token.ok_or_else(|| Error::Auth("A stream token is required".to_string()))?;
+        if self
+            .tokens
+            .iter()
+            .any(|known| constant_time_eq(known.as_bytes(), token.as_bytes()))
+        {
+            Ok(true)
+        } else {
+            Err(Error::Auth("Invalid stream token".to_string()))
+        }
+    }
+}
+
+/// Compare secrets in a time independent of where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_topics() {
+        let topics = Topic::parse_list("ticker:btc/usd, trade:*,fills").unwrap();
+        assert_eq!(
+            topics.iter().map(Topic::to_string).collect::<Vec<_>>(),
+            vec!["ticker:BTC/USD", "trade", "fills"]
+        );
+
+        let event = StreamEvent::new(Channel::Ticker, Some("BTC/USD".into()), json!({}));
+        assert!(topics[0].matches(&event));
+        assert!(!Topic::parse("ticker:ETH/USD").unwrap().matches(&event));
+        assert!(!topics[1].matches(&event));
+
+        assert!(Topic::parse("candles").is_err());
+        assert!(Topic::parse("balances:XBT").is_err());
+    }
+
+    #[test]
+    fn test_authorize() {
+        let open = StreamAuth::new(vec![" ".to_string()]);
+        assert!(!open.authorize(None).unwrap());
+        assert!(!open.authorize(Some("anything")).unwrap());
+
+        let auth = StreamAuth::new(vec!["s3cret".to_string(), "other".to_string()]);
+        assert!(auth.authorize(Some("other")).unwrap());
+        assert!(matches!(auth.authorize(None), Err(Error::Auth(_))));
+        assert!(matches!(auth.authorize(Some("s3cre")), Err(Error::Auth(_))));
+    }
+}
@@ -0,0 +1,282 @@
+use super::{hub::EventHub, Channel, StreamEvent};
+use crate::{
+    feeds::{book::BookLadder, stream::MarketStream, websocket::MarketDataSocket},
+    models::trading::ExecutionReport,
+    orders::manager::OrderManager,
+    utils::config::StreamingConfig,
+};
+use serde_json::{json, Value};
+use std::{collections::HashMap, sync::Arc};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// Subscriptions of the public feed relayed to streaming clients
+pub fn market_subscriptions(config: &StreamingConfig) -> Vec<Value> {
+    let symbols: Vec<&str> = config.symbols.iter().map(String::as_str).collect();
+    let mut book = MarketDataSocket::subscription("book", &symbols);
+    book["params"]["depth"] = config.book_depth.into();
+    let mut ohlc = MarketDataSocket::subscription("ohlc", &symbols);
+    ohlc["params"]["interval"] = config.ohlc_interval.into();
+    vec![
+        MarketDataSocket::subscription("ticker", &symbols),
+        MarketDataSocket::subscription("trade", &symbols),
+        book,
+        ohlc,
+    ]
+}
+
+/// Publishes the messages of the public feed on the hub, keeping each symbol's book so clients
+/// receive its best levels rather than the raw increments
+pub struct MarketRelay {
+    hub: Arc<EventHub>,
+    depth: usize,
+    books: HashMap<String, BookLadder>,
+}
+
+impl MarketRelay {
+    pub fn new(hub: Arc<EventHub>, depth: usize) -> Self {
+        Self {
+            hub,
+            depth,
+            books: HashMap::new(),
+        }
+    }
+
+    pub fn handle(&mut self, message: &Value) {
+        let channel = match message["channel"].as_str() {
+            Some("ticker") => Channel::Ticker,
+            Some("trade") => Channel::Trade,
+            Some("book") => Channel::Book,
+            Some("ohlc") => Channel::Ohlc,
+            _ => {
+                if message["success"] == false {
+                    warn!("Market data subscription failed: {}", message["error"]);
+                }
+                return;
+            }
+        };
+        let snapshot = message["type"] == "snapshot";
+        for entry in message["data"].as_array().into_iter().flatten() {
+            let Some(symbol) = entry["symbol"].as_str() else {
+                continue;
+            };
+            let data = if channel == Channel::Book {
+                let book = self
+                    .books
+                    .entry(symbol.to_string())
+                    .or_insert_with(|| BookLadder::new(self.depth));
+                book.apply(entry, snapshot);
+                book_top(book)
+            } else {
+                entry.clone()
+            };
+            self.hub
+                .publish(StreamEvent::new(channel, Some(symbol.to_string()), data));
+        }
+    }
+
+    /// Relay the messages of `stream` until it ends
+    pub fn spawn(mut self, mut stream: MarketStream) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            while let Some(message) = stream.next().await {
+                match message.json() {
+                    Ok(message) => self.handle(&message),
+                    Err(e) => warn!("Invalid market data message: {}", e),
+                }
+            }
+        })
+    }
+}
+
+fn book_top(book: &BookLadder) -> Value {
+    let levels = |levels: Vec<(f64, f64)>| {
+        levels
+            .into_iter()
+            .map(|(price, qty)| json!({ "price": price, "qty": qty }))
+            .collect::<Vec<_>>()
+    };
+    json!({
+        "bids": levels(book.bids()),
+        "asks": levels(book.asks()),
+        "mid": book.mid(),
+    })
+}
+
+/// Publishes the account's executions and balance changes on the hub, and keeps the order
+/// manager up to date with the executions
+pub struct PrivateRelay {
+    hub: Arc<EventHub>,
+    orders: Option<Arc<OrderManager>>,
+}
+
+impl PrivateRelay {
+    pub fn new(hub: Arc<EventHub>) -> Self {
+        Self { hub, orders: None }
+    }
+
+    pub fn with_order_manager(mut self, orders: Arc<OrderManager>) -> Self {
+        self.orders = Some(orders);
+        self
+    }
+
+    pub async fn handle(&self, message: &Value) {
+        let snapshot = message["type"] == "snapshot";
+        let entries = message["data"].as_array().into_iter().flatten();
+        match message["channel"].as_str() {
+            Some("executions") => {
+                for entry in entries {
+                    if let (Some(orders), Ok(report)) = (
+                        &self.orders,
+                        serde_json::from_value::<ExecutionReport>(entry.clone()),
+                    ) {
+                        orders.apply_execution(&report).await;
+                    }
+                    // The snapshot replays past trades and open orders; only changes are news
+                    if snapshot {
+                        continue;
+                    }
+                    let channel = if entry["exec_type"] == "trade" {
+                        Channel::Fills
+                    } else {
+                        Channel::Orders
+                    };
+                    self.hub
+                        .publish(StreamEvent::new(channel, None, entry.clone()));
+                }
+            }
+            Some("balances") => {
+                for entry in entries {
+                    self.hub
+                        .publish(StreamEvent::new(Channel::Balances, None, entry.clone()));
+                }
+            }
+            _ => {
+                if message["success"] == false {
+                    warn!("Account event subscription failed: {}", message["error"]);
+                }
+            }
+        }
+    }
+
+    /// Relay the messages of `stream` until it ends
+    pub fn spawn(self, mut stream: MarketStream) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            while let Some(message) = stream.next().await {
+                match message.json() {
+                    Ok(message) => self.handle(&message).await,
+                    Err(e) => warn!("Invalid account event message: {}", e),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaming::{hub::Subscriber, Topic};
+
+    async fn next_data(subscriber: &mut Subscriber) -> (String, Value) {
+        let delivery = subscriber.recv().await.unwrap();
+        let message: Value = serde_json::from_str(&delivery.payload()).unwrap();
+        (delivery.name().to_string(), message["data"].clone())
+    }
+
+    #[test]
+    fn test_market_subscriptions() {
+        let subscriptions = market_subscriptions(&StreamingConfig {
+            book_depth: 25,
+            ohlc_interval: 5,
+            ..StreamingConfig::default()
+        });
+        assert_eq!(subscriptions.len(), 4);
+        assert_eq!(subscriptions[2]["params"]["depth"], 25);
+        assert_eq!(subscriptions[3]["params"]["interval"], 5);
+        assert_eq!(subscriptions[0]["params"]["symbol"], json!(["BTC/USD"]));
+    }
+
+    #[tokio::test]
+    async fn test_market_relay() {
+        let hub = Arc::new(EventHub::new(&StreamingConfig::default()));
+        let mut client = hub.connect(false, "ws").unwrap();
+        client
+            .subscribe(&Topic::parse_list("book:BTC/USD,ticker").unwrap())
+            .unwrap();
+        let mut relay = MarketRelay::new(hub.clone(), 10);
+
+        relay.handle(&json!({"method": "subscribe", "success": true}));
+        relay.handle(&json!({
+            "channel": "book", "type": "snapshot",
+            "data": [{
+                "symbol": "BTC/USD",
+                "bids": [{ "price": 100.0, "qty": 1.0 }],
+                "asks": [{ "price": 101.0, "qty": 2.0 }],
+            }]
+        }));
+        relay.handle(&json!({
+            "channel": "book", "type": "update",
+            "data": [{ "symbol": "BTC/USD", "bids": [{ "price": 100.5, "qty": 3.0 }], "asks": [] }]
+        }));
+        relay.handle(&json!({
+            "channel": "ticker", "type": "update",
+            "data": [{ "symbol": "BTC/USD", "last": 100.7 }]
+        }));
+
+        let (name, data) = next_data(&mut client).await;
+        assert_eq!(name, "book");
+        assert_eq!(data["mid"], 100.5);
+        let (_, data) = next_data(&mut client).await;
+        assert_eq!(
+            data,
+            json!({
+                "bids": [{ "price": 100.5, "qty": 3.0 }, { "price": 100.0, "qty": 1.0 }],
+                "asks": [{ "price": 101.0, "qty": 2.0 }],
+                "mid": 100.75,
+            })
+        );
+        assert_eq!(
+            next_data(&mut client).await,
+            (
+                "ticker".to_string(),
+                json!({ "symbol": "BTC/USD", "last": 100.7 })
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_private_relay() {
+        let hub = Arc::new(EventHub::new(&StreamingConfig::default()));
+        let mut client = hub.connect(true, "sse").unwrap();
+        client
+            .subscribe(&Topic::parse_list("fills,orders,balances").unwrap())
+            .unwrap();
+        let relay = PrivateRelay::new(hub.clone());
+
+        relay
+            .handle(&json!({
+                "channel": "executions", "type": "snapshot",
+                "data": [{ "exec_type": "trade", "order_id": "O1" }]
+            }))
+            .await;
+        relay
+            .handle(&json!({
+                "channel": "executions", "type": "update",
+                "data": [
+                    { "exec_type": "new", "order_id": "O2" },
+                    { "exec_type": "trade", "order_id": "O2", "last_qty": 0.1 },
+                ]
+            }))
+            .await;
+        relay
+            .handle(&json!({
+                "channel": "balances", "type": "update",
+                "data": [{ "asset": "BTC", "balance": 1.5 }]
+            }))
+            .await;
+
+        assert_eq!(next_data(&mut client).await.0, "orders");
+        let (name, data) = next_data(&mut client).await;
+        assert_eq!((name.as_str(), &data["last_qty"]), ("fills", &json!(0.1)));
+        assert_eq!(next_data(&mut client).await.0, "balances");
+    }
+}
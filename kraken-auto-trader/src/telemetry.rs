@@ -0,0 +1,263 @@
+use crate::{
+    errors::Error,
+    utils::config::{LogFormat, LoggingConfig},
+};
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+};
+use std::{collections::HashMap, fmt, time::Instant};
+use tracing::{field::Empty, info_span, warn, Instrument};
+use tracing_subscriber::{
+    layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer, Registry,
+};
+
+/// Header carrying the id of a request, taken from the caller when given
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Header names and form fields whose values never appear in logs
+const SENSITIVE: &[&str] = &["api-key", "api-sign", "otp", "password", "secret", "token"];
+
+const REDACTED: &str = "[redacted]";
+
+/// Handle on the installed subscriber, used to change the level of a running process
+#[derive(Clone)]
+pub struct Telemetry {
+    filter: reload::Handle<EnvFilter, Registry>,
+    #[cfg(feature = "otlp")]
+    tracer_provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Telemetry {
+    /// Install the global subscriber writing to stdout in the configured format.
+    ///
+    /// `RUST_LOG`, when set, adds per-module directives to the configured level. Records of
+    /// the `log` crate, used by actix-web, are written too.
+    pub fn init(config: &LoggingConfig) -> Result<Self, Error> {
+        let (filter, handle) = reload::Layer::new(env_filter(config)?);
+        let output = match config.format {
+            LogFormat::Json => tracing_subscriber::fmt::layer()
+                .json()
+                .flatten_event(true)
+                .with_current_span(true)
+                .with_span_list(true)
+                .boxed(),
+            LogFormat::Pretty => tracing_subscriber::fmt::layer().boxed(),
+        };
+
+        #[cfg(feature = "otlp")]
+        let (otlp, tracer_provider) = match &config.otlp_endpoint {
+            Some(endpoint) => {
+                let (layer, provider) = otlp_layer(endpoint)?;
+                (Some(layer), Some(provider))
+            }
+            None => (None, None),
+        };
+        #[cfg(not(feature = "otlp"))]
+        let otlp: Option<tracing_subscriber::layer::Identity> = None;
+
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(output)
+            .with(otlp)
+            .try_init()
+            .map_err(|e| Error::Unknown(format!("Failed to install the logger: {}", e)))?;
+
+        #[cfg(not(feature = "otlp"))]
+        if config.otlp_endpoint.is_some() {
+            warn!("Ignoring logging.otlp_endpoint: built without the `otlp` feature");
+        }
+
+        Ok(Self {
+            filter: handle,
+            #[cfg(feature = "otlp")]
+            tracer_provider,
+        })
+    }
+
+    /// Apply the level of a reloaded configuration
+    pub fn set_level(&self, config: &LoggingConfig) -> Result<(), Error> {
+        let filter = env_filter(config)?;
+        self.filter
+            .reload(filter)
+            .map_err(|e| Error::Unknown(format!("Failed to change the log level: {}", e)))
+    }
+
+    /// Send the spans still buffered for the collector
+    pub fn shutdown(&self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = &self.tracer_provider {
+            if let Err(e) = provider.shutdown() {
+                warn!("Failed to flush spans: {}", e);
+            }
+        }
+    }
+}
+
+fn env_filter(config: &LoggingConfig) -> Result<EnvFilter, Error> {
+    let mut directives = config.level.clone();
+    if let Ok(env) = std::env::var(EnvFilter::DEFAULT_ENV) {
+        if !env.trim().is_empty() {
+            directives = format!("{},{}", directives, env);
+        }
+    }
+    LoggingConfig {
+        level: directives,
+        ..config.clone()
+    }
+    .env_filter()
+    .map_err(|e| Error::ValidationError(e.to_string()))
+}
+
+#[cfg(feature = "otlp")]
+fn otlp_layer<S>(
+    endpoint: &str,
+) -> Result<(impl Layer<S>, opentelemetry_sdk::trace::SdkTracerProvider), Error>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|e| Error::Unknown(format!("Failed to create the OTLP exporter: {}", e)))?;
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder()
+                .with_service_name(env!("CARGO_PKG_NAME"))
+                .build(),
+        )
+        .build();
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    Ok((tracing_opentelemetry::layer().with_tracer(tracer), provider))
+}
+
+fn is_sensitive(name: &str) -> bool {
+    SENSITIVE.iter().any(|key| name.eq_ignore_ascii_case(key))
+}
+
+/// Request headers for logging, with keys and signatures hidden
+pub struct RedactedHeaders<'a>(pub &'a HashMap<String, String>);
+
+impl fmt::Debug for RedactedHeaders<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.0.keys().collect();
+        names.sort();
+        f.debug_map()
+            .entries(names.into_iter().map(|name| {
+                let value = if is_sensitive(name) {
+                    REDACTED
+                } else {
+                    self.0[name].as_str()
+                };
+                (name, value)
+            }))
+            .finish()
+    }
+}
+
+/// A URL-encoded form body for logging, with one-time passwords and secrets hidden
+pub fn redact_form(body: &str) -> String {
+    body.split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if is_sensitive(name) => format!("{}={}", name, REDACTED),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Value of `name` in a URL-encoded form body
+pub fn form_value<'a>(body: &'a str, name: &str) -> Option<&'a str> {
+    body.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Middleware running each request in an `http_request` span carrying its request id, so the
+/// Kraken calls it makes are logged and traced under it. The id is echoed in the response.
+pub async fn trace_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(new_request_id);
+    let span = info_span!(
+        "http_request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.path(),
+        status = Empty,
+        latency_ms = Empty,
+    );
+
+    let started = Instant::now();
+    let mut response = next.call(req).instrument(span.clone()).await?;
+    span.record("status", response.status().as_u16());
+    span.record("latency_ms", started.elapsed().as_secs_f64() * 1000.0);
+    span.in_scope(|| tracing::debug!("Request served"));
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+    Ok(response)
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+fn new_request_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redaction() {
+        let headers = HashMap::from([
+            ("API-Key".to_string(), "my-key".to_string()),
+            ("API-Sign".to_string(), "my-signature".to_string()),
+            ("Accept".to_string(), "application/json".to_string()),
+        ]);
+        let logged = format!("{:?}", RedactedHeaders(&headers));
+        assert!(!logged.contains("my-key"));
+        assert!(!logged.contains("my-signature"));
+        assert!(logged.contains("application/json"));
+
+        assert_eq!(
+            redact_form("nonce=1&otp=123456&pair=XBTUSD"),
+            "nonce=1&otp=[redacted]&pair=XBTUSD"
+        );
+        assert_eq!(form_value("nonce=17&pair=XBTUSD", "nonce"), Some("17"));
+        assert_eq!(form_value("pair=XBTUSD", "nonce"), None);
+    }
+
+    #[test]
+    fn test_request_ids() {
+        assert!(is_valid_request_id("3f2a-abc_1"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("a b"));
+        assert!(is_valid_request_id(&new_request_id()));
+    }
+}
@@ -0,0 +1,510 @@
+use super::config::{
+    CacheConfig, CircuitBreakerConfig, ClockConfig, DeadManSwitchConfig, HealthConfig, KrakenConfig, LoggingConfig, RateLimitConfig,
+    ReloadConfig, RiskConfig, ServerConfig, StorageConfig, StrategyConfig, StreamingConfig,
+};
+use config::{Config, ConfigError, File, FileFormat, Map, Source, Value};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// File read when no `--config` is given, if it exists
+pub const DEFAULT_CONFIG_FILE: &str = "kraken.toml";
+
+/// Environment variables read before the structured `KRAKEN__SECTION__KEY` form existed
+const LEGACY_ENV: &[(&str, &str)] = &[
+    ("KRAKEN_API_URL", "client.base_url"),
+    ("KRAKEN_USER_AGENT", "client.user_agent"),
+    ("KRAKEN_TIMEOUT", "client.timeout"),
+    ("KRAKEN_MAX_RETRIES", "client.max_retries"),
+    ("KRAKEN_RETRY_DELAY_MS", "client.retry_delay_ms"),
+    ("KRAKEN_RATE_LIMIT_DELAY_MS", "client.rate_limit_delay_ms"),
+    ("KRAKEN_DMS_ENABLED", "dead_man_switch.enabled"),
+    ("KRAKEN_DMS_TIMEOUT_SECS", "dead_man_switch.timeout_secs"),
+    ("KRAKEN_DMS_INTERVAL_SECS", "dead_man_switch.interval_secs"),
+    ("KRAKEN_KEYSTORE_PATH", "storage.keystore_path"),
+    (
+        "KRAKEN_SYNTHETIC_ORDERS_PATH",
+        "storage.synthetic_orders.state_path",
+    ),
+    (
+        "KRAKEN_SYNTHETIC_POLL_INTERVAL_SECS",
+        "storage.synthetic_orders.poll_interval_secs",
+    ),
+    ("KRAKEN_RECORDER_ENABLED", "storage.recorder.enabled"),
+    ("KRAKEN_RECORDER_DIR", "storage.recorder.directory"),
+    (
+        "KRAKEN_RECORDER_ROTATE_SECS",
+        "storage.recorder.rotate_secs",
+    ),
+    ("KRAKEN_NONCE_RESOLUTION", "storage.nonce.resolution"),
+    ("KRAKEN_NONCE_FILE", "storage.nonce.file"),
+    ("KRAKEN_NONCE_RETRIES", "storage.nonce.retries"),
+];
+
+/// Flags selecting and overriding the configuration, shared by the binaries
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct ConfigArgs {
+    /// TOML configuration file [default: kraken.toml, if present]
+    #[arg(long, short = 'c', env = "KRAKEN_CONFIG", value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Apply the `[profiles.<NAME>]` table of the file on top of it, and sign with the keystore
+    /// key profile of that name
+    #[arg(long, env = "KRAKEN_PROFILE", value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Override any setting, e.g. `--set risk.max_open_orders=10`
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    pub overrides: Vec<String>,
+
+    /// Address the API binds to
+    #[arg(long)]
+    pub host: Option<String>,
+
+    /// Port the API listens on
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// Base URL of the Kraken API
+    #[arg(long)]
+    pub base_url: Option<String>,
+}
+
+impl ConfigArgs {
+    /// Flags as `key=value` overrides, the dedicated flags last so they win
+    fn overrides(&self) -> Result<Vec<(String, String)>, ConfigError> {
+        let mut overrides = self
+            .overrides
+            .iter()
+            .map(|item| {
+                item.split_once('=')
+                    .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+                    .ok_or_else(|| {
+                        ConfigError::Message(format!("--set expects KEY=VALUE, got `{}`", item))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let flags = [
+            ("server.host", self.host.clone()),
+            ("server.port", self.port.map(|port| port.to_string())),
+            ("client.base_url", self.base_url.clone()),
+        ];
+        overrides.extend(
+            flags
+                .into_iter()
+                .filter_map(|(key, value)| Some((key.to_string(), value?))),
+        );
+        Ok(overrides)
+    }
+}
+
+/// Settings of the whole application.
+///
+/// Built from layers, each overriding the previous: built-in defaults, the TOML file, the
+/// selected `[profiles.<name>]` table of that file, environment variables and command line
+/// flags. API secrets and the keystore passphrase are only read from the environment and never
+/// appear here.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub client: KrakenConfig,
+    pub server: ServerConfig,
+    pub rate_limit: RateLimitConfig,
+    pub circuit_breaker: CircuitBreakerConfig,
+    pub risk: RiskConfig,
+    pub dead_man_switch: DeadManSwitchConfig,
+    pub clock: ClockConfig,
+    pub cache: CacheConfig,
+    pub health: HealthConfig,
+    pub streaming: StreamingConfig,
+    pub logging: LoggingConfig,
+    pub reload: ReloadConfig,
+    pub storage: StorageConfig,
+    pub strategies: Vec<StrategyConfig>,
+    /// Profile selected with `--profile`, also naming the keystore key profile to sign with
+    #[serde(skip)]
+    pub profile: Option<String>,
+}
+
+impl AppConfig {
+    /// Load and validate the configuration selected by `args` and the process environment
+    pub fn load(args: &ConfigArgs) -> Result<Self, ConfigError> {
+        Self::load_with_env(args, std::env::vars())
+    }
+
+    /// Load and validate the configuration, reading environment variables from `env`
+    pub fn load_with_env(
+        args: &ConfigArgs,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let path = Self::source_file(args);
+        let mut file = match &path {
+            Some(path) => read_file(path)?,
+            None => Map::new(),
+        };
+
+        let mut profiles = match file.remove("profiles") {
+            Some(profiles) => profiles.into_table()?,
+            None => Map::new(),
+        };
+        // A profile may only name a key of the keystore, which is checked when it is opened
+        let profile = match &args.profile {
+            Some(name) => profiles.remove(name).map(|profile| profile.into_table()).transpose()?,
+            None => None,
+        };
+        let overlay_missing = args.profile.is_some() && profile.is_none();
+
+        let mut builder = Config::builder()
+            .add_source(Layer(file))
+            .add_source(Layer(profile.unwrap_or_default()))
+            .add_source(Layer(env_layer(env)));
+        for (key, value) in args.overrides()? {
+            builder = builder.set_override(key, value)?;
+        }
+
+        let mut config: AppConfig = builder.build()?.try_deserialize()?;
+        if overlay_missing && config.storage.keystore_path.is_none() {
+            return Err(ConfigError::Message(format!(
+                "Profile `{}` is not defined in {}, and there is no keystore to hold such a key",
+                args.profile.as_deref().unwrap_or_default(),
+                path.as_deref()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|| "the configuration (no file loaded)".into())
+            )));
+        }
+        config.profile = args.profile.clone();
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check every section, reporting all problems at once
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        let mut check = |section: &str, result: Result<(), ConfigError>| {
+            if let Err(e) = result {
+                problems.push(format!("{}: {}", section, e));
+            }
+        };
+
+        check("client", self.validate_client());
+        check("server", self.validate_server());
+        check("rate_limit", self.validate_rate_limit());
+        check("circuit_breaker", self.circuit_breaker.validate());
+        check("risk", self.risk.validate());
+        check("dead_man_switch", self.dead_man_switch.validate());
+        check("clock", self.clock.validate());
+        check("health", self.health.validate());
+        check("streaming", self.streaming.validate());
+        check("logging", self.logging.validate());
+        if self.reload.enabled && self.reload.poll_interval_secs == 0 {
+            check(
+                "reload",
+                Err(ConfigError::Message(
+                    "poll_interval_secs must be positive".to_string(),
+                )),
+            );
+        }
+        check("storage.synthetic_orders", self.validate_synthetic_orders());
+        if self.storage.recorder.enabled && self.storage.recorder.rotate_secs == 0 {
+            check(
+                "storage.recorder",
+                Err(ConfigError::Message(
+                    "rotate_secs must be positive".to_string(),
+                )),
+            );
+        }
+        check("strategies", self.validate_strategies());
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Message(format!(
+                "Invalid configuration:\n  - {}",
+                problems.join("\n  - ")
+            )))
+        }
+    }
+
+    /// File the configuration is read from: the `--config` file, or the default one if present
+    pub fn source_file(args: &ConfigArgs) -> Option<PathBuf> {
+        match &args.config {
+            Some(path) => Some(path.clone()),
+            None => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|path| path.exists()),
+        }
+    }
+
+    /// Effective configuration as TOML, for `--print-config`
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        toml::to_string_pretty(self).map_err(|e| ConfigError::Message(e.to_string()))
+    }
+
+    fn validate_client(&self) -> Result<(), ConfigError> {
+        let client = &self.client;
+        if !(client.base_url.starts_with("https://") || client.base_url.starts_with("http://")) {
+            return Err(ConfigError::Message(format!(
+                "base_url must be an http(s) URL, got `{}`",
+                client.base_url
+            )));
+        }
+        if client.timeout == 0 {
+            return Err(ConfigError::Message("timeout must be positive".to_string()));
+        }
+        Ok(())
+    }
+
+    fn validate_server(&self) -> Result<(), ConfigError> {
+        if self.server.host.trim().is_empty() {
+            return Err(ConfigError::Message("host must not be empty".to_string()));
+        }
+        if self.server.port == 0 {
+            return Err(ConfigError::Message("port must be positive".to_string()));
+        }
+        if self.server.workers == Some(0) {
+            return Err(ConfigError::Message("workers must be positive".to_string()));
+        }
+        Ok(())
+    }
+
+    fn validate_rate_limit(&self) -> Result<(), ConfigError> {
+        let rate_limit = &self.rate_limit;
+        if rate_limit.capacity == 0 {
+            return Err(ConfigError::Message(
+                "capacity must be positive".to_string(),
+            ));
+        }
+        if !(rate_limit.refill_per_sec.is_finite() && rate_limit.refill_per_sec > 0.0) {
+            return Err(ConfigError::Message(format!(
+                "refill_per_sec must be positive, got {}",
+                rate_limit.refill_per_sec
+            )));
+        }
+        Ok(())
+    }
+
+    fn validate_synthetic_orders(&self) -> Result<(), ConfigError> {
+        let synthetic = &self.storage.synthetic_orders;
+        synthetic.validate()?;
+        // Every evaluation syncs the working orders with a private call, which must leave at
+        // least half of the private rate limit to heartbeats and other orders
+        let refill = self.rate_limit.refill_per_sec;
+        if refill > 0.0 && (synthetic.poll_interval_secs as f64) * refill < 2.0 {
+            return Err(ConfigError::Message(format!(
+                "poll_interval_secs must be at least {} at {} private calls per second, got {}",
+                (2.0 / refill).ceil(),
+                refill,
+                synthetic.poll_interval_secs
+            )));
+        }
+        Ok(())
+    }
+
+    fn validate_strategies(&self) -> Result<(), ConfigError> {
+        let mut names = HashSet::new();
+        for strategy in &self.strategies {
+            if strategy.name.trim().is_empty() || strategy.pair.trim().is_empty() {
+                return Err(ConfigError::Message(format!(
+                    "strategy `{}` needs a name and a pair",
+                    strategy.name
+                )));
+            }
+            if !names.insert(strategy.name.as_str()) {
+                return Err(ConfigError::Message(format!(
+                    "strategy name `{}` is used twice",
+                    strategy.name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_file(path: &Path) -> Result<Map<String, Value>, ConfigError> {
+    Config::builder()
+        .add_source(File::from(path).format(FileFormat::Toml).required(true))
+        .build()?
+        .try_deserialize()
+}
+
+/// Settings from `KRAKEN__SECTION__KEY` variables and the legacy `KRAKEN_*` names
+fn env_layer(env: impl IntoIterator<Item = (String, String)>) -> Map<String, Value> {
+    let mut legacy = Map::new();
+    let mut structured = Map::new();
+    for (name, value) in env {
+        if let Some(path) = name.strip_prefix("KRAKEN__") {
+            let key = path
+                .to_lowercase()
+                .split("__")
+                .collect::<Vec<_>>()
+                .join(".");
+            structured.insert(key, Value::from(value));
+        } else if let Some((_, key)) = LEGACY_ENV.iter().find(|(legacy, _)| *legacy == name) {
+            legacy.insert(key.to_string(), Value::from(value));
+        }
+    }
+    // The structured names are explicit about their target, so they win
+    legacy.extend(structured);
+    legacy
+}
+
+/// A table of settings merged in as one configuration layer
+#[derive(Debug, Clone)]
+struct Layer(Map<String, Value>);
+
+impl Source for Layer {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+        Ok(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_config(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "kraken-config-{}-{}.toml",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let path = write_config(
+            "layers",
+            r#"
+            [server]
+            port = 9000
+
+            [risk]
+            max_open_orders = 5
+            allowed_pairs = ["XBTUSD"]
+
+            [[strategies]]
+            name = "btc-grid"
+            kind = "grid"
+            pair = "XBTUSD"
+            params = { levels = 10 }
+
+            [profiles.paper.client]
+            base_url = "http://localhost:9999"
+
+            [profiles.paper.risk]
+            max_open_orders = 2
+            "#,
+        );
+        let args = ConfigArgs {
+            config: Some(path.clone()),
+            profile: Some("paper".to_string()),
+            overrides: vec!["rate_limit.capacity=20".to_string()],
+            host: Some("0.0.0.0".to_string()),
+            ..ConfigArgs::default()
+        };
+        let config = AppConfig::load_with_env(
+            &args,
+            env(&[
+                ("KRAKEN_TIMEOUT", "12"),
+                ("KRAKEN__SERVER__PORT", "9100"),
+                ("KRAKEN__RISK__MAX_OPEN_ORDERS", "3"),
+            ]),
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.client.base_url, "http://localhost:9999");
+        assert_eq!(config.client.timeout, 12);
+        assert_eq!(config.server.host, "0.0.0.0");
+        assert_eq!(config.server.port, 9100);
+        assert_eq!(config.risk.max_open_orders, Some(3));
+        assert_eq!(config.risk.allowed_pairs, vec!["XBTUSD"]);
+        assert_eq!(config.rate_limit.capacity, 20);
+        assert_eq!(config.strategies[0].params["levels"], 10);
+        assert_eq!(config.dead_man_switch, DeadManSwitchConfig::default());
+
+        assert_eq!(config.profile.as_deref(), Some("paper"));
+        let printed: AppConfig = toml::from_str(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(
+            printed,
+            AppConfig {
+                profile: None,
+                ..config
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_config_is_reported() {
+        let path = write_config(
+            "invalid",
+            "[server]\nport = 0\n[rate_limit]\ncapacity = 0\n\
+             [logging]\nlevel = \"loud\"\nformat = \"json\"\n\
+             [storage.synthetic_orders]\npoll_interval_secs = 2\n",
+        );
+        let args = ConfigArgs {
+            config: Some(path.clone()),
+            ..ConfigArgs::default()
+        };
+        let error = AppConfig::load_with_env(&args, Vec::new())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("server: port must be positive"), "{}", error);
+        assert!(
+            error.contains("rate_limit: capacity must be positive"),
+            "{}",
+            error
+        );
+        assert!(
+            error.contains("storage.synthetic_orders: poll_interval_secs must be at least 8"),
+            "{}",
+            error
+        );
+
+        let typo = ConfigArgs {
+            overrides: vec!["server.prot=80".to_string()],
+            ..ConfigArgs::default()
+        };
+        let error = AppConfig::load_with_env(&typo, Vec::new()).unwrap_err();
+        assert!(error.to_string().contains("prot"), "{}", error);
+
+        let unknown_profile = ConfigArgs {
+            config: Some(path.clone()),
+            profile: Some("live".to_string()),
+            ..ConfigArgs::default()
+        };
+        assert!(AppConfig::load_with_env(&unknown_profile, Vec::new()).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_profile_may_name_a_keystore_key() {
+        let path = write_config("key-profile", "[server]\nport = 9000\n");
+        let args = ConfigArgs {
+            config: Some(path.clone()),
+            profile: Some("trading".to_string()),
+            ..ConfigArgs::default()
+        };
+        // Without a keystore the name has to be a table of the file
+        assert!(AppConfig::load_with_env(&args, Vec::new()).is_err());
+
+        let config =
+            AppConfig::load_with_env(&args, env(&[("KRAKEN_KEYSTORE_PATH", "keys.json")]))
+                .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(config.profile.as_deref(), Some("trading"));
+        assert_eq!(config.server.port, 9000);
+    }
+}
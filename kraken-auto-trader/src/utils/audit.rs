@@ -0,0 +1,105 @@
+use crate::errors::Error;
+use serde_json::{json, Map, Value};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Append-only record of operator-visible changes, one JSON object per line.
+///
+/// Every entry carries the time it was written (`ts`, Unix seconds) and an `event` name, plus
+/// the fields given to [`AuditLog::record`].
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    /// Append to the log at `path`, creating it and its directory if needed
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an `event` entry with the fields of `details`, which should be a JSON object
+    pub fn record(&self, event: &str, details: Value) -> Result<(), Error> {
+        let mut entry = Map::new();
+        entry.insert("ts".to_string(), json!(unix_time()));
+        entry.insert("event".to_string(), json!(event));
+        match details {
+            Value::Object(fields) => entry.extend(fields),
+            Value::Null => {}
+            other => {
+                entry.insert("details".to_string(), other);
+            }
+        }
+
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        // One write per entry, so concurrent writers never interleave within a line
+        file.write_all(&line)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Entries written so far, oldest first
+    pub fn entries(&self) -> Result<Vec<Value>, Error> {
+        std::fs::read_to_string(&self.path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(Error::from))
+            .collect()
+    }
+}
+
+fn unix_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_are_appended() {
+        let path = std::env::temp_dir().join(format!("kraken-audit-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let log = AuditLog::open(&path).unwrap();
+        log.record(
+            "config_change",
+            json!({ "key": "risk.max_open_orders", "new": 5 }),
+        )
+        .unwrap();
+        drop(log);
+        let log = AuditLog::open(&path).unwrap();
+        log.record("restart", Value::Null).unwrap();
+
+        let entries = log.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["event"], "config_change");
+        assert_eq!(entries[0]["new"], 5);
+        assert_eq!(entries[1]["event"], "restart");
+        assert!(entries[1]["ts"].as_f64().unwrap() > 0.0);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,7 +1,16 @@
-use serde::Deserialize;
-use std::time::Duration;
+use crate::{
+    client::nonce::{FileNonce, MonotonicNonce, NonceProvider, NonceResolution},
+    errors::Error,
+    instruments::symbols::SymbolResolver,
+    models::trading::NewOrder,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc, time::Duration};
+use tracing_subscriber::EnvFilter;
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct KrakenConfig {
     /// Base URL for the Kraken API
     pub base_url: String,
@@ -74,6 +83,784 @@ impl KrakenConfig {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeadManSwitchConfig {
+    /// Whether the dead man's switch is armed at startup. Off by default: when it fires, Kraken
+    /// cancels every order on the account, not only those placed by this process
+    pub enabled: bool,
+
+    /// Countdown after which Kraken cancels all orders, in seconds
+    pub timeout_secs: u32,
+
+    /// Interval between heartbeats re-arming the countdown, in seconds
+    pub interval_secs: u64,
+}
+
+impl Default for DeadManSwitchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_secs: 60,
+            interval_secs: 15,
+        }
+    }
+}
+
+impl DeadManSwitchConfig {
+    /// Create a new configuration from environment variables
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        let mut config = Self::default();
+
+        if let Ok(enabled) = std::env::var("KRAKEN_DMS_ENABLED") {
+            config.enabled = enabled.parse().unwrap_or(false);
+        }
+        if let Ok(timeout) = std::env::var("KRAKEN_DMS_TIMEOUT_SECS") {
+            config.timeout_secs = timeout.parse().unwrap_or(60);
+        }
+        if let Ok(interval) = std::env::var("KRAKEN_DMS_INTERVAL_SECS") {
+            config.interval_secs = interval.parse().unwrap_or(15);
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), config::ConfigError> {
+        if self.interval_secs == 0 || self.interval_secs >= self.timeout_secs as u64 {
+            return Err(config::ConfigError::Message(format!(
+                "Dead man's switch interval ({}s) must be positive and shorter than its timeout ({}s)",
+                self.interval_secs, self.timeout_secs
+            )));
+        }
+        Ok(())
+    }
+
+    /// Get the heartbeat interval as a Duration
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
+
+/// Synthetic order configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyntheticOrderConfig {
+    /// File the synthetic order state is persisted to
+    pub state_path: PathBuf,
+
+    /// Interval between two evaluations of the synthetic orders, in seconds
+    pub poll_interval_secs: u64,
+}
+
+impl Default for SyntheticOrderConfig {
+    fn default() -> Self {
+        Self {
+            state_path: PathBuf::from("synthetic_orders.json"),
+            // Each evaluation syncs the working orders with a private call; every 10s takes
+            // less than half of the default private rate limit
+            poll_interval_secs: 10,
+        }
+    }
+}
+
+impl SyntheticOrderConfig {
+    /// Create a new configuration from environment variables
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        let mut config = Self::default();
+
+        if let Ok(path) = std::env::var("KRAKEN_SYNTHETIC_ORDERS_PATH") {
+            config.state_path = PathBuf::from(path);
+        }
+        if let Ok(interval) = std::env::var("KRAKEN_SYNTHETIC_POLL_INTERVAL_SECS") {
+            config.poll_interval_secs = interval.parse().unwrap_or(10);
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), config::ConfigError> {
+        if self.poll_interval_secs == 0 {
+            return Err(config::ConfigError::Message(
+                "Synthetic order poll interval must be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Get the poll interval as a Duration
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_secs)
+    }
+}
+
+/// Market data recording configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecorderConfig {
+    /// Whether public REST responses are recorded
+    pub enabled: bool,
+
+    /// Directory the recording files are written to
+    pub directory: PathBuf,
+
+    /// Interval after which a new recording file is started, in seconds
+    pub rotate_secs: u64,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: PathBuf::from("recordings"),
+            rotate_secs: 3600,
+        }
+    }
+}
+
+impl RecorderConfig {
+    /// Create a new configuration from environment variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Ok(enabled) = std::env::var("KRAKEN_RECORDER_ENABLED") {
+            config.enabled = enabled.parse().unwrap_or(false);
+        }
+        if let Ok(directory) = std::env::var("KRAKEN_RECORDER_DIR") {
+            config.directory = PathBuf::from(directory);
+        }
+        if let Ok(rotate) = std::env::var("KRAKEN_RECORDER_ROTATE_SECS") {
+            config.rotate_secs = rotate.parse().unwrap_or(3600);
+        }
+
+        config
+    }
+}
+
+/// Nonce generation for private requests
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NonceConfig {
+    /// Unit of time the nonces count in
+    pub resolution: NonceResolution,
+
+    /// File holding the last nonce, shared by every process using the same API key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+
+    /// Times a request is retried with a later nonce after `EAPI:Invalid nonce`
+    pub retries: u32,
+}
+
+impl Default for NonceConfig {
+    fn default() -> Self {
+        Self {
+            resolution: NonceResolution::default(),
+            file: None,
+            retries: 3,
+        }
+    }
+}
+
+impl NonceConfig {
+    /// Create a new configuration from environment variables
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        let mut config = Self::default();
+
+        if let Ok(resolution) = std::env::var("KRAKEN_NONCE_RESOLUTION") {
+            config.resolution = resolution
+                .parse()
+                .map_err(|e: crate::errors::Error| config::ConfigError::Message(e.to_string()))?;
+        }
+        if let Ok(file) = std::env::var("KRAKEN_NONCE_FILE") {
+            config.file = Some(PathBuf::from(file));
+        }
+        if let Ok(retries) = std::env::var("KRAKEN_NONCE_RETRIES") {
+            config.retries = retries.parse().unwrap_or(3);
+        }
+
+        Ok(config)
+    }
+
+    /// The file-backed provider when a file is set, otherwise the process-wide generator
+    pub fn provider(&self) -> Arc<dyn NonceProvider> {
+        match &self.file {
+            Some(file) => Arc::new(FileNonce::new(file, self.resolution)),
+            None => MonotonicNonce::shared(self.resolution),
+        }
+    }
+}
+
+/// HTTP server configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address the API binds to
+    pub host: String,
+
+    /// Port the API listens on
+    pub port: u16,
+
+    /// Number of worker threads, one per CPU core when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workers: Option<usize>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            workers: None,
+        }
+    }
+}
+
+/// Client-side limit on private REST calls, mirroring Kraken's call counter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Calls that can be made in a burst
+    pub capacity: u32,
+
+    /// Calls regained per second
+    pub refill_per_sec: f64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        // 15 calls per minute, within Kraken's limits for every verification tier
+        Self {
+            capacity: 15,
+            refill_per_sec: 0.25,
+        }
+    }
+}
+
+/// Failing fast while Kraken is failing, and backing off between retries
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CircuitBreakerConfig {
+    pub enabled: bool,
+
+    /// Consecutive upstream failures of a class of endpoints that open its circuit
+    pub failure_threshold: u32,
+
+    /// How long a circuit stays open after tripping the first time, in seconds; doubled on
+    /// every trip that follows a failed probe
+    pub open_secs: u64,
+
+    /// Longest time a circuit stays open, in seconds
+    pub max_open_secs: u64,
+
+    /// Longest delay between two retries of a call, in milliseconds. Retries grow
+    /// exponentially from `client.retry_delay_ms` up to it.
+    pub max_backoff_ms: u64,
+
+    /// Share of failing calls over `budget_window_secs` above which calls are no longer retried
+    pub error_budget: f64,
+
+    pub budget_window_secs: u64,
+
+    /// Calls needed in the window before the error budget applies
+    pub budget_min_calls: usize,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            failure_threshold: 5,
+            open_secs: 15,
+            max_open_secs: 300,
+            max_backoff_ms: 10_000,
+            error_budget: 0.5,
+            budget_window_secs: 60,
+            budget_min_calls: 20,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    pub fn validate(&self) -> Result<(), config::ConfigError> {
+        if self.failure_threshold == 0 || self.open_secs == 0 || self.budget_window_secs == 0 {
+            return Err(config::ConfigError::Message(
+                "failure_threshold, open_secs and budget_window_secs must be positive".to_string(),
+            ));
+        }
+        if self.max_open_secs < self.open_secs {
+            return Err(config::ConfigError::Message(
+                "max_open_secs must not be below open_secs".to_string(),
+            ));
+        }
+        if !(self.error_budget > 0.0 && self.error_budget <= 1.0) {
+            return Err(config::ConfigError::Message(format!(
+                "error_budget must be in (0, 1], got {}",
+                self.error_budget
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn open_duration(&self) -> Duration {
+        Duration::from_secs(self.open_secs)
+    }
+
+    pub fn max_backoff(&self) -> Duration {
+        Duration::from_millis(self.max_backoff_ms)
+    }
+
+    pub fn budget_window(&self) -> Duration {
+        Duration::from_secs(self.budget_window_secs)
+    }
+}
+
+/// Pre-trade limits checked before an order is sent; unset limits are not enforced
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskConfig {
+    /// Largest volume of a single order, in base currency
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_order_volume: Option<f64>,
+
+    /// Largest value of a single order, in quote currency. Orders without a limit price are
+    /// valued at the last trade price, and refused when there is none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_order_notional: Option<f64>,
+
+    /// Most orders that may be working at once
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_open_orders: Option<usize>,
+
+    /// Pairs orders may be placed on, any pair when empty
+    pub allowed_pairs: Vec<String>,
+}
+
+impl RiskConfig {
+    pub fn validate(&self) -> Result<(), config::ConfigError> {
+        let positive = |name: &str, value: Option<f64>| match value {
+            Some(value) if !(value.is_finite() && value > 0.0) => Err(
+                config::ConfigError::Message(format!("{} must be positive, got {}", name, value)),
+            ),
+            _ => Ok(()),
+        };
+        positive("max_order_volume", self.max_order_volume)?;
+        positive("max_order_notional", self.max_order_notional)?;
+        if self.max_open_orders == Some(0) {
+            return Err(config::ConfigError::Message(
+                "max_open_orders must be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether [`check`](Self::check) needs a reference price to value `order`
+    pub fn needs_reference_price(&self, order: &NewOrder) -> bool {
+        self.max_order_notional.is_some() && limit_price(order).is_none()
+    }
+
+    /// Check `order` against the limits on its pair, volume and value.
+    ///
+    /// Pairs are compared through `resolver` when given, so that any spelling of an allowed
+    /// pair matches. Orders without a limit price are valued at `reference_price`.
+    pub fn check(
+        &self,
+        order: &NewOrder,
+        resolver: Option<&SymbolResolver>,
+        reference_price: Option<f64>,
+    ) -> Result<(), Error> {
+        let pair = order.pair.as_str();
+        let canonical = |name: &str| {
+            resolver
+                .and_then(|resolver| resolver.resolve_pair(name).ok())
+                .map(|pair| pair.as_str().to_string())
+                .unwrap_or_else(|| name.trim().to_uppercase())
+        };
+        if !self.allowed_pairs.is_empty()
+            && !self
+                .allowed_pairs
+                .iter()
+                .any(|allowed| canonical(allowed) == canonical(pair))
+        {
+            return Err(Error::ValidationError(format!(
+                "Risk limit: {} is not an allowed pair",
+                pair
+            )));
+        }
+
+        let volume: f64 = order.volume.parse().map_err(|_| {
+            Error::InvalidParameter(format!("Invalid order volume: {}", order.volume))
+        })?;
+        if let Some(max) = self.max_order_volume.filter(|max| volume > *max) {
+            return Err(Error::ValidationError(format!(
+                "Risk limit: volume {} exceeds the maximum of {}",
+                volume, max
+            )));
+        }
+        if let Some(max) = self.max_order_notional {
+            let price = limit_price(order).or(reference_price).ok_or_else(|| {
+                Error::ValidationError(format!(
+                    "Risk limit: no price to value the order on {} against the maximum of {}",
+                    pair, max
+                ))
+            })?;
+            if volume * price > max {
+                return Err(Error::ValidationError(format!(
+                    "Risk limit: order value {} exceeds the maximum of {}",
+                    volume * price,
+                    max
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that another order may be placed, given the number of orders already working
+    pub fn check_open_orders(&self, open_orders: usize) -> Result<(), Error> {
+        if let Some(max) = self.max_open_orders.filter(|max| open_orders >= *max) {
+            return Err(Error::ValidationError(format!(
+                "Risk limit: {} orders already working, the maximum is {}",
+                open_orders, max
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn limit_price(order: &NewOrder) -> Option<f64> {
+    order
+        .price
+        .as_deref()
+        .and_then(|price| price.parse::<f64>().ok())
+        .filter(|price| price.is_finite() && *price > 0.0)
+}
+
+/// A strategy instance to run, with its own parameters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StrategyConfig {
+    /// Unique name of the instance
+    pub name: String,
+
+    /// Strategy implementation, e.g. `twap` or `grid`
+    pub kind: String,
+
+    /// Pair the strategy trades
+    pub pair: String,
+
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Settings specific to the strategy kind
+    #[serde(default)]
+    pub params: BTreeMap<String, Value>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Log output
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Most verbose level written (`off`, `error`, `warn`, `info`, `debug` or `trace`),
+    /// optionally followed by per-module directives such as `info,kraken_auto_trader=debug`
+    pub level: String,
+
+    pub format: LogFormat,
+
+    /// OTLP/HTTP endpoint of a collector receiving spans, e.g.
+    /// `http://localhost:4318/v1/traces`. Requires the `otlp` feature.
+    pub otlp_endpoint: Option<String>,
+}
+
+/// How log lines are written to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// One JSON object per line, with the fields of the enclosing spans
+    Json,
+    /// Human-readable lines for a terminal
+    Pretty,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            format: LogFormat::Json,
+            otlp_endpoint: None,
+        }
+    }
+}
+
+impl LoggingConfig {
+    /// Filter keeping the events at or above the configured level
+    pub fn env_filter(&self) -> Result<EnvFilter, config::ConfigError> {
+        EnvFilter::builder().parse(&self.level).map_err(|e| {
+            config::ConfigError::Message(format!("Invalid log level `{}`: {}", self.level, e))
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), config::ConfigError> {
+        self.env_filter()?;
+        if let Some(endpoint) = &self.otlp_endpoint {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                return Err(config::ConfigError::Message(format!(
+                    "OTLP endpoint `{}` must be an http(s) URL",
+                    endpoint
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Correcting the local clock against Kraken's server time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClockConfig {
+    pub enabled: bool,
+
+    /// Interval between two samples of the server time, in seconds
+    pub interval_secs: u64,
+
+    /// Number of recent samples the offset is estimated from
+    pub window: usize,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 60,
+            window: 8,
+        }
+    }
+}
+
+impl ClockConfig {
+    pub fn validate(&self) -> Result<(), config::ConfigError> {
+        if self.interval_secs == 0 || self.window == 0 {
+            return Err(config::ConfigError::Message(
+                "interval_secs and window must be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
+
+/// Caching of public market data in front of the REST API
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub enabled: bool,
+
+    /// How long assets and asset pairs are served from the cache, in seconds
+    pub metadata_ttl_secs: u64,
+
+    /// How long tickers are served from the cache, in milliseconds
+    pub ticker_ttl_ms: u64,
+
+    /// How long order books, recent trades, spreads and candles are served from the cache, in
+    /// milliseconds. With 0, identical requests in flight are still sent only once.
+    pub market_ttl_ms: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            metadata_ttl_secs: 3600,
+            ticker_ttl_ms: 1000,
+            market_ttl_ms: 0,
+        }
+    }
+}
+
+/// Periodic checks of the exchange behind `/healthz` and `/readyz`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    /// Interval between two checks of the exchange, in seconds
+    pub interval_secs: u64,
+
+    /// Largest difference between the local and the exchange clock before the process is
+    /// reported as not ready, in milliseconds
+    pub max_clock_drift_ms: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 30,
+            max_clock_drift_ms: 1000,
+        }
+    }
+}
+
+impl HealthConfig {
+    pub fn validate(&self) -> Result<(), config::ConfigError> {
+        if self.interval_secs == 0 {
+            return Err(config::ConfigError::Message(
+                "interval_secs must be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
+
+/// Watching the configuration file for changes applied without a restart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReloadConfig {
+    pub enabled: bool,
+
+    /// Interval between two checks of the file, in seconds
+    pub poll_interval_secs: u64,
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            poll_interval_secs: 2,
+        }
+    }
+}
+
+impl ReloadConfig {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_secs)
+    }
+}
+
+/// Re-broadcast of market data and account events to API clients over SSE and WebSocket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StreamingConfig {
+    pub enabled: bool,
+
+    /// Pairs whose market data is streamed, in WebSocket notation, e.g. `BTC/USD`
+    pub symbols: Vec<String>,
+
+    /// Price levels on each side of the streamed book: 10, 25, 100, 500 or 1000
+    pub book_depth: usize,
+
+    /// Length of the streamed candles, in minutes
+    pub ohlc_interval: u32,
+
+    /// Relay fills, order updates and balance changes when the API key has the WebSocket
+    /// permission
+    pub private_events: bool,
+
+    /// Events queued for a client before newer ones are dropped
+    pub client_buffer: usize,
+
+    /// Events dropped in a row after which a client is disconnected as too slow
+    pub max_dropped: u64,
+
+    /// Most clients streaming at once
+    pub max_clients: usize,
+
+    /// Interval between keep-alive messages to idle clients, in seconds
+    pub keepalive_secs: u64,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            symbols: vec!["BTC/USD".to_string()],
+            book_depth: 10,
+            ohlc_interval: 1,
+            private_events: true,
+            client_buffer: 256,
+            max_dropped: 1024,
+            max_clients: 100,
+            keepalive_secs: 15,
+        }
+    }
+}
+
+impl StreamingConfig {
+    pub fn validate(&self) -> Result<(), config::ConfigError> {
+        if ![10, 25, 100, 500, 1000].contains(&self.book_depth) {
+            return Err(config::ConfigError::Message(format!(
+                "book_depth must be 10, 25, 100, 500 or 1000, got {}",
+                self.book_depth
+            )));
+        }
+        if ![1, 5, 15, 30, 60, 240, 1440, 10080, 21600].contains(&self.ohlc_interval) {
+            return Err(config::ConfigError::Message(format!(
+                "ohlc_interval {} is not a Kraken candle interval",
+                self.ohlc_interval
+            )));
+        }
+        if self.client_buffer == 0
+            || self.max_dropped == 0
+            || self.max_clients == 0
+            || self.keepalive_secs == 0
+        {
+            return Err(config::ConfigError::Message(
+                "client_buffer, max_dropped, max_clients and keepalive_secs must be positive"
+                    .to_string(),
+            ));
+        }
+        if let Some(symbol) = self.symbols.iter().find(|symbol| !symbol.contains('/')) {
+            return Err(config::ConfigError::Message(format!(
+                "symbol `{}` must be in WebSocket notation, e.g. BTC/USD",
+                symbol
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn keepalive(&self) -> Duration {
+        Duration::from_secs(self.keepalive_secs)
+    }
+}
+
+/// Where state is kept on disk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Encrypted keystore holding the API key profiles
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keystore_path: Option<PathBuf>,
+
+    /// Append-only log of configuration changes
+    pub audit_log: PathBuf,
+
+    pub synthetic_orders: SyntheticOrderConfig,
+
+    pub recorder: RecorderConfig,
+
+    pub nonce: NonceConfig,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            keystore_path: None,
+            audit_log: PathBuf::from("audit.jsonl"),
+            synthetic_orders: SyntheticOrderConfig::default(),
+            recorder: RecorderConfig::default(),
+            nonce: NonceConfig::default(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,4 +902,114 @@ mod tests {
         std::env::remove_var("KRAKEN_RETRY_DELAY_MS");
         std::env::remove_var("KRAKEN_RATE_LIMIT_DELAY_MS");
     }
+
+    #[test]
+    fn test_risk_limits() {
+        use crate::models::{
+            symbols::Pair,
+            trading::{OrderSide, OrderType},
+        };
+
+        let risk = RiskConfig {
+            max_order_volume: Some(1.0),
+            max_order_notional: Some(10_000.0),
+            max_open_orders: Some(2),
+            allowed_pairs: vec!["XBTUSD".to_string()],
+        };
+        let order = |pair: &str, volume: &str, price: Option<&str>| {
+            let mut order = NewOrder::new(
+                Pair::from(pair),
+                OrderSide::Buy,
+                OrderType::Limit,
+                volume.to_string(),
+            );
+            order.price = price.map(str::to_string);
+            order
+        };
+
+        assert!(risk
+            .check(&order("XBTUSD", "0.1", Some("50000")), None, None)
+            .is_ok());
+        assert!(risk
+            .check(&order("xbtusd", "0.1", Some("50000")), None, None)
+            .is_ok());
+        assert!(risk
+            .check(&order("ETHUSD", "0.1", Some("3000")), None, None)
+            .is_err());
+        assert!(risk.check(&order("XBTUSD", "2", None), None, None).is_err());
+        assert!(risk
+            .check(&order("XBTUSD", "0.5", Some("50000")), None, None)
+            .is_err());
+
+        // Market orders are valued at the reference price, and refused without one
+        let market = order("XBTUSD", "0.5", None);
+        assert!(risk.needs_reference_price(&market));
+        assert!(!risk.needs_reference_price(&order("XBTUSD", "0.5", Some("50000"))));
+        assert!(risk.check(&market, None, Some(10_000.0)).is_ok());
+        assert!(risk.check(&market, None, Some(50_000.0)).is_err());
+        assert!(matches!(
+            risk.check(&market, None, None),
+            Err(Error::ValidationError(_))
+        ));
+
+        assert!(risk.check_open_orders(1).is_ok());
+        assert!(matches!(
+            risk.check_open_orders(2),
+            Err(Error::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_risk_allowed_pairs_are_resolved() {
+        use crate::{
+            mock::fixtures::default_fixtures,
+            models::{
+                symbols::Pair,
+                trading::{OrderSide, OrderType},
+            },
+            utils::endpoints::market::{ASSET_INFO, TRADABLE_ASSET_PAIRS},
+        };
+
+        let fixtures = default_fixtures();
+        let resolver = SymbolResolver::new(
+            &serde_json::from_value(fixtures[TRADABLE_ASSET_PAIRS].clone()).unwrap(),
+            &serde_json::from_value(fixtures[ASSET_INFO].clone()).unwrap(),
+        );
+
+        let risk = RiskConfig {
+            allowed_pairs: vec!["BTC/USD".to_string()],
+            ..RiskConfig::default()
+        };
+        let order = |pair: &str| {
+            let mut order = NewOrder::new(
+                Pair::from(pair),
+                OrderSide::Buy,
+                OrderType::Limit,
+                "0.1".to_string(),
+            );
+            order.price = Some("50000".to_string());
+            order
+        };
+        for pair in ["XXBTZUSD", "XBTUSD", "xbt/usd"] {
+            assert!(risk.check(&order(pair), Some(&resolver), None).is_ok());
+        }
+        assert!(risk.check(&order("XXBTZUSD"), None, None).is_err());
+        assert!(risk.check(&order("ETHUSD"), Some(&resolver), None).is_err());
+    }
+
+    #[test]
+    fn test_dead_man_switch_config_from_env() {
+        std::env::set_var("KRAKEN_DMS_TIMEOUT_SECS", "10");
+        std::env::set_var("KRAKEN_DMS_INTERVAL_SECS", "20");
+        assert!(DeadManSwitchConfig::from_env().is_err());
+
+        std::env::set_var("KRAKEN_DMS_INTERVAL_SECS", "5");
+        let config = DeadManSwitchConfig::from_env().unwrap();
+        assert!(!config.enabled);
+        assert_eq!(config.timeout_secs, 10);
+        assert_eq!(config.interval(), Duration::from_secs(5));
+
+        std::env::remove_var("KRAKEN_DMS_TIMEOUT_SECS");
+        std::env::remove_var("KRAKEN_DMS_INTERVAL_SECS");
+    }
 } 
\ No newline at end of file
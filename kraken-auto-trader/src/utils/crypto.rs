@@ -1,16 +1,13 @@
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use hmac::{Hmac, Mac};
 use sha2::{Digest, Sha256, Sha512};
-use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
 
-use crate::errors::Error;
+use crate::{clock::clock, errors::Error};
 
 /// Generate a nonce for API requests
 pub fn generate_nonce() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards")
-        .as_millis() as u64
+    clock().now().as_millis() as u64
 }
 
 pub fn get_signature(
@@ -20,10 +17,10 @@ pub fn get_signature(
     api_secret: &str,
 ) -> Result<String, Error> {
     // Decode the API secret
-    let secret = match BASE64.decode(api_secret) {
-        Ok(s) => s,
-        Err(_) => return Ok("Invalid API secret".to_string()),
-    };
+    let secret = BASE64
+        .decode(api_secret)
+        .map(Zeroizing::new)
+        .map_err(|e| Error::Auth(format!("Malformed API secret: {}", e)))?;
 
     // Create the message to sign
     // Format: nonce + postdata
@@ -60,4 +57,10 @@ mod tests {
         let nonce2 = generate_nonce();
         assert!(nonce2 > nonce1);
     }
+
+    #[test]
+    fn test_malformed_secret_fails() {
+        let result = get_signature("/0/private/Balance", 1, "nonce=1", "not base64!");
+        assert!(matches!(result, Err(Error::Auth(_))));
+    }
 }
@@ -1,3 +1,7 @@
+pub mod app_config;
+pub mod audit;
 pub mod endpoints;
 pub mod config;
-pub mod crypto;
\ No newline at end of file
+pub mod crypto;
+pub mod reload;
+pub mod time;
\ No newline at end of file
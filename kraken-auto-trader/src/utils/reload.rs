@@ -0,0 +1,359 @@
+use super::{
+    app_config::{AppConfig, ConfigArgs},
+    audit::AuditLog,
+};
+use config::ConfigError;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeSet, path::Path, sync::Arc, time::Duration};
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{info, warn};
+
+/// Settings running components pick up without a restart. Anything else, such as the bind
+/// address, the API endpoint or the keystore, only changes on the next start.
+pub const LIVE_SETTINGS: &[&str] = &[
+    "risk",
+    "logging.level",
+    "rate_limit",
+    "client.max_retries",
+    "client.retry_delay_ms",
+    "client.rate_limit_delay_ms",
+];
+
+/// Whether the setting at `key` (e.g. `risk.max_open_orders`) can be changed live
+pub fn is_live(key: &str) -> bool {
+    LIVE_SETTINGS.iter().any(|live| {
+        key == *live
+            || key
+                .strip_prefix(live)
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+/// A setting whose value differs between two configurations
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    pub key: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// What a reload did with the changes found in the file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReloadOutcome {
+    pub applied: Vec<ConfigChange>,
+    /// Changes that need a restart; the running value is kept
+    pub rejected: Vec<ConfigChange>,
+}
+
+/// Re-reads the configuration when its file changes and publishes the live settings.
+///
+/// Components follow the effective configuration through [`ConfigReloader::subscribe`]. Every
+/// applied or rejected change is written to the audit log.
+pub struct ConfigReloader {
+    args: ConfigArgs,
+    current: watch::Sender<Arc<AppConfig>>,
+    audit: Option<Arc<AuditLog>>,
+}
+
+impl ConfigReloader {
+    /// Reload with the same flags the running `config` was loaded with
+    pub fn new(args: ConfigArgs, config: AppConfig) -> Self {
+        Self {
+            args,
+            current: watch::Sender::new(Arc::new(config)),
+            audit: None,
+        }
+    }
+
+    pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    pub fn current(&self) -> Arc<AppConfig> {
+        self.current.borrow().clone()
+    }
+
+    /// Receive the effective configuration after every reload that changed it
+    pub fn subscribe(&self) -> watch::Receiver<Arc<AppConfig>> {
+        self.current.subscribe()
+    }
+
+    /// Load the configuration again and apply what changed
+    pub fn reload(&self) -> Result<ReloadOutcome, ConfigError> {
+        match AppConfig::load(&self.args) {
+            Ok(config) => self.apply(config),
+            Err(e) => {
+                self.audit("config_reload_failed", json!({ "error": e.to_string() }));
+                Err(e)
+            }
+        }
+    }
+
+    /// Apply the live settings of `config`, rejecting the changes that need a restart
+    pub fn apply(&self, config: AppConfig) -> Result<ReloadOutcome, ConfigError> {
+        let current = self.current();
+        let old = to_value(&current)?;
+        let mut changes = Vec::new();
+        diff("", &old, &to_value(&config)?, &mut changes);
+
+        let (applied, rejected): (Vec<_>, Vec<_>) =
+            changes.into_iter().partition(|change| is_live(&change.key));
+        for change in &rejected {
+            warn!(
+                "Ignoring change of {} from {} to {}: it requires a restart",
+                change.key, change.old, change.new
+            );
+            self.audit(
+                "config_change_rejected",
+                json!({
+                    "key": change.key,
+                    "old": change.old,
+                    "new": change.new,
+                    "reason": "requires a restart",
+                }),
+            );
+        }
+
+        if !applied.is_empty() {
+            let mut patched = old;
+            for change in &applied {
+                set_path(&mut patched, &change.key, change.new.clone());
+            }
+            let patched: AppConfig =
+                serde_json::from_value(patched).map_err(|e| ConfigError::Message(e.to_string()))?;
+            patched.validate()?;
+            self.current.send_replace(Arc::new(patched));
+
+            for change in &applied {
+                info!(
+                    "Applied {} = {} (was {})",
+                    change.key, change.new, change.old
+                );
+                self.audit(
+                    "config_change",
+                    json!({ "key": change.key, "old": change.old, "new": change.new }),
+                );
+            }
+        }
+
+        Ok(ReloadOutcome { applied, rejected })
+    }
+
+    /// Check the configuration file every `interval` and reload when its contents change.
+    /// Nothing is watched when the configuration did not come from a file.
+    pub fn spawn(self: &Arc<Self>, interval: Duration) -> Option<JoinHandle<()>> {
+        let path = AppConfig::source_file(&self.args)?;
+        let reloader = self.clone();
+        Some(tokio::spawn(async move {
+            let mut last = fingerprint(&path);
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let contents = fingerprint(&path);
+                // A file missing or half-written by an editor is picked up on a later check
+                if contents.is_none() || contents == last {
+                    continue;
+                }
+                last = contents;
+                match reloader.reload() {
+                    Ok(outcome) if outcome.applied.is_empty() && outcome.rejected.is_empty() => {}
+                    Ok(outcome) => info!(
+                        "Reloaded {}: {} change(s) applied, {} rejected",
+                        path.display(),
+                        outcome.applied.len(),
+                        outcome.rejected.len()
+                    ),
+                    Err(e) => warn!("Keeping the running configuration: {}", e),
+                }
+            }
+        }))
+    }
+
+    fn audit(&self, event: &str, details: Value) {
+        if let Some(audit) = &self.audit {
+            if let Err(e) = audit.record(event, details) {
+                warn!("Failed to write to the audit log: {}", e);
+            }
+        }
+    }
+}
+
+fn to_value(config: &AppConfig) -> Result<Value, ConfigError> {
+    serde_json::to_value(config).map_err(|e| ConfigError::Message(e.to_string()))
+}
+
+fn fingerprint(path: &Path) -> Option<Vec<u8>> {
+    std::fs::read(path)
+        .ok()
+        .map(|contents| Sha256::digest(contents).to_vec())
+}
+
+/// Collect the leaves that differ between `old` and `new`; lists compare as a whole
+fn diff(prefix: &str, old: &Value, new: &Value, changes: &mut Vec<ConfigChange>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                let missing = Value::Null;
+                diff(
+                    &path,
+                    old.get(key).unwrap_or(&missing),
+                    new.get(key).unwrap_or(&missing),
+                    changes,
+                );
+            }
+        }
+        _ if old != new => changes.push(ConfigChange {
+            key: prefix.to_string(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Set the setting at the dotted `key`; `null` removes it, restoring its default
+fn set_path(root: &mut Value, key: &str, value: Value) {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let Some(leaf) = parts.pop() else {
+        return;
+    };
+    let mut node = root;
+    for part in parts {
+        if !node.is_object() {
+            *node = Value::Object(Map::new());
+        }
+        let Value::Object(object) = node else {
+            unreachable!("replaced by an object above")
+        };
+        node = object
+            .entry(part)
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    if let Some(object) = node.as_object_mut() {
+        if value.is_null() {
+            object.remove(leaf);
+        } else {
+            object.insert(leaf.to_string(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn write_config(path: &Path, contents: &str) {
+        std::fs::write(path, contents).unwrap();
+    }
+
+    fn temp_path(name: &str, extension: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "kraken-reload-{}-{}.{}",
+            name,
+            std::process::id(),
+            extension
+        ))
+    }
+
+    #[test]
+    fn test_live_settings() {
+        assert!(is_live("risk.max_open_orders"));
+        // Strategies are only read at startup
+        assert!(!is_live("strategies"));
+        assert!(is_live("client.max_retries"));
+        assert!(is_live("logging.level"));
+        assert!(!is_live("logging.format"));
+        assert!(!is_live("client.base_url"));
+        assert!(!is_live("server.port"));
+        assert!(!is_live("storage.keystore_path"));
+        assert!(!is_live("risky"));
+    }
+
+    #[test]
+    fn test_reload_applies_live_changes_and_rejects_others() {
+        let path = temp_path("config", "toml");
+        let audit_path = temp_path("audit", "jsonl");
+        let _ = std::fs::remove_file(&audit_path);
+        write_config(&path, "[risk]\nmax_open_orders = 5\n");
+        let args = ConfigArgs {
+            config: Some(path.clone()),
+            ..ConfigArgs::default()
+        };
+        let audit = Arc::new(AuditLog::open(&audit_path).unwrap());
+        let reloader = ConfigReloader::new(args.clone(), AppConfig::load(&args).unwrap())
+            .with_audit_log(audit.clone());
+        let mut updates = reloader.subscribe();
+
+        write_config(
+            &path,
+            "[risk]\nmax_open_orders = 8\nmax_order_volume = 2.5\n\
+             [logging]\nlevel = \"debug\"\n[server]\nport = 9999\n",
+        );
+        let outcome = reloader.reload().unwrap();
+        let keys = |changes: &[ConfigChange]| {
+            changes
+                .iter()
+                .map(|change| change.key.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            keys(&outcome.applied),
+            vec![
+                "logging.level",
+                "risk.max_open_orders",
+                "risk.max_order_volume"
+            ]
+        );
+        assert_eq!(keys(&outcome.rejected), vec!["server.port"]);
+
+        assert!(updates.has_changed().unwrap());
+        let current = updates.borrow_and_update().clone();
+        assert_eq!(current.risk.max_open_orders, Some(8));
+        assert_eq!(current.risk.max_order_volume, Some(2.5));
+        assert_eq!(current.logging.level, "debug");
+        assert_eq!(current.server.port, 8080);
+
+        // Removing an optional limit restores its default
+        write_config(
+            &path,
+            "[risk]\nmax_open_orders = 8\n[logging]\nlevel = \"debug\"\n",
+        );
+        let outcome = reloader.reload().unwrap();
+        assert_eq!(keys(&outcome.applied), vec!["risk.max_order_volume"]);
+        assert_eq!(reloader.current().risk.max_order_volume, None);
+
+        // An invalid file changes nothing
+        write_config(&path, "[risk]\nmax_open_orders = 0\n");
+        assert!(reloader.reload().is_err());
+        assert_eq!(reloader.current().risk.max_open_orders, Some(8));
+
+        let events: Vec<String> = audit
+            .entries()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["event"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                "config_change_rejected",
+                "config_change",
+                "config_change",
+                "config_change",
+                "config_change",
+                "config_reload_failed",
+            ]
+        );
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&audit_path).unwrap();
+    }
+}
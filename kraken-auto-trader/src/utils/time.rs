@@ -0,0 +1,94 @@
+/// Parse an RFC 3339 UTC timestamp such as `2024-05-01T12:30:00.123456Z` into Unix seconds.
+///
+/// Only the `Z` and `+00:00` offsets Kraken sends are accepted.
+pub fn parse_rfc3339(value: &str) -> Option<f64> {
+    let value = value
+        .strip_suffix('Z')
+        .or_else(|| value.strip_suffix("+00:00"))?;
+    let (date, time) = value.split_once(['T', ' '])?;
+
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let (time, fraction) = match time.split_once('.') {
+        Some((time, fraction)) if fraction.bytes().all(|b| b.is_ascii_digit()) => {
+            (time, format!("0.{}", fraction).parse::<f64>().ok()?)
+        }
+        Some(_) => return None,
+        None => (time, 0.0),
+    };
+    let mut time = time.splitn(3, ':').map(str::parse::<i64>);
+    let (hour, minute, second) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let secs = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second;
+    Some(secs as f64 + fraction)
+}
+
+/// Calendar date and time of day of unix seconds (Howard Hinnant's civil_from_days)
+pub fn civil(secs: u64) -> (i64, i64, i64, u64, u64, u64) {
+    let days = (secs / 86_400) as i64;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let rem = secs % 86_400;
+    (year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+/// e.g. "2026-01-01T00:00:00Z", as in `CancelAllOrdersAfterX` responses
+pub fn format_rfc3339(secs: u64) -> String {
+    let (year, month, day, hour, minute, second) = civil(secs);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rfc3339() {
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z"), Some(0.0));
+        assert_eq!(parse_rfc3339("2000-03-01T00:00:00Z"), Some(951_868_800.0));
+        assert_eq!(
+            parse_rfc3339("2024-02-29T23:59:59.5+00:00"),
+            Some(1_709_251_199.5)
+        );
+        assert_eq!(parse_rfc3339("2024-02-29T23:59:59"), None);
+        assert_eq!(parse_rfc3339("2024-13-01T00:00:00Z"), None);
+        assert_eq!(parse_rfc3339("yesterday"), None);
+    }
+
+    #[test]
+    fn test_format_rfc3339() {
+        assert_eq!(format_rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_rfc3339(951_827_696), "2000-02-29T12:34:56Z");
+        assert_eq!(
+            parse_rfc3339(&format_rfc3339(1_709_251_199)),
+            Some(1_709_251_199.0)
+        );
+    }
+}
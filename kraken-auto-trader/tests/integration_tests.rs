@@ -1,11 +1,51 @@
 use kraken_auto_trader::{
+    api,
+    cli::{output::OutputFormat, Cli, CliContext},
+    client::cache::PublicCache,
+    client::circuit_breaker::{CircuitBreakers, CircuitState},
+    client::credentials::{ApiCredentials, KeyProfile, Permission},
+    client::kraken_apis::PrivateApi,
+    client::nonce::{MonotonicNonce, NonceProvider, NonceResolution},
+    client::otp::{Otp, Totp},
     client::kraken_client::KrakenClient,
-    utils::config::KrakenConfig,
-    services::account_details::Account,
+    utils::config::{CacheConfig, CircuitBreakerConfig, KrakenConfig},
+    utils::endpoints::{
+        account::{BALANCE, QUERY_ORDERS, TRADE_BALANCE},
+        market::{SYSTEM_STATUS, TICKER, TRADABLE_ASSET_PAIRS},
+        funding::WITHDRAW_FUNDS,
+        trading::{ADD_ORDER, CANCEL_ALL_ORDERS, CANCEL_ALL_ORDERS_AFTER_X, CANCEL_ORDER},
+    },
+    orders::{
+        dead_man_switch::DeadManSwitch,
+        manager::OrderManager,
+        synthetic::{
+            SyntheticOrderManager, SyntheticOrderRequest, SyntheticOrderStatus, SyntheticOrderStore,
+        },
+        trading_gate::TradingGate,
+    },
+    utils::config::{DeadManSwitchConfig, HealthConfig, RiskConfig},
+    services::{
+        account_details::Account, funding::Funding, market_data::MarketData, trading::Trading,
+    },
     errors::Error,
+    feeds::websocket::SocketStatus,
+    health::{self, HealthMonitor},
+    metrics::metrics,
     middleware::KrakenClientState,
+    mock::{exchange::Quote, Fault, MockKraken, MOCK_API_KEY, MOCK_API_SECRET},
+    models::{
+        symbols::{AssetId, Pair},
+        trading::{ExecutionReport, NewOrder, OrderSide, OrderType},
+    },
+    streaming::{hub::EventHub, Channel, StreamAuth, StreamEvent},
+    utils::config::StreamingConfig,
 };
-use actix_web::test;
+use actix_web::{test, web, App, HttpServer};
+use clap::Parser;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::{sync::Arc, time::Duration};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
 #[actix_web::test]
 async fn test_get_balance_integration() {
     // Temporarily unset API credentials to test error case
@@ -14,7 +54,8 @@ async fn test_get_balance_integration() {
     std::env::remove_var("KRAKEN_API_KEY");
     std::env::remove_var("KRAKEN_API_SECRET");
 
-    let config = KrakenConfig::default();
+    let server = MockKraken::start().await.unwrap();
+    let config = server.config();
     let client = KrakenClient::new(config).unwrap();
     
     let req = test::TestRequest::default()
@@ -41,7 +82,8 @@ async fn test_invalid_api_credentials() {
     std::env::remove_var("KRAKEN_API_KEY");
     std::env::remove_var("KRAKEN_API_SECRET");
 
-    let config = KrakenConfig::default();
+    let server = MockKraken::start().await.unwrap();
+    let config = server.config();
     let client = KrakenClient::new(config).unwrap();
     
     let req = test::TestRequest::default()
@@ -73,7 +115,8 @@ async fn test_rate_limiting() {
     std::env::remove_var("KRAKEN_API_KEY");
     std::env::remove_var("KRAKEN_API_SECRET");
 
-    let config = KrakenConfig::default();
+    let server = MockKraken::start().await.unwrap();
+    let config = server.config();
     let client = KrakenClient::new(config).unwrap();
     
     let req = test::TestRequest::default()
@@ -121,3 +164,1053 @@ async fn test_config_from_env() {
     std::env::remove_var("KRAKEN_RETRY_DELAY_MS");
     std::env::remove_var("KRAKEN_RATE_LIMIT_DELAY_MS");
 }
+
+#[actix_web::test]
+async fn test_market_data_offline() {
+    let server = MockKraken::start().await.unwrap();
+    let state = server.client_state().unwrap();
+    let market = MarketData::new();
+
+    let ticker = market.get_ticker(state.clone(), &[Pair::from("XBTUSD")]).await.unwrap();
+    assert_eq!(ticker[&Pair::from("XXBTZUSD")].a[0], "60010.00000");
+
+    let book = market
+        .get_order_book(state.clone(), Pair::from("XBTUSD"), Some(5))
+        .await
+        .unwrap();
+    assert_eq!(book[&Pair::from("XXBTZUSD")].asks.len(), 5);
+
+    let ohlc = market
+        .get_ohlc(state.clone(), Pair::from("ETHGBP"), Some(1), None)
+        .await
+        .unwrap();
+    assert!(!ohlc.data[&Pair::from("XETHZGBP")].is_empty());
+
+    let unknown = market.get_ticker(state, &[Pair::from("DOGEUSD")]).await;
+    assert!(matches!(unknown, Err(Error::Api(_))));
+}
+
+#[actix_web::test]
+async fn test_order_lifecycle_offline() {
+    let server = MockKraken::start().await.unwrap();
+    let state = server.client_state().unwrap();
+    let trading = Trading::with_api(server.private_api().unwrap());
+    let account = Account::with_api(server.private_api().unwrap());
+
+    let mut order = NewOrder::new(
+        Pair::from("XBTUSD"),
+        OrderSide::Buy,
+        OrderType::Limit,
+        "0.1".to_string(),
+    );
+    order.price = Some("50000".to_string());
+    order.userref = Some(42);
+    let placed = trading.add_order(state.clone(), &order).await.unwrap();
+    let txid = placed.txid[0].clone();
+
+    let open = account
+        .get_open_orders(state.clone(), None, Some("42".to_string()), None)
+        .await
+        .unwrap();
+    assert!(open.open.contains_key(&txid));
+
+    // The market trades down through the bid and fills it
+    let quote = Quote {
+        bid: 49_900.0,
+        ask: 49_950.0,
+        last: 49_950.0,
+    };
+    server.set_quote("XBTUSD", quote).unwrap();
+    let orders = account
+        .query_orders(state.clone(), None, None, vec![txid.clone()], None)
+        .await
+        .unwrap();
+    assert_eq!(orders[&txid].status, "closed");
+    assert_eq!(orders[&txid].price, "49950.00000");
+
+    let balance = account.get_balance(state.clone()).await.unwrap().unwrap();
+    assert_eq!(balance[&AssetId::from("XXBT")], "2.1000000000");
+    assert!(server.balance("ZUSD") < 100_000.0 - 4_995.0);
+
+    let cancelled = trading.cancel_order(state, Some(txid), None).await;
+    assert!(matches!(cancelled, Err(Error::Api(e)) if e.contains("Unknown order")));
+}
+
+#[actix_web::test]
+async fn test_concurrent_submissions_place_once_offline() {
+    let server = MockKraken::start().await.unwrap();
+    server.set_balance("ZUSD", 100_000.0);
+    let state = server.client_state().unwrap();
+    let api = server.private_api().unwrap();
+    let manager = OrderManager::with_services(Trading::with_api(api.clone()), Account::with_api(api));
+
+    let mut order = NewOrder::new(Pair::from("XBTUSD"), OrderSide::Buy, OrderType::Limit, "0.1".to_string());
+    order.price = Some("40000".to_string());
+    order.cl_ord_id = Some("0123456789abcdef0123456789abcdef".to_string());
+    // The second submission arrives while the first one is still waiting for Kraken
+    server.inject(ADD_ORDER, Fault::Delay(Duration::from_millis(300)));
+    let (first, second) = futures::join!(
+        manager.submit(state.clone(), order.clone()),
+        manager.submit(state.clone(), order),
+    );
+    let (first, second) = (first.unwrap(), second.unwrap());
+    assert!(first.txid.is_some());
+    assert_eq!(first.txid, second.txid);
+    // The second one waited for the first instead of looking the order up and placing it again
+    let paths: Vec<String> = server.requests().into_iter().map(|request| request.path).collect();
+    assert_eq!(paths, vec![ADD_ORDER.to_string()]);
+}
+
+#[actix_web::test]
+async fn test_submissions_are_identified_by_cl_ord_id_only_offline() {
+    let server = MockKraken::start().await.unwrap();
+    server.set_balance("ZUSD", 100_000.0);
+    let state = server.client_state().unwrap();
+    let api = server.private_api().unwrap();
+    let manager = OrderManager::with_services(Trading::with_api(api.clone()), Account::with_api(api));
+
+    let mut order = NewOrder::new(Pair::from("XBTUSD"), OrderSide::Buy, OrderType::Limit, "0.1".to_string());
+    order.price = Some("40000".to_string());
+    let tracked = manager.submit(state.clone(), order.clone()).await.unwrap();
+    assert!(tracked.txid.is_some());
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].params.get("cl_ord_id"), Some(&tracked.cl_ord_id));
+    assert!(!requests[0].params.contains_key("userref"));
+
+    order.userref = Some(7);
+    assert!(matches!(
+        manager.submit(state, order).await,
+        Err(Error::InvalidParameter(_))
+    ));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[actix_web::test]
+async fn test_concurrent_submissions_respect_max_open_orders_offline() {
+    let server = MockKraken::start().await.unwrap();
+    server.set_balance("ZUSD", 100_000.0);
+    let state = server.client_state().unwrap();
+    let api = server.private_api().unwrap();
+    let manager = OrderManager::with_services(Trading::with_api(api.clone()), Account::with_api(api))
+        .with_risk_limits(RiskConfig {
+            max_open_orders: Some(1),
+            ..RiskConfig::default()
+        });
+
+    let mut order = NewOrder::new(Pair::from("XBTUSD"), OrderSide::Buy, OrderType::Limit, "0.1".to_string());
+    order.price = Some("40000".to_string());
+    // Both are checked while the first one is still waiting for Kraken
+    server.inject(ADD_ORDER, Fault::Delay(Duration::from_millis(300)));
+    let (first, second) = futures::join!(
+        manager.submit(state.clone(), order.clone()),
+        manager.submit(state.clone(), order),
+    );
+    assert!(first.is_ok());
+    assert!(matches!(second, Err(Error::ValidationError(_))));
+    assert_eq!(manager.working_orders().await.len(), 1);
+    let paths: Vec<String> = server.requests().into_iter().map(|request| request.path).collect();
+    assert_eq!(paths, vec![ADD_ORDER.to_string()]);
+}
+
+#[actix_web::test]
+async fn test_market_orders_are_valued_at_the_last_trade_offline() {
+    let server = MockKraken::start().await.unwrap();
+    server.set_balance("ZUSD", 100_000.0);
+    let state = server.client_state().unwrap();
+    let api = server.private_api().unwrap();
+    let manager = OrderManager::with_services(Trading::with_api(api.clone()), Account::with_api(api))
+        .with_risk_limits(RiskConfig {
+            max_order_notional: Some(10_000.0),
+            ..RiskConfig::default()
+        });
+
+    let order = |volume: &str| NewOrder::new(Pair::from("XXBTZUSD"), OrderSide::Buy, OrderType::Market, volume.to_string());
+    // Last trade at 60000
+    assert!(matches!(
+        manager.submit(state.clone(), order("0.5")).await,
+        Err(Error::ValidationError(_))
+    ));
+    manager.submit(state.clone(), order("0.1")).await.unwrap();
+
+    let paths: Vec<String> = server.requests().into_iter().map(|request| request.path).collect();
+    assert_eq!(paths, vec![TICKER.to_string(), TICKER.to_string(), ADD_ORDER.to_string()]);
+}
+
+#[actix_web::test]
+async fn test_dead_man_switch_shutdown_cancels_all_orders_offline() {
+    let server = MockKraken::start().await.unwrap();
+    server.set_balance("ZUSD", 100_000.0);
+    let state = server.client_state().unwrap();
+    let api = server.private_api().unwrap();
+    let trading = Trading::with_api(api.clone());
+    let manager = Arc::new(OrderManager::with_services(
+        Trading::with_api(api.clone()),
+        Account::with_api(api),
+    ));
+
+    let mut order = NewOrder::new(Pair::from("XBTUSD"), OrderSide::Buy, OrderType::Limit, "0.1".to_string());
+    order.price = Some("40000".to_string());
+    manager.submit(state.clone(), order.clone()).await.unwrap();
+    // Placed from elsewhere on the same account
+    trading.add_order(state.clone(), &order).await.unwrap();
+
+    // Heartbeats are off by default, and shutdown still cancels everything
+    let config = DeadManSwitchConfig::default();
+    assert!(!config.enabled);
+    let switch = DeadManSwitch::new(trading, config).with_order_manager(manager.clone());
+    assert_eq!(switch.shutdown(state).await.unwrap(), 2);
+    assert!(manager.working_orders().await.is_empty());
+
+    let paths: Vec<String> = server.requests().into_iter().map(|request| request.path).collect();
+    assert!(paths.contains(&CANCEL_ALL_ORDERS.to_string()));
+    assert!(!paths.contains(&CANCEL_ORDER.to_string()));
+    assert_eq!(paths.last().map(String::as_str), Some(CANCEL_ALL_ORDERS_AFTER_X));
+}
+
+#[actix_web::test]
+async fn test_synthetic_orders_readable_during_tick_offline() {
+    let server = MockKraken::start().await.unwrap();
+    server.set_balance("ZUSD", 100_000.0);
+    let state = server.client_state().unwrap();
+    let api = server.private_api().unwrap();
+    let orders = Arc::new(OrderManager::with_services(
+        Trading::with_api(api.clone()),
+        Account::with_api(api),
+    ));
+    let path = std::env::temp_dir().join(format!("synthetic-tick-{}.json", std::process::id()));
+    let manager = SyntheticOrderManager::new(orders, state, SyntheticOrderStore::new(&path));
+
+    let mut order = NewOrder::new(Pair::from("XBTUSD"), OrderSide::Buy, OrderType::Limit, "0.1".to_string());
+    order.price = Some("40000".to_string());
+    let submitted = manager
+        .submit(SyntheticOrderRequest::Timed {
+            order,
+            activate_at: None,
+            cancel_at: None,
+        })
+        .await
+        .unwrap();
+
+    // A slow sync of the child orders doesn't keep readers waiting
+    server.inject(QUERY_ORDERS, Fault::Delay(Duration::from_millis(500)));
+    let (ticked, read) = futures::join!(manager.tick(), async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        tokio::time::timeout(Duration::from_millis(200), manager.get(&submitted.id)).await
+    });
+    ticked.unwrap();
+    assert_eq!(read.unwrap().unwrap().id, submitted.id);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[actix_web::test]
+async fn test_bracket_protects_partial_fills_offline() {
+    let server = MockKraken::start().await.unwrap();
+    server.set_balance("ZUSD", 100_000.0);
+    server.set_balance("XXBT", 0.0);
+    let state = server.client_state().unwrap();
+    let api = server.private_api().unwrap();
+    let orders = Arc::new(OrderManager::with_services(
+        Trading::with_api(api.clone()),
+        Account::with_api(api),
+    ));
+    let path = std::env::temp_dir().join(format!("synthetic-bracket-{}.json", std::process::id()));
+    let manager = SyntheticOrderManager::new(orders.clone(), state, SyntheticOrderStore::new(&path));
+
+    let mut entry = NewOrder::new(Pair::from("XBTUSD"), OrderSide::Buy, OrderType::Limit, "1.0".to_string());
+    entry.price = Some("40000".to_string());
+    let bracket = manager
+        .submit(SyntheticOrderRequest::Bracket {
+            entry,
+            take_profit: 90_000.0,
+            stop_loss: 30_000.0,
+        })
+        .await
+        .unwrap();
+    let entry_txid = bracket.children[0].txid.clone().unwrap();
+
+    // Part of the entry fills while there is no bitcoin to sell yet: the take profit fails
+    orders
+        .apply_execution(&ExecutionReport {
+            exec_type: "trade".to_string(),
+            order_id: entry_txid,
+            cl_ord_id: None,
+            order_userref: None,
+            order_status: Some("partially_filled".to_string()),
+            cum_qty: Some(0.4),
+            avg_price: Some(40_000.0),
+            reason: None,
+            timestamp: None,
+        })
+        .await
+        .unwrap();
+    manager.tick().await.unwrap();
+    let failed = manager.get(&bracket.id).await.unwrap();
+    assert_eq!(failed.status, SyntheticOrderStatus::Working);
+    assert_eq!(failed.children.len(), 1);
+    assert_eq!(failed.exit_attempts, 1);
+    assert!(failed.last_error.is_some());
+
+    // It is placed again once the backoff has passed, for the volume filled so far
+    server.set_balance("XXBT", 0.4);
+    let placed = server.requests().len();
+    manager.tick().await.unwrap();
+    assert!(server.requests()[placed..].iter().all(|request| request.path != ADD_ORDER));
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    manager.tick().await.unwrap();
+    let protected = manager.get(&bracket.id).await.unwrap();
+    assert_eq!(protected.children.len(), 2);
+    assert_eq!(protected.children[1].order.volume, "0.40000000");
+    assert_eq!(protected.children[1].order.price.as_deref(), Some("90000"));
+    assert_eq!(protected.exit_attempts, 0);
+
+    // The market falls through the stop, filling the rest of the entry on the way
+    let quote = Quote {
+        bid: 28_990.0,
+        ask: 29_000.0,
+        last: 29_000.0,
+    };
+    server.set_quote("XBTUSD", quote).unwrap();
+    for _ in 0..5 {
+        manager.tick().await.unwrap();
+    }
+    let closed = manager.get(&bracket.id).await.unwrap();
+    assert_eq!(closed.status, SyntheticOrderStatus::Completed);
+    assert!(closed.stop_triggered);
+    let stop_exit = closed.children.last().unwrap();
+    assert_eq!(stop_exit.order.ordertype, OrderType::Market);
+    assert_eq!(stop_exit.order.volume, "1.00000000");
+    let _ = std::fs::remove_file(&path);
+}
+
+#[actix_web::test]
+async fn test_invalid_signature_offline() {
+    let server = MockKraken::start().await.unwrap();
+    let api = PrivateApi::builder()
+        .with_api_key(MOCK_API_KEY.to_string())
+        .with_api_secret("d3Jvbmctc2VjcmV0".to_string())
+        .build()
+        .unwrap();
+
+    let balance = Account::with_api(api)
+        .get_balance(server.client_state().unwrap())
+        .await;
+    assert!(matches!(balance, Err(Error::Api(e)) if e == "EAPI:Invalid signature"));
+}
+
+#[actix_web::test]
+async fn test_rate_limit_is_retried_offline() {
+    let server = MockKraken::start().await.unwrap();
+    server.inject(BALANCE, Fault::RateLimit);
+
+    let account = Account::with_api(server.private_api().unwrap());
+    let balance = account.get_balance(server.client_state().unwrap()).await;
+    assert!(balance.is_ok());
+
+    let attempts = server
+        .requests()
+        .iter()
+        .filter(|request| request.path == BALANCE)
+        .count();
+    assert_eq!(attempts, 2);
+}
+
+#[actix_web::test]
+async fn test_service_errors_offline() {
+    let server = MockKraken::start().await.unwrap();
+    let market = MarketData::new();
+    let state = server.client_state().unwrap();
+
+    server.inject(TICKER, Fault::Unavailable);
+    let unavailable = market.get_ticker(state.clone(), &[Pair::from("XBTUSD")]).await;
+    assert!(matches!(unavailable, Err(Error::Api(e)) if e == "EService:Unavailable"));
+
+    server.inject(TICKER, Fault::MalformedJson);
+    let malformed = market.get_ticker(state, &[Pair::from("XBTUSD")]).await;
+    assert!(matches!(malformed, Err(Error::SerializationError(_))));
+
+    let config = KrakenConfig {
+        timeout: 1,
+        ..server.config()
+    };
+    let state = KrakenClientState::new(KrakenClient::new(config).unwrap());
+    server.inject(TICKER, Fault::Delay(Duration::from_secs(2)));
+    let timed_out = market.get_ticker(state, &[Pair::from("XBTUSD")]).await;
+    assert!(matches!(timed_out, Err(Error::HttpError(e)) if e.is_timeout()));
+}
+
+#[actix_web::test]
+async fn test_invalid_nonce_is_recovered_offline() {
+    let server = MockKraken::start().await.unwrap();
+    let state = server.client_state().unwrap();
+    let account = |provider: Arc<dyn NonceProvider>, retries| {
+        let api = PrivateApi::builder()
+            .with_api_key(MOCK_API_KEY.to_string())
+            .with_api_secret(MOCK_API_SECRET.to_string())
+            .with_nonce_provider(provider)
+            .with_nonce_retries(retries)
+            .build()
+            .unwrap();
+        Account::with_api(api)
+    };
+
+    // Another process sharing the key runs a second ahead of our clock
+    let ahead = Arc::new(MonotonicNonce::new(NonceResolution::Millis));
+    ahead.bump(NonceResolution::Millis.now()).unwrap();
+    account(ahead, 0).get_balance(state.clone()).await.unwrap();
+
+    let strict = account(Arc::new(MonotonicNonce::default()), 0);
+    let rejected = strict.get_balance(state.clone()).await;
+    assert!(matches!(rejected, Err(e) if e.is_invalid_nonce()));
+
+    let recovering = account(Arc::new(MonotonicNonce::default()), 3);
+    assert!(recovering.get_balance(state).await.is_ok());
+    let attempts = server
+        .requests()
+        .iter()
+        .filter(|request| request.path == BALANCE)
+        .count();
+    assert!(attempts >= 4);
+}
+
+#[actix_web::test]
+async fn test_otp_is_sent_with_private_calls_offline() {
+    let totp = Totp::from_base32("JBSWY3DPEHPK3PXP").unwrap();
+    let server = MockKraken::builder()
+        .with_otp(MOCK_API_KEY, Otp::Totp(totp.clone()))
+        .start()
+        .await
+        .unwrap();
+    let state = server.client_state().unwrap();
+
+    let without_otp = Account::with_api(server.private_api().unwrap());
+    let rejected = without_otp.get_balance(state.clone()).await;
+    assert!(matches!(rejected, Err(Error::Api(e)) if e == "EAPI:Invalid key"));
+
+    let api = PrivateApi::builder()
+        .with_api_key(MOCK_API_KEY.to_string())
+        .with_api_secret(MOCK_API_SECRET.to_string())
+        .with_otp(Otp::Totp(totp))
+        .build()
+        .unwrap();
+    assert!(Account::with_api(api).get_balance(state).await.is_ok());
+
+    let sent = server.requests().last().unwrap().params["otp"].clone();
+    assert_eq!(sent.len(), 6);
+}
+
+#[actix_web::test]
+async fn test_least_privileged_key_signs_each_call_offline() {
+    const READ_ONLY_KEY: &str = "read-only-key";
+    const TRADING_KEY: &str = "trading-key";
+    let server = MockKraken::builder()
+        .with_credentials(READ_ONLY_KEY, MOCK_API_SECRET)
+        .with_permissions(READ_ONLY_KEY, [Permission::Query])
+        .with_credentials(TRADING_KEY, MOCK_API_SECRET)
+        .with_permissions(TRADING_KEY, [Permission::Query, Permission::Trade])
+        .start()
+        .await
+        .unwrap();
+    let state = server.client_state().unwrap();
+    let profile = |name: &str, key: &str, permissions: &[Permission]| {
+        KeyProfile::new(name, ApiCredentials::new(key, MOCK_API_SECRET).unwrap())
+            .with_permissions(permissions.iter().copied())
+    };
+    let api = || {
+        PrivateApi::builder()
+            .with_profile(profile("trading", TRADING_KEY, &[Permission::Query, Permission::Trade]))
+            .with_profile(profile("read-only", READ_ONLY_KEY, &[Permission::Query]))
+    };
+
+    let account = Account::with_api(api().build().unwrap());
+    account.get_balance(state.clone()).await.unwrap();
+    let last_key = || server.requests().last().unwrap().api_key.clone().unwrap();
+    assert_eq!(last_key(), READ_ONLY_KEY);
+
+    let trading = Trading::with_api(api().build().unwrap());
+    trading.cancel_all_orders(state.clone()).await.unwrap();
+    assert_eq!(last_key(), TRADING_KEY);
+
+    // Without a trading key the call fails locally instead of reaching the exchange
+    let read_only = PrivateApi::builder()
+        .with_profile(profile("read-only", READ_ONLY_KEY, &[Permission::Query]))
+        .build()
+        .unwrap();
+    let sent = server.requests().len();
+    let denied = Trading::with_api(read_only).cancel_all_orders(state).await;
+    assert!(matches!(denied, Err(Error::Auth(_))));
+    assert_eq!(server.requests().len(), sent);
+
+    let malformed = PrivateApi::builder()
+        .with_api_key(READ_ONLY_KEY.to_string())
+        .with_api_secret("not base64!".to_string())
+        .build();
+    assert!(matches!(malformed, Err(Error::Auth(_))));
+}
+
+#[actix_web::test]
+async fn test_metrics_are_recorded_offline() {
+    let server = MockKraken::start().await.unwrap();
+    let metrics = metrics();
+    let calls = |status: &str| {
+        metrics
+            .kraken_requests
+            .with_label_values(&[TRADE_BALANCE, "POST", status])
+            .get()
+    };
+    let retries = || {
+        metrics
+            .kraken_retries
+            .with_label_values(&[TRADE_BALANCE, "retryable"])
+            .get()
+    };
+    let (ok, rate_limited, retried) = (calls("ok"), calls("rate_limited"), retries());
+
+    server.inject(TRADE_BALANCE, Fault::RateLimit);
+    let account = Account::with_api(server.private_api().unwrap());
+    let balance = account
+        .get_trade_balance(server.client_state().unwrap(), None)
+        .await
+        .unwrap();
+
+    // Other tests share the process-wide metrics, so only lower bounds hold
+    assert!(calls("ok") > ok);
+    assert!(calls("rate_limited") > rate_limited);
+    assert!(retries() > retried);
+    assert!(
+        metrics
+            .kraken_errors
+            .with_label_values(&[TRADE_BALANCE, "EAPI:Rate limit exceeded"])
+            .get()
+            > 0
+    );
+    assert_eq!(
+        metrics
+            .portfolio_equity
+            .with_label_values(&["ZUSD"])
+            .get(),
+        balance.e.parse::<f64>().unwrap()
+    );
+
+    let text = metrics.render();
+    assert!(text.contains("kraken_request_duration_seconds_bucket"));
+    assert!(text.contains("kraken_rate_limiter_tokens"));
+}
+
+#[actix_web::test]
+async fn test_exchange_status_gates_orders_offline() {
+    let server = MockKraken::start().await.unwrap();
+    let state = server.client_state().unwrap();
+    let gate = Arc::new(TradingGate::new());
+    let monitor = HealthMonitor::new(HealthConfig::default()).with_trading_gate(gate.clone());
+    let orders = OrderManager::with_services(
+        Trading::with_api(server.private_api().unwrap()),
+        Account::with_api(server.private_api().unwrap()),
+    )
+    .with_trading_gate(gate.clone());
+    let order = |ordertype| {
+        let mut order = NewOrder::new(
+            Pair::from("XBTUSD"),
+            OrderSide::Buy,
+            ordertype,
+            "0.1".to_string(),
+        );
+        order.price = Some("40000".to_string());
+        order
+    };
+    let set_status = |status: &str| {
+        server.set_fixture(
+            SYSTEM_STATUS,
+            serde_json::json!({ "status": status, "timestamp": "2024-05-01T00:00:00Z" }),
+        )
+    };
+
+    let health = monitor.check(state.clone()).await;
+    assert!(health.reachable);
+    assert!(health.clock_drift_ms.unwrap().abs() < 2000.0);
+    assert!(monitor.report().await.ready);
+
+    set_status("maintenance");
+    monitor.check(state.clone()).await;
+    let report = monitor.report().await;
+    assert!(!report.ready);
+    assert!(report.problems.iter().any(|p| p.contains("maintenance")));
+    let paused = orders.submit(state.clone(), order(OrderType::Limit)).await;
+    assert!(matches!(paused, Err(Error::ValidationError(_))));
+
+    set_status("post_only");
+    monitor.check(state.clone()).await;
+    let market = orders.submit(state.clone(), order(OrderType::Market)).await;
+    assert!(matches!(market, Err(Error::ValidationError(_))));
+    orders
+        .submit(state.clone(), order(OrderType::Limit))
+        .await
+        .unwrap();
+    let sent: Vec<_> = server
+        .requests()
+        .into_iter()
+        .filter(|request| request.path == ADD_ORDER)
+        .collect();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].params["oflags"], "post");
+
+    set_status("online");
+    monitor.check(state.clone()).await;
+    assert!(monitor.report().await.ready);
+    orders.submit(state, order(OrderType::Market)).await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_public_data_is_cached_and_coalesced_offline() {
+    let server = MockKraken::start().await.unwrap();
+    let cache = Arc::new(PublicCache::new(CacheConfig::default()));
+    let state = server.client_state().unwrap().with_public_cache(cache.clone());
+    let market = MarketData::new();
+    let sent = |endpoint: &str| {
+        server
+            .requests()
+            .iter()
+            .filter(|request| request.path == endpoint)
+            .count()
+    };
+
+    for _ in 0..3 {
+        market
+            .get_tradable_asset_pairs(state.clone(), None, None, None)
+            .await
+            .unwrap();
+    }
+    assert_eq!(sent(TRADABLE_ASSET_PAIRS), 1);
+
+    // Tickers asked for at once share a single slow request
+    server.inject(TICKER, Fault::Delay(Duration::from_millis(300)));
+    let pairs = [Pair::from("XBTUSD")];
+    let tickers = futures::future::join_all(
+        (0..4).map(|_| market.get_ticker(state.clone(), &pairs)),
+    )
+    .await;
+    assert!(tickers.iter().all(Result::is_ok));
+    assert_eq!(sent(TICKER), 1);
+
+    // Server time is never cached
+    market.get_server_time(state.clone()).await.unwrap();
+    market.get_server_time(state.clone()).await.unwrap();
+
+    cache.invalidate(TRADABLE_ASSET_PAIRS);
+    market
+        .get_tradable_asset_pairs(state, None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(sent(TRADABLE_ASSET_PAIRS), 2);
+
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.coalesced), (2, 3, 3));
+}
+
+#[actix_web::test]
+async fn test_circuit_breaker_fails_fast_offline() {
+    let server = MockKraken::start().await.unwrap();
+    let client = KrakenClient::new(server.config())
+        .unwrap()
+        .with_circuit_breaker(&CircuitBreakerConfig {
+            failure_threshold: 3,
+            ..CircuitBreakerConfig::default()
+        });
+    let breakers: Arc<CircuitBreakers> = client.circuit_breakers();
+    let state = KrakenClientState::new(client);
+    let monitor = HealthMonitor::new(HealthConfig::default()).with_circuit_breakers(breakers);
+    let market = MarketData::new();
+    let account = Account::with_api(server.private_api().unwrap());
+    let sent = |endpoint: &str| {
+        server
+            .requests()
+            .iter()
+            .filter(|request| request.path == endpoint)
+            .count()
+    };
+
+    // Transient statuses of public calls are retried with backoff
+    server.inject(TICKER, Fault::Status(503));
+    market
+        .get_ticker(state.clone(), &[Pair::from("XBTUSD")])
+        .await
+        .unwrap();
+    assert_eq!(sent(TICKER), 2);
+
+    // Kraken asking to come back in a minute holds the public circuit open
+    server.inject(TICKER, Fault::RetryAfter(Duration::from_secs(60)));
+    let limited = market.get_ticker(state.clone(), &[Pair::from("XBTUSD")]).await;
+    assert!(matches!(limited, Err(Error::InvalidResponse(status)) if status.starts_with("429")));
+    let rejected = market.get_ticker(state.clone(), &[Pair::from("XBTUSD")]).await;
+    assert!(matches!(rejected, Err(Error::CircuitOpen(_))));
+    assert_eq!(sent(TICKER), 3);
+
+    // Private calls have their own circuit, opened by consecutive failures
+    account.get_balance(state.clone()).await.unwrap();
+    for _ in 0..3 {
+        server.inject(BALANCE, Fault::Unavailable);
+        assert!(account.get_balance(state.clone()).await.is_err());
+    }
+    let fast = account.get_balance(state.clone()).await;
+    assert!(matches!(fast, Err(Error::CircuitOpen(_))));
+    assert_eq!(sent(BALANCE), 4);
+
+    let report = monitor.report().await;
+    let circuits = report.circuits.unwrap().circuits;
+    let open: Vec<_> = circuits
+        .iter()
+        .filter(|circuit| circuit.state == CircuitState::Open)
+        .map(|circuit| circuit.class.as_str())
+        .collect();
+    assert_eq!(open, vec!["public", "account"]);
+    assert!(report
+        .problems
+        .iter()
+        .any(|problem| problem.contains("Circuit of account endpoints is open")));
+}
+
+#[actix_web::test]
+async fn test_non_idempotent_calls_are_not_resent_offline() {
+    let server = MockKraken::start().await.unwrap();
+    server.set_balance("XXBT", 1.0);
+    server.set_balance("ZUSD", 100_000.0);
+    let config = KrakenConfig {
+        timeout: 1,
+        ..server.config()
+    };
+    let state = KrakenClientState::new(KrakenClient::new(config).unwrap());
+    let sent = |endpoint: &str| {
+        server
+            .requests()
+            .iter()
+            .filter(|request| request.path == endpoint)
+            .count()
+    };
+
+    // The withdrawal times out but is carried out, and is found instead of being sent again
+    let funding = Funding::with_api(server.private_api().unwrap())
+        .with_reconcile_delay(Duration::from_secs(1));
+    server.inject(WITHDRAW_FUNDS, Fault::Delay(Duration::from_millis(1500)));
+    let withdrawal = funding
+        .withdraw(state.clone(), AssetId::from("XXBT"), "cold-storage", "0.25")
+        .await
+        .unwrap();
+    assert!(withdrawal.refid.starts_with('W'));
+    assert_eq!(sent(WITHDRAW_FUNDS), 1);
+    assert!((server.balance("XXBT") - 0.75).abs() < 1e-9);
+
+    // A second withdrawal of the same amount is not mistaken for the first
+    server.inject(WITHDRAW_FUNDS, Fault::Delay(Duration::from_millis(1500)));
+    let second = funding
+        .withdraw(state.clone(), AssetId::from("XXBT"), "cold-storage", "0.25")
+        .await
+        .unwrap();
+    assert_ne!(second.refid, withdrawal.refid);
+    assert_eq!(sent(WITHDRAW_FUNDS), 2);
+
+    // An order whose outcome is unknown is left to the caller to reconcile
+    let trading = Trading::with_api(server.private_api().unwrap());
+    server.inject(ADD_ORDER, Fault::Delay(Duration::from_millis(1500)));
+    let order = NewOrder::new(Pair::from("XBTUSD"), OrderSide::Buy, OrderType::Market, "0.01".to_string());
+    let result = trading.add_order(state.clone(), &order).await;
+    assert!(result.is_err());
+    assert_eq!(sent(ADD_ORDER), 1);
+}
+
+#[actix_web::test]
+async fn test_cli_commands_offline() {
+    let server = MockKraken::start().await.unwrap();
+    server.set_balance("ZUSD", 100_000.0);
+    server.set_balance("XETH", 0.0);
+    let ctx = CliContext::new(server.client_state().unwrap(), server.private_api());
+    let parse = |args: &[&str]| Cli::try_parse_from(["kraken-cli"].iter().chain(args)).unwrap();
+    let sent = |endpoint: &str| {
+        server
+            .requests()
+            .iter()
+            .filter(|request| request.path == endpoint)
+            .count()
+    };
+
+    // Pairs are resolved from any spelling to Kraken's names
+    let ticker = parse(&["ticker", "btc/usd", "ETHGBP"]).run(&ctx).await.unwrap();
+    assert_eq!(ticker.table.column("pair"), vec!["XETHZGBP", "XXBTZUSD"]);
+    assert!(ticker.table.column("bid")[0].parse::<f64>().is_ok());
+
+    let cli = parse(&["-o", "csv", "balance"]);
+    let balance = cli.run(&ctx).await.unwrap().render(cli.output).unwrap();
+    assert!(balance.starts_with("asset,balance\n"));
+    assert!(balance.contains("\nZUSD,100000"));
+    assert!(!balance.contains("XETH"));
+
+    // A dry run is validated by Kraken and places nothing
+    let place = ["place", "buy", "XBTUSD", "0.01", "--type", "limit", "--price", "1000"];
+    let validated = parse(&[&["--dry-run"], &place[..]].concat())
+        .run(&ctx)
+        .await
+        .unwrap();
+    assert_eq!(validated.table.column("txid"), vec!["(validated)"]);
+    let placed = parse(&place).run(&ctx).await.unwrap();
+    let txid = placed.table.column("txid")[0].to_string();
+    assert!(txid.starts_with('O'));
+    assert_eq!(sent(ADD_ORDER), 2);
+
+    let cli = parse(&["-o", "json", "orders"]);
+    assert_eq!(cli.output, OutputFormat::Json);
+    let orders = cli.run(&ctx).await.unwrap();
+    assert_eq!(orders.table.column("txid"), vec![txid.as_str()]);
+    assert_eq!(orders.value["count"], 1);
+
+    // Cancellations have no validate parameter, a dry run only shows the request
+    let not_sent = parse(&["--dry-run", "cancel-all"]).run(&ctx).await.unwrap();
+    assert_eq!(not_sent.table.column("sent"), vec!["false"]);
+    let cancelled = parse(&["cancel", &txid]).run(&ctx).await.unwrap();
+    assert_eq!(cancelled.table.column("count"), vec!["1"]);
+
+    // Private commands fail without credentials, public ones still work
+    let anonymous = CliContext::new(
+        server.client_state().unwrap(),
+        Err(Error::Auth("No API credentials".to_string())),
+    );
+    let balance = parse(&["balance"]).run(&anonymous).await;
+    assert!(matches!(balance, Err(Error::Auth(_))));
+    let status = parse(&["status"]).run(&anonymous).await.unwrap();
+    assert_eq!(status.table.column("status"), vec!["online"]);
+}
+
+/// Next JSON message of a WebSocket stream, skipping control frames
+async fn next_json(
+    ws: &mut tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >,
+) -> Value {
+    loop {
+        match tokio::time::timeout(Duration::from_secs(5), ws.next()).await {
+            Ok(Some(Ok(Message::Text(text)))) => return serde_json::from_str(&text).unwrap(),
+            Ok(Some(Ok(_))) => continue,
+            other => panic!("No message from the stream: {:?}", other),
+        }
+    }
+}
+
+#[actix_web::test]
+async fn test_streaming_endpoints_offline() {
+    let hub = Arc::new(EventHub::new(&StreamingConfig::default()));
+    let auth = Arc::new(StreamAuth::new(vec!["s3cret".to_string()]));
+    let (app_hub, app_auth) = (hub.clone(), auth.clone());
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::from(app_hub.clone()))
+            .app_data(web::Data::from(app_auth.clone()))
+            .service(web::scope("/api").configure(api::config))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let addr = server.addrs()[0];
+    let server = server.run();
+    let handle = server.handle();
+    actix_web::rt::spawn(server);
+    let http = reqwest::Client::new();
+    let events = format!("http://{}/api/stream/events", addr);
+
+    // Clients need a token, and only the configured symbols are streamed
+    let denied = http.get(format!("{}?subscribe=ticker", events)).send().await.unwrap();
+    assert_eq!(denied.status(), 401);
+    let unknown = http
+        .get(format!("{}?subscribe=ticker:SOL/USD", events))
+        .bearer_auth("s3cret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unknown.status(), 400);
+
+    let mut sse = http
+        .get(format!("{}?subscribe=ticker:BTC/USD,fills", events))
+        .bearer_auth("s3cret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(sse.status(), 200);
+    assert_eq!(sse.headers()["content-type"], "text/event-stream");
+    let mut body = String::new();
+    while !body.contains("\n\n") {
+        let chunk = sse.chunk().await.unwrap().unwrap();
+        body.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    assert!(body.starts_with("event: subscribed\ndata: {"));
+    assert!(body.contains(r#""topics":["fills","ticker:BTC/USD"]"#));
+
+    // WebSocket clients change their topics as they go
+    let (mut ws, _) = connect_async(format!("ws://{}/api/stream/ws?token=s3cret", addr))
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut ws).await, json!({ "type": "subscribed", "topics": [] }));
+    ws.send(Message::Text(r#"{"method":"subscribe","topics":["trade:BTC/USD"]}"#.into()))
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut ws).await["topics"], json!(["trade:BTC/USD"]));
+
+    hub.publish(StreamEvent::new(Channel::Trade, Some("BTC/USD".into()), json!({ "price": 1.0 })));
+    hub.publish(StreamEvent::new(Channel::Ticker, Some("BTC/USD".into()), json!({ "last": 2.0 })));
+    hub.publish(StreamEvent::new(Channel::Fills, None, json!({ "order_id": "O1" })));
+
+    let trade = next_json(&mut ws).await;
+    assert_eq!((&trade["type"], &trade["channel"]), (&json!("event"), &json!("trade")));
+    assert_eq!(trade["data"]["price"], 1.0);
+
+    while !body.contains("event: fills") {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), sse.chunk())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        body.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    assert!(body.contains("event: ticker\ndata: {\"type\":\"event\",\"channel\":\"ticker\""));
+    assert!(!body.contains("event: trade"));
+    assert_eq!(hub.client_count(), 2);
+
+    handle.stop(false).await;
+}
+
+#[actix_web::test]
+async fn test_state_changing_endpoints_need_a_token_offline() {
+    let requests = || {
+        vec![
+            test::TestRequest::post().uri("/api/algos").set_json(json!({
+                "pair": "XBTUSD",
+                "side": "buy",
+                "volume": 1.0,
+                "duration_secs": 60,
+                "algo": "twap",
+                "slices": 2,
+            })),
+            test::TestRequest::post().uri("/api/algos/A1/pause"),
+            test::TestRequest::post().uri("/api/algos/A1/resume"),
+            test::TestRequest::delete().uri("/api/algos/A1"),
+            test::TestRequest::post().uri("/api/synthetic-orders").set_json(json!({
+                "type": "timed",
+                "order": {
+                    "pair": "XBTUSD",
+                    "side": "buy",
+                    "ordertype": "limit",
+                    "volume": "0.1",
+                    "price": "40000",
+                },
+            })),
+            test::TestRequest::delete().uri("/api/synthetic-orders/S1"),
+            test::TestRequest::delete().uri("/api/cache"),
+        ]
+    };
+
+    // Nothing changes state while no token is configured
+    let open = test::init_service(App::new().service(web::scope("/api").configure(api::config))).await;
+    for request in requests() {
+        let response = test::call_service(&open, request.to_request()).await;
+        assert_eq!(response.status(), 401);
+    }
+
+    let auth = web::Data::new(StreamAuth::new(vec!["s3cret".to_string()]));
+    let app = test::init_service(
+        App::new()
+            .app_data(auth)
+            .service(web::scope("/api").configure(api::config)),
+    )
+    .await;
+    for request in requests() {
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), 401);
+        assert!(response.headers().contains_key("www-authenticate"));
+    }
+    for request in requests() {
+        let request = request.insert_header(("Authorization", "Bearer other"));
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), 401);
+    }
+    // Past the token check, the services are simply not configured here
+    for request in requests() {
+        let request = request.insert_header(("Authorization", "Bearer s3cret"));
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), 503);
+    }
+}
+
+#[actix_web::test]
+async fn test_account_endpoints_use_the_shared_account_offline() {
+    let server = MockKraken::start().await.unwrap();
+    let state = server.client_state().unwrap();
+
+    let unconfigured = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state.clone()))
+            .service(web::scope("/api").configure(api::config)),
+    )
+    .await;
+    for uri in ["/api/balance", "/api/open-orders", "/api/trade-volume"] {
+        let request = test::TestRequest::get().uri(uri).to_request();
+        assert_eq!(test::call_service(&unconfigured, request).await.status(), 503);
+    }
+
+    let account = web::Data::new(Account::with_api(server.private_api().unwrap()));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .app_data(account)
+            .service(web::scope("/api").configure(api::config)),
+    )
+    .await;
+    let request = test::TestRequest::get().uri("/api/open-orders").to_request();
+    let body: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(body["count"], 0);
+    assert_eq!(body["synthetic"], json!([]));
+
+    server.set_balance("ZUSD", 1_000.0);
+    let request = test::TestRequest::get().uri("/api/balance").to_request();
+    let balance: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(balance["ZUSD"], "1000.0000000000");
+    let request = test::TestRequest::get().uri("/api/trade-volume").to_request();
+    assert!(test::call_service(&app, request).await.status().is_success());
+}
+
+#[actix_web::test]
+async fn test_disconnected_websocket_fails_readiness_offline() {
+    let server = MockKraken::start().await.unwrap();
+    let state = server.client_state().unwrap();
+    let readyz = |monitor: Arc<HealthMonitor>| async move {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(monitor))
+                .route("/readyz", web::get().to(health::readyz)),
+        )
+        .await;
+        let response = test::call_service(&app, test::TestRequest::get().uri("/readyz").to_request()).await;
+        let status = response.status();
+        let report: Value = test::read_body_json(response).await;
+        (status, report)
+    };
+
+    let monitor = Arc::new(HealthMonitor::new(HealthConfig::default()));
+    monitor.check(state.clone()).await;
+    let (status, report) = readyz(monitor).await;
+    assert_eq!(status, 200, "{}", report);
+
+    // A socket that has not connected yet, or has dropped, keeps the process out of rotation
+    let socket = Arc::new(SocketStatus::default());
+    let monitor = Arc::new(HealthMonitor::new(HealthConfig::default()).with_websocket(socket));
+    monitor.check(state).await;
+    let (status, report) = readyz(monitor).await;
+    assert_eq!(status, 503);
+    assert_eq!(report["websocket"]["connected"], false);
+    assert_eq!(report["problems"], json!(["Market data WebSocket is disconnected"]));
+
+    let monitor = Arc::new(
+        HealthMonitor::new(HealthConfig::default())
+            .with_private_websocket(Arc::new(SocketStatus::default())),
+    );
+    monitor.check(server.client_state().unwrap()).await;
+    let (status, report) = readyz(monitor).await;
+    assert_eq!(status, 503);
+    assert_eq!(report["private_websocket"]["connected"], false);
+    assert_eq!(report["problems"], json!(["Account events WebSocket is disconnected"]));
+}